@@ -0,0 +1,6 @@
+CREATE TABLE job_leases (
+    job_name     VARCHAR(64) NOT NULL PRIMARY KEY,
+    holder       VARCHAR(64) NOT NULL,
+    locked_until DATETIME    NOT NULL,
+    last_slot    DATETIME    NOT NULL
+);
@@ -0,0 +1,10 @@
+CREATE TABLE ws_broadcasts (
+    id         BIGINT      NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    origin     VARCHAR(64) NOT NULL,
+    anonymous  BOOLEAN     NOT NULL DEFAULT FALSE,
+    target     VARCHAR(64) NOT NULL,
+    data       BLOB        NOT NULL,
+    created_at DATETIME    NOT NULL
+);
+
+CREATE INDEX ws_broadcasts_created_at ON ws_broadcasts (created_at);
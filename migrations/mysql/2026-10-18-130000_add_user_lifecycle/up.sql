@@ -0,0 +1,5 @@
+ALTER TABLE users
+ADD COLUMN lifecycle_warned_at DATETIME;
+
+ALTER TABLE users
+ADD COLUMN lifecycle_disabled_at DATETIME;
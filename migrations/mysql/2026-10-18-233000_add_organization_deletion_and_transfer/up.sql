@@ -0,0 +1,11 @@
+ALTER TABLE organizations
+ADD COLUMN deleted_at DATETIME;
+
+ALTER TABLE organizations
+ADD COLUMN transfer_org_user_uuid VARCHAR(40);
+
+ALTER TABLE organizations
+ADD COLUMN transfer_initiator_uuid VARCHAR(40);
+
+ALTER TABLE organizations
+ADD COLUMN transfer_date DATETIME;
@@ -0,0 +1,12 @@
+CREATE TABLE job_runs (
+    uuid           CHAR(36)     NOT NULL PRIMARY KEY,
+    job_name       VARCHAR(64)  NOT NULL,
+    manual         BOOLEAN      NOT NULL DEFAULT FALSE,
+    started_at     TIMESTAMP    NOT NULL,
+    finished_at    TIMESTAMP,
+    status         INTEGER      NOT NULL,
+    items_affected BIGINT,
+    error          TEXT
+);
+
+CREATE INDEX job_runs_job_name_started_at ON job_runs (job_name, started_at);
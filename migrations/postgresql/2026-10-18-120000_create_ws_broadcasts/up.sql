@@ -0,0 +1,10 @@
+CREATE TABLE ws_broadcasts (
+    id         BIGSERIAL   PRIMARY KEY,
+    origin     VARCHAR(64) NOT NULL,
+    anonymous  BOOLEAN     NOT NULL DEFAULT FALSE,
+    target     VARCHAR(64) NOT NULL,
+    data       BYTEA       NOT NULL,
+    created_at TIMESTAMP   NOT NULL
+);
+
+CREATE INDEX ws_broadcasts_created_at ON ws_broadcasts (created_at);
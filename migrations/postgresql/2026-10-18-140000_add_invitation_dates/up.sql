@@ -0,0 +1,10 @@
+ALTER TABLE users_organizations
+ADD COLUMN invited_at TIMESTAMP;
+
+-- Start the expiration of the already pending invitations from now on
+UPDATE users_organizations SET invited_at = (NOW() AT TIME ZONE 'UTC') WHERE status = 0;
+
+ALTER TABLE invitations
+ADD COLUMN created_at TIMESTAMP;
+
+UPDATE invitations SET created_at = (NOW() AT TIME ZONE 'UTC');
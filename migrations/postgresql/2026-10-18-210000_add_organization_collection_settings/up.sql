@@ -0,0 +1,11 @@
+ALTER TABLE organizations
+ADD COLUMN limit_collection_creation_deletion BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Existing organizations always reported this setting as enabled to the clients, so they keep it enabled
+UPDATE organizations SET limit_collection_creation_deletion = TRUE;
+
+ALTER TABLE organizations
+ADD COLUMN allow_admin_access_to_all_collection_items BOOLEAN NOT NULL DEFAULT TRUE;
+
+ALTER TABLE organizations
+ADD COLUMN members_can_edit_items BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,16 @@
+CREATE TABLE organization_domains (
+    uuid            VARCHAR(40)  NOT NULL PRIMARY KEY,
+    org_uuid        VARCHAR(40)  NOT NULL REFERENCES organizations(uuid),
+    domain_name     VARCHAR(255) NOT NULL,
+    txt             TEXT         NOT NULL,
+    creation_date   TIMESTAMP    NOT NULL,
+    last_checked_at TIMESTAMP,
+    verified_at     TIMESTAMP,
+    job_run_count   INTEGER      NOT NULL DEFAULT 0,
+    UNIQUE (org_uuid, domain_name)
+);
+
+CREATE INDEX organization_domains_domain_name ON organization_domains (domain_name);
+
+ALTER TABLE organizations
+ADD COLUMN limit_invitations_to_verified_domains BOOLEAN NOT NULL DEFAULT FALSE;
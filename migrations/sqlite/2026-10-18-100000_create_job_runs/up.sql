@@ -0,0 +1,12 @@
+CREATE TABLE job_runs (
+    uuid           TEXT     NOT NULL PRIMARY KEY,
+    job_name       TEXT     NOT NULL,
+    manual         BOOLEAN  NOT NULL DEFAULT 0,
+    started_at     DATETIME NOT NULL,
+    finished_at    DATETIME,
+    status         INTEGER  NOT NULL,
+    items_affected BIGINT,
+    error          TEXT
+);
+
+CREATE INDEX job_runs_job_name_started_at ON job_runs (job_name, started_at);
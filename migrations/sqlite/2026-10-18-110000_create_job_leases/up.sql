@@ -0,0 +1,6 @@
+CREATE TABLE job_leases (
+    job_name     TEXT     NOT NULL PRIMARY KEY,
+    holder       TEXT     NOT NULL,
+    locked_until DATETIME NOT NULL,
+    last_slot    DATETIME NOT NULL
+);
@@ -0,0 +1,10 @@
+CREATE TABLE ws_broadcasts (
+    id         INTEGER  PRIMARY KEY AUTOINCREMENT,
+    origin     TEXT     NOT NULL,
+    anonymous  BOOLEAN  NOT NULL DEFAULT 0,
+    target     TEXT     NOT NULL,
+    data       BLOB     NOT NULL,
+    created_at DATETIME NOT NULL
+);
+
+CREATE INDEX ws_broadcasts_created_at ON ws_broadcasts (created_at);
@@ -0,0 +1,10 @@
+ALTER TABLE users_organizations
+ADD COLUMN invited_at DATETIME;
+
+-- Start the expiration of the already pending invitations from now on
+UPDATE users_organizations SET invited_at = CURRENT_TIMESTAMP WHERE status = 0;
+
+ALTER TABLE invitations
+ADD COLUMN created_at DATETIME;
+
+UPDATE invitations SET created_at = CURRENT_TIMESTAMP;
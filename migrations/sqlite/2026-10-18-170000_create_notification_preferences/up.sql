@@ -0,0 +1,9 @@
+CREATE TABLE notification_preferences (
+    user_uuid                  TEXT    NOT NULL PRIMARY KEY REFERENCES users(uuid),
+    new_device_logged_in       BOOLEAN,
+    incomplete_2fa_login       BOOLEAN,
+    org_membership_updates     BOOLEAN,
+    emergency_access_updates   BOOLEAN,
+    emergency_access_reminders BOOLEAN,
+    security_digest            BOOLEAN
+);
@@ -0,0 +1,6 @@
+ALTER TABLE users
+ADD COLUMN pgp_public_key TEXT;
+
+-- Set once the user has been told their key can't be used anymore
+ALTER TABLE users
+ADD COLUMN pgp_key_unusable_notified_at DATETIME;
@@ -0,0 +1,11 @@
+ALTER TABLE organizations
+ADD COLUMN limit_collection_creation_deletion BOOLEAN NOT NULL DEFAULT 0; -- FALSE
+
+-- Existing organizations always reported this setting as enabled to the clients, so they keep it enabled
+UPDATE organizations SET limit_collection_creation_deletion = 1;
+
+ALTER TABLE organizations
+ADD COLUMN allow_admin_access_to_all_collection_items BOOLEAN NOT NULL DEFAULT 1; -- TRUE
+
+ALTER TABLE organizations
+ADD COLUMN members_can_edit_items BOOLEAN NOT NULL DEFAULT 1; -- TRUE
@@ -0,0 +1,16 @@
+CREATE TABLE organization_domains (
+    uuid            TEXT     NOT NULL PRIMARY KEY,
+    org_uuid        TEXT     NOT NULL REFERENCES organizations(uuid),
+    domain_name     TEXT     NOT NULL,
+    txt             TEXT     NOT NULL,
+    creation_date   DATETIME NOT NULL,
+    last_checked_at DATETIME,
+    verified_at     DATETIME,
+    job_run_count   INTEGER  NOT NULL DEFAULT 0,
+    UNIQUE (org_uuid, domain_name)
+);
+
+CREATE INDEX organization_domains_domain_name ON organization_domains (domain_name);
+
+ALTER TABLE organizations
+ADD COLUMN limit_invitations_to_verified_domains BOOLEAN NOT NULL DEFAULT 0; -- FALSE
@@ -0,0 +1,11 @@
+ALTER TABLE organization_domains
+ADD COLUMN auto_enroll BOOLEAN NOT NULL DEFAULT 0; -- FALSE
+
+ALTER TABLE organization_domains
+ADD COLUMN auto_enroll_type INTEGER NOT NULL DEFAULT 2;
+
+ALTER TABLE organization_domains
+ADD COLUMN auto_enroll_groups TEXT;
+
+ALTER TABLE organization_domains
+ADD COLUMN auto_enroll_collections TEXT;
@@ -2,27 +2,31 @@ use once_cell::sync::Lazy;
 use reqwest::Method;
 use serde::de::DeserializeOwned;
 use serde_json::Value;
-use std::env;
+use std::{collections::HashSet, env};
 
 use rocket::serde::json::Json;
 use rocket::{
     form::Form,
-    http::{Cookie, CookieJar, MediaType, SameSite, Status},
+    http::{ContentType, Cookie, CookieJar, MediaType, SameSite, Status},
     request::{FromRequest, Outcome, Request},
     response::{content::RawHtml as Html, Redirect},
-    Catcher, Route,
+    Catcher, Route, State,
 };
 
 use crate::{
     api::{
-        core::{log_event, two_factor},
+        core::{
+            build_member_security_report, log_event, member_security_report_csv, restore_org, restore_user,
+            schedule_org_deletion, schedule_user_deletion, two_factor, MemberSecurity,
+        },
         unregister_push_device, ApiResult, EmptyResult, JsonResult, Notify,
     },
     auth::{decode_admin, encode_jwt, generate_admin_claims, ClientIp, Secure},
-    config::ConfigBuilder,
-    db::{backup_database, get_sql_server_version, models::*, DbConn, DbConnType},
+    config::{is_email_partial, ConfigBuilder, EMAIL_TEMPLATES},
+    db::{backup_database, get_sql_server_version, models::*, DbConn, DbConnType, DbPool},
     error::{Error, MapResult},
     http_client::make_http_request,
+    jobs::{self, ScheduledJob},
     mail,
     util::{
         container_base_image, format_naive_datetime_local, get_display_size, is_running_in_container, NumberOrString,
@@ -45,6 +49,7 @@ pub fn routes() -> Vec<Route> {
         invite_user,
         logout,
         delete_user,
+        restore_user_account,
         deauth_user,
         disable_user,
         enable_user,
@@ -57,10 +62,23 @@ pub fn routes() -> Vec<Route> {
         test_smtp,
         users_overview,
         organizations_overview,
+        organization_security,
+        organization_security_csv,
         delete_organization,
+        restore_organization,
         diagnostics,
         get_diagnostics_config,
         resend_user_invite,
+        jobs_overview,
+        run_job,
+        connections_overview,
+        disconnect_connection,
+        templates_overview,
+        get_template_source,
+        preview_template,
+        save_template,
+        revert_template,
+        test_template,
     ]
 }
 
@@ -297,7 +315,16 @@ async fn invite_user(data: Json<InviteData>, _token: AdminToken, mut conn: DbCon
 
     async fn _generate_invite(user: &User, conn: &mut DbConn) -> EmptyResult {
         if CONFIG.mail_enabled() {
-            mail::send_invite(&user.email, &user.uuid, None, None, &CONFIG.invitation_org_name(), None).await
+            mail::send_invite(
+                &user.email,
+                user.locale.as_deref(),
+                &user.uuid,
+                None,
+                None,
+                &CONFIG.invitation_org_name(),
+                None,
+            )
+            .await
         } else {
             let invitation = Invitation::new(&user.email);
             invitation.save(conn).await
@@ -348,6 +375,12 @@ async fn get_users_json(_token: AdminToken, mut conn: DbConn) -> Json<Value> {
 #[get("/users/overview")]
 async fn users_overview(_token: AdminToken, mut conn: DbConn) -> ApiResult<Html<String>> {
     let users = User::get_all(&mut conn).await;
+    let lifecycle_enabled = ScheduledJob::AccountLifecycle.is_scheduled();
+    let org_owners: HashSet<String> = if lifecycle_enabled {
+        UserOrganization::find_owner_user_uuids(&mut conn).await.into_iter().collect()
+    } else {
+        HashSet::new()
+    };
     let mut users_json = Vec::with_capacity(users.len());
     for u in users {
         let mut usr = u.to_json(&mut conn).await;
@@ -356,10 +389,21 @@ async fn users_overview(_token: AdminToken, mut conn: DbConn) -> ApiResult<Html<
         usr["attachment_size"] = json!(get_display_size(Attachment::size_by_user(&u.uuid, &mut conn).await));
         usr["user_enabled"] = json!(u.enabled);
         usr["created_at"] = json!(format_naive_datetime_local(&u.created_at, DT_FMT));
-        usr["last_active"] = match u.last_active(&mut conn).await {
+        usr["purge_date"] = json!(u.purge_date().map(|d| format_naive_datetime_local(&d, DT_FMT)));
+        usr["deletion_retention_days"] = json!(CONFIG.user_deletion_retention_days());
+        let last_active = u.last_active(&mut conn).await;
+        usr["last_active"] = match last_active {
             Some(dt) => json!(format_naive_datetime_local(&dt, DT_FMT)),
             None => json!("Never"),
         };
+        if lifecycle_enabled {
+            if let Some((action, due)) = u.next_lifecycle_action(last_active) {
+                if !org_owners.contains(&u.uuid) {
+                    usr["lifecycle_action"] = json!(action.as_str());
+                    usr["lifecycle_due"] = json!(format_naive_datetime_local(&due, DT_FMT));
+                }
+            }
+        }
         users_json.push(usr);
     }
 
@@ -388,9 +432,13 @@ async fn get_user_json(uuid: &str, _token: AdminToken, mut conn: DbConn) -> Json
     Ok(Json(usr))
 }
 
+// Deleting an account which is already deleted skips the remainder of the retention period
 #[post("/users/<uuid>/delete")]
 async fn delete_user(uuid: &str, token: AdminToken, mut conn: DbConn) -> EmptyResult {
     let user = get_user_or_404(uuid, &mut conn).await?;
+    if !user.is_deleted() {
+        return schedule_user_deletion(user, true, DeviceType::UnknownBrowser as i32, &token.ip.ip, &mut conn).await;
+    }
 
     // Get the user_org records before deleting the actual user
     let user_orgs = UserOrganization::find_any_state_by_user(uuid, &mut conn).await;
@@ -412,6 +460,12 @@ async fn delete_user(uuid: &str, token: AdminToken, mut conn: DbConn) -> EmptyRe
     res
 }
 
+#[post("/users/<uuid>/restore")]
+async fn restore_user_account(uuid: &str, token: AdminToken, mut conn: DbConn) -> EmptyResult {
+    let user = get_user_or_404(uuid, &mut conn).await?;
+    restore_user(user, &token.ip.ip, &mut conn).await
+}
+
 #[post("/users/<uuid>/deauth")]
 async fn deauth_user(uuid: &str, _token: AdminToken, mut conn: DbConn, nt: Notify<'_>) -> EmptyResult {
     let mut user = get_user_or_404(uuid, &mut conn).await?;
@@ -451,6 +505,9 @@ async fn disable_user(uuid: &str, _token: AdminToken, mut conn: DbConn, nt: Noti
 async fn enable_user(uuid: &str, _token: AdminToken, mut conn: DbConn) -> EmptyResult {
     let mut user = get_user_or_404(uuid, &mut conn).await?;
     user.enabled = true;
+    // Give accounts disabled by the account lifecycle job a fresh start
+    user.lifecycle_warned_at = None;
+    user.lifecycle_disabled_at = None;
 
     user.save(&mut conn).await
 }
@@ -473,7 +530,16 @@ async fn resend_user_invite(uuid: &str, _token: AdminToken, mut conn: DbConn) ->
         }
 
         if CONFIG.mail_enabled() {
-            mail::send_invite(&user.email, &user.uuid, None, None, &CONFIG.invitation_org_name(), None).await
+            mail::send_invite(
+                &user.email,
+                user.locale.as_deref(),
+                &user.uuid,
+                None,
+                None,
+                &CONFIG.invitation_org_name(),
+                None,
+            )
+            .await
         } else {
             Ok(())
         }
@@ -540,7 +606,15 @@ async fn update_user_org_type(data: Json<UserOrgTypeData>, token: AdminToken, mu
     )
     .await;
 
+    if user_to_edit.atype != new_type {
+        Organization::cancel_transfer_of_member(&user_to_edit, &mut conn).await?;
+    }
+
     user_to_edit.atype = new_type;
+    // The permissions of a Custom role can only be chosen in the web vault, so keep them only while the type stays Custom
+    if new_type != UserOrgType::Custom {
+        user_to_edit.permissions = None;
+    }
     user_to_edit.save(&mut conn).await
 }
 
@@ -562,6 +636,14 @@ async fn organizations_overview(_token: AdminToken, mut conn: DbConn) -> ApiResu
         org["event_count"] = json!(Event::count_by_org(&o.uuid, &mut conn).await);
         org["attachment_count"] = json!(Attachment::count_by_org(&o.uuid, &mut conn).await);
         org["attachment_size"] = json!(get_display_size(Attachment::size_by_org(&o.uuid, &mut conn).await));
+        org["verified_domains"] = json!(OrganizationDomain::find_by_org(&o.uuid, &mut conn)
+            .await
+            .into_iter()
+            .filter(OrganizationDomain::is_verified)
+            .map(|d| d.domain_name)
+            .collect::<Vec<String>>());
+        org["deletion_date"] = json!(o.purge_date().map(|d| format_naive_datetime_local(&d, DT_FMT)));
+        org["deletion_grace_days"] = json!(CONFIG.org_deletion_grace_days());
         organizations_json.push(org);
     }
 
@@ -569,10 +651,240 @@ async fn organizations_overview(_token: AdminToken, mut conn: DbConn) -> ApiResu
     Ok(Html(text))
 }
 
+#[get("/organizations/<uuid>/security")]
+async fn organization_security(uuid: &str, _token: AdminToken, mut conn: DbConn) -> ApiResult<Html<String>> {
+    let org = Organization::find_by_uuid(uuid, &mut conn).await.map_res("Organization doesn't exist")?;
+    let members_json: Vec<Value> = build_member_security_report(uuid, &mut conn)
+        .await
+        .iter()
+        .map(|m| {
+            let mut member = m.to_json();
+            member["last_active"] = match m.last_active() {
+                Some(dt) => json!(format_naive_datetime_local(&dt, DT_FMT)),
+                None => json!("Never"),
+            };
+            member
+        })
+        .collect();
+
+    let page_data = json!({
+        "organization": org.to_json(),
+        "members": members_json,
+    });
+    let text = AdminTemplateData::new("admin/organization_security", page_data).render()?;
+    Ok(Html(text))
+}
+
+#[get("/organizations/<uuid>/security/csv")]
+async fn organization_security_csv(uuid: &str, _token: AdminToken, mut conn: DbConn) -> (ContentType, String) {
+    let report: Vec<MemberSecurity> = build_member_security_report(uuid, &mut conn).await;
+    (ContentType::CSV, member_security_report_csv(&report))
+}
+
+// Deleting an organization which is already scheduled for deletion skips the remainder of the grace period
 #[post("/organizations/<uuid>/delete")]
-async fn delete_organization(uuid: &str, _token: AdminToken, mut conn: DbConn) -> EmptyResult {
+async fn delete_organization(uuid: &str, _token: AdminToken, mut conn: DbConn, nt: Notify<'_>) -> EmptyResult {
+    let org = Organization::find_by_uuid(uuid, &mut conn).await.map_res("Organization doesn't exist")?;
+    if org.is_pending_deletion() {
+        org.delete(&mut conn).await
+    } else {
+        schedule_org_deletion(org, "the server administrator", &mut conn, &nt).await
+    }
+}
+
+#[post("/organizations/<uuid>/restore")]
+async fn restore_organization(uuid: &str, _token: AdminToken, mut conn: DbConn, nt: Notify<'_>) -> EmptyResult {
     let org = Organization::find_by_uuid(uuid, &mut conn).await.map_res("Organization doesn't exist")?;
-    org.delete(&mut conn).await
+    restore_org(org, "the server administrator", &mut conn, &nt).await
+}
+
+#[get("/jobs/overview")]
+async fn jobs_overview(_token: AdminToken, mut conn: DbConn) -> ApiResult<Html<String>> {
+    let mut jobs_json = Vec::with_capacity(ScheduledJob::ALL.len());
+    for job in ScheduledJob::ALL {
+        jobs_json.push(json!({
+            "name": job.name(),
+            "description": job.description(),
+            "schedule": job.schedule(),
+            "scheduled": job.is_scheduled(),
+            "next_run": job.next_run().map(|dt| format_naive_datetime_local(&dt.naive_utc(), DT_FMT)),
+            "last_run": JobRun::find_last_by_job(job.name(), &mut conn).await.map(|r| r.to_json(DT_FMT)),
+        }));
+    }
+
+    let runs_json: Vec<Value> = JobRun::find_recent(100, &mut conn).await.iter().map(|r| r.to_json(DT_FMT)).collect();
+
+    let jobs_data = json!({
+        "jobs": jobs_json,
+        "runs": runs_json,
+        "scheduler_enabled": CONFIG.job_poll_interval_ms() > 0,
+        "history_days": CONFIG.job_history_days_retain(),
+    });
+
+    let text = AdminTemplateData::new("admin/jobs", jobs_data).render()?;
+    Ok(Html(text))
+}
+
+#[post("/jobs/<name>/run")]
+async fn run_job(name: &str, _token: AdminToken, pool: &State<DbPool>) -> JsonResult {
+    let Some(job) = ScheduledJob::from_name(name) else {
+        err_code!("Job doesn't exist", Status::NotFound.code)
+    };
+
+    match jobs::run_job(job, pool.inner().clone(), true).await {
+        Some(run) => Ok(Json(run.to_json(DT_FMT))),
+        None => err!("This job is already running on this or another instance"),
+    }
+}
+
+#[get("/connections/overview")]
+async fn connections_overview(_token: AdminToken, mut conn: DbConn, nt: Notify<'_>) -> ApiResult<Html<String>> {
+    let connections = nt.connections();
+    let mut connections_json = Vec::with_capacity(connections.len());
+    for c in connections {
+        let email = User::find_by_uuid(&c.user_uuid, &mut conn).await.map(|u| u.email);
+        let device = Device::find_by_uuid_and_user(&c.device_uuid, &c.user_uuid, &mut conn).await;
+        connections_json.push(json!({
+            "id": c.id,
+            "user_uuid": c.user_uuid,
+            "email": email,
+            "device_name": device.as_ref().map(|d| d.name.clone()),
+            "device_type": device.map(|d| DeviceType::from_i32(d.atype).to_string()),
+            "ip": c.ip,
+            "connected_at": format_naive_datetime_local(&c.connected_at, DT_FMT),
+        }));
+    }
+
+    let connections_data = json!({
+        "connection_count": connections_json.len(),
+        "connections": connections_json,
+        "websocket_enabled": CONFIG.enable_websocket(),
+        "max_connections": CONFIG.ws_max_connections(),
+        "max_connections_per_user": CONFIG.ws_max_connections_per_user(),
+    });
+
+    let text = AdminTemplateData::new("admin/connections", connections_data).render()?;
+    Ok(Html(text))
+}
+
+#[post("/connections/<id>/disconnect")]
+fn disconnect_connection(id: &str, _token: AdminToken, nt: Notify<'_>) -> EmptyResult {
+    if !nt.disconnect(id) {
+        err_code!("Connection doesn't exist", Status::NotFound.code)
+    }
+    Ok(())
+}
+
+#[get("/templates/overview")]
+fn templates_overview(_token: AdminToken) -> ApiResult<Html<String>> {
+    let templates_json: Vec<Value> = EMAIL_TEMPLATES
+        .iter()
+        .map(|(name, _)| {
+            json!({
+                "name": name,
+                "partial": is_email_partial(name),
+                "overridden": CONFIG.template_override(name).is_some(),
+            })
+        })
+        .collect();
+
+    let templates_data = json!({
+        "templates": templates_json,
+        "templates_folder": CONFIG.templates_folder(),
+        "mail_enabled": CONFIG.mail_enabled(),
+    });
+
+    let text = AdminTemplateData::new("admin/templates", templates_data).render()?;
+    Ok(Html(text))
+}
+
+fn get_builtin_template(name: &str) -> ApiResult<&'static str> {
+    match EMAIL_TEMPLATES.iter().find(|(n, _)| *n == name) {
+        Some((_, source)) => Ok(source),
+        None => err_code!("Template doesn't exist", Status::NotFound.code),
+    }
+}
+
+#[get("/templates/source?<name>")]
+fn get_template_source(name: &str, _token: AdminToken) -> JsonResult {
+    let builtin = get_builtin_template(name)?;
+    let source = CONFIG.template_override(name);
+
+    Ok(Json(json!({
+        "name": name,
+        "builtin": builtin,
+        "overridden": source.is_some(),
+        "source": source.as_deref().unwrap_or(builtin),
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TemplateData {
+    name: String,
+    source: String,
+}
+
+#[post("/templates/preview", data = "<data>")]
+fn preview_template(data: Json<TemplateData>, _token: AdminToken) -> JsonResult {
+    let data: TemplateData = data.into_inner();
+    get_builtin_template(&data.name)?;
+
+    let (subject, body_html, body_text) = mail::preview_template(&data.name, &data.source)?;
+    Ok(Json(json!({
+        "subject": subject,
+        "html": body_html,
+        "text": body_text,
+    })))
+}
+
+#[post("/templates/save", data = "<data>")]
+fn save_template(data: Json<TemplateData>, _token: AdminToken) -> EmptyResult {
+    let data: TemplateData = data.into_inner();
+    get_builtin_template(&data.name)?;
+
+    // Make sure the template compiles and renders before it replaces the current one
+    mail::preview_template(&data.name, &data.source)?;
+    CONFIG.save_template_override(&data.name, &data.source)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TemplateNameData {
+    name: String,
+}
+
+#[post("/templates/revert", data = "<data>")]
+fn revert_template(data: Json<TemplateNameData>, _token: AdminToken) -> EmptyResult {
+    let data: TemplateNameData = data.into_inner();
+    get_builtin_template(&data.name)?;
+
+    CONFIG.remove_template_override(&data.name)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TemplateTestData {
+    name: String,
+    email: String,
+}
+
+#[post("/templates/test", data = "<data>")]
+async fn test_template(data: Json<TemplateTestData>, _token: AdminToken) -> EmptyResult {
+    let data: TemplateTestData = data.into_inner();
+    get_builtin_template(&data.name)?;
+
+    if !CONFIG.mail_enabled() {
+        err!("Mail is not enabled")
+    }
+
+    // Partials can't be sent on their own, send the SMTP test mail which includes them instead
+    let mail_name = if is_email_partial(&data.name) {
+        "email/smtp_test"
+    } else {
+        data.name.strip_suffix(".html").unwrap_or(&data.name)
+    };
+    mail::send_template_test(&data.email, mail_name).await
 }
 
 #[derive(Deserialize)]
@@ -1,19 +1,25 @@
+use std::{
+    collections::{HashMap, HashSet},
+    net::{IpAddr, Ipv4Addr},
+};
+
 use crate::db::DbPool;
-use chrono::Utc;
+use chrono::{NaiveDateTime, TimeDelta, Utc};
 use rocket::serde::json::Json;
 use serde_json::Value;
 
 use crate::{
     api::{
-        core::{log_user_event, two_factor::email},
+        core::{auto_enroll_user, log_user_event, two_factor::email},
         register_push_device, unregister_push_device, AnonymousNotify, EmptyResult, JsonResult, Notify,
-        PasswordOrOtpData, UpdateType,
+        PasswordOrOtpData, UpdateType, WS_USERS,
     },
-    auth::{decode_delete, decode_invite, decode_verify_email, ClientHeaders, Headers},
+    auth::{decode_delete, decode_invite, decode_verify_email, ClientHeaders, ClientIp, Headers},
     crypto,
     db::{models::*, DbConn},
+    jobs::JobResult,
     mail,
-    util::NumberOrString,
+    util::{normalize_locale, NumberOrString},
     CONFIG,
 };
 
@@ -51,6 +57,14 @@ pub fn routes() -> Vec<rocket::Route> {
         rotate_api_key,
         get_known_device,
         put_avatar,
+        get_notification_preferences,
+        put_notification_preferences,
+        post_notification_preferences,
+        get_pgp_key,
+        put_pgp_key,
+        post_pgp_key,
+        delete_pgp_key,
+        post_delete_pgp_key,
         put_device_token,
         put_clear_device_token,
         post_clear_device_token,
@@ -119,11 +133,11 @@ async fn is_email_2fa_required(org_user_uuid: Option<String>, conn: &mut DbConn)
 }
 
 #[post("/accounts/register", data = "<data>")]
-async fn register(data: Json<RegisterData>, conn: DbConn) -> JsonResult {
-    _register(data, conn).await
+async fn register(data: Json<RegisterData>, client_header: ClientHeaders, conn: DbConn) -> JsonResult {
+    _register(data, client_header, conn).await
 }
 
-pub async fn _register(data: Json<RegisterData>, mut conn: DbConn) -> JsonResult {
+pub async fn _register(data: Json<RegisterData>, client_header: ClientHeaders, mut conn: DbConn) -> JsonResult {
     let data: RegisterData = data.into_inner();
     let email = data.email.to_lowercase();
 
@@ -212,13 +226,17 @@ pub async fn _register(data: Json<RegisterData>, mut conn: DbConn) -> JsonResult
         user.public_key = Some(keys.public_key);
     }
 
+    if client_header.locale.is_some() {
+        user.locale = client_header.locale.clone();
+    }
+
     if CONFIG.mail_enabled() {
         if CONFIG.signups_verify() && !verified_by_invite {
-            if let Err(e) = mail::send_welcome_must_verify(&user.email, &user.uuid).await {
+            if let Err(e) = mail::send_welcome_must_verify(&user.email, user.locale.as_deref(), &user.uuid).await {
                 error!("Error sending welcome email: {:#?}", e);
             }
             user.last_verifying_at = Some(user.created_at);
-        } else if let Err(e) = mail::send_welcome(&user.email).await {
+        } else if let Err(e) = mail::send_welcome(&user.email, user.locale.as_deref()).await {
             error!("Error sending welcome email: {:#?}", e);
         }
 
@@ -228,6 +246,7 @@ pub async fn _register(data: Json<RegisterData>, mut conn: DbConn) -> JsonResult
     }
 
     user.save(&mut conn).await?;
+    auto_enroll_user(&user, &client_header, &mut conn).await;
 
     // accept any open emergency access invitations
     if !CONFIG.mail_enabled() && CONFIG.emergency_access_allowed() {
@@ -250,9 +269,11 @@ async fn profile(headers: Headers, mut conn: DbConn) -> Json<Value> {
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct ProfileData {
-    // culture: String, // Ignored, always use en-US
+    // culture: String, // Ignored, the clients always send en-US
     // masterPasswordHint: Option<String>, // Ignored, has been moved to ChangePassData
     name: String,
+    // Not sent by the official clients, the language used for emails. An empty value resets it.
+    locale: Option<String>,
 }
 
 #[put("/accounts/profile", data = "<data>")]
@@ -273,6 +294,15 @@ async fn post_profile(data: Json<ProfileData>, headers: Headers, mut conn: DbCon
     let mut user = headers.user;
     user.name = data.name;
 
+    match data.locale.as_deref().map(str::trim) {
+        None => {}
+        Some("") => user.locale = None,
+        Some(locale) => match normalize_locale(locale) {
+            Some(locale) => user.locale = Some(locale),
+            None => err!("The field Locale must be a language tag like en or en-US"),
+        },
+    }
+
     user.save(&mut conn).await?;
     Ok(Json(user.to_json(&mut conn).await))
 }
@@ -303,6 +333,125 @@ async fn put_avatar(data: Json<AvatarData>, headers: Headers, mut conn: DbConn)
     Ok(Json(user.to_json(&mut conn).await))
 }
 
+#[get("/accounts/notification-preferences")]
+async fn get_notification_preferences(headers: Headers, mut conn: DbConn) -> Json<Value> {
+    let prefs = NotificationPreference::find_or_default_by_user(&headers.user.uuid, &mut conn).await;
+    Json(prefs.to_json())
+}
+
+// Preferences which are left out or set to null fall back to the defaults configured by the admin
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NotificationPreferencesData {
+    new_device_logged_in: Option<bool>,
+    incomplete_2fa_login: Option<bool>,
+    org_membership_updates: Option<bool>,
+    emergency_access_updates: Option<bool>,
+    emergency_access_reminders: Option<bool>,
+    security_digest: Option<bool>,
+}
+
+#[put("/accounts/notification-preferences", data = "<data>")]
+async fn put_notification_preferences(
+    data: Json<NotificationPreferencesData>,
+    headers: Headers,
+    conn: DbConn,
+) -> JsonResult {
+    post_notification_preferences(data, headers, conn).await
+}
+
+#[post("/accounts/notification-preferences", data = "<data>")]
+async fn post_notification_preferences(
+    data: Json<NotificationPreferencesData>,
+    headers: Headers,
+    mut conn: DbConn,
+) -> JsonResult {
+    let data: NotificationPreferencesData = data.into_inner();
+
+    let prefs = NotificationPreference {
+        user_uuid: headers.user.uuid,
+        new_device_logged_in: data.new_device_logged_in,
+        incomplete_2fa_login: data.incomplete_2fa_login,
+        org_membership_updates: data.org_membership_updates,
+        emergency_access_updates: data.emergency_access_updates,
+        emergency_access_reminders: data.emergency_access_reminders,
+        security_digest: data.security_digest,
+    };
+    prefs.save(&mut conn).await?;
+
+    Ok(Json(prefs.to_json()))
+}
+
+fn pgp_key_json(user: &User) -> Value {
+    let fingerprint = user
+        .pgp_public_key
+        .as_deref()
+        .and_then(|armored_key| mail::read_pgp_public_key(armored_key).ok())
+        .map(|key| mail::pgp_fingerprint(&key));
+
+    json!({
+        "publicKey": user.pgp_public_key,
+        "fingerprint": fingerprint,
+        "object": "pgpPublicKey",
+    })
+}
+
+#[get("/accounts/pgp-key")]
+fn get_pgp_key(headers: Headers) -> Json<Value> {
+    Json(pgp_key_json(&headers.user))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PgpKeyData {
+    public_key: String,
+    master_password_hash: Option<String>,
+    otp: Option<String>,
+}
+
+#[put("/accounts/pgp-key", data = "<data>")]
+async fn put_pgp_key(data: Json<PgpKeyData>, headers: Headers, conn: DbConn) -> JsonResult {
+    post_pgp_key(data, headers, conn).await
+}
+
+#[post("/accounts/pgp-key", data = "<data>")]
+async fn post_pgp_key(data: Json<PgpKeyData>, headers: Headers, mut conn: DbConn) -> JsonResult {
+    let data: PgpKeyData = data.into_inner();
+    let mut user = headers.user;
+
+    PasswordOrOtpData {
+        master_password_hash: data.master_password_hash,
+        otp: data.otp,
+    }
+    .validate(&user, true, &mut conn)
+    .await?;
+
+    let public_key = data.public_key.trim();
+    mail::read_pgp_public_key(public_key)?;
+
+    user.pgp_public_key = Some(public_key.to_string());
+    user.pgp_key_unusable_notified_at = None;
+    user.save(&mut conn).await?;
+    Ok(Json(pgp_key_json(&user)))
+}
+
+#[post("/accounts/pgp-key/delete", data = "<data>")]
+async fn post_delete_pgp_key(data: Json<PasswordOrOtpData>, headers: Headers, conn: DbConn) -> EmptyResult {
+    delete_pgp_key(data, headers, conn).await
+}
+
+#[delete("/accounts/pgp-key", data = "<data>")]
+async fn delete_pgp_key(data: Json<PasswordOrOtpData>, headers: Headers, mut conn: DbConn) -> EmptyResult {
+    let data: PasswordOrOtpData = data.into_inner();
+    let mut user = headers.user;
+
+    data.validate(&user, true, &mut conn).await?;
+
+    user.pgp_public_key = None;
+    user.pgp_key_unusable_notified_at = None;
+    user.save(&mut conn).await
+}
+
 #[get("/users/<uuid>/public-key")]
 async fn get_public_keys(uuid: &str, _headers: Headers, mut conn: DbConn) -> JsonResult {
     let user = match User::find_by_uuid(uuid, &mut conn).await {
@@ -636,7 +785,7 @@ async fn post_email_token(data: Json<EmailTokenData>, headers: Headers, mut conn
     let token = crypto::generate_email_token(6);
 
     if CONFIG.mail_enabled() {
-        if let Err(e) = mail::send_change_email(&data.new_email, &token).await {
+        if let Err(e) = mail::send_change_email(&data.new_email, user.locale.as_deref(), &token).await {
             error!("Error sending change-email email: {:#?}", e);
         }
     } else {
@@ -721,7 +870,7 @@ async fn post_verify_email(headers: Headers) -> EmptyResult {
         err!("Cannot verify email address");
     }
 
-    if let Err(e) = mail::send_verify_email(&user.email, &user.uuid).await {
+    if let Err(e) = mail::send_verify_email(&user.email, user.locale.as_deref(), &user.uuid).await {
         error!("Error sending verify_email email: {:#?}", e);
     }
 
@@ -736,7 +885,11 @@ struct VerifyEmailTokenData {
 }
 
 #[post("/accounts/verify-email-token", data = "<data>")]
-async fn post_verify_email_token(data: Json<VerifyEmailTokenData>, mut conn: DbConn) -> EmptyResult {
+async fn post_verify_email_token(
+    data: Json<VerifyEmailTokenData>,
+    client_headers: ClientHeaders,
+    mut conn: DbConn,
+) -> EmptyResult {
     let data: VerifyEmailTokenData = data.into_inner();
 
     let mut user = match User::find_by_uuid(&data.user_id, &mut conn).await {
@@ -757,6 +910,7 @@ async fn post_verify_email_token(data: Json<VerifyEmailTokenData>, mut conn: DbC
     if let Err(e) = user.save(&mut conn).await {
         error!("Error saving email verification: {:#?}", e);
     }
+    auto_enroll_user(&user, &client_headers, &mut conn).await;
 
     Ok(())
 }
@@ -773,7 +927,14 @@ async fn post_delete_recover(data: Json<DeleteRecoverData>, mut conn: DbConn) ->
 
     if CONFIG.mail_enabled() {
         if let Some(user) = User::find_by_mail(&data.email, &mut conn).await {
-            if let Err(e) = mail::send_delete_account(&user.email, &user.uuid).await {
+            if let Err(e) = mail::send_delete_account(
+                &user.email,
+                user.locale.as_deref(),
+                user.pgp_public_key.as_deref(),
+                &user.uuid,
+            )
+            .await
+            {
                 error!("Error sending delete account email: {:#?}", e);
             }
         }
@@ -795,7 +956,7 @@ struct DeleteRecoverTokenData {
 }
 
 #[post("/accounts/delete-recover-token", data = "<data>")]
-async fn post_delete_recover_token(data: Json<DeleteRecoverTokenData>, mut conn: DbConn) -> EmptyResult {
+async fn post_delete_recover_token(data: Json<DeleteRecoverTokenData>, ip: ClientIp, mut conn: DbConn) -> EmptyResult {
     let data: DeleteRecoverTokenData = data.into_inner();
 
     let user = match User::find_by_uuid(&data.user_id, &mut conn).await {
@@ -810,7 +971,7 @@ async fn post_delete_recover_token(data: Json<DeleteRecoverTokenData>, mut conn:
     if claims.sub != user.uuid {
         err!("Invalid claim");
     }
-    user.delete(&mut conn).await
+    schedule_user_deletion(user, false, DeviceType::UnknownBrowser as i32, &ip.ip, &mut conn).await
 }
 
 #[post("/accounts/delete", data = "<data>")]
@@ -825,7 +986,76 @@ async fn delete_account(data: Json<PasswordOrOtpData>, headers: Headers, mut con
 
     data.validate(&user, true, &mut conn).await?;
 
-    user.delete(&mut conn).await
+    schedule_user_deletion(user, false, headers.device.atype, &headers.ip.ip, &mut conn).await
+}
+
+/// Deletes the account once `USER_DELETION_RETENTION_DAYS` have passed. Until then the user can't log in,
+/// is hidden from their organizations, and the account can be restored by an admin.
+/// Without a retention period the account is deleted immediately.
+pub async fn schedule_user_deletion(
+    mut user: User,
+    by_admin: bool,
+    device_type: i32,
+    ip: &IpAddr,
+    conn: &mut DbConn,
+) -> EmptyResult {
+    if CONFIG.user_deletion_retention_days() == 0 {
+        return user.delete(conn).await;
+    }
+    if user.is_deleted() {
+        err!("The account is already deleted")
+    }
+    for user_org in UserOrganization::find_confirmed_by_user(&user.uuid, conn).await {
+        let owners =
+            UserOrganization::count_confirmed_by_org_and_type(&user_org.org_uuid, UserOrgType::Owner, conn).await;
+        if is_last_owner(user_org.atype, owners) {
+            err!("Can't delete last owner")
+        }
+    }
+
+    Device::delete_all_by_user(&user.uuid, conn).await?;
+    user.reset_security_stamp();
+    user.deleted_at = Some(Utc::now().naive_utc());
+    user.save(conn).await?;
+    WS_USERS.send_logout(&user, None).await;
+    log_user_event(EventType::UserDeleted as i32, &user.uuid, device_type, ip, conn).await;
+
+    if CONFIG.mail_enabled() {
+        if let Err(e) = mail::send_account_deletion_scheduled(
+            &user.email,
+            user.locale.as_deref(),
+            by_admin,
+            &user.purge_date().unwrap(),
+        )
+        .await
+        {
+            error!("Error sending account deletion email to {}: {e:#?}", user.email);
+        }
+    }
+    Ok(())
+}
+
+// A deleted account doesn't count as an owner anymore, so the last confirmed owner can't be deleted
+fn is_last_owner(atype: i32, confirmed_owners: i64) -> bool {
+    atype == UserOrgType::Owner && confirmed_owners <= 1
+}
+
+/// Restores a deleted account, the user has to log in again on all of their devices
+pub async fn restore_user(mut user: User, ip: &IpAddr, conn: &mut DbConn) -> EmptyResult {
+    if !user.is_deleted() {
+        err!("The account is not deleted")
+    }
+
+    user.deleted_at = None;
+    user.save(conn).await?;
+    log_user_event(EventType::UserRestored as i32, &user.uuid, DeviceType::UnknownBrowser as i32, ip, conn).await;
+
+    if CONFIG.mail_enabled() {
+        if let Err(e) = mail::send_account_restored(&user.email, user.locale.as_deref()).await {
+            error!("Error sending account restored email to {}: {e:#?}", user.email);
+        }
+    }
+    Ok(())
 }
 
 #[get("/accounts/revision-date")]
@@ -871,7 +1101,7 @@ async fn password_hint(data: Json<PasswordHintData>, mut conn: DbConn) -> EmptyR
         Some(user) => {
             let hint: Option<String> = user.password_hint;
             if CONFIG.mail_enabled() {
-                mail::send_password_hint(email, hint).await?;
+                mail::send_password_hint(email, user.locale.as_deref(), user.pgp_public_key.as_deref(), hint).await?;
                 Ok(())
             } else if let Some(hint) = hint {
                 err!(format!("Your password hint is: {hint}"));
@@ -1276,11 +1506,318 @@ async fn get_auth_requests(headers: Headers, mut conn: DbConn) -> JsonResult {
     })))
 }
 
-pub async fn purge_auth_requests(pool: DbPool) {
+pub async fn purge_auth_requests(pool: DbPool) -> JobResult {
     debug!("Purging auth requests");
     if let Ok(mut conn) = pool.get().await {
-        AuthRequest::purge_expired_auth_requests(&mut conn).await;
+        Ok(AuthRequest::purge_expired_auth_requests(&mut conn).await)
     } else {
-        error!("Failed to get DB connection while purging trashed ciphers")
+        err!("Failed to get DB connection while purging auth requests")
+    }
+}
+
+/// Warns, then disables and finally (optionally) deletes unverified or inactive accounts,
+/// see [`User::next_lifecycle_action`] for when each of these actions is due.
+pub async fn account_lifecycle_job(pool: DbPool) -> JobResult {
+    debug!("Start account lifecycle job");
+    let Ok(mut conn) = pool.get().await else {
+        err!("Failed to get DB connection while running the account lifecycle job")
+    };
+
+    let now = Utc::now().naive_utc();
+    let ip = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+    let mut affected = 0;
+    let last_active: HashMap<String, Option<NaiveDateTime>> =
+        Device::find_last_active_all(&mut conn).await.into_iter().collect();
+    let org_owners: HashSet<String> = UserOrganization::find_owner_user_uuids(&mut conn).await.into_iter().collect();
+    for mut user in User::get_all(&mut conn).await {
+        let Some((action, due)) = user.next_lifecycle_action(last_active.get(&user.uuid).copied().flatten()) else {
+            continue;
+        };
+        if due > now || org_owners.contains(&user.uuid) {
+            continue;
+        }
+
+        let email = user.email.clone();
+        match action {
+            UserLifecycleAction::Warn => {
+                let disable_date = now + TimeDelta::try_days(CONFIG.account_lifecycle_warning_days()).unwrap();
+                if let Err(e) = mail::send_account_lifecycle_warning(
+                    &user.email,
+                    user.locale.as_deref(),
+                    user.verified_at.is_none(),
+                    &disable_date,
+                    CONFIG.account_lifecycle_delete_days(),
+                )
+                .await
+                {
+                    error!("Error sending account lifecycle warning to {email}: {e:#?}");
+                    continue;
+                }
+                user.lifecycle_warned_at = Some(now);
+                user.save(&mut conn).await?;
+                log_user_event(
+                    EventType::UserLifecycleWarned as i32,
+                    &user.uuid,
+                    DeviceType::UnknownBrowser as i32,
+                    &ip,
+                    &mut conn,
+                )
+                .await;
+            }
+            UserLifecycleAction::Disable => {
+                Device::delete_all_by_user(&user.uuid, &mut conn).await?;
+                user.reset_security_stamp();
+                user.enabled = false;
+                user.lifecycle_disabled_at = Some(now);
+                user.save(&mut conn).await?;
+                WS_USERS.send_logout(&user, None).await;
+                log_user_event(
+                    EventType::UserLifecycleDisabled as i32,
+                    &user.uuid,
+                    DeviceType::UnknownBrowser as i32,
+                    &ip,
+                    &mut conn,
+                )
+                .await;
+            }
+            UserLifecycleAction::Delete => {
+                // Log before deleting, the event is also stored for each organization the user is a member of
+                log_user_event(
+                    EventType::UserLifecycleDeleted as i32,
+                    &user.uuid,
+                    DeviceType::UnknownBrowser as i32,
+                    &ip,
+                    &mut conn,
+                )
+                .await;
+                // The account can still be restored by an admin until the deletion retention period has passed
+                if let Err(e) =
+                    schedule_user_deletion(user, false, DeviceType::UnknownBrowser as i32, &ip, &mut conn).await
+                {
+                    error!("Error deleting stale account {email}: {e:#?}");
+                    continue;
+                }
+            }
+        }
+        info!("Account lifecycle: {} done for {email}", action.as_str());
+        affected += 1;
+    }
+
+    Ok(affected)
+}
+
+/// Permanently deletes the accounts whose deletion retention period has passed, and lets their users know.
+pub async fn user_deletion_purge_job(pool: DbPool) -> JobResult {
+    debug!("Purging deleted accounts");
+    let Ok(mut conn) = pool.get().await else {
+        err!("Failed to get DB connection while purging deleted accounts")
+    };
+
+    let cutoff = Utc::now().naive_utc() - TimeDelta::try_days(CONFIG.user_deletion_retention_days()).unwrap();
+    let ip = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+    let mut purged = 0;
+    for user in User::find_deleted_before(&cutoff, &mut conn).await {
+        // Log before deleting, the event is also stored for each organization the user is a member of
+        log_user_event(EventType::UserPurged as i32, &user.uuid, DeviceType::UnknownBrowser as i32, &ip, &mut conn)
+            .await;
+        let (email, locale) = (user.email.clone(), user.locale.clone());
+        let user_uuid = user.uuid.clone();
+        if let Err(e) = user.delete(&mut conn).await {
+            error!("Error purging deleted account {user_uuid}: {e:#?}");
+            continue;
+        }
+        purged += 1;
+
+        if CONFIG.mail_enabled() {
+            if let Err(e) = mail::send_account_purged(&email, locale.as_deref()).await {
+                error!("Error sending account purged email to {email}: {e:#?}");
+            }
+        }
+    }
+
+    Ok(purged)
+}
+
+/// Sends a summary of the security related activity of the past week to the users who opted in to it.
+/// Digests without any activity are not sent. Apart from the new devices, everything is taken from the
+/// stored events, so `ORG_EVENTS_ENABLED` needs to be set for the digest to be complete.
+pub async fn security_digest_job(pool: DbPool) -> JobResult {
+    debug!("Start security digest job");
+    if !CONFIG.mail_enabled() {
+        return Ok(0);
+    }
+    let Ok(mut conn) = pool.get().await else {
+        err!("Failed to get DB connection while running the security digest job")
+    };
+
+    let now = Utc::now().naive_utc();
+    let since = now - TimeDelta::try_days(7).unwrap();
+    let mut sent = 0;
+    let prefs: HashMap<String, NotificationPreference> =
+        NotificationPreference::find_all(&mut conn).await.into_iter().map(|p| (p.user_uuid.clone(), p)).collect();
+    for user in User::get_all(&mut conn).await {
+        let enabled = match prefs.get(&user.uuid) {
+            Some(prefs) => prefs.is_enabled(NotificationCategory::SecurityDigest),
+            None => NotificationCategory::SecurityDigest.default_enabled(),
+        };
+        if !enabled || !user.enabled || user.is_deleted() {
+            continue;
+        }
+
+        let Some(digest) = security_digest(&user, &since, &mut conn).await else {
+            continue;
+        };
+        if let Err(e) = mail::send_security_digest(&user.email, user.locale.as_deref(), &since, &now, digest).await {
+            error!("Error sending security digest to {}: {e:#?}", user.email);
+            continue;
+        }
+        sent += 1;
+    }
+
+    Ok(sent)
+}
+
+/// Collects the activity of the user since the given date, returns `None` if there was none.
+async fn security_digest(user: &User, since: &NaiveDateTime, conn: &mut DbConn) -> Option<Value> {
+    use crate::util::format_naive_datetime_local;
+    const FMT: &str = "%a, %b %_d at %H:%M %Z";
+
+    let new_devices: Vec<Value> = Device::find_by_user(&user.uuid, conn)
+        .await
+        .into_iter()
+        .filter(|d| d.created_at >= *since)
+        .map(|d| {
+            json!({
+                "name": d.name,
+                "type": DeviceType::from_i32(d.atype).to_string(),
+                "date": format_naive_datetime_local(&d.created_at, FMT),
+            })
+        })
+        .collect();
+
+    let mut failed_logins = Vec::new();
+    let mut twofactor_changes = Vec::new();
+    let mut send_accesses = 0;
+    for event in Event::find_by_user_since(&user.uuid, since, conn).await {
+        let date = format_naive_datetime_local(&event.event_date, FMT);
+        let ip = event.ip_address.unwrap_or_default();
+        match event.event_type {
+            t if t == EventType::UserFailedLogIn as i32 => {
+                failed_logins.push(json!({"date": date, "ip": ip, "reason": "Wrong master password"}))
+            }
+            t if t == EventType::UserFailedLogIn2fa as i32 => {
+                failed_logins.push(json!({"date": date, "ip": ip, "reason": "Wrong two-step login code"}))
+            }
+            t if t == EventType::UserUpdated2fa as i32 => {
+                twofactor_changes.push(json!({"date": date, "action": "Two-step login method added or updated"}))
+            }
+            t if t == EventType::UserDisabled2fa as i32 => {
+                twofactor_changes.push(json!({"date": date, "action": "Two-step login method removed"}))
+            }
+            t if t == EventType::UserRecovered2fa as i32 => twofactor_changes
+                .push(json!({"date": date, "action": "Two-step login recovered with the recovery code"})),
+            t if t == EventType::UserSendAccessed as i32 => send_accesses += 1,
+            _ => {}
+        }
+    }
+
+    let mut emergency_access = Vec::new();
+    for ea in EmergencyAccess::find_all_by_grantor_uuid(&user.uuid, conn).await {
+        if ea.updated_at < *since {
+            continue;
+        }
+        let grantee = match &ea.grantee_uuid {
+            Some(grantee_uuid) => User::find_by_uuid(grantee_uuid, conn).await.map(|u| u.email),
+            None => ea.email.clone(),
+        };
+        emergency_access.push(json!({
+            "name": grantee.unwrap_or_default(),
+            "role": "your emergency contact",
+            "status": emergency_access_status(ea.status),
+            "date": format_naive_datetime_local(&ea.updated_at, FMT),
+        }));
+    }
+    for ea in EmergencyAccess::find_all_by_grantee_uuid(&user.uuid, conn).await {
+        if ea.updated_at < *since {
+            continue;
+        }
+        let grantor = User::find_by_uuid(&ea.grantor_uuid, conn).await.map(|u| u.email);
+        emergency_access.push(json!({
+            "name": grantor.unwrap_or_default(),
+            "role": "you are their emergency contact",
+            "status": emergency_access_status(ea.status),
+            "date": format_naive_datetime_local(&ea.updated_at, FMT),
+        }));
+    }
+
+    let memberships = UserOrganization::find_any_state_by_user(&user.uuid, conn).await;
+    let membership_uuids: Vec<String> = memberships.iter().map(|m| m.uuid.clone()).collect();
+    let mut org_changes = Vec::new();
+    for event in Event::find_by_org_users_since(&membership_uuids, since, conn).await {
+        let action = match event.event_type {
+            t if t == EventType::OrganizationUserInvited as i32 => "You were invited",
+            t if t == EventType::OrganizationUserConfirmed as i32 => "Your membership was confirmed",
+            t if t == EventType::OrganizationUserUpdated as i32 => "Your role or access was changed",
+            t if t == EventType::OrganizationUserUpdatedGroups as i32 => "Your groups were changed",
+            t if t == EventType::OrganizationUserResetPasswordEnroll as i32 => "You enrolled in account recovery",
+            t if t == EventType::OrganizationUserResetPasswordWithdraw as i32 => "You withdrew from account recovery",
+            t if t == EventType::OrganizationUserAdminResetPassword as i32 => "An admin reset your master password",
+            t if t == EventType::OrganizationUserRevoked as i32 => "Your access was revoked",
+            t if t == EventType::OrganizationUserRestored as i32 => "Your access was restored",
+            _ => continue,
+        };
+        let org_name = match &event.org_uuid {
+            Some(org_uuid) => Organization::find_by_uuid(org_uuid, conn).await.map(|o| o.name),
+            None => None,
+        };
+        org_changes.push(json!({
+            "org_name": org_name.unwrap_or_default(),
+            "action": action,
+            "date": format_naive_datetime_local(&event.event_date, FMT),
+        }));
+    }
+
+    if new_devices.is_empty()
+        && failed_logins.is_empty()
+        && twofactor_changes.is_empty()
+        && emergency_access.is_empty()
+        && send_accesses == 0
+        && org_changes.is_empty()
+    {
+        return None;
+    }
+
+    Some(json!({
+        "new_devices": new_devices,
+        "failed_logins": failed_logins,
+        "twofactor_changes": twofactor_changes,
+        "emergency_access": emergency_access,
+        "send_accesses": send_accesses,
+        "org_changes": org_changes,
+    }))
+}
+
+fn emergency_access_status(status: i32) -> &'static str {
+    match status {
+        s if s == EmergencyAccessStatus::Invited as i32 => "Invited",
+        s if s == EmergencyAccessStatus::Accepted as i32 => "Accepted",
+        s if s == EmergencyAccessStatus::Confirmed as i32 => "Confirmed",
+        s if s == EmergencyAccessStatus::RecoveryInitiated as i32 => "Access requested",
+        s if s == EmergencyAccessStatus::RecoveryApproved as i32 => "Access granted",
+        _ => "Unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_last_owner() {
+        assert!(is_last_owner(UserOrgType::Owner as i32, 1));
+        assert!(is_last_owner(UserOrgType::Owner as i32, 0));
+        assert!(!is_last_owner(UserOrgType::Owner as i32, 2));
+        assert!(!is_last_owner(UserOrgType::Admin as i32, 1));
+        assert!(!is_last_owner(UserOrgType::User as i32, 0));
     }
 }
@@ -16,6 +16,7 @@ use crate::{
     auth::Headers,
     crypto,
     db::{models::*, DbConn, DbPool},
+    jobs::JobResult,
     CONFIG,
 };
 
@@ -88,12 +89,12 @@ pub fn routes() -> Vec<Route> {
     ]
 }
 
-pub async fn purge_trashed_ciphers(pool: DbPool) {
+pub async fn purge_trashed_ciphers(pool: DbPool) -> JobResult {
     debug!("Purging trashed ciphers");
     if let Ok(mut conn) = pool.get().await {
-        Cipher::purge_trash(&mut conn).await;
+        Ok(Cipher::purge_trash(&mut conn).await)
     } else {
-        error!("Failed to get DB connection while purging trashed ciphers")
+        err!("Failed to get DB connection while purging trashed ciphers")
     }
 }
 
@@ -387,11 +388,17 @@ pub async fn update_cipher_from_data(
     let transfer_cipher = cipher.organization_uuid.is_none() && data.organization_id.is_some();
 
     if let Some(org_id) = data.organization_id {
+        // Organizations which are scheduled for deletion are disabled, until one of the owners restores them
+        if Organization::find_by_uuid(&org_id, conn).await.is_some_and(|org| org.is_pending_deletion()) {
+            err!("The organization is scheduled for deletion")
+        }
         match UserOrganization::find_by_user_and_org(&headers.user.uuid, &org_id, conn).await {
             None => err!("You don't have permission to add item to organization"),
             Some(org_user) => {
                 if shared_to_collections.is_some()
-                    || org_user.has_full_access()
+                    || Organization::find_by_uuid(&org_id, conn).await.is_some_and(|org| {
+                        org.has_full_item_access(&org_user) && !org.restricts_item_editing(&org_user)
+                    })
                     || cipher.is_write_accessible_to_user(&headers.user.uuid, conn).await
                 {
                     cipher.organization_uuid = Some(org_id);
@@ -1844,6 +1851,9 @@ pub struct CipherSyncData {
     pub user_collections: HashMap<String, CollectionUser>,
     pub user_collections_groups: HashMap<String, CollectionGroup>,
     pub user_group_full_access_for_organizations: HashSet<String>,
+    pub user_full_access_for_organizations: HashSet<String>,
+    pub item_editing_restricted_organizations: HashSet<String>,
+    pub pending_deletion_organizations: HashSet<String>,
 }
 
 #[derive(Eq, PartialEq)]
@@ -1921,6 +1931,26 @@ impl CipherSyncData {
             HashSet::new()
         };
 
+        // Get all organizations in which the user has access to all items, and those which don't allow the user to edit items of collections they don't manage
+        let mut user_full_access_for_organizations: HashSet<String> = HashSet::new();
+        let mut item_editing_restricted_organizations: HashSet<String> = HashSet::new();
+        let mut pending_deletion_organizations: HashSet<String> = HashSet::new();
+        let org_uuids: Vec<String> = user_organizations.keys().cloned().collect();
+        for org in Organization::find_by_uuids(&org_uuids, conn).await {
+            let Some(user_org) = user_organizations.get(&org.uuid) else {
+                continue;
+            };
+            if org.has_full_item_access(user_org) {
+                user_full_access_for_organizations.insert(org.uuid.clone());
+            }
+            if org.is_pending_deletion() {
+                pending_deletion_organizations.insert(org.uuid.clone());
+            }
+            if org.restricts_item_editing(user_org) {
+                item_editing_restricted_organizations.insert(org.uuid);
+            }
+        }
+
         Self {
             cipher_attachments,
             cipher_folders,
@@ -1930,6 +1960,9 @@ impl CipherSyncData {
             user_collections,
             user_collections_groups,
             user_group_full_access_for_organizations,
+            user_full_access_for_organizations,
+            item_editing_restricted_organizations,
+            pending_deletion_organizations,
         }
     }
 }
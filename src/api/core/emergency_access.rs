@@ -5,10 +5,11 @@ use serde_json::Value;
 use crate::{
     api::{
         core::{CipherSyncData, CipherSyncType},
-        EmptyResult, JsonResult,
+        ApiResult, EmptyResult, JsonResult,
     },
     auth::{decode_emergency_access_invite, Headers},
     db::{models::*, DbConn, DbPool},
+    jobs::JobResult,
     mail,
     util::NumberOrString,
     CONFIG,
@@ -266,6 +267,8 @@ async fn send_invite(data: Json<EmergencyAccessInviteData>, headers: Headers, mu
     if CONFIG.mail_enabled() {
         mail::send_emergency_access_invite(
             &new_emergency_access.email.expect("Grantee email does not exists"),
+            grantee_user.locale.as_deref(),
+            grantee_user.pgp_public_key.as_deref(),
             &grantee_user.uuid,
             &new_emergency_access.uuid,
             &grantor_user.name,
@@ -309,6 +312,8 @@ async fn resend_invite(emer_id: &str, headers: Headers, mut conn: DbConn) -> Emp
     if CONFIG.mail_enabled() {
         mail::send_emergency_access_invite(
             &email,
+            grantee_user.locale.as_deref(),
+            grantee_user.pgp_public_key.as_deref(),
             &grantor_user.uuid,
             &emergency_access.uuid,
             &grantor_user.name,
@@ -363,10 +368,7 @@ async fn accept_invite(emer_id: &str, data: Json<AcceptData>, headers: Headers,
         };
 
     // get grantor user to send Accepted email
-    let grantor_user = match User::find_by_uuid(&emergency_access.grantor_uuid, &mut conn).await {
-        Some(user) => user,
-        None => err!("Grantor user not found."),
-    };
+    let grantor_user = find_active_grantor(&emergency_access, &mut conn).await?;
 
     if emer_id == claims.emer_id
         && grantor_user.name == claims.grantor_name
@@ -375,7 +377,14 @@ async fn accept_invite(emer_id: &str, data: Json<AcceptData>, headers: Headers,
         emergency_access.accept_invite(&grantee_user.uuid, &grantee_user.email, &mut conn).await?;
 
         if CONFIG.mail_enabled() {
-            mail::send_emergency_access_invite_accepted(&grantor_user.email, &grantee_user.email).await?;
+            mail::send_emergency_access_invite_accepted(
+                &grantor_user.email,
+                grantor_user.locale.as_deref(),
+                grantor_user.pgp_public_key.as_deref(),
+                &grantee_user.email,
+                &NotificationPreference::find_or_default_by_user(&grantor_user.uuid, &mut conn).await,
+            )
+            .await?;
         }
 
         Ok(())
@@ -433,7 +442,14 @@ async fn confirm_emergency_access(
         emergency_access.save(&mut conn).await?;
 
         if CONFIG.mail_enabled() {
-            mail::send_emergency_access_invite_confirmed(&grantee_user.email, &grantor_user.name).await?;
+            mail::send_emergency_access_invite_confirmed(
+                &grantee_user.email,
+                grantee_user.locale.as_deref(),
+                grantee_user.pgp_public_key.as_deref(),
+                &grantor_user.name,
+                &NotificationPreference::find_or_default_by_user(&grantee_user.uuid, &mut conn).await,
+            )
+            .await?;
         }
         Ok(Json(emergency_access.to_json()))
     } else {
@@ -460,10 +476,7 @@ async fn initiate_emergency_access(emer_id: &str, headers: Headers, mut conn: Db
         err!("Emergency access not valid.")
     }
 
-    let grantor_user = match User::find_by_uuid(&emergency_access.grantor_uuid, &mut conn).await {
-        Some(user) => user,
-        None => err!("Grantor user not found."),
-    };
+    let grantor_user = find_active_grantor(&emergency_access, &mut conn).await?;
 
     let now = Utc::now().naive_utc();
     emergency_access.status = EmergencyAccessStatus::RecoveryInitiated as i32;
@@ -475,6 +488,8 @@ async fn initiate_emergency_access(emer_id: &str, headers: Headers, mut conn: Db
     if CONFIG.mail_enabled() {
         mail::send_emergency_access_recovery_initiated(
             &grantor_user.email,
+            grantor_user.locale.as_deref(),
+            grantor_user.pgp_public_key.as_deref(),
             &initiating_user.name,
             emergency_access.get_type_as_str(),
             &emergency_access.wait_time_days,
@@ -513,7 +528,14 @@ async fn approve_emergency_access(emer_id: &str, headers: Headers, mut conn: DbC
         emergency_access.save(&mut conn).await?;
 
         if CONFIG.mail_enabled() {
-            mail::send_emergency_access_recovery_approved(&grantee_user.email, &grantor_user.name).await?;
+            mail::send_emergency_access_recovery_approved(
+                &grantee_user.email,
+                grantee_user.locale.as_deref(),
+                grantee_user.pgp_public_key.as_deref(),
+                &grantor_user.name,
+                &NotificationPreference::find_or_default_by_user(&grantee_user.uuid, &mut conn).await,
+            )
+            .await?;
         }
         Ok(Json(emergency_access.to_json()))
     } else {
@@ -547,7 +569,14 @@ async fn reject_emergency_access(emer_id: &str, headers: Headers, mut conn: DbCo
         emergency_access.save(&mut conn).await?;
 
         if CONFIG.mail_enabled() {
-            mail::send_emergency_access_recovery_rejected(&grantee_user.email, &headers.user.name).await?;
+            mail::send_emergency_access_recovery_rejected(
+                &grantee_user.email,
+                grantee_user.locale.as_deref(),
+                grantee_user.pgp_public_key.as_deref(),
+                &headers.user.name,
+                &NotificationPreference::find_or_default_by_user(&grantee_user.uuid, &mut conn).await,
+            )
+            .await?;
         }
         Ok(Json(emergency_access.to_json()))
     } else {
@@ -573,6 +602,8 @@ async fn view_emergency_access(emer_id: &str, headers: Headers, mut conn: DbConn
         err!("Emergency access not valid.")
     }
 
+    find_active_grantor(&emergency_access, &mut conn).await?;
+
     let ciphers = Cipher::find_owned_by_user(&emergency_access.grantor_uuid, &mut conn).await;
     let cipher_sync_data = CipherSyncData::new(&emergency_access.grantor_uuid, CipherSyncType::User, &mut conn).await;
 
@@ -612,10 +643,7 @@ async fn takeover_emergency_access(emer_id: &str, headers: Headers, mut conn: Db
         err!("Emergency access not valid.")
     }
 
-    let grantor_user = match User::find_by_uuid(&emergency_access.grantor_uuid, &mut conn).await {
-        Some(user) => user,
-        None => err!("Grantor user not found."),
-    };
+    let grantor_user = find_active_grantor(&emergency_access, &mut conn).await?;
 
     let result = json!({
         "kdf": grantor_user.client_kdf_type,
@@ -660,10 +688,7 @@ async fn password_emergency_access(
         err!("Emergency access not valid.")
     }
 
-    let mut grantor_user = match User::find_by_uuid(&emergency_access.grantor_uuid, &mut conn).await {
-        Some(user) => user,
-        None => err!("Grantor user not found."),
-    };
+    let mut grantor_user = find_active_grantor(&emergency_access, &mut conn).await?;
 
     // change grantor_user password
     grantor_user.set_password(new_master_password_hash, Some(data.key), true, None);
@@ -696,10 +721,7 @@ async fn policies_emergency_access(emer_id: &str, headers: Headers, mut conn: Db
         err!("Emergency access not valid.")
     }
 
-    let grantor_user = match User::find_by_uuid(&emergency_access.grantor_uuid, &mut conn).await {
-        Some(user) => user,
-        None => err!("Grantor user not found."),
-    };
+    let grantor_user = find_active_grantor(&emergency_access, &mut conn).await?;
 
     let policies = OrgPolicy::find_confirmed_by_user(&grantor_user.uuid, &mut conn);
     let policies_json: Vec<Value> = policies.await.iter().map(OrgPolicy::to_json).collect();
@@ -722,6 +744,14 @@ fn is_valid_request(
         && emergency_access.atype == requested_access_type as i32
 }
 
+/// The emergency access to a deleted account is disabled until the account is restored
+async fn find_active_grantor(emergency_access: &EmergencyAccess, conn: &mut DbConn) -> ApiResult<User> {
+    match User::find_by_uuid(&emergency_access.grantor_uuid, conn).await {
+        Some(user) if !user.is_deleted() => Ok(user),
+        _ => err!("Grantor user not found."),
+    }
+}
+
 fn check_emergency_access_enabled() -> EmptyResult {
     if !CONFIG.emergency_access_allowed() {
         err!("Emergency access is not enabled.")
@@ -729,13 +759,14 @@ fn check_emergency_access_enabled() -> EmptyResult {
     Ok(())
 }
 
-pub async fn emergency_request_timeout_job(pool: DbPool) {
+pub async fn emergency_request_timeout_job(pool: DbPool) -> JobResult {
     debug!("Start emergency_request_timeout_job");
     if !CONFIG.emergency_access_allowed() {
-        return;
+        return Ok(0);
     }
 
     if let Ok(mut conn) = pool.get().await {
+        let mut approved = 0;
         let emergency_access_list = EmergencyAccess::find_all_recoveries_initiated(&mut conn).await;
 
         if emergency_access_list.is_empty() {
@@ -753,6 +784,7 @@ pub async fn emergency_request_timeout_job(pool: DbPool) {
                 emer.update_access_status_and_save(EmergencyAccessStatus::RecoveryApproved as i32, &now, &mut conn)
                     .await
                     .expect("Unable to update emergency access status");
+                approved += 1;
 
                 if CONFIG.mail_enabled() {
                     // get grantor user to send Accepted email
@@ -767,30 +799,40 @@ pub async fn emergency_request_timeout_job(pool: DbPool) {
 
                     mail::send_emergency_access_recovery_timed_out(
                         &grantor_user.email,
+                        grantor_user.locale.as_deref(),
+                        grantor_user.pgp_public_key.as_deref(),
                         &grantee_user.name,
                         emer.get_type_as_str(),
                     )
                     .await
                     .expect("Error on sending email");
 
-                    mail::send_emergency_access_recovery_approved(&grantee_user.email, &grantor_user.name)
-                        .await
-                        .expect("Error on sending email");
+                    mail::send_emergency_access_recovery_approved(
+                        &grantee_user.email,
+                        grantee_user.locale.as_deref(),
+                        grantee_user.pgp_public_key.as_deref(),
+                        &grantor_user.name,
+                        &NotificationPreference::find_or_default_by_user(&grantee_user.uuid, &mut conn).await,
+                    )
+                    .await
+                    .expect("Error on sending email");
                 }
             }
         }
+        Ok(approved)
     } else {
-        error!("Failed to get DB connection while searching emergency request timed out")
+        err!("Failed to get DB connection while searching emergency request timed out")
     }
 }
 
-pub async fn emergency_notification_reminder_job(pool: DbPool) {
+pub async fn emergency_notification_reminder_job(pool: DbPool) -> JobResult {
     debug!("Start emergency_notification_reminder_job");
     if !CONFIG.emergency_access_allowed() {
-        return;
+        return Ok(0);
     }
 
     if let Ok(mut conn) = pool.get().await {
+        let mut reminded = 0;
         let emergency_access_list = EmergencyAccess::find_all_recoveries_initiated(&mut conn).await;
 
         if emergency_access_list.is_empty() {
@@ -815,6 +857,7 @@ pub async fn emergency_notification_reminder_job(pool: DbPool) {
                 emer.update_last_notification_date_and_save(&now, &mut conn)
                     .await
                     .expect("Unable to update emergency access notification date");
+                reminded += 1;
 
                 if CONFIG.mail_enabled() {
                     // get grantor user to send Accepted email
@@ -829,16 +872,20 @@ pub async fn emergency_notification_reminder_job(pool: DbPool) {
 
                     mail::send_emergency_access_recovery_reminder(
                         &grantor_user.email,
+                        grantor_user.locale.as_deref(),
+                        grantor_user.pgp_public_key.as_deref(),
                         &grantee_user.name,
                         emer.get_type_as_str(),
                         "1", // This notification is only triggered one day before the activation
+                        &NotificationPreference::find_or_default_by_user(&grantor_user.uuid, &mut conn).await,
                     )
                     .await
                     .expect("Error on sending email");
                 }
             }
         }
+        Ok(reminded)
     } else {
-        error!("Failed to get DB connection while searching emergency notification reminder")
+        err!("Failed to get DB connection while searching emergency notification reminder")
     }
 }
@@ -6,11 +6,12 @@ use serde_json::Value;
 
 use crate::{
     api::{EmptyResult, JsonResult},
-    auth::{AdminHeaders, Headers},
+    auth::{EventLogHeaders, Headers},
     db::{
-        models::{Cipher, Event, UserOrganization},
+        models::{Cipher, Event, EventType, UserOrganization},
         DbConn, DbPool,
     },
+    jobs::JobResult,
     util::parse_date,
     CONFIG,
 };
@@ -31,7 +32,7 @@ struct EventRange {
 
 // Upstream: https://github.com/bitwarden/server/blob/9ecf69d9cabce732cf2c57976dd9afa5728578fb/src/Api/Controllers/EventsController.cs#LL84C35-L84C41
 #[get("/organizations/<org_id>/events?<data..>")]
-async fn get_org_events(org_id: &str, data: EventRange, _headers: AdminHeaders, mut conn: DbConn) -> JsonResult {
+async fn get_org_events(org_id: &str, data: EventRange, _headers: EventLogHeaders, mut conn: DbConn) -> JsonResult {
     // Return an empty vec when we org events are disabled.
     // This prevents client errors
     let events_json: Vec<Value> = if !CONFIG.org_events_enabled() {
@@ -95,7 +96,7 @@ async fn get_user_events(
     org_id: &str,
     user_org_id: &str,
     data: EventRange,
-    _headers: AdminHeaders,
+    _headers: EventLogHeaders,
     mut conn: DbConn,
 ) -> JsonResult {
     // Return an empty vec when we org events are disabled.
@@ -258,6 +259,19 @@ async fn _log_user_event(
     Event::save_user_event(events, conn).await.unwrap_or(());
 }
 
+/// Logs that a Send was accessed, for the security digest of its owner.
+/// Unlike the other user events this is not stored for the organizations of the user, as Sends are personal.
+pub async fn log_send_access(user_uuid: &str, ip: &IpAddr, conn: &mut DbConn) {
+    if !CONFIG.org_events_enabled() {
+        return;
+    }
+
+    let mut event = Event::new(EventType::UserSendAccessed as i32, None);
+    event.user_uuid = Some(String::from(user_uuid));
+    event.ip_address = Some(ip.to_string());
+    event.save(conn).await.unwrap_or(());
+}
+
 pub async fn log_event(
     event_type: i32,
     source_uuid: &str,
@@ -320,16 +334,16 @@ async fn _log_event(
     event.save(conn).await.unwrap_or(());
 }
 
-pub async fn event_cleanup_job(pool: DbPool) {
+pub async fn event_cleanup_job(pool: DbPool) -> JobResult {
     debug!("Start events cleanup job");
     if CONFIG.events_days_retain().is_none() {
         debug!("events_days_retain is not configured, abort");
-        return;
+        return Ok(0);
     }
 
     if let Ok(mut conn) = pool.get().await {
-        Event::clean_events(&mut conn).await.ok();
+        Event::clean_events(&mut conn).await
     } else {
-        error!("Failed to get DB connection while trying to cleanup the events table")
+        err!("Failed to get DB connection while trying to cleanup the events table")
     }
 }
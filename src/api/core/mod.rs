@@ -3,15 +3,23 @@ mod ciphers;
 mod emergency_access;
 mod events;
 mod folders;
+pub mod org_domains;
 mod organizations;
 mod public;
+mod reports;
 mod sends;
 pub mod two_factor;
 
-pub use accounts::purge_auth_requests;
+pub use accounts::{
+    account_lifecycle_job, purge_auth_requests, restore_user, schedule_user_deletion, security_digest_job,
+    user_deletion_purge_job,
+};
 pub use ciphers::{purge_trashed_ciphers, CipherData, CipherSyncData, CipherSyncType};
 pub use emergency_access::{emergency_notification_reminder_job, emergency_request_timeout_job};
-pub use events::{event_cleanup_job, log_event, log_user_event};
+pub use events::{event_cleanup_job, log_event, log_send_access, log_user_event};
+pub use org_domains::{auto_enroll_user, org_domain_verification_job};
+pub use organizations::{org_deletion_purge_job, purge_expired_invitations, restore_org, schedule_org_deletion};
+pub use reports::{build_member_security_report, member_security_report_csv, MemberSecurity};
 use reqwest::Method;
 pub use sends::purge_sends;
 
@@ -26,10 +34,12 @@ pub fn routes() -> Vec<Route> {
     routes.append(&mut emergency_access::routes());
     routes.append(&mut events::routes());
     routes.append(&mut folders::routes());
+    routes.append(&mut org_domains::routes());
     routes.append(&mut organizations::routes());
     routes.append(&mut two_factor::routes());
     routes.append(&mut sends::routes());
     routes.append(&mut public::routes());
+    routes.append(&mut reports::routes());
     routes.append(&mut eq_domains_routes);
     routes.append(&mut hibp_routes);
     routes.append(&mut meta_routes);
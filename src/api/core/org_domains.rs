@@ -0,0 +1,445 @@
+use std::net::{IpAddr, SocketAddr};
+
+use hickory_resolver::{
+    config::{NameServerConfigGroup, ResolverConfig, ResolverOpts},
+    system_conf::read_system_conf,
+    TokioAsyncResolver,
+};
+use rocket::{serde::json::Json, Route};
+use serde_json::Value;
+
+use crate::{
+    api::{core::log_event, ApiResult, EmptyResult, JsonResult},
+    auth::{AdminHeaders, ClientHeaders, Headers, OwnerHeaders},
+    db::{
+        models::{
+            email_domain, AutoEnrollCollection, Collection, CollectionUser, EventType, Group, GroupUser,
+            NotificationPreference, OrgPolicy, Organization, OrganizationDomain, User, UserOrgStatus, UserOrgType,
+            UserOrganization,
+        },
+        DbConn, DbPool,
+    },
+    jobs::JobResult,
+    mail,
+    util::NumberOrString,
+    CONFIG,
+};
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        get_org_domains,
+        get_org_domain,
+        post_org_domain,
+        verify_org_domain,
+        delete_org_domain,
+        post_delete_org_domain,
+        put_org_domain_auto_enroll,
+        get_org_suggestions,
+    ]
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OrgDomainData {
+    domain_name: String,
+}
+
+/// Normalizes a domain name entered by a user, returns `None` if it doesn't look like a domain name
+fn normalize_domain_name(domain_name: &str) -> Option<String> {
+    let domain_name = domain_name.trim().trim_end_matches('.').to_lowercase();
+    let valid = domain_name.contains('.')
+        && domain_name.len() <= 253
+        && domain_name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+    valid.then_some(domain_name)
+}
+
+fn dns_resolver() -> ApiResult<TokioAsyncResolver> {
+    match CONFIG.org_domain_dns_server() {
+        Some(server) => {
+            let addr = match server.parse::<SocketAddr>() {
+                Ok(addr) => addr,
+                Err(_) => match server.parse::<IpAddr>() {
+                    Ok(ip) => SocketAddr::new(ip, 53),
+                    Err(_) => err!("Invalid domain verification DNS server"),
+                },
+            };
+            let name_servers = NameServerConfigGroup::from_ips_clear(&[addr.ip()], addr.port(), true);
+            let config = ResolverConfig::from_parts(None, vec![], name_servers);
+            Ok(TokioAsyncResolver::tokio(config, ResolverOpts::default()))
+        }
+        None => match read_system_conf() {
+            Ok((config, opts)) => Ok(TokioAsyncResolver::tokio(config, opts)),
+            Err(e) => err!(format!("Error reading the system DNS configuration: {e}")),
+        },
+    }
+}
+
+/// Checks whether the TXT record of the organization domain is published.
+/// A failed lookup, like a domain without any TXT record, counts as not verified.
+async fn has_txt_record(resolver: &TokioAsyncResolver, domain: &OrganizationDomain) -> bool {
+    // Use the fully qualified name, so the search domains of the system are not tried
+    match resolver.txt_lookup(format!("{}.", domain.domain_name)).await {
+        Ok(lookup) => lookup.iter().any(|txt| {
+            let value: String = txt.txt_data().iter().map(|data| String::from_utf8_lossy(data)).collect();
+            value.trim() == domain.txt
+        }),
+        Err(e) => {
+            debug!("TXT lookup of {} failed: {e}", domain.domain_name);
+            false
+        }
+    }
+}
+
+/// A domain can only be verified by one organization at a time
+async fn is_claimed_by_other_org(domain: &OrganizationDomain, conn: &mut DbConn) -> bool {
+    OrganizationDomain::find_verified_by_domain(&domain.domain_name, conn)
+        .await
+        .iter()
+        .any(|d| d.org_uuid != domain.org_uuid)
+}
+
+#[get("/organizations/<org_id>/domain")]
+async fn get_org_domains(org_id: &str, _headers: AdminHeaders, mut conn: DbConn) -> Json<Value> {
+    let domains_json: Vec<Value> =
+        OrganizationDomain::find_by_org(org_id, &mut conn).await.iter().map(OrganizationDomain::to_json).collect();
+
+    Json(json!({
+        "data": domains_json,
+        "object": "list",
+        "continuationToken": null,
+    }))
+}
+
+#[get("/organizations/<org_id>/domain/<domain_id>")]
+async fn get_org_domain(org_id: &str, domain_id: &str, _headers: AdminHeaders, mut conn: DbConn) -> JsonResult {
+    let Some(domain) = OrganizationDomain::find_by_uuid_and_org(domain_id, org_id, &mut conn).await else {
+        err!("Domain not found")
+    };
+
+    Ok(Json(domain.to_json()))
+}
+
+#[post("/organizations/<org_id>/domain", data = "<data>")]
+async fn post_org_domain(
+    org_id: &str,
+    data: Json<OrgDomainData>,
+    _headers: AdminHeaders,
+    mut conn: DbConn,
+) -> JsonResult {
+    let data: OrgDomainData = data.into_inner();
+    let Some(domain_name) = normalize_domain_name(&data.domain_name) else {
+        err!("Invalid domain name")
+    };
+
+    if OrganizationDomain::find_by_org_and_domain(org_id, &domain_name, &mut conn).await.is_some() {
+        err!("The domain name has already been added to this organization")
+    }
+
+    let domain = OrganizationDomain::new(String::from(org_id), domain_name);
+    if is_claimed_by_other_org(&domain, &mut conn).await {
+        err!("The domain name has already been verified by another organization")
+    }
+    domain.save(&mut conn).await?;
+
+    Ok(Json(domain.to_json()))
+}
+
+#[post("/organizations/<org_id>/domain/<domain_id>/verify")]
+async fn verify_org_domain(org_id: &str, domain_id: &str, _headers: AdminHeaders, mut conn: DbConn) -> JsonResult {
+    let Some(mut domain) = OrganizationDomain::find_by_uuid_and_org(domain_id, org_id, &mut conn).await else {
+        err!("Domain not found")
+    };
+
+    if !domain.is_verified() {
+        if is_claimed_by_other_org(&domain, &mut conn).await {
+            err!("The domain name has already been verified by another organization")
+        }
+
+        let resolver = dns_resolver()?;
+        let verified = has_txt_record(&resolver, &domain).await;
+        domain.checked(verified);
+        domain.save(&mut conn).await?;
+    }
+
+    Ok(Json(domain.to_json()))
+}
+
+#[delete("/organizations/<org_id>/domain/<domain_id>")]
+async fn delete_org_domain(org_id: &str, domain_id: &str, _headers: AdminHeaders, mut conn: DbConn) -> EmptyResult {
+    let Some(domain) = OrganizationDomain::find_by_uuid_and_org(domain_id, org_id, &mut conn).await else {
+        err!("Domain not found")
+    };
+
+    domain.delete(&mut conn).await
+}
+
+#[post("/organizations/<org_id>/domain/<domain_id>/remove")]
+async fn post_delete_org_domain(org_id: &str, domain_id: &str, headers: AdminHeaders, conn: DbConn) -> EmptyResult {
+    delete_org_domain(org_id, domain_id, headers, conn).await
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AutoEnrollData {
+    enabled: bool,
+    r#type: NumberOrString,
+    #[serde(default)]
+    groups: Vec<String>,
+    #[serde(default)]
+    collections: Vec<AutoEnrollCollection>,
+}
+
+#[put("/organizations/<org_id>/domain/<domain_id>/auto-enroll", data = "<data>")]
+async fn put_org_domain_auto_enroll(
+    org_id: &str,
+    domain_id: &str,
+    data: Json<AutoEnrollData>,
+    _headers: OwnerHeaders,
+    mut conn: DbConn,
+) -> JsonResult {
+    let data: AutoEnrollData = data.into_inner();
+    let Some(mut domain) = OrganizationDomain::find_by_uuid_and_org(domain_id, org_id, &mut conn).await else {
+        err!("Domain not found")
+    };
+
+    if data.enabled && !domain.is_verified() {
+        err!("Only verified domains can be used for automatic enrollment")
+    }
+
+    // Anyone with an address in the domain can sign up, so they never get more than Manager rights this way
+    let atype = match UserOrgType::from_str(&data.r#type.into_string()) {
+        Some(atype @ (UserOrgType::User | UserOrgType::Manager)) => atype as i32,
+        _ => err!("Automatically enrolled members can only be a User or a Manager"),
+    };
+
+    for group_id in &data.groups {
+        if !Group::find_by_uuid(group_id, &mut conn).await.is_some_and(|g| g.organizations_uuid == org_id) {
+            err!("Group not found in Organization")
+        }
+    }
+    for col in &data.collections {
+        if Collection::find_by_uuid_and_org(&col.id, org_id, &mut conn).await.is_none() {
+            err!("Collection not found in Organization")
+        }
+    }
+
+    domain.set_auto_enroll(data.enabled, atype, &data.groups, &data.collections);
+    domain.save(&mut conn).await?;
+
+    Ok(Json(domain.to_json()))
+}
+
+/// Adds a new user to the organizations which automatically enroll the email domain of the user.
+/// The membership is created as Accepted: the organization key can only be shared with the user by the client
+/// of an Owner or Admin, so confirming the member stays a manual step, just like for an accepted invitation.
+/// Only users with a verified email address are enrolled, otherwise anyone could join by signing up with the domain.
+/// Without mail the address can't be verified, so nobody is enrolled automatically then.
+/// At registration this only enrolls invited users, everyone else is enrolled by the email verification,
+/// which is asked for right away when SIGNUPS_VERIFY is enabled.
+pub async fn auto_enroll_user(user: &User, client_headers: &ClientHeaders, conn: &mut DbConn) {
+    if CONFIG.mail_enabled() {
+        enroll_by_email_domain(user, client_headers, conn).await;
+    }
+}
+
+/// Enrolls the user if their email address is verified, without checking whether mail is enabled
+pub async fn enroll_by_email_domain(user: &User, client_headers: &ClientHeaders, conn: &mut DbConn) {
+    if user.verified_at.is_none() {
+        return;
+    }
+    let Some(domain_name) = email_domain(&user.email) else {
+        return;
+    };
+
+    for domain in OrganizationDomain::find_auto_enroll_by_domain(&domain_name, conn).await {
+        if let Err(e) = auto_enroll_user_in_org(user, &domain, client_headers, conn).await {
+            error!("Error enrolling {} in organization {}: {e:#?}", user.email, domain.org_uuid);
+        }
+    }
+}
+
+async fn auto_enroll_user_in_org(
+    user: &User,
+    domain: &OrganizationDomain,
+    client_headers: &ClientHeaders,
+    conn: &mut DbConn,
+) -> EmptyResult {
+    if UserOrganization::find_by_user_and_org(&user.uuid, &domain.org_uuid, conn).await.is_some() {
+        return Ok(());
+    }
+    let Some(org) = Organization::find_by_uuid(&domain.org_uuid, conn).await else {
+        return Ok(());
+    };
+
+    // These policies need an action of the user first, the user has to be invited instead
+    if OrgPolicy::is_user_allowed(&user.uuid, &org.uuid, false, conn).await.is_err()
+        || OrgPolicy::org_is_reset_password_auto_enroll(&org.uuid, conn).await
+    {
+        info!("Not enrolling {} in organization {} because of its policies", user.email, org.uuid);
+        return Ok(());
+    }
+
+    let mut user_org = UserOrganization::new(user.uuid.clone(), org.uuid.clone());
+    user_org.atype = domain.auto_enroll_type;
+    user_org.status = UserOrgStatus::Accepted as i32;
+    user_org.save(conn).await?;
+
+    for col in domain.auto_enroll_collections() {
+        // Collections can be deleted after the enrollment was configured
+        if Collection::find_by_uuid_and_org(&col.id, &org.uuid, conn).await.is_some() {
+            CollectionUser::save(&user.uuid, &col.id, col.read_only, col.hide_passwords, col.manage, conn).await?;
+        }
+    }
+    for group_id in domain.auto_enroll_groups() {
+        if Group::find_by_uuid(&group_id, conn).await.is_some_and(|g| g.organizations_uuid == org.uuid) {
+            GroupUser::new(group_id, user_org.uuid.clone()).save(conn).await?;
+        }
+    }
+
+    log_event(
+        EventType::OrganizationUserInvited as i32,
+        &user_org.uuid,
+        &org.uuid,
+        &user.uuid,
+        client_headers.device_type,
+        &client_headers.ip.ip,
+        conn,
+    )
+    .await;
+
+    if CONFIG.mail_enabled() {
+        for admin in UserOrganization::find_confirmed_by_org(&org.uuid, conn).await {
+            if admin.atype < UserOrgType::Admin {
+                continue;
+            }
+            if let Some(admin_user) = User::find_by_uuid(&admin.user_uuid, conn).await {
+                // The user is a member already, a failed mail must not stop the other admins from being notified
+                if let Err(e) = mail::send_invite_accepted(
+                    &user.email,
+                    &admin_user.email,
+                    admin_user.locale.as_deref(),
+                    &org.name,
+                    &NotificationPreference::find_or_default_by_user(&admin_user.uuid, conn).await,
+                )
+                .await
+                {
+                    error!("Error sending invite accepted email: {e:#?}");
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the organizations which verified the email domain of the user, and which the user isn't a member of yet.
+/// Nothing is suggested as long as the user didn't verify the email address, as it could belong to someone else.
+async fn org_suggestions(user: &User, conn: &mut DbConn) -> Vec<Value> {
+    if CONFIG.mail_enabled() && user.verified_at.is_none() {
+        return Vec::new();
+    }
+    let Some(domain_name) = email_domain(&user.email) else {
+        return Vec::new();
+    };
+
+    let mut suggestions = Vec::new();
+    for domain in OrganizationDomain::find_verified_by_domain(&domain_name, conn).await {
+        if UserOrganization::find_by_user_and_org(&user.uuid, &domain.org_uuid, conn).await.is_some() {
+            continue;
+        }
+        if let Some(org) = Organization::find_by_uuid(&domain.org_uuid, conn).await {
+            suggestions.push(json!({
+                "id": org.uuid,
+                "name": org.name,
+                "domainName": domain.domain_name,
+                "object": "organizationSuggestion",
+            }));
+        }
+    }
+    suggestions
+}
+
+#[get("/organizations/domain/suggestions")]
+async fn get_org_suggestions(headers: Headers, mut conn: DbConn) -> Json<Value> {
+    let suggestions = org_suggestions(&headers.user, &mut conn).await;
+
+    Json(json!({
+        "data": suggestions,
+        "object": "list",
+        "continuationToken": null,
+    }))
+}
+
+/// The job stops checking a domain after this many attempts, it can still be verified manually afterwards
+const MAX_VERIFICATION_JOB_RUNS: i32 = 72;
+
+/// Checks the TXT records of all the organization domains which are not verified yet
+pub async fn org_domain_verification_job(pool: DbPool) -> JobResult {
+    debug!("Start organization domain verification job");
+    let Ok(mut conn) = pool.get().await else {
+        err!("Failed to get DB connection while verifying organization domains")
+    };
+
+    let domains = OrganizationDomain::find_unverified(MAX_VERIFICATION_JOB_RUNS, &mut conn).await;
+    if domains.is_empty() {
+        return Ok(0);
+    }
+
+    let resolver = dns_resolver()?;
+    let mut verified = 0;
+    for mut domain in domains {
+        if is_claimed_by_other_org(&domain, &mut conn).await {
+            continue;
+        }
+
+        let found = has_txt_record(&resolver, &domain).await;
+        domain.checked(found);
+        domain.job_run_count += 1;
+        domain.save(&mut conn).await?;
+        if found {
+            info!("Domain {} verified for organization {}", domain.domain_name, domain.org_uuid);
+            verified += 1;
+        } else if domain.job_run_count >= MAX_VERIFICATION_JOB_RUNS {
+            info!(
+                "Giving up on verifying domain {} for organization {} after {} attempts",
+                domain.domain_name, domain.org_uuid, domain.job_run_count
+            );
+        }
+    }
+
+    Ok(verified)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_domain_name() {
+        assert_eq!(normalize_domain_name("example.com"), Some(String::from("example.com")));
+        assert_eq!(normalize_domain_name("  Mail.Example.COM. "), Some(String::from("mail.example.com")));
+        assert_eq!(normalize_domain_name("my-company.example"), Some(String::from("my-company.example")));
+        assert_eq!(normalize_domain_name("xn--bcher-kva.example"), Some(String::from("xn--bcher-kva.example")));
+    }
+
+    #[test]
+    fn test_normalize_domain_name_invalid() {
+        assert_eq!(normalize_domain_name(""), None);
+        assert_eq!(normalize_domain_name("localhost"), None);
+        assert_eq!(normalize_domain_name("example..com"), None);
+        assert_eq!(normalize_domain_name(".example.com"), None);
+        assert_eq!(normalize_domain_name("-example.com"), None);
+        assert_eq!(normalize_domain_name("example-.com"), None);
+        assert_eq!(normalize_domain_name("exa mple.com"), None);
+        assert_eq!(normalize_domain_name("user@example.com"), None);
+        assert_eq!(normalize_domain_name("https://example.com"), None);
+        assert_eq!(normalize_domain_name(&format!("{}.com", "a".repeat(64))), None);
+        assert_eq!(normalize_domain_name(&format!("{}.com", vec!["a".repeat(60); 5].join("."))), None);
+    }
+}
@@ -1,3 +1,6 @@
+use std::collections::HashSet;
+
+use chrono::{TimeDelta, Utc};
 use num_traits::FromPrimitive;
 use rocket::serde::json::Json;
 use rocket::Route;
@@ -8,9 +11,14 @@ use crate::{
         core::{log_event, two_factor, CipherSyncData, CipherSyncType},
         EmptyResult, JsonResult, Notify, PasswordOrOtpData, UpdateType,
     },
-    auth::{decode_invite, AdminHeaders, Headers, ManagerHeaders, ManagerHeadersLoose, OwnerHeaders},
-    db::{models::*, DbConn},
+    auth::{
+        decode_invite, AccountRecoveryHeaders, AdminHeaders, CollectionManagerHeaders, Headers, ImportExportHeaders,
+        ManageGroupsHeaders, ManagePoliciesHeaders, ManageUsersHeaders, ManagerHeaders, ManagerHeadersLoose,
+        OrgHeaders, OwnerHeaders, ViewMembersHeaders,
+    },
+    db::{models::*, DbConn, DbPool},
     error::Error,
+    jobs::JobResult,
     mail,
     util::{convert_json_key_lcase_first, NumberOrString},
     CONFIG,
@@ -22,6 +30,11 @@ pub fn routes() -> Vec<Route> {
         create_organization,
         delete_organization,
         post_delete_organization,
+        restore_organization,
+        get_ownership_transfer,
+        start_ownership_transfer,
+        accept_ownership_transfer,
+        cancel_ownership_transfer,
         leave_organization,
         get_user_collections,
         get_org_collections,
@@ -44,6 +57,7 @@ pub fn routes() -> Vec<Route> {
         send_invite,
         reinvite_user,
         bulk_reinvite_user,
+        bulk_reinvite_expired_users,
         confirm_invite,
         bulk_confirm_invite,
         accept_invite,
@@ -116,6 +130,10 @@ struct OrgData {
 struct OrganizationUpdateData {
     billing_email: String,
     name: String,
+    limit_collection_creation_deletion: Option<bool>,
+    allow_admin_access_to_all_collection_items: Option<bool>,
+    members_can_edit_items: Option<bool>,
+    limit_invitations_to_verified_domains: Option<bool>,
 }
 
 #[derive(Deserialize)]
@@ -133,6 +151,8 @@ struct NewCollectionObjectData {
     hide_passwords: bool,
     id: String,
     read_only: bool,
+    #[serde(default)]
+    manage: bool,
 }
 
 #[derive(Deserialize)]
@@ -189,6 +209,7 @@ async fn delete_organization(
     data: Json<PasswordOrOtpData>,
     headers: OwnerHeaders,
     mut conn: DbConn,
+    nt: Notify<'_>,
 ) -> EmptyResult {
     let data: PasswordOrOtpData = data.into_inner();
 
@@ -196,7 +217,7 @@ async fn delete_organization(
 
     match Organization::find_by_uuid(org_id, &mut conn).await {
         None => err!("Organization not found"),
-        Some(org) => org.delete(&mut conn).await,
+        Some(org) => schedule_org_deletion(org, &headers.user.email, &mut conn, &nt).await,
     }
 }
 
@@ -206,8 +227,312 @@ async fn post_delete_organization(
     data: Json<PasswordOrOtpData>,
     headers: OwnerHeaders,
     conn: DbConn,
+    nt: Notify<'_>,
+) -> EmptyResult {
+    delete_organization(org_id, data, headers, conn, nt).await
+}
+
+/// Deletes the organization once `ORG_DELETION_GRACE_DAYS` have passed, until then it is disabled and can be restored.
+/// Without a grace period the organization is deleted immediately.
+pub async fn schedule_org_deletion(
+    mut org: Organization,
+    deleted_by: &str,
+    conn: &mut DbConn,
+    nt: &Notify<'_>,
 ) -> EmptyResult {
-    delete_organization(org_id, data, headers, conn).await
+    if CONFIG.org_deletion_grace_days() == 0 {
+        return org.delete(conn).await;
+    }
+    if org.is_pending_deletion() {
+        err!("The organization is already scheduled for deletion")
+    }
+
+    let mut targets = SyncTargets::default();
+    targets.add_confirmed_members(&org.uuid, conn).await;
+
+    org.deleted_at = Some(Utc::now().naive_utc());
+    org.clear_transfer();
+    org.save(conn).await?;
+
+    if CONFIG.mail_enabled() {
+        let delete_date = org.purge_date().unwrap();
+        for owner in confirmed_owners(&org.uuid, conn).await {
+            if let Err(e) = mail::send_org_deletion_scheduled(
+                &owner.email,
+                owner.locale.as_deref(),
+                &org.name,
+                deleted_by,
+                &delete_date,
+            )
+            .await
+            {
+                error!("Error sending organization deletion email: {e:#?}");
+            }
+        }
+    }
+
+    targets.notify(UpdateType::SyncOrgKeys, nt, conn).await;
+    Ok(())
+}
+
+/// Cancels the scheduled deletion of the organization
+pub async fn restore_org(mut org: Organization, restored_by: &str, conn: &mut DbConn, nt: &Notify<'_>) -> EmptyResult {
+    if !org.is_pending_deletion() {
+        err!("The organization is not scheduled for deletion")
+    }
+
+    org.deleted_at = None;
+    org.save(conn).await?;
+
+    if CONFIG.mail_enabled() {
+        for owner in confirmed_owners(&org.uuid, conn).await {
+            if let Err(e) =
+                mail::send_org_deletion_restored(&owner.email, owner.locale.as_deref(), &org.name, restored_by).await
+            {
+                error!("Error sending organization restore email: {e:#?}");
+            }
+        }
+    }
+
+    let mut targets = SyncTargets::default();
+    targets.add_confirmed_members(&org.uuid, conn).await;
+    targets.notify(UpdateType::SyncOrgKeys, nt, conn).await;
+    Ok(())
+}
+
+/// Organizations which are scheduled for deletion are disabled until one of the owners restores them,
+/// their items can't be accessed and no members can be added
+async fn check_org_not_pending_deletion(org_id: &str, conn: &mut DbConn) -> EmptyResult {
+    match Organization::find_by_uuid(org_id, conn).await {
+        Some(org) if org.is_pending_deletion() => err!("The organization is scheduled for deletion"),
+        Some(_) => Ok(()),
+        None => err!("The organization doesn't exist"),
+    }
+}
+
+async fn confirmed_owners(org_id: &str, conn: &mut DbConn) -> Vec<User> {
+    let mut owners = Vec::new();
+    for user_org in UserOrganization::find_by_org_and_type(org_id, UserOrgType::Owner, conn).await {
+        if user_org.status != UserOrgStatus::Confirmed as i32 {
+            continue;
+        }
+        if let Some(user) = User::find_by_uuid(&user_org.user_uuid, conn).await {
+            owners.push(user);
+        }
+    }
+    owners
+}
+
+#[post("/organizations/<org_id>/restore", data = "<data>")]
+async fn restore_organization(
+    org_id: &str,
+    data: Json<PasswordOrOtpData>,
+    headers: OwnerHeaders,
+    mut conn: DbConn,
+    nt: Notify<'_>,
+) -> JsonResult {
+    let data: PasswordOrOtpData = data.into_inner();
+
+    data.validate(&headers.user, true, &mut conn).await?;
+
+    let Some(org) = Organization::find_by_uuid(org_id, &mut conn).await else {
+        err!("Organization not found")
+    };
+    restore_org(org, &headers.user.email, &mut conn, &nt).await?;
+
+    match Organization::find_by_uuid(org_id, &mut conn).await {
+        Some(org) => Ok(Json(org.to_json())),
+        None => err!("Organization not found"),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OwnershipTransferData {
+    new_owner_id: String,
+    #[serde(flatten)]
+    auth: PasswordOrOtpData,
+}
+
+#[get("/organizations/<org_id>/ownership-transfer")]
+async fn get_ownership_transfer(org_id: &str, headers: OrgHeaders, mut conn: DbConn) -> JsonResult {
+    let Some(org) = Organization::find_by_uuid(org_id, &mut conn).await else {
+        err!("Organization not found")
+    };
+
+    // Only the owners and the proposed new owner can see the pending transfer
+    match &org.transfer_org_user_uuid {
+        Some(new_owner_id) if headers.org_user_type == UserOrgType::Owner || *new_owner_id == headers.org_user.uuid => {
+            Ok(Json(org.transfer_to_json()))
+        }
+        _ => err!("There is no pending ownership transfer"),
+    }
+}
+
+#[post("/organizations/<org_id>/ownership-transfer", data = "<data>")]
+async fn start_ownership_transfer(
+    org_id: &str,
+    data: Json<OwnershipTransferData>,
+    headers: OwnerHeaders,
+    mut conn: DbConn,
+) -> JsonResult {
+    let data: OwnershipTransferData = data.into_inner();
+
+    data.auth.validate(&headers.user, true, &mut conn).await?;
+
+    let Some(mut org) = Organization::find_by_uuid(org_id, &mut conn).await else {
+        err!("Organization not found")
+    };
+    if org.is_pending_deletion() {
+        err!("The organization is scheduled for deletion")
+    }
+    if org.transfer_org_user_uuid.is_some() {
+        err!("There already is a pending ownership transfer, cancel it first")
+    }
+
+    let Some(new_owner) = UserOrganization::find_by_uuid_and_org(&data.new_owner_id, org_id, &mut conn).await else {
+        err!("The new owner isn't a member of the organization")
+    };
+    if new_owner.status != UserOrgStatus::Confirmed as i32 {
+        err!("The new owner must be a confirmed member of the organization")
+    }
+    if new_owner.atype == UserOrgType::Owner {
+        err!("This member already is an owner of the organization")
+    }
+
+    org.set_transfer(&new_owner.uuid, &headers.user.uuid);
+    org.save(&mut conn).await?;
+
+    if CONFIG.mail_enabled() {
+        if let Some(user) = User::find_by_uuid(&new_owner.user_uuid, &mut conn).await {
+            if let Err(e) =
+                mail::send_org_transfer_requested(&user.email, user.locale.as_deref(), &org.name, &headers.user.email)
+                    .await
+            {
+                error!("Error sending ownership transfer email: {e:#?}");
+            }
+        }
+    }
+
+    Ok(Json(org.transfer_to_json()))
+}
+
+#[post("/organizations/<org_id>/ownership-transfer/accept", data = "<data>")]
+async fn accept_ownership_transfer(
+    org_id: &str,
+    data: Json<PasswordOrOtpData>,
+    headers: OrgHeaders,
+    mut conn: DbConn,
+    nt: Notify<'_>,
+) -> EmptyResult {
+    let data: PasswordOrOtpData = data.into_inner();
+
+    let Some(mut org) = Organization::find_by_uuid(org_id, &mut conn).await else {
+        err!("Organization not found")
+    };
+    if org.transfer_org_user_uuid.as_deref() != Some(headers.org_user.uuid.as_str()) {
+        err!("There is no pending ownership transfer to you")
+    }
+    if org.is_pending_deletion() {
+        err!("The organization is scheduled for deletion")
+    }
+
+    data.validate(&headers.user, true, &mut conn).await?;
+
+    // The owner who started the transfer hands over the ownership and stays on as an admin,
+    // so the transfer is only valid as long as they still are a confirmed owner
+    let initiator = match &org.transfer_initiator_uuid {
+        Some(initiator_uuid) => UserOrganization::find_by_user_and_org(initiator_uuid, org_id, &mut conn).await,
+        None => None,
+    };
+    let Some(mut initiator) =
+        initiator.filter(|i| i.atype == UserOrgType::Owner && i.status == UserOrgStatus::Confirmed as i32)
+    else {
+        org.clear_transfer();
+        org.save(&mut conn).await?;
+        err!("The owner who started the ownership transfer is no longer an owner of the organization")
+    };
+
+    let mut targets = SyncTargets::default();
+    let mut new_owner = headers.org_user;
+    new_owner.atype = UserOrgType::Owner as i32;
+    new_owner.save(&mut conn).await?;
+    targets.add_user(&new_owner.user_uuid);
+    log_event(
+        EventType::OrganizationUserUpdated as i32,
+        &new_owner.uuid,
+        org_id,
+        &headers.user.uuid,
+        headers.device.atype,
+        &headers.ip.ip,
+        &mut conn,
+    )
+    .await;
+
+    initiator.atype = UserOrgType::Admin as i32;
+    initiator.save(&mut conn).await?;
+    targets.add_user(&initiator.user_uuid);
+    log_event(
+        EventType::OrganizationUserUpdated as i32,
+        &initiator.uuid,
+        org_id,
+        &headers.user.uuid,
+        headers.device.atype,
+        &headers.ip.ip,
+        &mut conn,
+    )
+    .await;
+
+    // The initiator is notified as well, since they are no longer an owner
+    let mut notify_emails = Vec::new();
+    let mut initiator_email = String::new();
+    if let Some(user) = User::find_by_uuid(&initiator.user_uuid, &mut conn).await {
+        initiator_email.clone_from(&user.email);
+        notify_emails.push((user.email, user.locale));
+    }
+
+    org.clear_transfer();
+    org.save(&mut conn).await?;
+
+    if CONFIG.mail_enabled() {
+        for owner in confirmed_owners(org_id, &mut conn).await {
+            if !notify_emails.iter().any(|(email, _)| *email == owner.email) {
+                notify_emails.push((owner.email, owner.locale));
+            }
+        }
+        for (email, locale) in notify_emails {
+            if let Err(e) = mail::send_org_transfer_completed(
+                &email,
+                locale.as_deref(),
+                &org.name,
+                &headers.user.email,
+                &initiator_email,
+            )
+            .await
+            {
+                error!("Error sending ownership transfer email: {e:#?}");
+            }
+        }
+    }
+
+    targets.notify(UpdateType::SyncOrgKeys, &nt, &mut conn).await;
+    Ok(())
+}
+
+// Used by the owners to cancel the transfer, and by the proposed new owner to decline it
+#[delete("/organizations/<org_id>/ownership-transfer")]
+async fn cancel_ownership_transfer(org_id: &str, headers: OrgHeaders, mut conn: DbConn) -> EmptyResult {
+    let Some(mut org) = Organization::find_by_uuid(org_id, &mut conn).await else {
+        err!("Organization not found")
+    };
+
+    match &org.transfer_org_user_uuid {
+        Some(new_owner_id) if headers.org_user_type == UserOrgType::Owner || *new_owner_id == headers.org_user.uuid => {
+            org.clear_transfer();
+            org.save(&mut conn).await
+        }
+        _ => err!("There is no pending ownership transfer"),
+    }
 }
 
 #[post("/organizations/<org_id>/leave")]
@@ -232,6 +557,7 @@ async fn leave_organization(org_id: &str, headers: Headers, mut conn: DbConn) ->
             )
             .await;
 
+            Organization::cancel_transfer_of_member(&user_org, &mut conn).await?;
             user_org.delete(&mut conn).await
         }
     }
@@ -271,6 +597,19 @@ async fn post_organization(
 
     org.name = data.name;
     org.billing_email = data.billing_email;
+    // The collection management settings are optional, so clients which don't know about them keep the current values
+    if let Some(limit_collection_creation_deletion) = data.limit_collection_creation_deletion {
+        org.limit_collection_creation_deletion = limit_collection_creation_deletion;
+    }
+    if let Some(allow_admin_access_to_all_collection_items) = data.allow_admin_access_to_all_collection_items {
+        org.allow_admin_access_to_all_collection_items = allow_admin_access_to_all_collection_items;
+    }
+    if let Some(members_can_edit_items) = data.members_can_edit_items {
+        org.members_can_edit_items = members_can_edit_items;
+    }
+    if let Some(limit_invitations_to_verified_domains) = data.limit_invitations_to_verified_domains {
+        org.limit_invitations_to_verified_domains = limit_invitations_to_verified_domains;
+    }
 
     org.save(&mut conn).await?;
 
@@ -303,16 +642,19 @@ async fn get_user_collections(headers: Headers, mut conn: DbConn) -> Json<Value>
 }
 
 #[get("/organizations/<org_id>/collections")]
-async fn get_org_collections(org_id: &str, _headers: ManagerHeadersLoose, mut conn: DbConn) -> Json<Value> {
-    Json(json!({
+async fn get_org_collections(org_id: &str, _headers: ManagerHeadersLoose, mut conn: DbConn) -> JsonResult {
+    check_org_not_pending_deletion(org_id, &mut conn).await?;
+
+    Ok(Json(json!({
         "data": _get_org_collections(org_id, &mut conn).await,
         "object": "list",
         "continuationToken": null,
-    }))
+    })))
 }
 
 #[get("/organizations/<org_id>/collections/details")]
 async fn get_org_collections_details(org_id: &str, headers: ManagerHeadersLoose, mut conn: DbConn) -> JsonResult {
+    check_org_not_pending_deletion(org_id, &mut conn).await?;
     let mut data = Vec::new();
 
     let user_org = match UserOrganization::find_by_user_and_org(&headers.user.uuid, org_id, &mut conn).await {
@@ -380,6 +722,7 @@ async fn post_organization_collections(
     headers: ManagerHeadersLoose,
     data: Json<NewCollectionData>,
     mut conn: DbConn,
+    nt: Notify<'_>,
 ) -> JsonResult {
     let data: NewCollectionData = data.into_inner();
 
@@ -387,6 +730,13 @@ async fn post_organization_collections(
         Some(organization) => organization,
         None => err!("Can't find organization details"),
     };
+    if org.is_pending_deletion() {
+        err!("The organization is scheduled for deletion")
+    }
+
+    if !org.can_create_delete_collections(&headers.org_user) {
+        err!("Only Owners and Admins can create collections in this organization")
+    }
 
     let collection = Collection::new(org.uuid, data.name, data.external_id);
     collection.save(&mut conn).await?;
@@ -403,7 +753,7 @@ async fn post_organization_collections(
     .await;
 
     for group in data.groups {
-        CollectionGroup::new(collection.uuid.clone(), group.id, group.read_only, group.hide_passwords)
+        CollectionGroup::new(collection.uuid.clone(), group.id, group.read_only, group.hide_passwords, group.manage)
             .save(&mut conn)
             .await?;
     }
@@ -418,14 +768,25 @@ async fn post_organization_collections(
             continue;
         }
 
-        CollectionUser::save(&org_user.user_uuid, &collection.uuid, user.read_only, user.hide_passwords, &mut conn)
-            .await?;
+        CollectionUser::save(
+            &org_user.user_uuid,
+            &collection.uuid,
+            user.read_only,
+            user.hide_passwords,
+            user.manage,
+            &mut conn,
+        )
+        .await?;
     }
 
     if headers.org_user.atype == UserOrgType::Manager && !headers.org_user.access_all {
-        CollectionUser::save(&headers.org_user.user_uuid, &collection.uuid, false, false, &mut conn).await?;
+        CollectionUser::save(&headers.org_user.user_uuid, &collection.uuid, false, false, false, &mut conn).await?;
     }
 
+    let mut targets = SyncTargets::default();
+    targets.add_collection(&collection.uuid, org_id, &mut conn).await;
+    targets.notify(UpdateType::SyncVault, &nt, &mut conn).await;
+
     Ok(Json(collection.to_json()))
 }
 
@@ -433,20 +794,22 @@ async fn post_organization_collections(
 async fn put_organization_collection_update(
     org_id: &str,
     col_id: &str,
-    headers: ManagerHeaders,
+    headers: CollectionManagerHeaders,
     data: Json<NewCollectionData>,
     conn: DbConn,
+    nt: Notify<'_>,
 ) -> JsonResult {
-    post_organization_collection_update(org_id, col_id, headers, data, conn).await
+    post_organization_collection_update(org_id, col_id, headers, data, conn, nt).await
 }
 
 #[post("/organizations/<org_id>/collections/<col_id>", data = "<data>")]
 async fn post_organization_collection_update(
     org_id: &str,
     col_id: &str,
-    headers: ManagerHeaders,
+    headers: CollectionManagerHeaders,
     data: Json<NewCollectionData>,
     mut conn: DbConn,
+    nt: Notify<'_>,
 ) -> JsonResult {
     let data: NewCollectionData = data.into_inner();
 
@@ -483,10 +846,14 @@ async fn post_organization_collection_update(
     )
     .await;
 
+    // Members which lose access need to sync as well
+    let mut targets = SyncTargets::default();
+    targets.add_collection(col_id, org_id, &mut conn).await;
+
     CollectionGroup::delete_all_by_collection(col_id, &mut conn).await?;
 
     for group in data.groups {
-        CollectionGroup::new(String::from(col_id), group.id, group.read_only, group.hide_passwords)
+        CollectionGroup::new(String::from(col_id), group.id, group.read_only, group.hide_passwords, group.manage)
             .save(&mut conn)
             .await?;
     }
@@ -503,9 +870,13 @@ async fn post_organization_collection_update(
             continue;
         }
 
-        CollectionUser::save(&org_user.user_uuid, col_id, user.read_only, user.hide_passwords, &mut conn).await?;
+        CollectionUser::save(&org_user.user_uuid, col_id, user.read_only, user.hide_passwords, user.manage, &mut conn)
+            .await?;
     }
 
+    targets.add_collection(col_id, org_id, &mut conn).await;
+    targets.notify(UpdateType::SyncVault, &nt, &mut conn).await;
+
     Ok(Json(collection.to_json()))
 }
 
@@ -516,6 +887,7 @@ async fn delete_organization_collection_user(
     org_user_id: &str,
     _headers: AdminHeaders,
     mut conn: DbConn,
+    nt: Notify<'_>,
 ) -> EmptyResult {
     let collection = match Collection::find_by_uuid(col_id, &mut conn).await {
         None => err!("Collection not found"),
@@ -528,15 +900,20 @@ async fn delete_organization_collection_user(
         }
     };
 
-    match UserOrganization::find_by_uuid_and_org(org_user_id, org_id, &mut conn).await {
+    let user_org = match UserOrganization::find_by_uuid_and_org(org_user_id, org_id, &mut conn).await {
         None => err!("User not found in organization"),
-        Some(user_org) => {
-            match CollectionUser::find_by_collection_and_user(&collection.uuid, &user_org.user_uuid, &mut conn).await {
-                None => err!("User not assigned to collection"),
-                Some(col_user) => col_user.delete(&mut conn).await,
-            }
-        }
+        Some(user_org) => user_org,
+    };
+
+    match CollectionUser::find_by_collection_and_user(&collection.uuid, &user_org.user_uuid, &mut conn).await {
+        None => err!("User not assigned to collection"),
+        Some(col_user) => col_user.delete(&mut conn).await?,
     }
+
+    let mut targets = SyncTargets::default();
+    targets.add_user(&user_org.user_uuid);
+    targets.notify(UpdateType::SyncVault, &nt, &mut conn).await;
+    Ok(())
 }
 
 #[post("/organizations/<org_id>/collections/<col_id>/delete-user/<org_user_id>")]
@@ -546,16 +923,27 @@ async fn post_organization_collection_delete_user(
     org_user_id: &str,
     headers: AdminHeaders,
     conn: DbConn,
+    nt: Notify<'_>,
 ) -> EmptyResult {
-    delete_organization_collection_user(org_id, col_id, org_user_id, headers, conn).await
+    delete_organization_collection_user(org_id, col_id, org_user_id, headers, conn, nt).await
 }
 
 async fn _delete_organization_collection(
     org_id: &str,
     col_id: &str,
     headers: &ManagerHeaders,
+    targets: &mut SyncTargets,
     conn: &mut DbConn,
 ) -> EmptyResult {
+    let org = match Organization::find_by_uuid(org_id, conn).await {
+        Some(organization) => organization,
+        None => err!("Can't find organization details"),
+    };
+    match UserOrganization::find_by_user_and_org(&headers.user.uuid, org_id, conn).await {
+        Some(member) if org.can_create_delete_collections(&member) => {}
+        _ => err!("Only Owners and Admins can delete collections in this organization"),
+    }
+
     match Collection::find_by_uuid(col_id, conn).await {
         None => err!("Collection not found"),
         Some(collection) => {
@@ -570,6 +958,7 @@ async fn _delete_organization_collection(
                     conn,
                 )
                 .await;
+                targets.add_collection(&collection.uuid, org_id, conn).await;
                 collection.delete(conn).await
             } else {
                 err!("Collection and Organization id do not match")
@@ -584,8 +973,12 @@ async fn delete_organization_collection(
     col_id: &str,
     headers: ManagerHeaders,
     mut conn: DbConn,
+    nt: Notify<'_>,
 ) -> EmptyResult {
-    _delete_organization_collection(org_id, col_id, &headers, &mut conn).await
+    let mut targets = SyncTargets::default();
+    _delete_organization_collection(org_id, col_id, &headers, &mut targets, &mut conn).await?;
+    targets.notify(UpdateType::SyncVault, &nt, &mut conn).await;
+    Ok(())
 }
 
 #[derive(Deserialize, Debug)]
@@ -604,8 +997,12 @@ async fn post_organization_collection_delete(
     headers: ManagerHeaders,
     _data: Json<DeleteCollectionData>,
     mut conn: DbConn,
+    nt: Notify<'_>,
 ) -> EmptyResult {
-    _delete_organization_collection(org_id, col_id, &headers, &mut conn).await
+    let mut targets = SyncTargets::default();
+    _delete_organization_collection(org_id, col_id, &headers, &mut targets, &mut conn).await?;
+    targets.notify(UpdateType::SyncVault, &nt, &mut conn).await;
+    Ok(())
 }
 
 #[derive(Deserialize, Debug)]
@@ -620,6 +1017,7 @@ async fn bulk_delete_organization_collections(
     headers: ManagerHeadersLoose,
     data: Json<BulkCollectionIds>,
     mut conn: DbConn,
+    nt: Notify<'_>,
 ) -> EmptyResult {
     let data: BulkCollectionIds = data.into_inner();
 
@@ -627,9 +1025,11 @@ async fn bulk_delete_organization_collections(
 
     let headers = ManagerHeaders::from_loose(headers, &collections, &mut conn).await?;
 
+    let mut targets = SyncTargets::default();
     for col_id in collections {
-        _delete_organization_collection(org_id, &col_id, &headers, &mut conn).await?
+        _delete_organization_collection(org_id, &col_id, &headers, &mut targets, &mut conn).await?
     }
+    targets.notify(UpdateType::SyncVault, &nt, &mut conn).await;
     Ok(())
 }
 
@@ -637,9 +1037,10 @@ async fn bulk_delete_organization_collections(
 async fn get_org_collection_detail(
     org_id: &str,
     coll_id: &str,
-    headers: ManagerHeaders,
+    headers: CollectionManagerHeaders,
     mut conn: DbConn,
 ) -> JsonResult {
+    check_org_not_pending_deletion(org_id, &mut conn).await?;
     match Collection::find_by_uuid_and_user(coll_id, headers.user.uuid.clone(), &mut conn).await {
         None => err!("Collection not found"),
         Some(collection) => {
@@ -689,7 +1090,12 @@ async fn get_org_collection_detail(
 }
 
 #[get("/organizations/<org_id>/collections/<coll_id>/users")]
-async fn get_collection_users(org_id: &str, coll_id: &str, _headers: ManagerHeaders, mut conn: DbConn) -> JsonResult {
+async fn get_collection_users(
+    org_id: &str,
+    coll_id: &str,
+    _headers: CollectionManagerHeaders,
+    mut conn: DbConn,
+) -> JsonResult {
     // Get org and collection, check that collection is from org
     let collection = match Collection::find_by_uuid_and_org(coll_id, org_id, &mut conn).await {
         None => err!("Collection not found in Organization"),
@@ -714,14 +1120,19 @@ async fn put_collection_users(
     org_id: &str,
     coll_id: &str,
     data: Json<Vec<CollectionData>>,
-    _headers: ManagerHeaders,
+    _headers: CollectionManagerHeaders,
     mut conn: DbConn,
+    nt: Notify<'_>,
 ) -> EmptyResult {
     // Get org and collection, check that collection is from org
     if Collection::find_by_uuid_and_org(coll_id, org_id, &mut conn).await.is_none() {
         err!("Collection not found in Organization")
     }
 
+    // Members which lose access need to sync as well
+    let mut targets = SyncTargets::default();
+    targets.add_collection(coll_id, org_id, &mut conn).await;
+
     // Delete all the user-collections
     CollectionUser::delete_all_by_collection(coll_id, &mut conn).await?;
 
@@ -736,9 +1147,12 @@ async fn put_collection_users(
             continue;
         }
 
-        CollectionUser::save(&user.user_uuid, coll_id, d.read_only, d.hide_passwords, &mut conn).await?;
+        CollectionUser::save(&user.user_uuid, coll_id, d.read_only, d.hide_passwords, d.manage, &mut conn).await?;
     }
 
+    targets.add_collection(coll_id, org_id, &mut conn).await;
+    targets.notify(UpdateType::SyncVault, &nt, &mut conn).await;
+
     Ok(())
 }
 
@@ -749,20 +1163,39 @@ struct OrgIdData {
 }
 
 #[get("/ciphers/organization-details?<data..>")]
-async fn get_org_details(data: OrgIdData, headers: Headers, mut conn: DbConn) -> Json<Value> {
-    Json(json!({
+async fn get_org_details(data: OrgIdData, headers: Headers, mut conn: DbConn) -> JsonResult {
+    check_org_not_pending_deletion(&data.organization_id, &mut conn).await?;
+
+    Ok(Json(json!({
         "data": _get_org_details(&data.organization_id, &headers.host, &headers.user.uuid, &mut conn).await,
         "object": "list",
         "continuationToken": null,
-    }))
+    })))
 }
 
 async fn _get_org_details(org_id: &str, host: &str, user_uuid: &str, conn: &mut DbConn) -> Value {
     let ciphers = Cipher::find_by_org(org_id, conn).await;
     let cipher_sync_data = CipherSyncData::new(user_uuid, CipherSyncType::Organization, conn).await;
 
+    // Owners and Admins only get the items of their assigned collections, unless the organization allows them to access all items
+    let only_assigned = cipher_sync_data.user_organizations.get(org_id).is_some_and(|user_org| {
+        user_org.atype >= UserOrgType::Admin
+            && !cipher_sync_data.user_full_access_for_organizations.contains(org_id)
+            && !cipher_sync_data.user_group_full_access_for_organizations.contains(org_id)
+    });
+
     let mut ciphers_json = Vec::with_capacity(ciphers.len());
     for c in ciphers {
+        if only_assigned
+            && !cipher_sync_data.cipher_collections.get(&c.uuid).is_some_and(|collections| {
+                collections.iter().any(|collection| {
+                    cipher_sync_data.user_collections.contains_key(collection)
+                        || cipher_sync_data.user_collections_groups.contains_key(collection)
+                })
+            })
+        {
+            continue;
+        }
         ciphers_json
             .push(c.to_json(host, user_uuid, Some(&cipher_sync_data), CipherSyncType::Organization, conn).await);
     }
@@ -781,7 +1214,7 @@ struct GetOrgUserData {
 async fn get_org_users(
     data: GetOrgUserData,
     org_id: &str,
-    _headers: ManagerHeadersLoose,
+    _headers: ViewMembersHeaders,
     mut conn: DbConn,
 ) -> Json<Value> {
     let mut users_json = Vec::new();
@@ -835,6 +1268,8 @@ struct CollectionData {
     id: String,
     read_only: bool,
     hide_passwords: bool,
+    #[serde(default)]
+    manage: bool,
 }
 
 #[derive(Deserialize)]
@@ -845,10 +1280,99 @@ struct InviteData {
     r#type: NumberOrString,
     collections: Option<Vec<CollectionData>>,
     access_all: Option<bool>,
+    permissions: Option<UserOrgPermissions>,
+}
+
+/// Organizations can limit invitations to the email addresses in their verified domains
+async fn check_invite_domains(org: &Organization, emails: &[String], conn: &mut DbConn) -> EmptyResult {
+    if !org.limit_invitations_to_verified_domains {
+        return Ok(());
+    }
+
+    let verified_domains: Vec<String> = OrganizationDomain::find_by_org(&org.uuid, conn)
+        .await
+        .into_iter()
+        .filter(OrganizationDomain::is_verified)
+        .map(|d| d.domain_name)
+        .collect();
+    for email in emails {
+        if !email_domain(email).is_some_and(|d| verified_domains.contains(&d)) {
+            err!(format!("Only users with an email address in a verified domain can be invited: {email}"))
+        }
+    }
+    Ok(())
+}
+
+/// Members with a Custom role can only grant access to the collections they can manage themselves,
+/// else they could give their own or other accounts more access than they have
+async fn check_custom_collection_grants(
+    org_user: &UserOrganization,
+    col_ids: &[String],
+    conn: &mut DbConn,
+) -> EmptyResult {
+    if org_user.atype != UserOrgType::Custom {
+        return Ok(());
+    }
+    for col_id in col_ids {
+        if !Collection::can_manage_collection(org_user, col_id, conn).await {
+            err!("You can only grant access to collections you can manage")
+        }
+    }
+    Ok(())
+}
+
+/// The same as `check_custom_collection_grants`, for the collections members get through their groups
+async fn check_custom_group_grants(
+    org_user: &UserOrganization,
+    group_ids: &[String],
+    conn: &mut DbConn,
+) -> EmptyResult {
+    if org_user.atype != UserOrgType::Custom {
+        return Ok(());
+    }
+    for group_id in group_ids {
+        let Some(group) = Group::find_by_uuid(group_id, conn).await else {
+            err!("Group not found")
+        };
+        if group.organizations_uuid != org_user.org_uuid {
+            err!("Group doesn't belong to organization")
+        }
+        if group.access_all {
+            err!("Only Admins or Owners can manage groups with access to all collections")
+        }
+        let col_ids: Vec<String> =
+            CollectionGroup::find_by_group(group_id, conn).await.into_iter().map(|cg| cg.collections_uuid).collect();
+        check_custom_collection_grants(org_user, &col_ids, conn).await?;
+    }
+    Ok(())
+}
+
+/// Checks the access a member with a Custom role wants to give when inviting or editing a member
+async fn check_custom_member_access(
+    access_all: bool,
+    collections: Option<&Vec<CollectionData>>,
+    groups: &[String],
+    headers: &ManageUsersHeaders,
+    conn: &mut DbConn,
+) -> EmptyResult {
+    if headers.org_user_type != UserOrgType::Custom {
+        return Ok(());
+    }
+    if access_all {
+        err!("Only Admins or Owners can give access to all collections")
+    }
+    let col_ids: Vec<String> = collections.into_iter().flatten().map(|col| col.id.clone()).collect();
+    check_custom_collection_grants(&headers.org_user, &col_ids, conn).await?;
+    check_custom_group_grants(&headers.org_user, groups, conn).await
 }
 
 #[post("/organizations/<org_id>/users/invite", data = "<data>")]
-async fn send_invite(org_id: &str, data: Json<InviteData>, headers: AdminHeaders, mut conn: DbConn) -> EmptyResult {
+async fn send_invite(
+    org_id: &str,
+    data: Json<InviteData>,
+    headers: ManageUsersHeaders,
+    mut conn: DbConn,
+) -> EmptyResult {
     let data: InviteData = data.into_inner();
 
     let new_type = match UserOrgType::from_str(&data.r#type.into_string()) {
@@ -860,6 +1384,24 @@ async fn send_invite(org_id: &str, data: Json<InviteData>, headers: AdminHeaders
         err!("Only Owners can invite Managers, Admins or Owners")
     }
 
+    let Some(org) = Organization::find_by_uuid(org_id, &mut conn).await else {
+        err!("Error looking up organization")
+    };
+    if org.is_pending_deletion() {
+        err!("The organization is scheduled for deletion")
+    }
+
+    // Check all the addresses first, so a rejected one doesn't leave the other invitations half done
+    check_invite_domains(&org, &data.emails, &mut conn).await?;
+    check_custom_member_access(
+        data.access_all.unwrap_or(false),
+        data.collections.as_ref(),
+        &data.groups,
+        &headers,
+        &mut conn,
+    )
+    .await?;
+
     for email in data.emails.iter() {
         let email = email.to_lowercase();
         let mut user_org_status = UserOrgStatus::Invited as i32;
@@ -899,6 +1441,7 @@ async fn send_invite(org_id: &str, data: Json<InviteData>, headers: AdminHeaders
         let access_all = data.access_all.unwrap_or(false);
         new_user.access_all = access_all;
         new_user.atype = new_type;
+        new_user.set_permissions(data.permissions.clone());
         new_user.status = user_org_status;
 
         // If no accessAll, add the collections received
@@ -912,6 +1455,7 @@ async fn send_invite(org_id: &str, data: Json<InviteData>, headers: AdminHeaders
                             &collection.uuid,
                             col.read_only,
                             col.hide_passwords,
+                            col.manage,
                             &mut conn,
                         )
                         .await?;
@@ -939,17 +1483,13 @@ async fn send_invite(org_id: &str, data: Json<InviteData>, headers: AdminHeaders
         .await;
 
         if CONFIG.mail_enabled() {
-            let org_name = match Organization::find_by_uuid(org_id, &mut conn).await {
-                Some(org) => org.name,
-                None => err!("Error looking up organization"),
-            };
-
             mail::send_invite(
                 &email,
+                user.locale.as_deref(),
                 &user.uuid,
                 Some(String::from(org_id)),
                 Some(new_user.uuid),
-                &org_name,
+                &org.name,
                 Some(headers.user.email.clone()),
             )
             .await?;
@@ -963,7 +1503,7 @@ async fn send_invite(org_id: &str, data: Json<InviteData>, headers: AdminHeaders
 async fn bulk_reinvite_user(
     org_id: &str,
     data: Json<OrgBulkIds>,
-    headers: AdminHeaders,
+    headers: ManageUsersHeaders,
     mut conn: DbConn,
 ) -> Json<Value> {
     let data: OrgBulkIds = data.into_inner();
@@ -991,13 +1531,40 @@ async fn bulk_reinvite_user(
     }))
 }
 
+#[post("/organizations/<org_id>/users/reinvite-expired")]
+async fn bulk_reinvite_expired_users(org_id: &str, headers: ManageUsersHeaders, mut conn: DbConn) -> Json<Value> {
+    let cutoff = UserOrganization::invitation_expiration_cutoff();
+
+    let mut bulk_response = Vec::new();
+    for user_org in UserOrganization::find_invited_before(&cutoff, Some(org_id), &mut conn).await {
+        let err_msg = match _reinvite_user(org_id, &user_org.uuid, &headers.user.email, &mut conn).await {
+            Ok(_) => String::new(),
+            Err(e) => format!("{e:?}"),
+        };
+
+        bulk_response.push(json!(
+            {
+                "object": "OrganizationBulkConfirmResponseModel",
+                "id": user_org.uuid,
+                "error": err_msg
+            }
+        ))
+    }
+
+    Json(json!({
+        "data": bulk_response,
+        "object": "list",
+        "continuationToken": null
+    }))
+}
+
 #[post("/organizations/<org_id>/users/<user_org>/reinvite")]
-async fn reinvite_user(org_id: &str, user_org: &str, headers: AdminHeaders, mut conn: DbConn) -> EmptyResult {
+async fn reinvite_user(org_id: &str, user_org: &str, headers: ManageUsersHeaders, mut conn: DbConn) -> EmptyResult {
     _reinvite_user(org_id, user_org, &headers.user.email, &mut conn).await
 }
 
 async fn _reinvite_user(org_id: &str, user_org: &str, invited_by_email: &str, conn: &mut DbConn) -> EmptyResult {
-    let user_org = match UserOrganization::find_by_uuid(user_org, conn).await {
+    let mut user_org = match UserOrganization::find_by_uuid(user_org, conn).await {
         Some(user_org) => user_org,
         None => err!("The user hasn't been invited to the organization."),
     };
@@ -1015,18 +1582,23 @@ async fn _reinvite_user(org_id: &str, user_org: &str, invited_by_email: &str, co
         err!("Invitations are not allowed.")
     }
 
-    let org_name = match Organization::find_by_uuid(org_id, conn).await {
-        Some(org) => org.name,
-        None => err!("Error looking up organization."),
+    let Some(org) = Organization::find_by_uuid(org_id, conn).await else {
+        err!("Error looking up organization.")
     };
+    if org.is_pending_deletion() {
+        err!("The organization is scheduled for deletion")
+    }
+
+    check_invite_domains(&org, &[user.email.clone()], conn).await?;
 
     if CONFIG.mail_enabled() {
         mail::send_invite(
             &user.email,
+            user.locale.as_deref(),
             &user.uuid,
             Some(org_id.to_string()),
-            Some(user_org.uuid),
-            &org_name,
+            Some(user_org.uuid.clone()),
+            &org.name,
             Some(invited_by_email.to_string()),
         )
         .await?;
@@ -1035,11 +1607,13 @@ async fn _reinvite_user(org_id: &str, user_org: &str, invited_by_email: &str, co
         invitation.save(conn).await?;
     } else {
         let _ = Invitation::take(&user.email, conn).await;
-        let mut user_org = user_org;
         user_org.status = UserOrgStatus::Accepted as i32;
-        user_org.save(conn).await?;
     }
 
+    // Restart the expiration of the invitation
+    user_org.invited_at = Some(Utc::now().naive_utc());
+    user_org.save(conn).await?;
+
     Ok(())
 }
 
@@ -1056,7 +1630,7 @@ async fn accept_invite(org_id: &str, _org_user_id: &str, data: Json<AcceptData>,
     let data: AcceptData = data.into_inner();
     let claims = decode_invite(&data.token)?;
 
-    match User::find_by_mail(&claims.email, &mut conn).await {
+    let user = match User::find_by_mail(&claims.email, &mut conn).await {
         Some(user) => {
             Invitation::take(&claims.email, &mut conn).await;
 
@@ -1065,6 +1639,7 @@ async fn accept_invite(org_id: &str, _org_user_id: &str, data: Json<AcceptData>,
                     Some(user_org) => user_org,
                     None => err!("Error accepting the invitation"),
                 };
+                check_org_not_pending_deletion(org, &mut conn).await?;
 
                 if user_org.status != UserOrgStatus::Invited as i32 {
                     err!("User already accepted the invitation")
@@ -1101,9 +1676,10 @@ async fn accept_invite(org_id: &str, _org_user_id: &str, data: Json<AcceptData>,
 
                 user_org.save(&mut conn).await?;
             }
+            user
         }
         None => err!("Invited user not found"),
-    }
+    };
 
     if CONFIG.mail_enabled() {
         let mut org_name = CONFIG.invitation_org_name();
@@ -1115,10 +1691,21 @@ async fn accept_invite(org_id: &str, _org_user_id: &str, data: Json<AcceptData>,
         };
         if let Some(invited_by_email) = &claims.invited_by_email {
             // User was invited to an organization, so they must be confirmed manually after acceptance
-            mail::send_invite_accepted(&claims.email, invited_by_email, &org_name).await?;
+            if let Some(invited_by) = User::find_by_mail(invited_by_email, &mut conn).await {
+                let prefs = NotificationPreference::find_or_default_by_user(&invited_by.uuid, &mut conn).await;
+                mail::send_invite_accepted(
+                    &claims.email,
+                    &invited_by.email,
+                    invited_by.locale.as_deref(),
+                    &org_name,
+                    &prefs,
+                )
+                .await?;
+            }
         } else {
             // User was invited from /admin, so they are automatically confirmed
-            mail::send_invite_confirmed(&claims.email, &org_name).await?;
+            let prefs = NotificationPreference::find_or_default_by_user(&user.uuid, &mut conn).await;
+            mail::send_invite_confirmed(&claims.email, user.locale.as_deref(), &org_name, &prefs).await?;
         }
     }
 
@@ -1142,22 +1729,24 @@ struct BulkConfirmData {
 async fn bulk_confirm_invite(
     org_id: &str,
     data: Json<BulkConfirmData>,
-    headers: AdminHeaders,
+    headers: ManageUsersHeaders,
     mut conn: DbConn,
     nt: Notify<'_>,
 ) -> Json<Value> {
     let data = data.into_inner();
 
     let mut bulk_response = Vec::new();
+    let mut targets = SyncTargets::default();
     match data.keys {
         Some(keys) => {
             for invite in keys {
                 let org_user_id = invite.id.unwrap_or_default();
                 let user_key = invite.key.unwrap_or_default();
-                let err_msg = match _confirm_invite(org_id, &org_user_id, &user_key, &headers, &mut conn, &nt).await {
-                    Ok(_) => String::new(),
-                    Err(e) => format!("{e:?}"),
-                };
+                let err_msg =
+                    match _confirm_invite(org_id, &org_user_id, &user_key, &headers, &mut targets, &mut conn).await {
+                        Ok(_) => String::new(),
+                        Err(e) => format!("{e:?}"),
+                    };
 
                 bulk_response.push(json!(
                     {
@@ -1171,6 +1760,8 @@ async fn bulk_confirm_invite(
         None => error!("No keys to confirm"),
     }
 
+    targets.notify(UpdateType::SyncOrgKeys, &nt, &mut conn).await;
+
     Json(json!({
         "data": bulk_response,
         "object": "list",
@@ -1183,26 +1774,30 @@ async fn confirm_invite(
     org_id: &str,
     org_user_id: &str,
     data: Json<ConfirmData>,
-    headers: AdminHeaders,
+    headers: ManageUsersHeaders,
     mut conn: DbConn,
     nt: Notify<'_>,
 ) -> EmptyResult {
     let data = data.into_inner();
     let user_key = data.key.unwrap_or_default();
-    _confirm_invite(org_id, org_user_id, &user_key, &headers, &mut conn, &nt).await
+    let mut targets = SyncTargets::default();
+    _confirm_invite(org_id, org_user_id, &user_key, &headers, &mut targets, &mut conn).await?;
+    targets.notify(UpdateType::SyncOrgKeys, &nt, &mut conn).await;
+    Ok(())
 }
 
 async fn _confirm_invite(
     org_id: &str,
     org_user_id: &str,
     key: &str,
-    headers: &AdminHeaders,
+    headers: &ManageUsersHeaders,
+    targets: &mut SyncTargets,
     conn: &mut DbConn,
-    nt: &Notify<'_>,
 ) -> EmptyResult {
     if key.is_empty() || org_user_id.is_empty() {
         err!("Key or UserId is not set, unable to process request");
     }
+    check_org_not_pending_deletion(org_id, conn).await?;
 
     let mut user_to_confirm = match UserOrganization::find_by_uuid_and_org(org_user_id, org_id, conn).await {
         Some(user) => user,
@@ -1254,20 +1849,17 @@ async fn _confirm_invite(
             Some(org) => org.name,
             None => err!("Error looking up organization."),
         };
-        let address = match User::find_by_uuid(&user_to_confirm.user_uuid, conn).await {
-            Some(user) => user.email,
+        let user = match User::find_by_uuid(&user_to_confirm.user_uuid, conn).await {
+            Some(user) => user,
             None => err!("Error looking up user."),
         };
-        mail::send_invite_confirmed(&address, &org_name).await?;
+        let prefs = NotificationPreference::find_or_default_by_user(&user.uuid, conn).await;
+        mail::send_invite_confirmed(&user.email, user.locale.as_deref(), &org_name, &prefs).await?;
     }
 
-    let save_result = user_to_confirm.save(conn).await;
-
-    if let Some(user) = User::find_by_uuid(&user_to_confirm.user_uuid, conn).await {
-        nt.send_user_update(UpdateType::SyncOrgKeys, &user).await;
-    }
-
-    save_result
+    user_to_confirm.save(conn).await?;
+    targets.add_user(&user_to_confirm.user_uuid);
+    Ok(())
 }
 
 #[get("/organizations/<org_id>/users/<org_user_id>?<data..>")]
@@ -1275,7 +1867,7 @@ async fn get_user(
     org_id: &str,
     org_user_id: &str,
     data: GetOrgUserData,
-    _headers: AdminHeaders,
+    _headers: ManageUsersHeaders,
     mut conn: DbConn,
 ) -> JsonResult {
     let user = match UserOrganization::find_by_uuid_and_org(org_user_id, org_id, &mut conn).await {
@@ -1298,6 +1890,7 @@ struct EditUserData {
     collections: Option<Vec<CollectionData>>,
     groups: Option<Vec<String>>,
     access_all: bool,
+    permissions: Option<UserOrgPermissions>,
 }
 
 #[put("/organizations/<org_id>/users/<org_user_id>", data = "<data>", rank = 1)]
@@ -1305,10 +1898,11 @@ async fn put_organization_user(
     org_id: &str,
     org_user_id: &str,
     data: Json<EditUserData>,
-    headers: AdminHeaders,
+    headers: ManageUsersHeaders,
     conn: DbConn,
+    nt: Notify<'_>,
 ) -> EmptyResult {
-    edit_user(org_id, org_user_id, data, headers, conn).await
+    edit_user(org_id, org_user_id, data, headers, conn, nt).await
 }
 
 #[post("/organizations/<org_id>/users/<org_user_id>", data = "<data>", rank = 1)]
@@ -1316,8 +1910,9 @@ async fn edit_user(
     org_id: &str,
     org_user_id: &str,
     data: Json<EditUserData>,
-    headers: AdminHeaders,
+    headers: ManageUsersHeaders,
     mut conn: DbConn,
+    nt: Notify<'_>,
 ) -> EmptyResult {
     let data: EditUserData = data.into_inner();
 
@@ -1342,6 +1937,21 @@ async fn edit_user(
         err!("Only Owners can edit Owner users")
     }
 
+    // Members with a Custom role are only allowed to manage regular users, else they could grant themselves more access
+    if headers.org_user_type == UserOrgType::Custom
+        && (user_to_edit.atype != UserOrgType::User || new_type != UserOrgType::User)
+    {
+        err!("Only Admins or Owners can grant and remove Manager or Custom privileges")
+    }
+    check_custom_member_access(
+        data.access_all,
+        data.collections.as_ref(),
+        data.groups.as_deref().unwrap_or_default(),
+        &headers,
+        &mut conn,
+    )
+    .await?;
+
     if user_to_edit.atype == UserOrgType::Owner
         && new_type != UserOrgType::Owner
         && user_to_edit.status == UserOrgStatus::Confirmed as i32
@@ -1370,8 +1980,13 @@ async fn edit_user(
         }
     }
 
+    if user_to_edit.atype != new_type {
+        Organization::cancel_transfer_of_member(&user_to_edit, &mut conn).await?;
+    }
+
     user_to_edit.access_all = data.access_all;
     user_to_edit.atype = new_type as i32;
+    user_to_edit.set_permissions(data.permissions);
 
     // Delete all the odd collections
     for c in CollectionUser::find_by_organization_and_user_uuid(org_id, &user_to_edit.user_uuid, &mut conn).await {
@@ -1389,6 +2004,7 @@ async fn edit_user(
                         &collection.uuid,
                         col.read_only,
                         col.hide_passwords,
+                        col.manage,
                         &mut conn,
                     )
                     .await?;
@@ -1415,22 +2031,29 @@ async fn edit_user(
     )
     .await;
 
-    user_to_edit.save(&mut conn).await
+    user_to_edit.save(&mut conn).await?;
+
+    // The type of the member could have changed, which is part of the organization details the clients keep
+    let mut targets = SyncTargets::default();
+    targets.add_user(&user_to_edit.user_uuid);
+    targets.notify(UpdateType::SyncOrgKeys, &nt, &mut conn).await;
+    Ok(())
 }
 
 #[delete("/organizations/<org_id>/users", data = "<data>")]
 async fn bulk_delete_user(
     org_id: &str,
     data: Json<OrgBulkIds>,
-    headers: AdminHeaders,
+    headers: ManageUsersHeaders,
     mut conn: DbConn,
     nt: Notify<'_>,
 ) -> Json<Value> {
     let data: OrgBulkIds = data.into_inner();
 
     let mut bulk_response = Vec::new();
+    let mut targets = SyncTargets::default();
     for org_user_id in data.ids {
-        let err_msg = match _delete_user(org_id, &org_user_id, &headers, &mut conn, &nt).await {
+        let err_msg = match _delete_user(org_id, &org_user_id, &headers, &mut targets, &mut conn).await {
             Ok(_) => String::new(),
             Err(e) => format!("{e:?}"),
         };
@@ -1444,6 +2067,8 @@ async fn bulk_delete_user(
         ))
     }
 
+    targets.notify(UpdateType::SyncOrgKeys, &nt, &mut conn).await;
+
     Json(json!({
         "data": bulk_response,
         "object": "list",
@@ -1455,30 +2080,36 @@ async fn bulk_delete_user(
 async fn delete_user(
     org_id: &str,
     org_user_id: &str,
-    headers: AdminHeaders,
+    headers: ManageUsersHeaders,
     mut conn: DbConn,
     nt: Notify<'_>,
 ) -> EmptyResult {
-    _delete_user(org_id, org_user_id, &headers, &mut conn, &nt).await
+    let mut targets = SyncTargets::default();
+    _delete_user(org_id, org_user_id, &headers, &mut targets, &mut conn).await?;
+    targets.notify(UpdateType::SyncOrgKeys, &nt, &mut conn).await;
+    Ok(())
 }
 
 #[post("/organizations/<org_id>/users/<org_user_id>/delete")]
 async fn post_delete_user(
     org_id: &str,
     org_user_id: &str,
-    headers: AdminHeaders,
+    headers: ManageUsersHeaders,
     mut conn: DbConn,
     nt: Notify<'_>,
 ) -> EmptyResult {
-    _delete_user(org_id, org_user_id, &headers, &mut conn, &nt).await
+    let mut targets = SyncTargets::default();
+    _delete_user(org_id, org_user_id, &headers, &mut targets, &mut conn).await?;
+    targets.notify(UpdateType::SyncOrgKeys, &nt, &mut conn).await;
+    Ok(())
 }
 
 async fn _delete_user(
     org_id: &str,
     org_user_id: &str,
-    headers: &AdminHeaders,
+    headers: &ManageUsersHeaders,
+    targets: &mut SyncTargets,
     conn: &mut DbConn,
-    nt: &Notify<'_>,
 ) -> EmptyResult {
     let user_to_delete = match UserOrganization::find_by_uuid_and_org(org_user_id, org_id, conn).await {
         Some(user) => user,
@@ -1507,10 +2138,8 @@ async fn _delete_user(
     )
     .await;
 
-    if let Some(user) = User::find_by_uuid(&user_to_delete.user_uuid, conn).await {
-        nt.send_user_update(UpdateType::SyncOrgKeys, &user).await;
-    }
-
+    Organization::cancel_transfer_of_member(&user_to_delete, conn).await?;
+    targets.add_user(&user_to_delete.user_uuid);
     user_to_delete.delete(conn).await
 }
 
@@ -1518,7 +2147,7 @@ async fn _delete_user(
 async fn bulk_public_keys(
     org_id: &str,
     data: Json<OrgBulkIds>,
-    _headers: AdminHeaders,
+    _headers: ManageUsersHeaders,
     mut conn: DbConn,
 ) -> Json<Value> {
     let data: OrgBulkIds = data.into_inner();
@@ -1575,12 +2204,13 @@ struct RelationsData {
 async fn post_org_import(
     query: OrgIdData,
     data: Json<ImportData>,
-    headers: AdminHeaders,
+    headers: ImportExportHeaders,
     mut conn: DbConn,
     nt: Notify<'_>,
 ) -> EmptyResult {
     let data: ImportData = data.into_inner();
     let org_id = query.organization_id;
+    check_org_not_pending_deletion(&org_id, &mut conn).await?;
 
     // Validate the import before continuing
     // Bitwarden does not process the import if there is one item invalid.
@@ -1625,12 +2255,18 @@ async fn post_org_import(
         CollectionCipher::save(cipher_id, coll_id, &mut conn).await?;
     }
 
-    let mut user = headers.user;
-    user.update_revision(&mut conn).await
+    // The ciphers are imported without notifications, so send a single sync to everyone who can see them
+    let mut targets = SyncTargets::default();
+    targets.add_user(&headers.user.uuid);
+    for coll in collections.iter().flatten() {
+        targets.add_collection(&coll.uuid, &org_id, &mut conn).await;
+    }
+    targets.notify(UpdateType::SyncVault, &nt, &mut conn).await;
+    Ok(())
 }
 
 #[get("/organizations/<org_id>/policies")]
-async fn list_policies(org_id: &str, _headers: AdminHeaders, mut conn: DbConn) -> Json<Value> {
+async fn list_policies(org_id: &str, _headers: ManagePoliciesHeaders, mut conn: DbConn) -> Json<Value> {
     let policies = OrgPolicy::find_by_org(org_id, &mut conn).await;
     let policies_json: Vec<Value> = policies.iter().map(OrgPolicy::to_json).collect();
 
@@ -1666,7 +2302,7 @@ async fn list_policies_token(org_id: &str, token: &str, mut conn: DbConn) -> Jso
 }
 
 #[get("/organizations/<org_id>/policies/<pol_type>")]
-async fn get_policy(org_id: &str, pol_type: i32, _headers: AdminHeaders, mut conn: DbConn) -> JsonResult {
+async fn get_policy(org_id: &str, pol_type: i32, _headers: ManagePoliciesHeaders, mut conn: DbConn) -> JsonResult {
     let pol_type_enum = match OrgPolicyType::from_i32(pol_type) {
         Some(pt) => pt,
         None => err!("Invalid or unsupported policy type"),
@@ -1693,8 +2329,9 @@ async fn put_policy(
     org_id: &str,
     pol_type: i32,
     data: Json<PolicyData>,
-    headers: AdminHeaders,
+    headers: ManagePoliciesHeaders,
     mut conn: DbConn,
+    nt: Notify<'_>,
 ) -> JsonResult {
     let data: PolicyData = data.into_inner();
 
@@ -1716,6 +2353,7 @@ async fn put_policy(
     }
 
     // When enabling the SingleOrg policy, remove this org's members that are members of other orgs
+    let mut removed_members = SyncTargets::default();
     if pol_type_enum == OrgPolicyType::SingleOrg && data.enabled {
         for member in UserOrganization::find_by_org(org_id, &mut conn).await.into_iter() {
             // Policy only applies to non-Owner/non-Admin members who have accepted joining the org
@@ -1730,7 +2368,7 @@ async fn put_policy(
                     let org = Organization::find_by_uuid(&member.org_uuid, &mut conn).await.unwrap();
                     let user = User::find_by_uuid(&member.user_uuid, &mut conn).await.unwrap();
 
-                    mail::send_single_org_removed_from_org(&user.email, &org.name).await?;
+                    mail::send_single_org_removed_from_org(&user.email, user.locale.as_deref(), &org.name).await?;
                 }
 
                 log_event(
@@ -1744,6 +2382,7 @@ async fn put_policy(
                 )
                 .await;
 
+                removed_members.add_user(&member.user_uuid);
                 member.delete(&mut conn).await?;
             }
         }
@@ -1769,6 +2408,12 @@ async fn put_policy(
     )
     .await;
 
+    removed_members.notify(UpdateType::SyncOrgKeys, &nt, &mut conn).await;
+
+    let mut targets = SyncTargets::default();
+    targets.add_confirmed_members(org_id, &mut conn).await;
+    targets.notify(UpdateType::SyncSettings, &nt, &mut conn).await;
+
     Ok(Json(policy.to_json()))
 }
 
@@ -1874,6 +2519,24 @@ async fn import(org_id: &str, data: Json<OrgImportData>, headers: Headers, mut c
         None => err!("User not part of organization"),
     };
 
+    let Some(org) = Organization::find_by_uuid(org_id, &mut conn).await else {
+        err!("Error looking up organization")
+    };
+    if org.is_pending_deletion() {
+        err!("The organization is scheduled for deletion")
+    }
+
+    // Check the addresses of all the users who will be invited first, so a rejected one doesn't leave the import half done
+    let mut invite_emails = Vec::new();
+    for user_data in data.users.iter().filter(|u| !u.deleted) {
+        if UserOrganization::find_by_email_and_org(&user_data.email, org_id, &mut conn).await.is_none()
+            && User::find_by_mail(&user_data.email, &mut conn).await.is_some()
+        {
+            invite_emails.push(user_data.email.clone());
+        }
+    }
+    check_invite_domains(&org, &invite_emails, &mut conn).await?;
+
     for user_data in &data.users {
         if user_data.deleted {
             // If user is marked for deletion and it exists, delete it
@@ -1920,17 +2583,13 @@ async fn import(org_id: &str, data: Json<OrgImportData>, headers: Headers, mut c
                 .await;
 
                 if CONFIG.mail_enabled() {
-                    let org_name = match Organization::find_by_uuid(org_id, &mut conn).await {
-                        Some(org) => org.name,
-                        None => err!("Error looking up organization"),
-                    };
-
                     mail::send_invite(
                         &user_data.email,
+                        user.locale.as_deref(),
                         &user.uuid,
                         Some(String::from(org_id)),
                         Some(new_org_user.uuid),
-                        &org_name,
+                        &org.name,
                         Some(headers.user.email.clone()),
                     )
                     .await?;
@@ -1969,10 +2628,14 @@ async fn import(org_id: &str, data: Json<OrgImportData>, headers: Headers, mut c
 async fn deactivate_organization_user(
     org_id: &str,
     org_user_id: &str,
-    headers: AdminHeaders,
+    headers: ManageUsersHeaders,
     mut conn: DbConn,
+    nt: Notify<'_>,
 ) -> EmptyResult {
-    _revoke_organization_user(org_id, org_user_id, &headers, &mut conn).await
+    let mut targets = SyncTargets::default();
+    _revoke_organization_user(org_id, org_user_id, &headers, &mut targets, &mut conn).await?;
+    targets.notify(UpdateType::SyncOrgKeys, &nt, &mut conn).await;
+    Ok(())
 }
 
 // Pre web-vault v2022.9.x endpoint
@@ -1980,20 +2643,25 @@ async fn deactivate_organization_user(
 async fn bulk_deactivate_organization_user(
     org_id: &str,
     data: Json<OrgBulkRevokeData>,
-    headers: AdminHeaders,
+    headers: ManageUsersHeaders,
     conn: DbConn,
+    nt: Notify<'_>,
 ) -> Json<Value> {
-    bulk_revoke_organization_user(org_id, data, headers, conn).await
+    bulk_revoke_organization_user(org_id, data, headers, conn, nt).await
 }
 
 #[put("/organizations/<org_id>/users/<org_user_id>/revoke")]
 async fn revoke_organization_user(
     org_id: &str,
     org_user_id: &str,
-    headers: AdminHeaders,
+    headers: ManageUsersHeaders,
     mut conn: DbConn,
+    nt: Notify<'_>,
 ) -> EmptyResult {
-    _revoke_organization_user(org_id, org_user_id, &headers, &mut conn).await
+    let mut targets = SyncTargets::default();
+    _revoke_organization_user(org_id, org_user_id, &headers, &mut targets, &mut conn).await?;
+    targets.notify(UpdateType::SyncOrgKeys, &nt, &mut conn).await;
+    Ok(())
 }
 
 #[derive(Deserialize, Debug)]
@@ -2006,19 +2674,22 @@ struct OrgBulkRevokeData {
 async fn bulk_revoke_organization_user(
     org_id: &str,
     data: Json<OrgBulkRevokeData>,
-    headers: AdminHeaders,
+    headers: ManageUsersHeaders,
     mut conn: DbConn,
+    nt: Notify<'_>,
 ) -> Json<Value> {
     let data = data.into_inner();
 
     let mut bulk_response = Vec::new();
+    let mut targets = SyncTargets::default();
     match data.ids {
         Some(org_users) => {
             for org_user_id in org_users {
-                let err_msg = match _revoke_organization_user(org_id, &org_user_id, &headers, &mut conn).await {
-                    Ok(_) => String::new(),
-                    Err(e) => format!("{e:?}"),
-                };
+                let err_msg =
+                    match _revoke_organization_user(org_id, &org_user_id, &headers, &mut targets, &mut conn).await {
+                        Ok(_) => String::new(),
+                        Err(e) => format!("{e:?}"),
+                    };
 
                 bulk_response.push(json!(
                     {
@@ -2032,6 +2703,8 @@ async fn bulk_revoke_organization_user(
         None => error!("No users to revoke"),
     }
 
+    targets.notify(UpdateType::SyncOrgKeys, &nt, &mut conn).await;
+
     Json(json!({
         "data": bulk_response,
         "object": "list",
@@ -2042,7 +2715,8 @@ async fn bulk_revoke_organization_user(
 async fn _revoke_organization_user(
     org_id: &str,
     org_user_id: &str,
-    headers: &AdminHeaders,
+    headers: &ManageUsersHeaders,
+    targets: &mut SyncTargets,
     conn: &mut DbConn,
 ) -> EmptyResult {
     match UserOrganization::find_by_uuid_and_org(org_user_id, org_id, conn).await {
@@ -2053,14 +2727,19 @@ async fn _revoke_organization_user(
             if user_org.atype == UserOrgType::Owner && headers.org_user_type != UserOrgType::Owner {
                 err!("Only owners can revoke other owners")
             }
+            if user_org.atype != UserOrgType::User && headers.org_user_type == UserOrgType::Custom {
+                err!("Only admins or owners can revoke members with elevated privileges")
+            }
             if user_org.atype == UserOrgType::Owner
                 && UserOrganization::count_confirmed_by_org_and_type(org_id, UserOrgType::Owner, conn).await <= 1
             {
                 err!("Organization must have at least one confirmed owner")
             }
 
+            Organization::cancel_transfer_of_member(&user_org, conn).await?;
             user_org.revoke();
             user_org.save(conn).await?;
+            targets.add_user(&user_org.user_uuid);
 
             log_event(
                 EventType::OrganizationUserRevoked as i32,
@@ -2084,10 +2763,14 @@ async fn _revoke_organization_user(
 async fn activate_organization_user(
     org_id: &str,
     org_user_id: &str,
-    headers: AdminHeaders,
+    headers: ManageUsersHeaders,
     mut conn: DbConn,
+    nt: Notify<'_>,
 ) -> EmptyResult {
-    _restore_organization_user(org_id, org_user_id, &headers, &mut conn).await
+    let mut targets = SyncTargets::default();
+    _restore_organization_user(org_id, org_user_id, &headers, &mut targets, &mut conn).await?;
+    targets.notify(UpdateType::SyncOrgKeys, &nt, &mut conn).await;
+    Ok(())
 }
 
 // Pre web-vault v2022.9.x endpoint
@@ -2095,34 +2778,41 @@ async fn activate_organization_user(
 async fn bulk_activate_organization_user(
     org_id: &str,
     data: Json<OrgBulkIds>,
-    headers: AdminHeaders,
+    headers: ManageUsersHeaders,
     conn: DbConn,
+    nt: Notify<'_>,
 ) -> Json<Value> {
-    bulk_restore_organization_user(org_id, data, headers, conn).await
+    bulk_restore_organization_user(org_id, data, headers, conn, nt).await
 }
 
 #[put("/organizations/<org_id>/users/<org_user_id>/restore")]
 async fn restore_organization_user(
     org_id: &str,
     org_user_id: &str,
-    headers: AdminHeaders,
+    headers: ManageUsersHeaders,
     mut conn: DbConn,
+    nt: Notify<'_>,
 ) -> EmptyResult {
-    _restore_organization_user(org_id, org_user_id, &headers, &mut conn).await
+    let mut targets = SyncTargets::default();
+    _restore_organization_user(org_id, org_user_id, &headers, &mut targets, &mut conn).await?;
+    targets.notify(UpdateType::SyncOrgKeys, &nt, &mut conn).await;
+    Ok(())
 }
 
 #[put("/organizations/<org_id>/users/restore", data = "<data>")]
 async fn bulk_restore_organization_user(
     org_id: &str,
     data: Json<OrgBulkIds>,
-    headers: AdminHeaders,
+    headers: ManageUsersHeaders,
     mut conn: DbConn,
+    nt: Notify<'_>,
 ) -> Json<Value> {
     let data = data.into_inner();
 
     let mut bulk_response = Vec::new();
+    let mut targets = SyncTargets::default();
     for org_user_id in data.ids {
-        let err_msg = match _restore_organization_user(org_id, &org_user_id, &headers, &mut conn).await {
+        let err_msg = match _restore_organization_user(org_id, &org_user_id, &headers, &mut targets, &mut conn).await {
             Ok(_) => String::new(),
             Err(e) => format!("{e:?}"),
         };
@@ -2136,6 +2826,8 @@ async fn bulk_restore_organization_user(
         ));
     }
 
+    targets.notify(UpdateType::SyncOrgKeys, &nt, &mut conn).await;
+
     Json(json!({
         "data": bulk_response,
         "object": "list",
@@ -2146,7 +2838,8 @@ async fn bulk_restore_organization_user(
 async fn _restore_organization_user(
     org_id: &str,
     org_user_id: &str,
-    headers: &AdminHeaders,
+    headers: &ManageUsersHeaders,
+    targets: &mut SyncTargets,
     conn: &mut DbConn,
 ) -> EmptyResult {
     match UserOrganization::find_by_uuid_and_org(org_user_id, org_id, conn).await {
@@ -2157,6 +2850,9 @@ async fn _restore_organization_user(
             if user_org.atype == UserOrgType::Owner && headers.org_user_type != UserOrgType::Owner {
                 err!("Only owners can restore other owners")
             }
+            if user_org.atype != UserOrgType::User && headers.org_user_type == UserOrgType::Custom {
+                err!("Only admins or owners can restore members with elevated privileges")
+            }
 
             // This check is also done at accept_invite(), _confirm_invite, _activate_user(), edit_user(), admin::update_user_org_type
             // It returns different error messages per function.
@@ -2178,6 +2874,7 @@ async fn _restore_organization_user(
 
             user_org.restore();
             user_org.save(conn).await?;
+            targets.add_user(&user_org.user_uuid);
 
             log_event(
                 EventType::OrganizationUserRestored as i32,
@@ -2197,7 +2894,7 @@ async fn _restore_organization_user(
 }
 
 #[get("/organizations/<org_id>/groups")]
-async fn get_groups(org_id: &str, _headers: ManagerHeadersLoose, mut conn: DbConn) -> JsonResult {
+async fn get_groups(org_id: &str, _headers: ViewMembersHeaders, mut conn: DbConn) -> JsonResult {
     let groups: Vec<Value> = if CONFIG.org_groups_enabled() {
         // Group::find_by_organization(&org_id, &mut conn).await.iter().map(Group::to_json).collect::<Value>()
         let groups = Group::find_by_organization(org_id, &mut conn).await;
@@ -2247,6 +2944,10 @@ impl GroupRequest {
 
         group
     }
+
+    fn collection_ids(&self) -> Vec<String> {
+        self.collections.iter().map(|col| col.id.clone()).collect()
+    }
 }
 
 #[derive(Deserialize, Serialize)]
@@ -2255,11 +2956,13 @@ struct SelectionReadOnly {
     id: String,
     read_only: bool,
     hide_passwords: bool,
+    #[serde(default)]
+    manage: bool,
 }
 
 impl SelectionReadOnly {
     pub fn to_collection_group(&self, groups_uuid: String) -> CollectionGroup {
-        CollectionGroup::new(self.id.clone(), groups_uuid, self.read_only, self.hide_passwords)
+        CollectionGroup::new(self.id.clone(), groups_uuid, self.read_only, self.hide_passwords, self.manage)
     }
 
     pub fn to_collection_group_details_read_only(collection_group: &CollectionGroup) -> SelectionReadOnly {
@@ -2267,6 +2970,7 @@ impl SelectionReadOnly {
             id: collection_group.groups_uuid.clone(),
             read_only: collection_group.read_only,
             hide_passwords: collection_group.hide_passwords,
+            manage: collection_group.manage,
         }
     }
 
@@ -2275,6 +2979,7 @@ impl SelectionReadOnly {
             id: collection_user.user_uuid.clone(),
             read_only: collection_user.read_only,
             hide_passwords: collection_user.hide_passwords,
+            manage: collection_user.manage,
         }
     }
 
@@ -2283,24 +2988,72 @@ impl SelectionReadOnly {
     }
 }
 
+/// Members with a Custom role who can manage groups could otherwise give themselves more access through a group.
+/// They can't manage groups with access to all collections, can only give a group access to the collections
+/// they can manage themselves, and can't add or remove their own membership.
+async fn check_custom_group_changes(
+    group_id: Option<&str>,
+    access_all: bool,
+    col_ids: &[String],
+    users: &[String],
+    headers: &ManageGroupsHeaders,
+    conn: &mut DbConn,
+) -> EmptyResult {
+    if headers.org_user_type != UserOrgType::Custom {
+        return Ok(());
+    }
+    if access_all {
+        err!("Only Admins or Owners can manage groups with access to all collections")
+    }
+    check_custom_collection_grants(&headers.org_user, col_ids, conn).await?;
+
+    let own_id = &headers.org_user.uuid;
+    let was_member = match group_id {
+        Some(group_id) => {
+            GroupUser::find_by_group(group_id, conn).await.iter().any(|gu| &gu.users_organizations_uuid == own_id)
+        }
+        None => false,
+    };
+    if was_member != users.contains(own_id) {
+        err!("You can't change your own group memberships")
+    }
+    Ok(())
+}
+
 #[post("/organizations/<org_id>/groups/<group_id>", data = "<data>")]
 async fn post_group(
     org_id: &str,
     group_id: &str,
     data: Json<GroupRequest>,
-    headers: AdminHeaders,
+    headers: ManageGroupsHeaders,
     conn: DbConn,
+    nt: Notify<'_>,
 ) -> JsonResult {
-    put_group(org_id, group_id, data, headers, conn).await
+    put_group(org_id, group_id, data, headers, conn, nt).await
 }
 
 #[post("/organizations/<org_id>/groups", data = "<data>")]
-async fn post_groups(org_id: &str, headers: AdminHeaders, data: Json<GroupRequest>, mut conn: DbConn) -> JsonResult {
+async fn post_groups(
+    org_id: &str,
+    headers: ManageGroupsHeaders,
+    data: Json<GroupRequest>,
+    mut conn: DbConn,
+    nt: Notify<'_>,
+) -> JsonResult {
     if !CONFIG.org_groups_enabled() {
         err!("Group support is disabled");
     }
 
     let group_request = data.into_inner();
+    check_custom_group_changes(
+        None,
+        group_request.access_all.unwrap_or(false),
+        &group_request.collection_ids(),
+        &group_request.users,
+        &headers,
+        &mut conn,
+    )
+    .await?;
     let group = group_request.to_group(org_id);
 
     log_event(
@@ -2314,7 +3067,19 @@ async fn post_groups(org_id: &str, headers: AdminHeaders, data: Json<GroupReques
     )
     .await;
 
-    add_update_group(group, group_request.collections, group_request.users, org_id, &headers, &mut conn).await
+    let mut targets = SyncTargets::default();
+    let result = add_update_group(
+        group,
+        group_request.collections,
+        group_request.users,
+        org_id,
+        &headers,
+        &mut targets,
+        &mut conn,
+    )
+    .await?;
+    targets.notify(UpdateType::SyncVault, &nt, &mut conn).await;
+    Ok(result)
 }
 
 #[put("/organizations/<org_id>/groups/<group_id>", data = "<data>")]
@@ -2322,8 +3087,9 @@ async fn put_group(
     org_id: &str,
     group_id: &str,
     data: Json<GroupRequest>,
-    headers: AdminHeaders,
+    headers: ManageGroupsHeaders,
     mut conn: DbConn,
+    nt: Notify<'_>,
 ) -> JsonResult {
     if !CONFIG.org_groups_enabled() {
         err!("Group support is disabled");
@@ -2335,8 +3101,21 @@ async fn put_group(
     };
 
     let group_request = data.into_inner();
+    check_custom_group_changes(
+        Some(group_id),
+        group.access_all || group_request.access_all.unwrap_or(false),
+        &group_request.collection_ids(),
+        &group_request.users,
+        &headers,
+        &mut conn,
+    )
+    .await?;
     let updated_group = group_request.update_group(group);
 
+    // Members which are removed from the group need to sync as well
+    let mut targets = SyncTargets::default();
+    targets.add_group(group_id, &mut conn).await;
+
     CollectionGroup::delete_all_by_group(group_id, &mut conn).await?;
     GroupUser::delete_all_by_group(group_id, &mut conn).await?;
 
@@ -2351,7 +3130,18 @@ async fn put_group(
     )
     .await;
 
-    add_update_group(updated_group, group_request.collections, group_request.users, org_id, &headers, &mut conn).await
+    let result = add_update_group(
+        updated_group,
+        group_request.collections,
+        group_request.users,
+        org_id,
+        &headers,
+        &mut targets,
+        &mut conn,
+    )
+    .await?;
+    targets.notify(UpdateType::SyncVault, &nt, &mut conn).await;
+    Ok(result)
 }
 
 async fn add_update_group(
@@ -2359,7 +3149,8 @@ async fn add_update_group(
     collections: Vec<SelectionReadOnly>,
     users: Vec<String>,
     org_id: &str,
-    headers: &AdminHeaders,
+    headers: &ManageGroupsHeaders,
+    targets: &mut SyncTargets,
     conn: &mut DbConn,
 ) -> JsonResult {
     group.save(conn).await?;
@@ -2385,6 +3176,8 @@ async fn add_update_group(
         .await;
     }
 
+    targets.add_group(&group.uuid, conn).await;
+
     Ok(Json(json!({
         "id": group.uuid,
         "organizationId": group.organizations_uuid,
@@ -2395,7 +3188,12 @@ async fn add_update_group(
 }
 
 #[get("/organizations/<_org_id>/groups/<group_id>/details")]
-async fn get_group_details(_org_id: &str, group_id: &str, _headers: AdminHeaders, mut conn: DbConn) -> JsonResult {
+async fn get_group_details(
+    _org_id: &str,
+    group_id: &str,
+    _headers: ManageGroupsHeaders,
+    mut conn: DbConn,
+) -> JsonResult {
     if !CONFIG.org_groups_enabled() {
         err!("Group support is disabled");
     }
@@ -2409,16 +3207,37 @@ async fn get_group_details(_org_id: &str, group_id: &str, _headers: AdminHeaders
 }
 
 #[post("/organizations/<org_id>/groups/<group_id>/delete")]
-async fn post_delete_group(org_id: &str, group_id: &str, headers: AdminHeaders, mut conn: DbConn) -> EmptyResult {
-    _delete_group(org_id, group_id, &headers, &mut conn).await
+async fn post_delete_group(
+    org_id: &str,
+    group_id: &str,
+    headers: ManageGroupsHeaders,
+    conn: DbConn,
+    nt: Notify<'_>,
+) -> EmptyResult {
+    delete_group(org_id, group_id, headers, conn, nt).await
 }
 
 #[delete("/organizations/<org_id>/groups/<group_id>")]
-async fn delete_group(org_id: &str, group_id: &str, headers: AdminHeaders, mut conn: DbConn) -> EmptyResult {
-    _delete_group(org_id, group_id, &headers, &mut conn).await
+async fn delete_group(
+    org_id: &str,
+    group_id: &str,
+    headers: ManageGroupsHeaders,
+    mut conn: DbConn,
+    nt: Notify<'_>,
+) -> EmptyResult {
+    let mut targets = SyncTargets::default();
+    _delete_group(org_id, group_id, &headers, &mut targets, &mut conn).await?;
+    targets.notify(UpdateType::SyncVault, &nt, &mut conn).await;
+    Ok(())
 }
 
-async fn _delete_group(org_id: &str, group_id: &str, headers: &AdminHeaders, conn: &mut DbConn) -> EmptyResult {
+async fn _delete_group(
+    org_id: &str,
+    group_id: &str,
+    headers: &ManageGroupsHeaders,
+    targets: &mut SyncTargets,
+    conn: &mut DbConn,
+) -> EmptyResult {
     if !CONFIG.org_groups_enabled() {
         err!("Group support is disabled");
     }
@@ -2439,6 +3258,7 @@ async fn _delete_group(org_id: &str, group_id: &str, headers: &AdminHeaders, con
     )
     .await;
 
+    targets.add_group(&group.uuid, conn).await;
     group.delete(conn).await
 }
 
@@ -2446,8 +3266,9 @@ async fn _delete_group(org_id: &str, group_id: &str, headers: &AdminHeaders, con
 async fn bulk_delete_groups(
     org_id: &str,
     data: Json<OrgBulkIds>,
-    headers: AdminHeaders,
+    headers: ManageGroupsHeaders,
     mut conn: DbConn,
+    nt: Notify<'_>,
 ) -> EmptyResult {
     if !CONFIG.org_groups_enabled() {
         err!("Group support is disabled");
@@ -2455,14 +3276,16 @@ async fn bulk_delete_groups(
 
     let data: OrgBulkIds = data.into_inner();
 
+    let mut targets = SyncTargets::default();
     for group_id in data.ids {
-        _delete_group(org_id, &group_id, &headers, &mut conn).await?
+        _delete_group(org_id, &group_id, &headers, &mut targets, &mut conn).await?
     }
+    targets.notify(UpdateType::SyncVault, &nt, &mut conn).await;
     Ok(())
 }
 
 #[get("/organizations/<_org_id>/groups/<group_id>")]
-async fn get_group(_org_id: &str, group_id: &str, _headers: AdminHeaders, mut conn: DbConn) -> JsonResult {
+async fn get_group(_org_id: &str, group_id: &str, _headers: ManageGroupsHeaders, mut conn: DbConn) -> JsonResult {
     if !CONFIG.org_groups_enabled() {
         err!("Group support is disabled");
     }
@@ -2476,7 +3299,7 @@ async fn get_group(_org_id: &str, group_id: &str, _headers: AdminHeaders, mut co
 }
 
 #[get("/organizations/<_org_id>/groups/<group_id>/users")]
-async fn get_group_users(_org_id: &str, group_id: &str, _headers: AdminHeaders, mut conn: DbConn) -> JsonResult {
+async fn get_group_users(_org_id: &str, group_id: &str, _headers: ManageGroupsHeaders, mut conn: DbConn) -> JsonResult {
     if !CONFIG.org_groups_enabled() {
         err!("Group support is disabled");
     }
@@ -2499,22 +3322,31 @@ async fn get_group_users(_org_id: &str, group_id: &str, _headers: AdminHeaders,
 async fn put_group_users(
     org_id: &str,
     group_id: &str,
-    headers: AdminHeaders,
+    headers: ManageGroupsHeaders,
     data: Json<Vec<String>>,
     mut conn: DbConn,
+    nt: Notify<'_>,
 ) -> EmptyResult {
     if !CONFIG.org_groups_enabled() {
         err!("Group support is disabled");
     }
 
-    match Group::find_by_uuid(group_id, &mut conn).await {
-        Some(_) => { /* Do nothing */ }
-        _ => err!("Group could not be found!"),
+    let Some(group) = Group::find_by_uuid(group_id, &mut conn).await else {
+        err!("Group could not be found!")
     };
 
+    let assigned_user_ids = data.into_inner();
+    let col_ids: Vec<String> =
+        CollectionGroup::find_by_group(group_id, &mut conn).await.into_iter().map(|cg| cg.collections_uuid).collect();
+    check_custom_group_changes(Some(group_id), group.access_all, &col_ids, &assigned_user_ids, &headers, &mut conn)
+        .await?;
+
+    // Members which are removed from the group need to sync as well
+    let mut targets = SyncTargets::default();
+    targets.add_group(group_id, &mut conn).await;
+
     GroupUser::delete_all_by_group(group_id, &mut conn).await?;
 
-    let assigned_user_ids = data.into_inner();
     for assigned_user_id in assigned_user_ids {
         let mut user_entry = GroupUser::new(String::from(group_id), assigned_user_id.clone());
         user_entry.save(&mut conn).await?;
@@ -2531,11 +3363,14 @@ async fn put_group_users(
         .await;
     }
 
+    targets.add_group(group_id, &mut conn).await;
+    targets.notify(UpdateType::SyncVault, &nt, &mut conn).await;
+
     Ok(())
 }
 
 #[get("/organizations/<_org_id>/users/<user_id>/groups")]
-async fn get_user_groups(_org_id: &str, user_id: &str, _headers: AdminHeaders, mut conn: DbConn) -> JsonResult {
+async fn get_user_groups(_org_id: &str, user_id: &str, _headers: ManageUsersHeaders, mut conn: DbConn) -> JsonResult {
     if !CONFIG.org_groups_enabled() {
         err!("Group support is disabled");
     }
@@ -2562,10 +3397,11 @@ async fn post_user_groups(
     org_id: &str,
     org_user_id: &str,
     data: Json<OrganizationUserUpdateGroupsRequest>,
-    headers: AdminHeaders,
+    headers: ManageUsersHeaders,
     conn: DbConn,
+    nt: Notify<'_>,
 ) -> EmptyResult {
-    put_user_groups(org_id, org_user_id, data, headers, conn).await
+    put_user_groups(org_id, org_user_id, data, headers, conn, nt).await
 }
 
 #[put("/organizations/<org_id>/users/<org_user_id>/groups", data = "<data>")]
@@ -2573,8 +3409,9 @@ async fn put_user_groups(
     org_id: &str,
     org_user_id: &str,
     data: Json<OrganizationUserUpdateGroupsRequest>,
-    headers: AdminHeaders,
+    headers: ManageUsersHeaders,
     mut conn: DbConn,
+    nt: Notify<'_>,
 ) -> EmptyResult {
     if !CONFIG.org_groups_enabled() {
         err!("Group support is disabled");
@@ -2589,9 +3426,15 @@ async fn put_user_groups(
         err!("Group doesn't belong to organization");
     }
 
-    GroupUser::delete_all_by_user(org_user_id, &mut conn).await?;
+    if user_org.atype != UserOrgType::User && headers.org_user_type == UserOrgType::Custom {
+        err!("Only Admins or Owners can change the groups of members with elevated privileges");
+    }
 
     let assigned_group_ids = data.into_inner();
+    check_custom_group_grants(&headers.org_user, &assigned_group_ids.group_ids, &mut conn).await?;
+
+    GroupUser::delete_all_by_user(org_user_id, &mut conn).await?;
+
     for assigned_group_id in assigned_group_ids.group_ids {
         let mut group_user = GroupUser::new(assigned_group_id.clone(), String::from(org_user_id));
         group_user.save(&mut conn).await?;
@@ -2608,6 +3451,10 @@ async fn put_user_groups(
     )
     .await;
 
+    let mut targets = SyncTargets::default();
+    targets.add_user(&user_org.user_uuid);
+    targets.notify(UpdateType::SyncVault, &nt, &mut conn).await;
+
     Ok(())
 }
 
@@ -2616,10 +3463,11 @@ async fn post_delete_group_user(
     org_id: &str,
     group_id: &str,
     org_user_id: &str,
-    headers: AdminHeaders,
+    headers: ManageGroupsHeaders,
     conn: DbConn,
+    nt: Notify<'_>,
 ) -> EmptyResult {
-    delete_group_user(org_id, group_id, org_user_id, headers, conn).await
+    delete_group_user(org_id, group_id, org_user_id, headers, conn, nt).await
 }
 
 #[delete("/organizations/<org_id>/groups/<group_id>/users/<org_user_id>")]
@@ -2627,8 +3475,9 @@ async fn delete_group_user(
     org_id: &str,
     group_id: &str,
     org_user_id: &str,
-    headers: AdminHeaders,
+    headers: ManageGroupsHeaders,
     mut conn: DbConn,
+    nt: Notify<'_>,
 ) -> EmptyResult {
     if !CONFIG.org_groups_enabled() {
         err!("Group support is disabled");
@@ -2663,7 +3512,12 @@ async fn delete_group_user(
     )
     .await;
 
-    GroupUser::delete_by_group_id_and_user_id(group_id, org_user_id, &mut conn).await
+    GroupUser::delete_by_group_id_and_user_id(group_id, org_user_id, &mut conn).await?;
+
+    let mut targets = SyncTargets::default();
+    targets.add_user(&user_org.user_uuid);
+    targets.notify(UpdateType::SyncVault, &nt, &mut conn).await;
+    Ok(())
 }
 
 #[derive(Deserialize)]
@@ -2699,7 +3553,7 @@ async fn get_organization_keys(org_id: &str, mut conn: DbConn) -> JsonResult {
 async fn put_reset_password(
     org_id: &str,
     org_user_id: &str,
-    headers: AdminHeaders,
+    headers: AccountRecoveryHeaders,
     data: Json<OrganizationUserResetPasswordRequest>,
     mut conn: DbConn,
     nt: Notify<'_>,
@@ -2730,7 +3584,7 @@ async fn put_reset_password(
 
     // Sending email before resetting password to ensure working email configuration and the resulting
     // user notification. Also this might add some protection against security flaws and misuse
-    if let Err(e) = mail::send_admin_reset_password(&user.email, &user.name, &org.name).await {
+    if let Err(e) = mail::send_admin_reset_password(&user.email, user.locale.as_deref(), &user.name, &org.name).await {
         err!(format!("Error sending user reset password email: {e:#?}"));
     }
 
@@ -2760,7 +3614,7 @@ async fn put_reset_password(
 async fn get_reset_password_details(
     org_id: &str,
     org_user_id: &str,
-    headers: AdminHeaders,
+    headers: AccountRecoveryHeaders,
     mut conn: DbConn,
 ) -> JsonResult {
     let org = match Organization::find_by_uuid(org_id, &mut conn).await {
@@ -2796,7 +3650,7 @@ async fn get_reset_password_details(
 async fn check_reset_password_applicable_and_permissions(
     org_id: &str,
     org_user_id: &str,
-    headers: &AdminHeaders,
+    headers: &AccountRecoveryHeaders,
     conn: &mut DbConn,
 ) -> EmptyResult {
     check_reset_password_applicable(org_id, conn).await?;
@@ -2810,6 +3664,7 @@ async fn check_reset_password_applicable_and_permissions(
     match headers.org_user_type {
         UserOrgType::Owner => Ok(()),
         UserOrgType::Admin if target_user.atype <= UserOrgType::Admin => Ok(()),
+        UserOrgType::Custom if target_user.atype == UserOrgType::User => Ok(()),
         _ => err!("No permission to reset this user's password"),
     }
 }
@@ -2885,9 +3740,11 @@ async fn put_reset_password_enrollment(
 //       We need to convert all keys so they have the first character to be a lowercase.
 //       Else the export will be just an empty JSON file.
 #[get("/organizations/<org_id>/export")]
-async fn get_org_export(org_id: &str, headers: AdminHeaders, mut conn: DbConn) -> Json<Value> {
+async fn get_org_export(org_id: &str, headers: ImportExportHeaders, mut conn: DbConn) -> JsonResult {
     use semver::{Version, VersionReq};
 
+    check_org_not_pending_deletion(org_id, &mut conn).await?;
+
     // Since version v2023.1.0 the format of the export is different.
     // Also, this endpoint was created since v2022.9.0.
     // Therefore, we will check for any version smaller then v2023.1.0 and return a different response.
@@ -2904,7 +3761,7 @@ async fn get_org_export(org_id: &str, headers: AdminHeaders, mut conn: DbConn) -
     // Also both main keys here need to be lowercase, else the export will fail.
     if use_list_response_model {
         // Backwards compatible pre v2023.1.0 response
-        Json(json!({
+        Ok(Json(json!({
             "collections": {
                 "data": convert_json_key_lcase_first(_get_org_collections(org_id, &mut conn).await),
                 "object": "list",
@@ -2915,13 +3772,13 @@ async fn get_org_export(org_id: &str, headers: AdminHeaders, mut conn: DbConn) -
                 "object": "list",
                 "continuationToken": null,
             }
-        }))
+        })))
     } else {
         // v2023.1.0 and newer response
-        Json(json!({
+        Ok(Json(json!({
             "collections": convert_json_key_lcase_first(_get_org_collections(org_id, &mut conn).await),
             "ciphers": convert_json_key_lcase_first(_get_org_details(org_id, &headers.host, &headers.user.uuid, &mut conn).await),
-        }))
+        })))
     }
 }
 
@@ -2976,3 +3833,134 @@ async fn rotate_api_key(
 ) -> JsonResult {
     _api_key(org_id, data, true, headers, conn).await
 }
+
+/// The users whose clients need to sync after an organization change.
+///
+/// Bulk operations can touch the same user multiple times, collecting them first makes sure every user is only notified once.
+#[derive(Default)]
+struct SyncTargets {
+    user_uuids: HashSet<String>,
+    /// The organizations whose access-all groups were already added
+    access_all_orgs: HashSet<String>,
+}
+
+impl SyncTargets {
+    fn add_user(&mut self, user_uuid: &str) {
+        self.user_uuids.insert(user_uuid.to_string());
+    }
+
+    async fn add_member(&mut self, org_user_id: &str, conn: &mut DbConn) {
+        if let Some(user_org) = UserOrganization::find_by_uuid(org_user_id, conn).await {
+            self.add_user(&user_org.user_uuid);
+        }
+    }
+
+    /// Adds every member which can access the collection, directly, through a group or by having access to all collections
+    async fn add_collection(&mut self, col_id: &str, org_id: &str, conn: &mut DbConn) {
+        for user_org in UserOrganization::find_by_collection_and_org(col_id, org_id, conn).await {
+            self.add_user(&user_org.user_uuid);
+        }
+
+        for col_group in CollectionGroup::find_by_collection(col_id, conn).await {
+            self.add_group(&col_group.groups_uuid, conn).await;
+        }
+
+        // The members of access-all groups can access every collection, they only need to be loaded once per organization
+        if self.access_all_orgs.insert(org_id.to_string()) {
+            for group in Group::find_by_organization(org_id, conn).await {
+                if group.access_all {
+                    self.add_group(&group.uuid, conn).await;
+                }
+            }
+        }
+    }
+
+    async fn add_group(&mut self, group_id: &str, conn: &mut DbConn) {
+        for group_user in GroupUser::find_by_group(group_id, conn).await {
+            self.add_member(&group_user.users_organizations_uuid, conn).await;
+        }
+    }
+
+    async fn add_confirmed_members(&mut self, org_id: &str, conn: &mut DbConn) {
+        for user_org in UserOrganization::find_confirmed_by_org(org_id, conn).await {
+            self.add_user(&user_org.user_uuid);
+        }
+    }
+
+    /// Notifies the clients of all collected users, both through WebSockets and the push relay
+    async fn notify(self, ut: UpdateType, nt: &Notify<'_>, conn: &mut DbConn) {
+        for user_uuid in self.user_uuids {
+            if let Some(mut user) = User::find_by_uuid(&user_uuid, conn).await {
+                // The clients only fetch the new data if the revision date has changed
+                if let Err(e) = user.update_revision(conn).await {
+                    warn!("Failed to update revision for {}: {:#?}", user.uuid, e);
+                }
+                nt.send_user_update(ut, &user).await;
+            }
+        }
+    }
+}
+
+/// Permanently deletes the organizations whose deletion grace period has passed, and lets their owners know.
+pub async fn org_deletion_purge_job(pool: DbPool) -> JobResult {
+    debug!("Purging deleted organizations");
+    let Ok(mut conn) = pool.get().await else {
+        err!("Failed to get DB connection while purging deleted organizations")
+    };
+
+    let cutoff = Utc::now().naive_utc() - TimeDelta::try_days(CONFIG.org_deletion_grace_days()).unwrap();
+    let mut purged = 0;
+    for org in Organization::find_deleted_before(&cutoff, &mut conn).await {
+        let owners = if CONFIG.mail_enabled() {
+            confirmed_owners(&org.uuid, &mut conn).await
+        } else {
+            Vec::new()
+        };
+        let (org_uuid, org_name) = (org.uuid.clone(), org.name.clone());
+        if let Err(e) = org.delete(&mut conn).await {
+            error!("Error purging deleted organization {org_uuid}: {e:#?}");
+            continue;
+        }
+        purged += 1;
+
+        for owner in owners {
+            if let Err(e) = mail::send_org_deleted(&owner.email, owner.locale.as_deref(), &org_name).await {
+                error!("Error sending organization deleted email: {e:#?}");
+            }
+        }
+    }
+
+    Ok(purged)
+}
+
+/// Removes the organization invitations which expired `EXPIRED_INVITATIONS_PURGE_DAYS` ago,
+/// until then they are shown as expired.
+pub async fn purge_expired_invitations(pool: DbPool) -> JobResult {
+    debug!("Purging expired invitations");
+    let Ok(mut conn) = pool.get().await else {
+        err!("Failed to get DB connection while purging expired invitations")
+    };
+
+    let mut purged = 0;
+    if let Some(days) = CONFIG.expired_invitations_purge_days() {
+        let cutoff = UserOrganization::invitation_expiration_cutoff() - TimeDelta::try_days(days).unwrap();
+        for user_org in UserOrganization::find_invited_before(&cutoff, None, &mut conn).await {
+            let user_uuid = user_org.user_uuid.clone();
+            user_org.delete(&mut conn).await?;
+            purged += 1;
+
+            // Also remove the user if it was only created for invitations, this removes its invitation row as well.
+            // A pending emergency access invitation still needs that row to sign up when signups are closed.
+            if let Some(user) = User::find_by_uuid(&user_uuid, &mut conn).await {
+                if user.password_hash.is_empty()
+                    && UserOrganization::find_any_state_by_user(&user_uuid, &mut conn).await.is_empty()
+                    && EmergencyAccess::find_all_invited_by_grantee_email(&user.email, &mut conn).await.is_empty()
+                {
+                    user.delete(&mut conn).await?;
+                }
+            }
+        }
+    }
+
+    Ok(purged)
+}
@@ -125,6 +125,7 @@ async fn ldap_import(data: Json<OrgImportData>, token: PublicToken, mut conn: Db
 
                 mail::send_invite(
                     &user_data.email,
+                    user.locale.as_deref(),
                     &user.uuid,
                     Some(org_id.clone()),
                     Some(new_org_user.uuid),
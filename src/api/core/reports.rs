@@ -0,0 +1,523 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::NaiveDateTime;
+use rocket::{http::ContentType, serde::json::Json, Route};
+use serde_json::Value;
+
+use crate::{
+    api::{ApiResult, JsonResult},
+    auth::AdminHeaders,
+    db::{
+        models::{
+            Collection, CollectionCipher, CollectionGroup, CollectionUser, Device, Group, GroupUser, Organization,
+            TwoFactor, TwoFactorType, User, UserKdfType, UserOrgStatus, UserOrgType, UserOrganization,
+        },
+        DbConn,
+    },
+    util::format_date,
+    CONFIG,
+};
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        get_member_access_report,
+        get_member_access_report_csv,
+        get_member_security_report,
+        get_member_security_report_csv,
+    ]
+}
+
+/// The effective access a member has to a single collection
+struct CollectionAccess {
+    read_only: bool,
+    hide_passwords: bool,
+    manage: bool,
+    all_collections: bool,
+    direct: bool,
+    groups: Vec<String>,
+}
+
+impl CollectionAccess {
+    fn new() -> Self {
+        Self {
+            read_only: true,
+            hide_passwords: true,
+            manage: false,
+            all_collections: false,
+            direct: false,
+            groups: Vec::new(),
+        }
+    }
+
+    /// Multiple assignments are combined the same way the clients do, the least restrictive one wins
+    fn merge(&mut self, read_only: bool, hide_passwords: bool, manage: bool) {
+        self.read_only &= read_only;
+        self.hide_passwords &= hide_passwords;
+        self.manage |= manage;
+    }
+
+    fn source(&self) -> String {
+        let mut sources = Vec::new();
+        if self.all_collections {
+            sources.push(String::from("all"));
+        }
+        if self.direct {
+            sources.push(String::from("direct"));
+        }
+        sources.extend(self.groups.iter().map(|g| format!("group:{g}")));
+        sources.join(";")
+    }
+}
+
+struct MemberAccessReport {
+    user_org: UserOrganization,
+    name: String,
+    email: String,
+    // Sorted by collection uuid to keep the output stable between requests
+    collections: BTreeMap<String, CollectionAccess>,
+}
+
+struct OrgAccessReport {
+    members: Vec<MemberAccessReport>,
+    collections: HashMap<String, Collection>,
+    item_counts: HashMap<String, i64>,
+}
+
+impl OrgAccessReport {
+    fn collection_name(&self, col_id: &str) -> &str {
+        // Collection names are encrypted with the organization key, which the server doesn't have, they are returned as is
+        self.collections.get(col_id).map(|c| c.name.as_str()).unwrap_or_default()
+    }
+
+    fn item_count(&self, col_id: &str) -> i64 {
+        self.item_counts.get(col_id).copied().unwrap_or(0)
+    }
+}
+
+async fn build_member_access_report(org_id: &str, conn: &mut DbConn) -> ApiResult<OrgAccessReport> {
+    let Some(org) = Organization::find_by_uuid(org_id, conn).await else {
+        err!("Organization not found")
+    };
+
+    // Everything is loaded with one query per table, to keep this usable for large organizations
+    let collections: HashMap<String, Collection> =
+        Collection::find_by_organization(org_id, conn).await.into_iter().map(|c| (c.uuid.clone(), c)).collect();
+    let item_counts: HashMap<String, i64> =
+        CollectionCipher::count_by_organization(org_id, conn).await.into_iter().collect();
+    let users: HashMap<String, User> =
+        User::find_by_org(org_id, conn).await.into_iter().map(|u| (u.uuid.clone(), u)).collect();
+
+    let mut user_collections: HashMap<String, Vec<CollectionUser>> = HashMap::new();
+    for cu in CollectionUser::find_by_organization(org_id, conn).await {
+        // `user_uuid` holds the uuid of the membership here
+        user_collections.entry(cu.user_uuid.clone()).or_default().push(cu);
+    }
+
+    // Groups don't give any access when they are disabled
+    let mut groups: HashMap<String, Group> = HashMap::new();
+    let mut group_collections: HashMap<String, Vec<CollectionGroup>> = HashMap::new();
+    let mut member_groups: HashMap<String, Vec<String>> = HashMap::new();
+    if CONFIG.org_groups_enabled() {
+        groups = Group::find_by_organization(org_id, conn).await.into_iter().map(|g| (g.uuid.clone(), g)).collect();
+
+        for cg in CollectionGroup::find_by_organization(org_id, conn).await {
+            group_collections.entry(cg.groups_uuid.clone()).or_default().push(cg);
+        }
+
+        for gu in GroupUser::find_by_organization(org_id, conn).await {
+            member_groups.entry(gu.users_organizations_uuid).or_default().push(gu.groups_uuid);
+        }
+    }
+
+    let mut members = Vec::new();
+    for user_org in UserOrganization::find_by_org(org_id, conn).await {
+        let (name, email) = match users.get(&user_org.user_uuid) {
+            Some(user) => (user.name.clone(), user.email.clone()),
+            None => (String::new(), String::new()),
+        };
+
+        let member_group_ids = member_groups.get(&user_org.uuid).map(Vec::as_slice).unwrap_or_default();
+        let full_access = org.has_full_item_access(&user_org)
+            || (user_org.has_status(UserOrgStatus::Confirmed)
+                && member_group_ids.iter().any(|g| groups.get(g).is_some_and(|g| g.access_all)));
+
+        let mut access: BTreeMap<String, CollectionAccess> = BTreeMap::new();
+        if full_access {
+            for col_id in collections.keys() {
+                let mut col_access = CollectionAccess::new();
+                col_access.merge(false, false, false);
+                col_access.all_collections = true;
+                access.insert(col_id.clone(), col_access);
+            }
+        }
+
+        for cu in user_collections.get(&user_org.uuid).into_iter().flatten() {
+            let col_access = access.entry(cu.collection_uuid.clone()).or_insert_with(CollectionAccess::new);
+            col_access.merge(cu.read_only, cu.hide_passwords, cu.manage);
+            col_access.direct = true;
+        }
+
+        for group_id in member_group_ids {
+            let Some(group) = groups.get(group_id) else {
+                continue;
+            };
+            for cg in group_collections.get(group_id).into_iter().flatten() {
+                let col_access = access.entry(cg.collections_uuid.clone()).or_insert_with(CollectionAccess::new);
+                col_access.merge(cg.read_only, cg.hide_passwords, cg.manage);
+                col_access.groups.push(group.name.clone());
+            }
+        }
+
+        let manage_all = user_org.atype >= UserOrgType::Manager || user_org.permissions().manage_all_collections;
+        let editing_restricted = org.restricts_item_editing(&user_org);
+        for col_access in access.values_mut() {
+            if manage_all {
+                col_access.manage = true;
+            }
+            if editing_restricted && !col_access.manage {
+                col_access.read_only = true;
+            }
+        }
+
+        // Revoked members don't have access to any collection until they are restored, their assignments are left out
+        if user_org.status <= UserOrgStatus::Revoked as i32 {
+            access.clear();
+        }
+
+        members.push(MemberAccessReport {
+            user_org,
+            name,
+            email,
+            collections: access,
+        });
+    }
+
+    Ok(OrgAccessReport {
+        members,
+        collections,
+        item_counts,
+    })
+}
+
+fn type_name(atype: i32) -> &'static str {
+    match atype {
+        t if t == UserOrgType::Owner as i32 => "Owner",
+        t if t == UserOrgType::Admin as i32 => "Admin",
+        t if t == UserOrgType::Manager as i32 => "Manager",
+        t if t == UserOrgType::Custom as i32 => "Custom",
+        _ => "User",
+    }
+}
+
+/// Revoked members keep their previous status with an offset, report them as revoked like the member list does
+fn member_status(user_org: &UserOrganization) -> i32 {
+    user_org.status.max(UserOrgStatus::Revoked as i32)
+}
+
+fn status_name(status: i32) -> &'static str {
+    match status {
+        s if s == UserOrgStatus::Revoked as i32 => "Revoked",
+        s if s == UserOrgStatus::Invited as i32 => "Invited",
+        s if s == UserOrgStatus::Accepted as i32 => "Accepted",
+        _ => "Confirmed",
+    }
+}
+
+/// Quote a field for CSV output when needed, see RFC 4180.
+/// Values which spreadsheet applications would evaluate as a formula are prefixed with a `'`.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{value}")
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+/// The collection names in the report are encrypted with the organization key, the server can't decrypt them
+#[get("/organizations/<org_id>/reports/member-access")]
+async fn get_member_access_report(org_id: &str, _headers: AdminHeaders, mut conn: DbConn) -> JsonResult {
+    let report = build_member_access_report(org_id, &mut conn).await?;
+
+    let members_json: Vec<Value> = report
+        .members
+        .iter()
+        .map(|member| {
+            let collections_json: Vec<Value> = member
+                .collections
+                .iter()
+                .map(|(col_id, access)| {
+                    json!({
+                        "id": col_id,
+                        "name": report.collection_name(col_id),
+                        "readOnly": access.read_only,
+                        "hidePasswords": access.hide_passwords,
+                        "manage": access.manage,
+                        "allCollections": access.all_collections,
+                        "direct": access.direct,
+                        "groups": access.groups,
+                        "itemCount": report.item_count(col_id),
+                    })
+                })
+                .collect();
+
+            json!({
+                "id": member.user_org.uuid,
+                "userId": member.user_org.user_uuid,
+                "name": member.name,
+                "email": member.email,
+                "type": member.user_org.atype,
+                "status": member_status(&member.user_org),
+                "accessAll": member.user_org.access_all,
+                "collections": collections_json,
+                "object": "memberAccessReport",
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "data": members_json,
+        "object": "list",
+        "continuationToken": null,
+    })))
+}
+
+/// Like the JSON report, the `collection_name` column holds the encrypted collection names
+#[get("/organizations/<org_id>/reports/member-access/csv")]
+async fn get_member_access_report_csv(
+    org_id: &str,
+    _headers: AdminHeaders,
+    mut conn: DbConn,
+) -> ApiResult<(ContentType, String)> {
+    let report = build_member_access_report(org_id, &mut conn).await?;
+
+    let mut csv = String::from(
+        "member_id,user_id,name,email,type,status,collection_id,collection_name,read_only,hide_passwords,manage,access_source,item_count\r\n",
+    );
+    for member in &report.members {
+        let member_fields = [
+            csv_field(&member.user_org.uuid),
+            csv_field(&member.user_org.user_uuid),
+            csv_field(&member.name),
+            csv_field(&member.email),
+            type_name(member.user_org.atype).to_string(),
+            status_name(member_status(&member.user_org)).to_string(),
+        ]
+        .join(",");
+
+        // Members without any collection still get a row, so the export lists the whole organization
+        if member.collections.is_empty() {
+            csv.push_str(&format!("{member_fields},,,,,,,\r\n"));
+            continue;
+        }
+
+        for (col_id, access) in &member.collections {
+            csv.push_str(&format!(
+                "{member_fields},{},{},{},{},{},{},{}\r\n",
+                csv_field(col_id),
+                csv_field(report.collection_name(col_id)),
+                access.read_only,
+                access.hide_passwords,
+                access.manage,
+                csv_field(&access.source()),
+                report.item_count(col_id),
+            ));
+        }
+    }
+
+    Ok((ContentType::CSV, csv))
+}
+
+/// The security related settings of a single member, used by the security report
+pub struct MemberSecurity {
+    user_org: UserOrganization,
+    name: String,
+    email: String,
+    two_factor: Vec<&'static str>,
+    kdf_type: i32,
+    kdf_iterations: i32,
+    kdf_memory: Option<i32>,
+    kdf_parallelism: Option<i32>,
+    last_active: Option<NaiveDateTime>,
+}
+
+impl MemberSecurity {
+    /// Anything below the current defaults of the clients is considered weak
+    pub fn weak_kdf(&self) -> bool {
+        if self.kdf_type == UserKdfType::Argon2id as i32 {
+            self.kdf_iterations < 3 || self.kdf_memory.unwrap_or(0) < 64 || self.kdf_parallelism.unwrap_or(0) < 4
+        } else {
+            self.kdf_iterations < User::CLIENT_KDF_ITER_DEFAULT
+        }
+    }
+
+    pub fn reset_password_enrolled(&self) -> bool {
+        self.user_org.reset_password_key.is_some()
+    }
+
+    pub fn revoked(&self) -> bool {
+        self.user_org.status <= UserOrgStatus::Revoked as i32
+    }
+
+    pub fn last_active(&self) -> Option<NaiveDateTime> {
+        self.last_active
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "id": self.user_org.uuid,
+            "userId": self.user_org.user_uuid,
+            "name": self.name,
+            "email": self.email,
+            "type": self.user_org.atype,
+            "status": member_status(&self.user_org),
+            "twoFactorEnabled": !self.two_factor.is_empty(),
+            "twoFactorProviders": self.two_factor,
+            "kdf": self.kdf_type,
+            "kdfIterations": self.kdf_iterations,
+            "kdfMemory": self.kdf_memory,
+            "kdfParallelism": self.kdf_parallelism,
+            "weakKdf": self.weak_kdf(),
+            "resetPasswordEnrolled": self.reset_password_enrolled(),
+            "lastActive": self.last_active.as_ref().map(format_date),
+            "revoked": self.revoked(),
+            "object": "memberSecurityReport",
+        })
+    }
+}
+
+fn two_factor_name(atype: i32) -> Option<&'static str> {
+    match atype {
+        t if t == TwoFactorType::Authenticator as i32 => Some("Authenticator"),
+        t if t == TwoFactorType::Email as i32 => Some("Email"),
+        t if t == TwoFactorType::Duo as i32 => Some("Duo"),
+        t if t == TwoFactorType::YubiKey as i32 => Some("YubiKey"),
+        t if t == TwoFactorType::U2f as i32 => Some("U2F"),
+        t if t == TwoFactorType::Webauthn as i32 => Some("WebAuthn"),
+        // Remember tokens and organization Duo are not a second factor of the member itself
+        _ => None,
+    }
+}
+
+pub async fn build_member_security_report(org_id: &str, conn: &mut DbConn) -> Vec<MemberSecurity> {
+    let users: HashMap<String, User> =
+        User::find_by_org(org_id, conn).await.into_iter().map(|u| (u.uuid.clone(), u)).collect();
+
+    let mut two_factors: HashMap<String, Vec<&'static str>> = HashMap::new();
+    for tf in TwoFactor::find_enabled_by_org(org_id, conn).await {
+        if let Some(name) = two_factor_name(tf.atype) {
+            two_factors.entry(tf.user_uuid).or_default().push(name);
+        }
+    }
+
+    let last_active: HashMap<String, Option<NaiveDateTime>> =
+        Device::find_last_active_by_org(org_id, conn).await.into_iter().collect();
+
+    let mut report = Vec::new();
+    for user_org in UserOrganization::find_by_org(org_id, conn).await {
+        let Some(user) = users.get(&user_org.user_uuid) else {
+            continue;
+        };
+        let mut two_factor = two_factors.remove(&user.uuid).unwrap_or_default();
+        two_factor.sort_unstable();
+
+        report.push(MemberSecurity {
+            name: user.name.clone(),
+            email: user.email.clone(),
+            two_factor,
+            kdf_type: user.client_kdf_type,
+            kdf_iterations: user.client_kdf_iter,
+            kdf_memory: user.client_kdf_memory,
+            kdf_parallelism: user.client_kdf_parallelism,
+            last_active: last_active.get(&user.uuid).copied().flatten(),
+            user_org,
+        });
+    }
+
+    report
+}
+
+pub fn member_security_report_csv(report: &[MemberSecurity]) -> String {
+    let mut csv = String::from(
+        "member_id,user_id,name,email,type,status,two_factor_providers,kdf,kdf_iterations,kdf_memory,kdf_parallelism,weak_kdf,reset_password_enrolled,last_active,revoked\r\n",
+    );
+    for member in report {
+        let kdf = if member.kdf_type == UserKdfType::Argon2id as i32 {
+            "Argon2id"
+        } else {
+            "PBKDF2"
+        };
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}\r\n",
+            csv_field(&member.user_org.uuid),
+            csv_field(&member.user_org.user_uuid),
+            csv_field(&member.name),
+            csv_field(&member.email),
+            type_name(member.user_org.atype),
+            status_name(member_status(&member.user_org)),
+            csv_field(&member.two_factor.join(";")),
+            kdf,
+            member.kdf_iterations,
+            member.kdf_memory.map(|m| m.to_string()).unwrap_or_default(),
+            member.kdf_parallelism.map(|p| p.to_string()).unwrap_or_default(),
+            member.weak_kdf(),
+            member.reset_password_enrolled(),
+            member.last_active.as_ref().map(format_date).unwrap_or_default(),
+            member.revoked(),
+        ));
+    }
+    csv
+}
+
+#[get("/organizations/<org_id>/reports/member-security")]
+async fn get_member_security_report(org_id: &str, _headers: AdminHeaders, mut conn: DbConn) -> JsonResult {
+    let members_json: Vec<Value> =
+        build_member_security_report(org_id, &mut conn).await.iter().map(MemberSecurity::to_json).collect();
+
+    Ok(Json(json!({
+        "data": members_json,
+        "object": "list",
+        "continuationToken": null,
+    })))
+}
+
+#[get("/organizations/<org_id>/reports/member-security/csv")]
+async fn get_member_security_report_csv(
+    org_id: &str,
+    _headers: AdminHeaders,
+    mut conn: DbConn,
+) -> (ContentType, String) {
+    let report = build_member_security_report(org_id, &mut conn).await;
+    (ContentType::CSV, member_security_report_csv(&report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_field_quoting() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field(""), "");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("line\nbreak"), "\"line\nbreak\"");
+    }
+
+    #[test]
+    fn test_csv_field_formula_injection() {
+        assert_eq!(csv_field("=1+1"), "'=1+1");
+        assert_eq!(csv_field("+1"), "'+1");
+        assert_eq!(csv_field("-1"), "'-1");
+        assert_eq!(csv_field("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(csv_field("\tTab"), "'\tTab");
+        assert_eq!(csv_field("=HYPERLINK(\"x\",\"y\")"), "\"'=HYPERLINK(\"\"x\"\",\"\"y\"\")\"");
+        // Only the start of a value matters
+        assert_eq!(csv_field("a=b"), "a=b");
+        assert_eq!(csv_field("user@example.com"), "user@example.com");
+    }
+}
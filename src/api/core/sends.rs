@@ -9,9 +9,10 @@ use rocket::serde::json::Json;
 use serde_json::Value;
 
 use crate::{
-    api::{ApiResult, EmptyResult, JsonResult, Notify, UpdateType},
+    api::{core::log_send_access, ApiResult, EmptyResult, JsonResult, Notify, UpdateType},
     auth::{ClientIp, Headers, Host},
     db::{models::*, DbConn, DbPool},
+    jobs::JobResult,
     util::{NumberOrString, SafeString},
     CONFIG,
 };
@@ -38,12 +39,12 @@ pub fn routes() -> Vec<rocket::Route> {
     ]
 }
 
-pub async fn purge_sends(pool: DbPool) {
+pub async fn purge_sends(pool: DbPool) -> JobResult {
     debug!("Purging sends");
     if let Ok(mut conn) = pool.get().await {
-        Send::purge(&mut conn).await;
+        Ok(Send::purge(&mut conn).await)
     } else {
-        error!("Failed to get DB connection while purging sends")
+        err!("Failed to get DB connection while purging sends")
     }
 }
 
@@ -429,7 +430,7 @@ async fn post_access(
         err_code!(SEND_INACCESSIBLE_MSG, 404)
     }
 
-    if send.disabled {
+    if send.disabled || send.is_owner_deleted(&mut conn).await {
         err_code!(SEND_INACCESSIBLE_MSG, 404)
     }
 
@@ -444,6 +445,9 @@ async fn post_access(
     // Files are incremented during the download
     if send.atype == SendType::Text as i32 {
         send.access_count += 1;
+        if let Some(user_uuid) = &send.user_uuid {
+            log_send_access(user_uuid, &ip.ip, &mut conn).await;
+        }
     }
 
     send.save(&mut conn).await?;
@@ -466,6 +470,7 @@ async fn post_access_file(
     file_id: &str,
     data: Json<SendAccessData>,
     host: Host,
+    ip: ClientIp,
     mut conn: DbConn,
     nt: Notify<'_>,
 ) -> JsonResult {
@@ -490,7 +495,7 @@ async fn post_access_file(
         err_code!(SEND_INACCESSIBLE_MSG, 404)
     }
 
-    if send.disabled {
+    if send.disabled || send.is_owner_deleted(&mut conn).await {
         err_code!(SEND_INACCESSIBLE_MSG, 404)
     }
 
@@ -503,6 +508,9 @@ async fn post_access_file(
     }
 
     send.access_count += 1;
+    if let Some(user_uuid) = &send.user_uuid {
+        log_send_access(user_uuid, &ip.ip, &mut conn).await;
+    }
 
     send.save(&mut conn).await?;
 
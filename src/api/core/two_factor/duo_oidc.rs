@@ -15,6 +15,7 @@ use crate::{
     },
     error::Error,
     http_client::make_http_request,
+    jobs::JobResult,
     CONFIG,
 };
 use url::Url;
@@ -345,12 +346,12 @@ async fn extract_context(state: &str, conn: &mut DbConn) -> Option<DuoAuthContex
 }
 
 // Task to clean up expired Duo authentication contexts that may have accumulated in the database.
-pub async fn purge_duo_contexts(pool: DbPool) {
+pub async fn purge_duo_contexts(pool: DbPool) -> JobResult {
     debug!("Purging Duo authentication contexts");
     if let Ok(mut conn) = pool.get().await {
-        TwoFactorDuoContext::purge_expired_duo_contexts(&mut conn).await;
+        Ok(TwoFactorDuoContext::purge_expired_duo_contexts(&mut conn).await)
     } else {
-        error!("Failed to get DB connection while purging expired Duo authentications")
+        err!("Failed to get DB connection while purging expired Duo authentications")
     }
 }
 
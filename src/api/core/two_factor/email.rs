@@ -72,7 +72,16 @@ pub async fn send_token(user_uuid: &str, conn: &mut DbConn) -> EmptyResult {
     twofactor.data = twofactor_data.to_json();
     twofactor.save(conn).await?;
 
-    mail::send_token(&twofactor_data.email, &twofactor_data.last_token.map_res("Token is empty")?).await?;
+    let Some(user) = User::find_by_uuid(user_uuid, conn).await else {
+        err!("User not found")
+    };
+    mail::send_token(
+        &twofactor_data.email,
+        user.locale.as_deref(),
+        user.pgp_public_key.as_deref(),
+        &twofactor_data.last_token.map_res("Token is empty")?,
+    )
+    .await?;
 
     Ok(())
 }
@@ -140,7 +149,13 @@ async fn send_email(data: Json<SendEmailData>, headers: Headers, mut conn: DbCon
     let twofactor = TwoFactor::new(user.uuid, TwoFactorType::EmailVerificationChallenge, twofactor_data.to_json());
     twofactor.save(&mut conn).await?;
 
-    mail::send_token(&twofactor_data.email, &twofactor_data.last_token.map_res("Token is empty")?).await?;
+    mail::send_token(
+        &twofactor_data.email,
+        user.locale.as_deref(),
+        user.pgp_public_key.as_deref(),
+        &twofactor_data.last_token.map_res("Token is empty")?,
+    )
+    .await?;
 
     Ok(())
 }
@@ -12,6 +12,7 @@ use crate::{
     auth::{ClientHeaders, Headers},
     crypto,
     db::{models::*, DbConn, DbPool},
+    jobs::JobResult,
     mail,
     util::NumberOrString,
     CONFIG,
@@ -187,7 +188,7 @@ pub async fn enforce_2fa_policy(
         if member.atype < UserOrgType::Admin {
             if CONFIG.mail_enabled() {
                 let org = Organization::find_by_uuid(&member.org_uuid, conn).await.unwrap();
-                mail::send_2fa_removed_from_org(&user.email, &org.name).await?;
+                mail::send_2fa_removed_from_org(&user.email, user.locale.as_deref(), &org.name).await?;
             }
             let mut member = member;
             member.revoke();
@@ -222,7 +223,7 @@ pub async fn enforce_2fa_policy_for_org(
         if member.atype < UserOrgType::Admin && TwoFactor::find_by_user(&member.user_uuid, conn).await.is_empty() {
             if CONFIG.mail_enabled() {
                 let user = User::find_by_uuid(&member.user_uuid, conn).await.unwrap();
-                mail::send_2fa_removed_from_org(&user.email, &org.name).await?;
+                mail::send_2fa_removed_from_org(&user.email, user.locale.as_deref(), &org.name).await?;
             }
             let mut member = member;
             member.revoke();
@@ -244,36 +245,42 @@ pub async fn enforce_2fa_policy_for_org(
     Ok(())
 }
 
-pub async fn send_incomplete_2fa_notifications(pool: DbPool) {
+pub async fn send_incomplete_2fa_notifications(pool: DbPool) -> JobResult {
     debug!("Sending notifications for incomplete 2FA logins");
 
     if CONFIG.incomplete_2fa_time_limit() <= 0 || !CONFIG.mail_enabled() {
-        return;
+        return Ok(0);
     }
 
     let mut conn = match pool.get().await {
         Ok(conn) => conn,
-        _ => {
-            error!("Failed to get DB connection in send_incomplete_2fa_notifications()");
-            return;
-        }
+        _ => err!("Failed to get DB connection in send_incomplete_2fa_notifications()"),
     };
 
     let now = Utc::now().naive_utc();
     let time_limit = TimeDelta::try_minutes(CONFIG.incomplete_2fa_time_limit()).unwrap();
     let time_before = now - time_limit;
     let incomplete_logins = TwoFactorIncomplete::find_logins_before(&time_before, &mut conn).await;
+    let notified = incomplete_logins.len();
     for login in incomplete_logins {
         let user = User::find_by_uuid(&login.user_uuid, &mut conn).await.expect("User not found");
         info!(
             "User {} did not complete a 2FA login within the configured time limit. IP: {}",
             user.email, login.ip_address
         );
-        mail::send_incomplete_2fa_login(&user.email, &login.ip_address, &login.login_time, &login.device_name)
-            .await
-            .expect("Error sending incomplete 2FA email");
+        mail::send_incomplete_2fa_login(
+            &user.email,
+            user.locale.as_deref(),
+            &login.ip_address,
+            &login.login_time,
+            &login.device_name,
+            &NotificationPreference::find_or_default_by_user(&user.uuid, &mut conn).await,
+        )
+        .await
+        .expect("Error sending incomplete 2FA email");
         login.delete(&mut conn).await.expect("Error deleting incomplete 2FA record");
     }
+    Ok(notified)
 }
 
 // This function currently is just a dummy and the actual part is not implemented yet.
@@ -76,7 +76,7 @@ async fn request_otp(headers: Headers, mut conn: DbConn) -> EmptyResult {
     let twofactor = TwoFactor::new(user.uuid, TwoFactorType::ProtectedActions, pa_data.to_json());
     twofactor.save(&mut conn).await?;
 
-    mail::send_protected_action_token(&user.email, &pa_data.token).await?;
+    mail::send_protected_action_token(&user.email, user.locale.as_deref(), &pa_data.token).await?;
 
     Ok(())
 }
@@ -48,7 +48,7 @@ async fn login(data: Form<ConnectData>, client_header: ClientHeaders, mut conn:
             _check_is_some(&data.device_name, "device_name cannot be blank")?;
             _check_is_some(&data.device_type, "device_type cannot be blank")?;
 
-            _password_login(data, &mut user_uuid, &mut conn, &client_header.ip).await
+            _password_login(data, &mut user_uuid, &mut conn, &client_header.ip, client_header.locale).await
         }
         "client_credentials" => {
             _check_is_some(&data.client_id, "client_id cannot be blank")?;
@@ -140,6 +140,7 @@ async fn _password_login(
     user_uuid: &mut Option<String>,
     conn: &mut DbConn,
     ip: &ClientIp,
+    locale: Option<String>,
 ) -> JsonResult {
     // Validate scope
     let scope = data.scope.as_ref().unwrap();
@@ -203,6 +204,17 @@ async fn _password_login(
         }
     }
 
+    // Deleted accounts can only be restored by an admin
+    if user.is_deleted() {
+        err!(
+            "This account has been deleted",
+            format!("IP: {}. Username: {}.", ip.ip, username),
+            ErrorEvent {
+                event: EventType::UserFailedLogIn
+            }
+        )
+    }
+
     // Check if the user is disabled
     if !user.enabled {
         err!(
@@ -214,6 +226,14 @@ async fn _password_login(
         )
     }
 
+    // Remember the language of the client for the emails, unless the user already has one
+    if user.locale.is_none() && locale.is_some() {
+        user.locale = locale;
+        if let Err(e) = user.save(conn).await {
+            error!("Error updating user: {:#?}", e);
+        }
+    }
+
     let now = Utc::now().naive_utc();
 
     if user.verified_at.is_none() && CONFIG.mail_enabled() && CONFIG.signups_verify() {
@@ -232,7 +252,7 @@ async fn _password_login(
                     error!("Error updating user: {:#?}", e);
                 }
 
-                if let Err(e) = mail::send_verify_email(&user.email, &user.uuid).await {
+                if let Err(e) = mail::send_verify_email(&user.email, user.locale.as_deref(), &user.uuid).await {
                     error!("Error auto-sending email verification email: {:#?}", e);
                 }
             }
@@ -253,7 +273,16 @@ async fn _password_login(
     let twofactor_token = twofactor_auth(&user, &data, &mut device, ip, conn).await?;
 
     if CONFIG.mail_enabled() && new_device {
-        if let Err(e) = mail::send_new_device_logged_in(&user.email, &ip.ip.to_string(), &now, &device.name).await {
+        if let Err(e) = mail::send_new_device_logged_in(
+            &user.email,
+            user.locale.as_deref(),
+            &ip.ip.to_string(),
+            &now,
+            &device.name,
+            &NotificationPreference::find_or_default_by_user(&user.uuid, conn).await,
+        )
+        .await
+        {
             error!("Error sending new device email: {:#?}", e);
 
             if CONFIG.require_device_email() {
@@ -354,6 +383,17 @@ async fn _user_api_key_login(
     // Set the user_uuid here to be passed back used for event logging.
     *user_uuid = Some(user.uuid.clone());
 
+    // Deleted accounts can only be restored by an admin
+    if user.is_deleted() {
+        err!(
+            "This account has been deleted (API key login)",
+            format!("IP: {}. Username: {}.", ip.ip, user.email),
+            ErrorEvent {
+                event: EventType::UserFailedLogIn
+            }
+        )
+    }
+
     // Check if the user is disabled
     if !user.enabled {
         err!(
@@ -381,7 +421,16 @@ async fn _user_api_key_login(
 
     if CONFIG.mail_enabled() && new_device {
         let now = Utc::now().naive_utc();
-        if let Err(e) = mail::send_new_device_logged_in(&user.email, &ip.ip.to_string(), &now, &device.name).await {
+        if let Err(e) = mail::send_new_device_logged_in(
+            &user.email,
+            user.locale.as_deref(),
+            &ip.ip.to_string(),
+            &now,
+            &device.name,
+            &NotificationPreference::find_or_default_by_user(&user.uuid, conn).await,
+        )
+        .await
+        {
             error!("Error sending new device email: {:#?}", e);
 
             if CONFIG.require_device_email() {
@@ -680,8 +729,8 @@ async fn prelogin(data: Json<PreloginData>, conn: DbConn) -> Json<Value> {
 }
 
 #[post("/accounts/register", data = "<data>")]
-async fn identity_register(data: Json<RegisterData>, conn: DbConn) -> JsonResult {
-    _register(data, conn).await
+async fn identity_register(data: Json<RegisterData>, client_header: ClientHeaders, conn: DbConn) -> JsonResult {
+    _register(data, client_header, conn).await
 }
 
 // https://github.com/bitwarden/jslib/blob/master/common/src/models/request/tokenRequest.ts
@@ -4,6 +4,8 @@ mod icons;
 mod identity;
 mod notifications;
 mod push;
+#[cfg(test)]
+pub(crate) mod tests;
 mod web;
 
 use rocket::serde::json::Json;
@@ -12,17 +14,24 @@ use serde_json::Value;
 pub use crate::api::{
     admin::catchers as admin_catchers,
     admin::routes as admin_routes,
+    core::account_lifecycle_job,
     core::catchers as core_catchers,
+    core::org_deletion_purge_job,
+    core::org_domain_verification_job,
     core::purge_auth_requests,
+    core::purge_expired_invitations,
     core::purge_sends,
     core::purge_trashed_ciphers,
     core::routes as core_routes,
+    core::security_digest_job,
     core::two_factor::send_incomplete_2fa_notifications,
+    core::user_deletion_purge_job,
     core::{emergency_notification_reminder_job, emergency_request_timeout_job},
     core::{event_cleanup_job, events_routes as core_events_routes},
     icons::routes as icons_routes,
     identity::routes as identity_routes,
     notifications::routes as notifications_routes,
+    notifications::start_ws_broadcast,
     notifications::{AnonymousNotify, Notify, UpdateType, WS_ANONYMOUS_SUBSCRIPTIONS, WS_USERS},
     push::{
         push_cipher_update, push_folder_update, push_logout, push_send_update, push_user_update, register_push_device,
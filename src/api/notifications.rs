@@ -1,6 +1,13 @@
-use std::{net::IpAddr, sync::Arc, time::Duration};
+use std::{
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
-use chrono::{NaiveDateTime, Utc};
+use chrono::{NaiveDateTime, TimeDelta, Utc};
 use rmpv::Value;
 use rocket::{futures::StreamExt, Route};
 use tokio::sync::mpsc::Sender;
@@ -10,13 +17,13 @@ use rocket_ws::{Message, WebSocket};
 use crate::{
     auth::{ClientIp, WsAccessTokenHeader},
     db::{
-        models::{Cipher, Folder, Send as DbSend, User},
-        DbConn,
+        models::{Cipher, Folder, Send as DbSend, User, WsBroadcast},
+        DbConn, DbPool,
     },
     Error, CONFIG,
 };
 
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
 
 pub static WS_USERS: Lazy<Arc<WebSocketUsers>> = Lazy::new(|| {
     Arc::new(WebSocketUsers {
@@ -31,10 +38,19 @@ pub static WS_ANONYMOUS_SUBSCRIPTIONS: Lazy<Arc<AnonymousWebSocketSubscriptions>
 });
 
 use super::{
-    push::push_auth_request, push::push_auth_response, push_cipher_update, push_folder_update, push_logout,
-    push_send_update, push_user_update,
+    push::push_auth_request, push::push_auth_response, push::push_user_sync, push_cipher_update, push_folder_update,
+    push_logout, push_send_update, push_user_update,
 };
 
+/// Number of open WebSocket connections of logged in users on this instance
+static WS_CONNECTION_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Users with an open cipher update batch window, with the number of updates which were held back during it.
+static CIPHER_UPDATE_BATCHES: Lazy<dashmap::DashMap<String, usize>> = Lazy::new(dashmap::DashMap::new);
+
+/// The backend used to share the WebSocket notifications with the other instances, if any.
+static WS_BROADCAST: OnceCell<WsBroadcastBackend> = OnceCell::new();
+
 static NOTIFICATIONS_DISABLED: Lazy<bool> = Lazy::new(|| !CONFIG.enable_websocket() && !CONFIG.push_enabled());
 
 pub fn routes() -> Vec<Route> {
@@ -73,8 +89,9 @@ impl Drop for WSEntryMapGuard {
     fn drop(&mut self) {
         info!("Closing WS connection from {}", self.addr);
         if let Some(mut entry) = self.users.map.get_mut(&self.user_uuid) {
-            entry.retain(|(uuid, _)| uuid != &self.entry_uuid);
+            entry.retain(|c| c.entry_uuid != self.entry_uuid);
         }
+        WS_CONNECTION_COUNT.fetch_sub(1, Ordering::Relaxed);
     }
 }
 
@@ -123,13 +140,33 @@ fn websockets_hub<'r>(
         err_code!("Invalid token", 401)
     };
 
+    let max_connections = CONFIG.ws_max_connections() as usize;
+    if WS_CONNECTION_COUNT.fetch_add(1, Ordering::Relaxed) >= max_connections && max_connections != 0 {
+        WS_CONNECTION_COUNT.fetch_sub(1, Ordering::Relaxed);
+        err_code!("Too many WebSocket connections", 429)
+    }
+
     let (mut rx, guard) = {
         let users = Arc::clone(&WS_USERS);
 
         // Add a channel to send messages to this client to the map
         let entry_uuid = uuid::Uuid::new_v4();
         let (tx, rx) = tokio::sync::mpsc::channel::<Message>(100);
-        users.map.entry(claims.sub.clone()).or_default().push((entry_uuid, tx));
+        {
+            let mut connections = users.map.entry(claims.sub.clone()).or_default();
+            let max_user_connections = CONFIG.ws_max_connections_per_user() as usize;
+            if max_user_connections != 0 && connections.len() >= max_user_connections {
+                WS_CONNECTION_COUNT.fetch_sub(1, Ordering::Relaxed);
+                err_code!("Too many WebSocket connections for this user", 429)
+            }
+            connections.push(UserConnection {
+                entry_uuid,
+                sender: tx,
+                device_uuid: claims.device,
+                addr,
+                connected_at: Utc::now().naive_utc(),
+            });
+        }
 
         // Once the guard goes out of scope, the connection will have been closed and the entry will be deleted from the map
         (rx, WSEntryMapGuard::new(users, claims.sub, entry_uuid, addr))
@@ -321,23 +358,88 @@ static INITIAL_MESSAGE: InitialMessage<'static> = InitialMessage {
 };
 
 // We attach the UUID to the sender so we can differentiate them when we need to remove them from the Vec
-type UserSenders = (uuid::Uuid, Sender<Message>);
+#[derive(Clone)]
+struct UserConnection {
+    entry_uuid: uuid::Uuid,
+    sender: Sender<Message>,
+    device_uuid: String,
+    addr: IpAddr,
+    connected_at: NaiveDateTime,
+}
+
+/// Details of an open WebSocket connection, as shown on the admin page
+pub struct WebSocketConnectionInfo {
+    pub id: String,
+    pub user_uuid: String,
+    pub device_uuid: String,
+    pub ip: String,
+    pub connected_at: NaiveDateTime,
+}
+
 #[derive(Clone)]
 pub struct WebSocketUsers {
-    map: Arc<dashmap::DashMap<String, Vec<UserSenders>>>,
+    map: Arc<dashmap::DashMap<String, Vec<UserConnection>>>,
 }
 
 impl WebSocketUsers {
     async fn send_update(&self, user_uuid: &str, data: &[u8]) {
+        self.send_local_update(user_uuid, data).await;
+        if let Some(broadcast) = WS_BROADCAST.get() {
+            broadcast.publish(false, user_uuid, data);
+        }
+    }
+
+    /// Sends the update only to the clients connected to this instance
+    async fn send_local_update(&self, user_uuid: &str, data: &[u8]) {
         if let Some(user) = self.map.get(user_uuid).map(|v| v.clone()) {
-            for (_, sender) in user.iter() {
-                if let Err(e) = sender.send(Message::binary(data)).await {
+            for connection in user.iter() {
+                if let Err(e) = connection.sender.send(Message::binary(data)).await {
                     error!("Error sending WS update {e}");
                 }
             }
         }
     }
 
+    /// Lists the connections of logged in users to this instance, oldest first
+    pub fn connections(&self) -> Vec<WebSocketConnectionInfo> {
+        let mut connections: Vec<WebSocketConnectionInfo> = self
+            .map
+            .iter()
+            .flat_map(|entry| {
+                entry
+                    .value()
+                    .iter()
+                    .map(|c| WebSocketConnectionInfo {
+                        id: c.entry_uuid.to_string(),
+                        user_uuid: entry.key().clone(),
+                        device_uuid: c.device_uuid.clone(),
+                        ip: c.addr.to_string(),
+                        connected_at: c.connected_at,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        connections.sort_by_key(|c| c.connected_at);
+        connections
+    }
+
+    /// Closes the connection with the given id, returns `false` if there is no such connection
+    pub fn disconnect(&self, id: &str) -> bool {
+        let Ok(entry_uuid) = uuid::Uuid::parse_str(id) else {
+            return false;
+        };
+
+        // Dropping the sender ends the connection loop, which closes the connection
+        for mut entry in self.map.iter_mut() {
+            let connections = entry.value_mut();
+            if let Some(pos) = connections.iter().position(|c| c.entry_uuid == entry_uuid) {
+                connections.remove(pos);
+                return true;
+            }
+        }
+        false
+    }
+
     // NOTE: The last modified date needs to be updated before calling these methods
     pub async fn send_user_update(&self, ut: UpdateType, user: &User) {
         // Skip any processing if both WebSockets and Push are not active
@@ -447,17 +549,71 @@ impl WebSocketUsers {
             Some(acting_device_uuid.into()),
         );
 
+        // Updates of users which are already within a batch window are replaced by a single sync once it closes
+        let personal = user_uuids.len() == 1;
+        let user_uuids: Vec<&String> = user_uuids.iter().filter(|uuid| self.start_or_join_cipher_batch(uuid)).collect();
+
         if CONFIG.enable_websocket() {
-            for uuid in user_uuids {
+            for uuid in &user_uuids {
                 self.send_update(uuid, &data).await;
             }
         }
 
-        if CONFIG.push_enabled() && user_uuids.len() == 1 {
+        if CONFIG.push_enabled() && personal && !user_uuids.is_empty() {
             push_cipher_update(ut, cipher, acting_device_uuid, conn).await;
         }
     }
 
+    /// Returns `true` if a cipher update for this user has to be sent right away.
+    ///
+    /// The first update opens a batch window of `NOTIFICATION_BATCH_WINDOW_MS`, further updates within it are only counted.
+    /// When the window closes, one `SyncCiphers` message is sent instead of all of those held back updates.
+    fn start_or_join_cipher_batch(&self, user_uuid: &str) -> bool {
+        let window_ms = CONFIG.notification_batch_window_ms();
+        if window_ms == 0 {
+            return true;
+        }
+
+        match CIPHER_UPDATE_BATCHES.entry(user_uuid.to_string()) {
+            dashmap::Entry::Occupied(mut entry) => {
+                *entry.get_mut() += 1;
+                false
+            }
+            dashmap::Entry::Vacant(entry) => {
+                entry.insert(0);
+                let users = self.clone();
+                let user_uuid = user_uuid.to_string();
+                tokio::spawn(async move {
+                    tokio::time::sleep(Duration::from_millis(window_ms)).await;
+                    if let Some((_, held_back)) = CIPHER_UPDATE_BATCHES.remove(&user_uuid) {
+                        if held_back > 0 {
+                            debug!("Replacing {held_back} cipher update(s) of user {user_uuid} by a single sync");
+                            users.send_cipher_sync(&user_uuid).await;
+                        }
+                    }
+                });
+                true
+            }
+        }
+    }
+
+    async fn send_cipher_sync(&self, user_uuid: &str) {
+        let date = Utc::now().naive_utc();
+        let data = create_update(
+            vec![("UserId".into(), user_uuid.into()), ("Date".into(), serialize_date(date))],
+            UpdateType::SyncCiphers,
+            None,
+        );
+
+        if CONFIG.enable_websocket() {
+            self.send_update(user_uuid, &data).await;
+        }
+
+        if CONFIG.push_enabled() {
+            push_user_sync(UpdateType::SyncCiphers, user_uuid, &date);
+        }
+    }
+
     pub async fn send_send_update(
         &self,
         ut: UpdateType,
@@ -551,6 +707,14 @@ pub struct AnonymousWebSocketSubscriptions {
 
 impl AnonymousWebSocketSubscriptions {
     async fn send_update(&self, token: &str, data: &[u8]) {
+        self.send_local_update(token, data).await;
+        if let Some(broadcast) = WS_BROADCAST.get() {
+            broadcast.publish(true, token, data);
+        }
+    }
+
+    /// Sends the update only to the client connected to this instance
+    async fn send_local_update(&self, token: &str, data: &[u8]) {
         if let Some(sender) = self.map.get(token).map(|v| v.clone()) {
             if let Err(e) = sender.send(Message::binary(data)).await {
                 error!("Error sending WS update {e}");
@@ -571,6 +735,289 @@ impl AnonymousWebSocketSubscriptions {
     }
 }
 
+//
+// Sharing notifications between instances
+//
+
+/// Relays the WebSocket notifications to the other instances which share the same database.
+/// Only the already serialized messages are shared, push notifications are sent once by the originating instance.
+enum WsBroadcastBackend {
+    /// Notifications are stored in the `ws_broadcasts` table, from where the other instances read them.
+    /// On PostgreSQL the other instances are woken up through LISTEN/NOTIFY, on MySQL and SQLite they poll the table.
+    Database(DbPool),
+}
+
+impl WsBroadcastBackend {
+    fn publish(&self, anonymous: bool, target: &str, data: &[u8]) {
+        match self {
+            Self::Database(pool) => {
+                let (target, data) = (target.to_string(), data.to_vec());
+                let pool = pool.clone();
+                // Don't hold up the request while waiting for a connection, it might already be holding one itself
+                tokio::spawn(async move {
+                    match pool.get().await {
+                        Ok(mut conn) => {
+                            if let Err(e) = WsBroadcast::create(anonymous, &target, &data, &mut conn).await {
+                                error!("Error saving WS broadcast: {e:?}");
+                            }
+                        }
+                        Err(e) => error!("Failed to get DB connection while saving WS broadcast: {e:?}"),
+                    }
+                });
+            }
+        }
+    }
+}
+
+/// Starts sharing the WebSocket notifications with the other instances, when configured to do so.
+pub fn start_ws_broadcast(pool: DbPool) {
+    if !CONFIG.enable_websocket() {
+        return;
+    }
+
+    match CONFIG.ws_broadcast_backend().as_str() {
+        "database" => {
+            WS_BROADCAST.set(WsBroadcastBackend::Database(pool.clone())).ok();
+
+            #[cfg(postgresql)]
+            if matches!(crate::db::DbConnType::from_url(&CONFIG.database_url()), Ok(crate::db::DbConnType::postgresql))
+            {
+                if let Some(wake) = listen_ws_broadcasts() {
+                    info!("Sharing WebSocket notifications with other instances through PostgreSQL LISTEN/NOTIFY");
+                    tokio::spawn(read_ws_broadcasts(pool, Some(wake)));
+                    return;
+                }
+            }
+
+            info!("Sharing WebSocket notifications with other instances by polling the database");
+            tokio::spawn(read_ws_broadcasts(pool, None));
+        }
+        _ => debug!("WebSocket notifications are not shared with other instances"),
+    }
+}
+
+/// Starts listening on the `ws_broadcasts` channel, the returned `Notify` is woken up when other instances
+/// store a broadcast. Returns `None` when the `DATABASE_URL` can't be used, the database is polled instead.
+#[cfg(postgresql)]
+fn listen_ws_broadcasts() -> Option<Arc<tokio::sync::Notify>> {
+    use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+
+    let (database_url, verification) = split_ssl_verification(&CONFIG.database_url());
+    let config = match database_url.parse::<tokio_postgres::Config>() {
+        Ok(config) => config,
+        Err(e) => {
+            warn!("Unable to use `DATABASE_URL` to listen for WS broadcasts, polling the database instead: {e}");
+            return None;
+        }
+    };
+    let tls = match SslConnector::builder(SslMethod::tls()) {
+        Ok(mut builder) => {
+            if verification.mode.is_some() {
+                builder.set_verify(SslVerifyMode::PEER);
+                let ca = match &verification.root_cert {
+                    Some(root_cert) => builder.set_ca_file(root_cert),
+                    None => builder.set_default_verify_paths(),
+                };
+                if let Err(e) = ca {
+                    warn!("Unable to load the CA certificates to listen for WS broadcasts, polling the database instead: {e}");
+                    return None;
+                }
+            } else {
+                // The same as the `prefer` and `require` SSL modes of libpq, which don't verify the certificate either
+                builder.set_verify(SslVerifyMode::NONE);
+            }
+            let mut tls = postgres_openssl::MakeTlsConnector::new(builder.build());
+            // Like libpq, `verify-ca` only checks the certificate chain and `verify-full` the host name as well
+            if verification.mode == Some(SslVerification::VERIFY_CA) {
+                tls.set_callback(|connect, _| {
+                    connect.set_verify_hostname(false);
+                    Ok(())
+                });
+            }
+            tls
+        }
+        Err(e) => {
+            warn!("Unable to set up TLS to listen for WS broadcasts, polling the database instead: {e}");
+            return None;
+        }
+    };
+
+    let wake = Arc::new(tokio::sync::Notify::new());
+    let listener_wake = Arc::clone(&wake);
+    tokio::spawn(async move {
+        loop {
+            match wait_for_ws_broadcasts(&config, tls.clone(), &listener_wake).await {
+                Ok(()) => warn!("The connection listening for WS broadcasts was closed, reconnecting in 5 seconds"),
+                Err(e) => error!("Error listening for WS broadcasts, reconnecting in 5 seconds: {e:?}"),
+            }
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+    });
+    Some(wake)
+}
+
+/// The certificate verification settings of libpq which tokio-postgres doesn't know about
+#[cfg_attr(not(postgresql), allow(dead_code))]
+#[derive(Debug, Default, PartialEq, Eq)]
+struct SslVerification {
+    mode: Option<&'static str>,
+    root_cert: Option<String>,
+}
+
+impl SslVerification {
+    const VERIFY_CA: &'static str = "verify-ca";
+    const VERIFY_FULL: &'static str = "verify-full";
+}
+
+/// tokio-postgres only supports the `disable`, `prefer` and `require` SSL modes and doesn't know `sslrootcert`.
+/// Returns the `DATABASE_URL` with `require` instead of a `verify-ca` or `verify-full` SSL mode and without `sslrootcert`,
+/// together with those settings, so the connection can verify the certificate itself.
+#[cfg_attr(not(postgresql), allow(dead_code))]
+fn split_ssl_verification(database_url: &str) -> (String, SslVerification) {
+    let mut verification = SslVerification::default();
+    // Returns the parameter to use instead, `None` to leave it out
+    let mut convert = |param: &str, key: &str, value: &str| match (key, value) {
+        ("sslmode", SslVerification::VERIFY_CA) => {
+            verification.mode = Some(SslVerification::VERIFY_CA);
+            Some(String::from("sslmode=require"))
+        }
+        ("sslmode", SslVerification::VERIFY_FULL) => {
+            verification.mode = Some(SslVerification::VERIFY_FULL);
+            Some(String::from("sslmode=require"))
+        }
+        ("sslrootcert", _) => {
+            verification.root_cert = Some(value.to_string());
+            None
+        }
+        _ => Some(param.to_string()),
+    };
+
+    let converted = if database_url.starts_with("postgres://") || database_url.starts_with("postgresql://") {
+        let (base, query) = database_url.split_once('?').unwrap_or((database_url, ""));
+        let params: Vec<String> = query
+            .split('&')
+            .filter(|param| !param.is_empty())
+            .filter_map(|param| {
+                let (key, value) = param.split_once('=').unwrap_or((param, ""));
+                convert(param, key, &percent_encoding::percent_decode_str(value).decode_utf8_lossy())
+            })
+            .collect();
+        if params.is_empty() {
+            base.to_string()
+        } else {
+            format!("{base}?{}", params.join("&"))
+        }
+    } else {
+        database_url
+            .split_whitespace()
+            .filter_map(|param| {
+                let (key, value) = param.split_once('=').unwrap_or((param, ""));
+                convert(param, key, value.trim_matches('\''))
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    // Anything else is passed on unchanged, like quoted keyword values with whitespace in them
+    if verification == SslVerification::default() {
+        return (database_url.to_string(), verification);
+    }
+    (converted, verification)
+}
+
+/// Keeps a dedicated connection listening on the `ws_broadcasts` channel until it is closed
+#[cfg(postgresql)]
+async fn wait_for_ws_broadcasts(
+    config: &tokio_postgres::Config,
+    tls: postgres_openssl::MakeTlsConnector,
+    wake: &Arc<tokio::sync::Notify>,
+) -> Result<(), tokio_postgres::Error> {
+    let (client, mut connection) = config.connect(tls).await?;
+
+    // The connection only makes progress while its messages are polled, which includes the reply to LISTEN
+    let messages_wake = Arc::clone(wake);
+    let messages = tokio::spawn(async move {
+        let mut messages = futures::stream::poll_fn(move |cx| connection.poll_message(cx));
+        while let Some(message) = messages.next().await {
+            if let tokio_postgres::AsyncMessage::Notification(_) = message? {
+                messages_wake.notify_one();
+            }
+        }
+        Ok(())
+    });
+
+    client.batch_execute("LISTEN ws_broadcasts").await?;
+    // Broadcasts stored while (re)connecting didn't wake anyone up
+    wake.notify_one();
+
+    // Keep the client until the connection is closed, dropping it closes the connection
+    let result = messages.await.unwrap_or(Ok(()));
+    drop(client);
+    result
+}
+
+/// Delivers the broadcasts of the other instances to the clients connected to this instance.
+/// With a `wake` the table is read when woken up, and only every minute otherwise in case a notification was missed.
+async fn read_ws_broadcasts(pool: DbPool, wake: Option<Arc<tokio::sync::Notify>>) {
+    // The ids of the broadcasts are assigned by the database, so they don't depend on the clocks of the instances.
+    // Only the broadcasts after the last one seen are read, those stored before this instance started are never delivered.
+    let retention = TimeDelta::try_minutes(5).unwrap();
+
+    let mut last_id = match pool.get().await {
+        Ok(mut conn) => WsBroadcast::find_last_id(&mut conn).await,
+        Err(e) => {
+            error!("Failed to get DB connection while starting to poll WS broadcasts: {e:?}");
+            0
+        }
+    };
+    let mut last_cleanup = Utc::now().naive_utc();
+    let mut interval = tokio::time::interval(Duration::from_millis(CONFIG.ws_broadcast_poll_interval_ms()));
+
+    loop {
+        match &wake {
+            Some(wake) => {
+                let _ = tokio::time::timeout(Duration::from_secs(60), wake.notified()).await;
+            }
+            None => {
+                interval.tick().await;
+            }
+        }
+
+        let mut conn = match pool.get().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                error!("Failed to get DB connection while polling WS broadcasts: {e:?}");
+                continue;
+            }
+        };
+
+        let broadcasts = match WsBroadcast::find_from_others_after(last_id, &mut conn).await {
+            Ok(broadcasts) => broadcasts,
+            Err(e) => {
+                error!("Error loading WS broadcasts: {e:?}");
+                continue;
+            }
+        };
+        for broadcast in broadcasts {
+            last_id = last_id.max(broadcast.id);
+            if broadcast.anonymous {
+                WS_ANONYMOUS_SUBSCRIPTIONS.send_local_update(&broadcast.target, &broadcast.data).await;
+            } else {
+                WS_USERS.send_local_update(&broadcast.target, &broadcast.data).await;
+            }
+        }
+
+        // Only the cleanup uses the clock, broadcasts are kept long enough for a reasonable clock skew between instances
+        let now = Utc::now().naive_utc();
+        if now - last_cleanup > TimeDelta::try_minutes(1).unwrap() {
+            if let Err(e) = WsBroadcast::delete_created_before(&(now - retention), &mut conn).await {
+                error!("Error deleting old WS broadcasts: {e:?}");
+            }
+            last_cleanup = now;
+        }
+    }
+}
+
 /* Message Structure
 [
     1, // MessageType.Invocation
@@ -656,3 +1103,26 @@ pub enum UpdateType {
 
 pub type Notify<'a> = &'a rocket::State<Arc<WebSocketUsers>>;
 pub type AnonymousNotify<'a> = &'a rocket::State<Arc<AnonymousWebSocketSubscriptions>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_ssl_verification() {
+        let url = "postgresql://user:pass@db/vaultwarden?sslmode=require";
+        assert_eq!(split_ssl_verification(url), (url.to_string(), SslVerification::default()));
+
+        let (url, verification) = split_ssl_verification(
+            "postgresql://user:pass@db/vaultwarden?sslmode=verify-full&sslrootcert=%2Fcerts%2Fca.pem&connect_timeout=5",
+        );
+        assert_eq!(url, "postgresql://user:pass@db/vaultwarden?sslmode=require&connect_timeout=5");
+        assert_eq!(verification.mode, Some(SslVerification::VERIFY_FULL));
+        assert_eq!(verification.root_cert.as_deref(), Some("/certs/ca.pem"));
+
+        let (url, verification) = split_ssl_verification("host=db user=vaultwarden sslmode=verify-ca");
+        assert_eq!(url, "host=db user=vaultwarden sslmode=require");
+        assert_eq!(verification.mode, Some(SslVerification::VERIFY_CA));
+        assert_eq!(verification.root_cert, None);
+    }
+}
@@ -1,3 +1,4 @@
+use chrono::NaiveDateTime;
 use reqwest::{
     header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE},
     Method,
@@ -197,15 +198,19 @@ pub fn push_logout(user: &User, acting_device_uuid: Option<String>) {
 }
 
 pub fn push_user_update(ut: UpdateType, user: &User) {
+    push_user_sync(ut, &user.uuid, &user.updated_at);
+}
+
+pub fn push_user_sync(ut: UpdateType, user_uuid: &str, date: &NaiveDateTime) {
     tokio::task::spawn(send_to_push_relay(json!({
-        "userId": user.uuid,
+        "userId": user_uuid,
         "organizationId": (),
         "deviceId": (),
         "identifier": (),
         "type": ut as i32,
         "payload": {
-            "userId": user.uuid,
-            "date": user.updated_at
+            "userId": user_uuid,
+            "date": date
         }
     })));
 }
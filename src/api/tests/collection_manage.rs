@@ -0,0 +1,86 @@
+use rocket::http::{Method, Status};
+use serde_json::{json, Value};
+
+use super::{client, conn, login, new_collection, new_member, new_org, send};
+use crate::db::{
+    models::{CollectionGroup, CollectionUser, Group, GroupUser, Organization, UserOrgType},
+    DbConn,
+};
+
+/// Returns an access token of a new User, with the given access to the collection
+async fn user_with_access(org: &Organization, col_id: &str, manage: bool, conn: &mut DbConn) -> String {
+    let (user, _) = new_member(org, UserOrgType::User, conn).await;
+    CollectionUser::save(&user.uuid, col_id, false, false, manage, conn).await.unwrap();
+    login(&user, conn).await
+}
+
+/// Returns an access token of a new User, who has access to the collection through a group
+async fn group_member_with_access(org: &Organization, col_id: &str, manage: bool, conn: &mut DbConn) -> String {
+    let (user, user_org) = new_member(org, UserOrgType::User, conn).await;
+    let mut group = Group::new(org.uuid.clone(), String::from("Group"), false, None);
+    group.save(conn).await.unwrap();
+    CollectionGroup::new(col_id.to_string(), group.uuid.clone(), false, false, manage).save(conn).await.unwrap();
+    GroupUser::new(group.uuid, user_org.uuid).save(conn).await.unwrap();
+    login(&user, conn).await
+}
+
+fn collection_body() -> Option<Value> {
+    Some(json!({"name": "Renamed", "groups": [], "users": [], "externalId": null}))
+}
+
+fn collection_users_body(member_id: &str) -> Option<Value> {
+    Some(json!([{"id": member_id, "readOnly": false, "hidePasswords": false, "manage": true}]))
+}
+
+#[rocket::async_test]
+async fn test_user_with_manage_can_edit_collection() {
+    let mut conn = conn().await;
+    let org = new_org(&mut conn).await;
+    let collection = new_collection(&org, &mut conn).await;
+    let (_, member) = new_member(&org, UserOrgType::User, &mut conn).await;
+
+    let client = client().await;
+    let uri = format!("organizations/{}/collections/{}", org.uuid, collection.uuid);
+    let users_uri = format!("{uri}/users");
+
+    // Both requests replace the access of everyone else, including the member making the request
+    let token = user_with_access(&org, &collection.uuid, true, &mut conn).await;
+    let body = collection_users_body(&member.uuid);
+    assert_eq!(send(&client, Method::Put, &users_uri, &token, body).await.0, Status::Ok);
+    let token = user_with_access(&org, &collection.uuid, true, &mut conn).await;
+    assert_eq!(send(&client, Method::Put, &uri, &token, collection_body()).await.0, Status::Ok);
+
+    let token = group_member_with_access(&org, &collection.uuid, true, &mut conn).await;
+    let body = collection_users_body(&member.uuid);
+    assert_eq!(send(&client, Method::Put, &users_uri, &token, body).await.0, Status::Ok);
+    let token = group_member_with_access(&org, &collection.uuid, true, &mut conn).await;
+    assert_eq!(send(&client, Method::Put, &uri, &token, collection_body()).await.0, Status::Ok);
+}
+
+#[rocket::async_test]
+async fn test_user_without_manage_cannot_edit_collection() {
+    let mut conn = conn().await;
+    let org = new_org(&mut conn).await;
+    let collection = new_collection(&org, &mut conn).await;
+    let (_, member) = new_member(&org, UserOrgType::User, &mut conn).await;
+
+    let client = client().await;
+    let uri = format!("organizations/{}/collections/{}", org.uuid, collection.uuid);
+    let users_uri = format!("{uri}/users");
+
+    let token = user_with_access(&org, &collection.uuid, false, &mut conn).await;
+    assert_eq!(send(&client, Method::Put, &uri, &token, collection_body()).await.0, Status::Unauthorized);
+    let body = collection_users_body(&member.uuid);
+    assert_eq!(send(&client, Method::Put, &users_uri, &token, body).await.0, Status::Unauthorized);
+
+    let token = group_member_with_access(&org, &collection.uuid, false, &mut conn).await;
+    assert_eq!(send(&client, Method::Put, &uri, &token, collection_body()).await.0, Status::Unauthorized);
+    let body = collection_users_body(&member.uuid);
+    assert_eq!(send(&client, Method::Put, &users_uri, &token, body).await.0, Status::Unauthorized);
+
+    // Members without any access to the collection can't manage it either
+    let (user, _) = new_member(&org, UserOrgType::User, &mut conn).await;
+    let token = login(&user, &mut conn).await;
+    let body = collection_users_body(&member.uuid);
+    assert_eq!(send(&client, Method::Put, &users_uri, &token, body).await.0, Status::Unauthorized);
+}
@@ -0,0 +1,105 @@
+use rocket::http::{Method, Status};
+use serde_json::{json, Value};
+
+use super::{client, conn, login, new_collection, new_member, new_org, send};
+use crate::db::{
+    models::{CollectionUser, Organization, User, UserOrgPermissions, UserOrgType},
+    DbConn,
+};
+
+/// Adds a member with the Custom role which can manage users and groups
+async fn new_custom_member(org: &Organization, conn: &mut DbConn) -> (User, String) {
+    let (user, mut custom) = new_member(org, UserOrgType::Custom, conn).await;
+    custom.set_permissions(Some(UserOrgPermissions {
+        manage_users: true,
+        manage_groups: true,
+        ..Default::default()
+    }));
+    custom.save(conn).await.unwrap();
+    (user, custom.uuid)
+}
+
+fn member_body(access_all: bool, col_id: &str) -> Option<Value> {
+    Some(json!({
+        "type": UserOrgType::User as i32,
+        "accessAll": access_all,
+        "collections": [{"id": col_id, "readOnly": false, "hidePasswords": false, "manage": true}],
+        "groups": [],
+    }))
+}
+
+fn group_body(col_id: &str, users: &[&str]) -> Option<Value> {
+    Some(json!({
+        "name": "Group",
+        "accessAll": false,
+        "collections": [{"id": col_id, "readOnly": false, "hidePasswords": false, "manage": true}],
+        "users": users,
+    }))
+}
+
+#[rocket::async_test]
+async fn test_custom_member_cannot_grant_access_all() {
+    let mut conn = conn().await;
+    let org = new_org(&mut conn).await;
+    let (custom, _) = new_custom_member(&org, &mut conn).await;
+    let (_, member) = new_member(&org, UserOrgType::User, &mut conn).await;
+    let collection = new_collection(&org, &mut conn).await;
+    CollectionUser::save(&custom.uuid, &collection.uuid, false, false, true, &mut conn).await.unwrap();
+
+    let client = client().await;
+    let token = login(&custom, &mut conn).await;
+    let uri = format!("organizations/{}/users/{}", org.uuid, member.uuid);
+    assert_eq!(
+        send(&client, Method::Put, &uri, &token, member_body(true, &collection.uuid)).await.0,
+        Status::BadRequest
+    );
+
+    let uri = format!("organizations/{}/users/invite", org.uuid);
+    let body = json!({
+        "emails": ["invited@example.com"],
+        "groups": [],
+        "type": UserOrgType::User as i32,
+        "accessAll": true,
+    });
+    assert_eq!(send(&client, Method::Post, &uri, &token, Some(body)).await.0, Status::BadRequest);
+}
+
+#[rocket::async_test]
+async fn test_custom_member_cannot_grant_unmanaged_collections() {
+    let mut conn = conn().await;
+    let org = new_org(&mut conn).await;
+    let (custom, _) = new_custom_member(&org, &mut conn).await;
+    let (_, member) = new_member(&org, UserOrgType::User, &mut conn).await;
+    // The Custom member can manage the first collection, but can't access the second one
+    let managed = new_collection(&org, &mut conn).await;
+    CollectionUser::save(&custom.uuid, &managed.uuid, false, false, true, &mut conn).await.unwrap();
+    let other = new_collection(&org, &mut conn).await;
+
+    let client = client().await;
+    let token = login(&custom, &mut conn).await;
+    let uri = format!("organizations/{}/users/{}", org.uuid, member.uuid);
+    assert_eq!(send(&client, Method::Put, &uri, &token, member_body(false, &other.uuid)).await.0, Status::BadRequest);
+    assert_eq!(send(&client, Method::Put, &uri, &token, member_body(false, &managed.uuid)).await.0, Status::Ok);
+}
+
+#[rocket::async_test]
+async fn test_custom_member_cannot_escalate_through_groups() {
+    let mut conn = conn().await;
+    let org = new_org(&mut conn).await;
+    let (custom, custom_id) = new_custom_member(&org, &mut conn).await;
+    // A regular member, for example a second account of the Custom member
+    let (_, member) = new_member(&org, UserOrgType::User, &mut conn).await;
+    let managed = new_collection(&org, &mut conn).await;
+    CollectionUser::save(&custom.uuid, &managed.uuid, false, false, true, &mut conn).await.unwrap();
+    let other = new_collection(&org, &mut conn).await;
+
+    let client = client().await;
+    let token = login(&custom, &mut conn).await;
+    let uri = format!("organizations/{}/groups", org.uuid);
+    let body = group_body(&other.uuid, &[&member.uuid]);
+    assert_eq!(send(&client, Method::Post, &uri, &token, body).await.0, Status::BadRequest);
+    let body = group_body(&managed.uuid, &[&custom_id]);
+    assert_eq!(send(&client, Method::Post, &uri, &token, body).await.0, Status::BadRequest);
+    let body = group_body(&managed.uuid, &[&member.uuid]);
+    assert_eq!(send(&client, Method::Post, &uri, &token, body).await.0, Status::Ok);
+}
@@ -0,0 +1,125 @@
+//! Endpoint tests, which run the API routes against a temporary SQLite database.
+//! The helpers here set up the config, organizations, members and logins the tests need.
+
+#[cfg(sqlite)]
+mod collection_manage;
+#[cfg(sqlite)]
+mod custom_roles;
+#[cfg(sqlite)]
+mod org_domains;
+#[cfg(sqlite)]
+mod ownership_transfer;
+#[cfg(sqlite)]
+mod reports;
+#[cfg(sqlite)]
+mod user_deletion;
+
+/// The config is loaded on first use and needs an existing data folder, so every test which uses it calls this first
+/// to point it to a temporary one. This also applies to the tests outside of this module, they share the same config.
+pub fn init_test_config() {
+    static INIT: std::sync::Once = std::sync::Once::new();
+    INIT.call_once(|| {
+        let data_folder = std::env::temp_dir().join(format!("vaultwarden-tests-{}", std::process::id()));
+        std::fs::create_dir_all(&data_folder).unwrap();
+        std::env::set_var("DATA_FOLDER", &data_folder);
+        // Some of the endpoint tests use groups
+        std::env::set_var("ORG_GROUPS_ENABLED", "true");
+        // The mail API test checks that the token is sent, without a `MAIL_API_URL` no mail goes through the API
+        std::env::set_var("MAIL_API_TOKEN", "mail-api-token");
+    });
+}
+
+#[cfg(sqlite)]
+pub use harness::*;
+
+/// The helpers of the endpoint tests
+#[cfg(sqlite)]
+mod harness {
+    use std::sync::Arc;
+
+    use once_cell::sync::Lazy;
+    use rocket::{
+        http::{ContentType, Header, Method, Status},
+        local::asynchronous::Client,
+    };
+    use serde_json::Value;
+
+    use crate::{
+        api::{WS_ANONYMOUS_SUBSCRIPTIONS, WS_USERS},
+        db::{
+            models::{Collection, Device, Organization, User, UserOrgStatus, UserOrgType, UserOrganization},
+            DbConn, DbPool,
+        },
+        util::get_uuid,
+    };
+
+    static POOL: Lazy<DbPool> = Lazy::new(|| {
+        super::init_test_config();
+        crate::auth::initialize_keys().unwrap();
+        DbPool::from_config().unwrap()
+    });
+
+    pub async fn conn() -> DbConn {
+        POOL.get().await.unwrap()
+    }
+
+    /// A client for the `/api` routes
+    pub async fn client() -> Client {
+        let rocket = rocket::build()
+            .mount("/api", crate::api::core_routes())
+            .manage(POOL.clone())
+            .manage(Arc::clone(&WS_USERS))
+            .manage(Arc::clone(&WS_ANONYMOUS_SUBSCRIPTIONS));
+        Client::tracked(rocket).await.unwrap()
+    }
+
+    /// Sends a request as the user the token belongs to, and returns the status and the JSON response if there is one
+    pub async fn send(client: &Client, method: Method, uri: &str, token: &str, body: Option<Value>) -> (Status, Value) {
+        let mut request = client
+            .req(method, format!("/api/{uri}"))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {token}")));
+        if let Some(body) = body {
+            request = request.body(body.to_string());
+        }
+        let response = request.dispatch().await;
+        let status = response.status();
+        (status, response.into_json().await.unwrap_or_default())
+    }
+
+    pub async fn new_user(conn: &mut DbConn) -> User {
+        let mut user = User::new(format!("{}@example.com", get_uuid()));
+        user.save(conn).await.unwrap();
+        user
+    }
+
+    pub async fn new_org(conn: &mut DbConn) -> Organization {
+        let org = Organization::new(String::from("Org"), String::from("org@example.com"), None, None);
+        org.save(conn).await.unwrap();
+        org
+    }
+
+    pub async fn new_collection(org: &Organization, conn: &mut DbConn) -> Collection {
+        let collection = Collection::new(org.uuid.clone(), String::from("Collection"), None);
+        collection.save(conn).await.unwrap();
+        collection
+    }
+
+    /// Adds a new user to the organization as a confirmed member of the given type
+    pub async fn new_member(org: &Organization, atype: UserOrgType, conn: &mut DbConn) -> (User, UserOrganization) {
+        let user = new_user(conn).await;
+        let mut user_org = UserOrganization::new(user.uuid.clone(), org.uuid.clone());
+        user_org.atype = atype as i32;
+        user_org.status = UserOrgStatus::Confirmed as i32;
+        user_org.save(conn).await.unwrap();
+        (user, user_org)
+    }
+
+    /// Returns an access token for a new device of the user
+    pub async fn login(user: &User, conn: &mut DbConn) -> String {
+        let mut device = Device::new(get_uuid(), user.uuid.clone(), String::from("test"), 14);
+        let (token, _) = device.refresh_tokens(user, vec![String::from("api")]);
+        device.save(conn).await.unwrap();
+        token
+    }
+}
@@ -0,0 +1,108 @@
+use std::net::{IpAddr, Ipv4Addr};
+
+use chrono::Utc;
+use rocket::http::{Method, Status};
+use serde_json::json;
+
+use super::{client, conn, login, new_collection, new_member, new_org, send};
+use crate::{
+    api::core::org_domains::enroll_by_email_domain,
+    auth::{ClientHeaders, ClientIp},
+    db::models::{
+        AutoEnrollCollection, CollectionUser, DeviceType, Group, GroupUser, OrgPolicy, OrgPolicyType,
+        OrganizationDomain, User, UserOrgStatus, UserOrgType, UserOrganization,
+    },
+    util::get_uuid,
+};
+
+#[rocket::async_test]
+async fn test_auto_enroll_settings() {
+    let mut conn = conn().await;
+    let org = new_org(&mut conn).await;
+    let mut domain = OrganizationDomain::new(org.uuid.clone(), format!("{}.example.com", get_uuid()));
+    domain.checked(true);
+    domain.save(&mut conn).await.unwrap();
+
+    let client = client().await;
+    let uri = format!("organizations/{}/domain/{}/auto-enroll", org.uuid, domain.uuid);
+    let body = |atype: UserOrgType| Some(json!({"enabled": true, "type": atype as i32}));
+
+    // Only Owners can change the automatic enrollment
+    let (admin, _) = new_member(&org, UserOrgType::Admin, &mut conn).await;
+    let token = login(&admin, &mut conn).await;
+    assert_eq!(send(&client, Method::Put, &uri, &token, body(UserOrgType::User)).await.0, Status::Unauthorized);
+
+    let (owner, _) = new_member(&org, UserOrgType::Owner, &mut conn).await;
+    let token = login(&owner, &mut conn).await;
+    let (status, json) = send(&client, Method::Put, &uri, &token, body(UserOrgType::User)).await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(json["autoEnroll"]["type"], UserOrgType::User as i32);
+    assert_eq!(send(&client, Method::Put, &uri, &token, body(UserOrgType::Manager)).await.0, Status::Ok);
+
+    // Enrolled members never get more than Manager rights
+    for atype in [UserOrgType::Owner, UserOrgType::Admin, UserOrgType::Custom] {
+        assert_eq!(send(&client, Method::Put, &uri, &token, body(atype)).await.0, Status::BadRequest);
+    }
+}
+
+#[rocket::async_test]
+async fn test_auto_enroll_user() {
+    let mut conn = conn().await;
+    let domain_name = format!("{}.example.com", get_uuid());
+
+    let org = new_org(&mut conn).await;
+    let mut group = Group::new(org.uuid.clone(), String::from("Group"), false, None);
+    group.save(&mut conn).await.unwrap();
+    let collection = new_collection(&org, &mut conn).await;
+    let mut domain = OrganizationDomain::new(org.uuid.clone(), domain_name.clone());
+    domain.checked(true);
+    let enroll_collection = AutoEnrollCollection {
+        id: collection.uuid.clone(),
+        read_only: true,
+        hide_passwords: false,
+        manage: false,
+    };
+    domain.set_auto_enroll(true, UserOrgType::Manager as i32, &[group.uuid.clone()], &[enroll_collection]);
+    domain.save(&mut conn).await.unwrap();
+
+    // Members of this organization need two-step login, which a new user can't have set up yet
+    let blocked_org = new_org(&mut conn).await;
+    let mut blocked_domain = OrganizationDomain::new(blocked_org.uuid.clone(), domain_name.clone());
+    blocked_domain.checked(true);
+    blocked_domain.set_auto_enroll(true, UserOrgType::User as i32, &[], &[]);
+    blocked_domain.save(&mut conn).await.unwrap();
+    let mut policy =
+        OrgPolicy::new(blocked_org.uuid.clone(), OrgPolicyType::TwoFactorAuthentication, String::from("{}"));
+    policy.enabled = true;
+    policy.save(&mut conn).await.unwrap();
+
+    let client_headers = ClientHeaders {
+        device_type: DeviceType::UnknownBrowser as i32,
+        ip: ClientIp {
+            ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        },
+        locale: None,
+    };
+
+    let mut unverified = User::new(format!("unverified@{domain_name}"));
+    unverified.save(&mut conn).await.unwrap();
+    enroll_by_email_domain(&unverified, &client_headers, &mut conn).await;
+    assert!(UserOrganization::find_by_user_and_org(&unverified.uuid, &org.uuid, &mut conn).await.is_none());
+
+    let mut user = User::new(format!("verified@{domain_name}"));
+    user.verified_at = Some(Utc::now().naive_utc());
+    user.save(&mut conn).await.unwrap();
+    enroll_by_email_domain(&user, &client_headers, &mut conn).await;
+
+    let user_org = UserOrganization::find_by_user_and_org(&user.uuid, &org.uuid, &mut conn).await.unwrap();
+    assert_eq!(user_org.status, UserOrgStatus::Accepted as i32);
+    assert_eq!(user_org.atype, UserOrgType::Manager as i32);
+    let groups = GroupUser::find_by_user(&user_org.uuid, &mut conn).await;
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups[0].groups_uuid, group.uuid);
+    let col_user = CollectionUser::find_by_collection_and_user(&collection.uuid, &user.uuid, &mut conn).await.unwrap();
+    assert!(col_user.read_only);
+    assert!(!col_user.hide_passwords && !col_user.manage);
+
+    assert!(UserOrganization::find_by_user_and_org(&user.uuid, &blocked_org.uuid, &mut conn).await.is_none());
+}
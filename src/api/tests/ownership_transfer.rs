@@ -0,0 +1,113 @@
+use chrono::Utc;
+use rocket::http::{Method, Status};
+use serde_json::{json, Value};
+
+use super::{client, conn, login, new_member, new_org, send};
+use crate::db::{
+    models::{Cipher, Organization, UserOrgType, UserOrganization},
+    DbConn,
+};
+
+const PASSWORD: &str = "master-password-hash";
+
+/// Adds a member whose password can be used to confirm the transfer, and returns their membership and access token
+async fn member_with_password(org: &Organization, atype: UserOrgType, conn: &mut DbConn) -> (UserOrganization, String) {
+    let (mut user, user_org) = new_member(org, atype, conn).await;
+    // Keeps hashing the password fast
+    user.password_iterations = 1;
+    user.set_password(PASSWORD, None, false, None);
+    user.save(conn).await.unwrap();
+    (user_org, login(&user, conn).await)
+}
+
+fn start_body(new_owner: &UserOrganization) -> Option<Value> {
+    Some(json!({"newOwnerId": new_owner.uuid, "masterPasswordHash": PASSWORD}))
+}
+
+fn accept_body() -> Option<Value> {
+    Some(json!({"masterPasswordHash": PASSWORD}))
+}
+
+async fn member_type(user_org: &UserOrganization, conn: &mut DbConn) -> i32 {
+    UserOrganization::find_by_uuid(&user_org.uuid, conn).await.unwrap().atype
+}
+
+#[rocket::async_test]
+async fn test_only_owners_can_start_ownership_transfer() {
+    let mut conn = conn().await;
+    let org = new_org(&mut conn).await;
+    let (_, owner_token) = member_with_password(&org, UserOrgType::Owner, &mut conn).await;
+    let (new_owner, new_owner_token) = member_with_password(&org, UserOrgType::User, &mut conn).await;
+    let (_, admin_token) = member_with_password(&org, UserOrgType::Admin, &mut conn).await;
+
+    let client = client().await;
+    let uri = format!("organizations/{}/ownership-transfer", org.uuid);
+    assert_eq!(send(&client, Method::Post, &uri, &admin_token, start_body(&new_owner)).await.0, Status::Unauthorized);
+    let status = send(&client, Method::Post, &uri, &new_owner_token, start_body(&new_owner)).await.0;
+    assert_eq!(status, Status::Unauthorized);
+
+    assert_eq!(send(&client, Method::Post, &uri, &owner_token, start_body(&new_owner)).await.0, Status::Ok);
+}
+
+#[rocket::async_test]
+async fn test_only_new_owner_can_accept_ownership_transfer() {
+    let mut conn = conn().await;
+    let org = new_org(&mut conn).await;
+    let (owner, owner_token) = member_with_password(&org, UserOrgType::Owner, &mut conn).await;
+    let (new_owner, new_owner_token) = member_with_password(&org, UserOrgType::User, &mut conn).await;
+
+    let client = client().await;
+    let uri = format!("organizations/{}/ownership-transfer", org.uuid);
+    assert_eq!(send(&client, Method::Post, &uri, &owner_token, start_body(&new_owner)).await.0, Status::Ok);
+
+    let accept_uri = format!("{uri}/accept");
+    let (other, other_token) = member_with_password(&org, UserOrgType::Admin, &mut conn).await;
+    assert_eq!(send(&client, Method::Post, &accept_uri, &other_token, accept_body()).await.0, Status::BadRequest);
+    assert_eq!(send(&client, Method::Post, &accept_uri, &owner_token, accept_body()).await.0, Status::BadRequest);
+    assert_eq!(member_type(&other, &mut conn).await, UserOrgType::Admin as i32);
+
+    // The owner who started the transfer stays on as an admin
+    assert_eq!(send(&client, Method::Post, &accept_uri, &new_owner_token, accept_body()).await.0, Status::Ok);
+    assert_eq!(member_type(&new_owner, &mut conn).await, UserOrgType::Owner as i32);
+    assert_eq!(member_type(&owner, &mut conn).await, UserOrgType::Admin as i32);
+
+    // The transfer can only be accepted once
+    let status = send(&client, Method::Post, &accept_uri, &new_owner_token, accept_body()).await.0;
+    assert_eq!(status, Status::BadRequest);
+}
+
+#[rocket::async_test]
+async fn test_pending_deletion_blocks_organization() {
+    let mut conn = conn().await;
+    let org = new_org(&mut conn).await;
+    let (_, owner_token) = member_with_password(&org, UserOrgType::Owner, &mut conn).await;
+    let (new_owner, new_owner_token) = member_with_password(&org, UserOrgType::User, &mut conn).await;
+    let mut cipher = Cipher::new(1, String::from("2.encrypted|name"));
+    cipher.organization_uuid = Some(org.uuid.clone());
+    cipher.save(&mut conn).await.unwrap();
+
+    let client = client().await;
+    let cipher_uri = format!("ciphers/{}", cipher.uuid);
+    assert_eq!(send(&client, Method::Get, &cipher_uri, &owner_token, None).await.0, Status::Ok);
+    let uri = format!("organizations/{}/ownership-transfer", org.uuid);
+    assert_eq!(send(&client, Method::Post, &uri, &owner_token, start_body(&new_owner)).await.0, Status::Ok);
+
+    // Scheduling the deletion through the API cancels the transfer, so it's set directly to test the accept
+    let mut org = Organization::find_by_uuid(&org.uuid, &mut conn).await.unwrap();
+    org.deleted_at = Some(Utc::now().naive_utc());
+    org.save(&mut conn).await.unwrap();
+
+    let accept_uri = format!("{uri}/accept");
+    assert_eq!(send(&client, Method::Post, &accept_uri, &new_owner_token, accept_body()).await.0, Status::BadRequest);
+    assert_eq!(member_type(&new_owner, &mut conn).await, UserOrgType::User as i32);
+
+    let uri = format!("organizations/{}/collections", org.uuid);
+    assert_eq!(send(&client, Method::Get, &uri, &owner_token, None).await.0, Status::BadRequest);
+    let uri = format!("organizations/{}/export", org.uuid);
+    assert_eq!(send(&client, Method::Get, &uri, &owner_token, None).await.0, Status::BadRequest);
+
+    // The items of the organization can't be accessed directly either
+    assert_eq!(send(&client, Method::Get, &cipher_uri, &owner_token, None).await.0, Status::BadRequest);
+    assert_eq!(send(&client, Method::Delete, &cipher_uri, &owner_token, None).await.0, Status::BadRequest);
+    assert!(Cipher::find_by_uuid(&cipher.uuid, &mut conn).await.is_some());
+}
@@ -0,0 +1,69 @@
+use rocket::http::{Header, Method, Status};
+use serde_json::Value;
+
+use super::{client, conn, login, new_member, new_org, send};
+use crate::db::models::{Collection, CollectionUser, UserOrgStatus, UserOrgType};
+
+fn member_report<'a>(report: &'a Value, member_id: &str) -> &'a Value {
+    report["data"].as_array().unwrap().iter().find(|m| m["id"] == member_id).unwrap()
+}
+
+#[rocket::async_test]
+async fn test_member_access_report() {
+    let mut conn = conn().await;
+    let org = new_org(&mut conn).await;
+    let collection = Collection::new(org.uuid.clone(), String::from("2.encrypted|name"), None);
+    collection.save(&mut conn).await.unwrap();
+
+    let (admin, admin_org) = new_member(&org, UserOrgType::Admin, &mut conn).await;
+    let (user, user_org) = new_member(&org, UserOrgType::User, &mut conn).await;
+    CollectionUser::save(&user.uuid, &collection.uuid, true, false, false, &mut conn).await.unwrap();
+    let (revoked, mut revoked_org) = new_member(&org, UserOrgType::User, &mut conn).await;
+    CollectionUser::save(&revoked.uuid, &collection.uuid, false, false, false, &mut conn).await.unwrap();
+    revoked_org.revoke();
+    revoked_org.save(&mut conn).await.unwrap();
+
+    let client = client().await;
+    let token = login(&admin, &mut conn).await;
+    let uri = format!("organizations/{}/reports/member-access", org.uuid);
+    let (status, report) = send(&client, Method::Get, &uri, &token, None).await;
+    assert_eq!(status, Status::Ok);
+
+    // Admins have access to every collection
+    let admin_report = member_report(&report, &admin_org.uuid);
+    assert_eq!(admin_report["collections"][0]["allCollections"], true);
+
+    let user_report = member_report(&report, &user_org.uuid);
+    let collections = user_report["collections"].as_array().unwrap();
+    assert_eq!(collections.len(), 1);
+    assert_eq!(collections[0]["id"], collection.uuid.as_str());
+    assert_eq!(collections[0]["name"], "2.encrypted|name");
+    assert_eq!(collections[0]["readOnly"], true);
+    assert_eq!(collections[0]["direct"], true);
+
+    // Revoked members are listed without their collections
+    let revoked_report = member_report(&report, &revoked_org.uuid);
+    assert_eq!(revoked_report["status"], UserOrgStatus::Revoked as i32);
+    assert!(revoked_report["collections"].as_array().unwrap().is_empty());
+
+    let csv = client
+        .get(format!("/api/{uri}/csv"))
+        .header(Header::new("Authorization", format!("Bearer {token}")))
+        .dispatch()
+        .await
+        .into_string()
+        .await
+        .unwrap();
+    assert!(csv.contains(&format!(
+        "{},{},{},{},User,Confirmed,{},2.encrypted|name,true,false,false,direct,0\r\n",
+        user_org.uuid, user.uuid, user.name, user.email, collection.uuid
+    )));
+    assert!(csv.contains(&format!(
+        "{},{},{},{},User,Revoked,,,,,,,\r\n",
+        revoked_org.uuid, revoked.uuid, revoked.name, revoked.email
+    )));
+
+    // Only Owners and Admins can get the report
+    let token = login(&user, &mut conn).await;
+    assert_eq!(send(&client, Method::Get, &uri, &token, None).await.0, Status::Unauthorized);
+}
@@ -0,0 +1,103 @@
+use std::net::{IpAddr, Ipv4Addr};
+
+use chrono::{TimeDelta, Utc};
+use rocket::http::{Method, Status};
+use serde_json::json;
+
+use super::{client, conn, login, new_member, new_org, new_user, send};
+use crate::{
+    api::core::schedule_user_deletion,
+    db::{
+        models::{
+            DeviceType, EmergencyAccess, EmergencyAccessStatus, EmergencyAccessType, Send, SendType, User, UserOrgType,
+        },
+        DbConn,
+    },
+};
+
+const IP: IpAddr = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+
+async fn delete_user(user: &User, conn: &mut DbConn) -> bool {
+    let user = User::find_by_uuid(&user.uuid, conn).await.unwrap();
+    schedule_user_deletion(user, false, DeviceType::UnknownBrowser as i32, &IP, conn).await.is_ok()
+}
+
+#[rocket::async_test]
+async fn test_deleted_owner_can_be_purged() {
+    let mut conn = conn().await;
+    let org = new_org(&mut conn).await;
+    let (owner, _) = new_member(&org, UserOrgType::Owner, &mut conn).await;
+    let (other_owner, _) = new_member(&org, UserOrgType::Owner, &mut conn).await;
+
+    assert!(delete_user(&owner, &mut conn).await);
+    // The remaining owner is the last one now
+    assert!(!delete_user(&other_owner, &mut conn).await);
+
+    // The deleted owner doesn't count as an owner anymore, but it must not block its own purge
+    let deleted_owner = User::find_by_uuid(&owner.uuid, &mut conn).await.unwrap();
+    assert!(deleted_owner.is_deleted());
+    deleted_owner.delete(&mut conn).await.unwrap();
+    assert!(User::find_by_uuid(&owner.uuid, &mut conn).await.is_none());
+
+    let other_owner = User::find_by_uuid(&other_owner.uuid, &mut conn).await.unwrap();
+    assert!(other_owner.delete(&mut conn).await.is_err());
+}
+
+#[rocket::async_test]
+async fn test_deleted_user_sends_are_disabled() {
+    let mut conn = conn().await;
+    let user = new_user(&mut conn).await;
+    let deletion_date = Utc::now().naive_utc() + TimeDelta::try_days(1).unwrap();
+    let mut send_item =
+        Send::new(SendType::Text as i32, String::from("name"), String::from("{}"), String::from("key"), deletion_date);
+    send_item.user_uuid = Some(user.uuid.clone());
+    send_item.save(&mut conn).await.unwrap();
+    let access_id = send_item.to_json()["accessId"].as_str().unwrap().to_string();
+
+    let client = client().await;
+    let uri = format!("sends/access/{access_id}");
+    assert_eq!(send(&client, Method::Post, &uri, "", Some(json!({}))).await.0, Status::Ok);
+
+    assert!(delete_user(&user, &mut conn).await);
+    assert_eq!(send(&client, Method::Post, &uri, "", Some(json!({}))).await.0, Status::NotFound);
+}
+
+#[rocket::async_test]
+async fn test_deleted_user_emergency_access_is_disabled() {
+    let mut conn = conn().await;
+    let grantor = new_user(&mut conn).await;
+    let grantee = new_user(&mut conn).await;
+
+    let mut approved = EmergencyAccess::new(
+        grantor.uuid.clone(),
+        grantee.email.clone(),
+        EmergencyAccessStatus::RecoveryApproved as i32,
+        EmergencyAccessType::View as i32,
+        7,
+    );
+    approved.grantee_uuid = Some(grantee.uuid.clone());
+    approved.save(&mut conn).await.unwrap();
+
+    let mut initiated = EmergencyAccess::new(
+        grantor.uuid.clone(),
+        grantee.email.clone(),
+        EmergencyAccessStatus::RecoveryInitiated as i32,
+        EmergencyAccessType::Takeover as i32,
+        7,
+    );
+    initiated.grantee_uuid = Some(grantee.uuid.clone());
+    initiated.recovery_initiated_at = Some(Utc::now().naive_utc());
+    initiated.save(&mut conn).await.unwrap();
+
+    let client = client().await;
+    let token = login(&grantee, &mut conn).await;
+    let uri = format!("emergency-access/{}/view", approved.uuid);
+    assert_eq!(send(&client, Method::Post, &uri, &token, None).await.0, Status::Ok);
+    let is_initiated = |list: Vec<EmergencyAccess>| list.iter().any(|emer| emer.uuid == initiated.uuid);
+    assert!(is_initiated(EmergencyAccess::find_all_recoveries_initiated(&mut conn).await));
+
+    // The grantee can't view the vault anymore, and the jobs skip the recovery until the account is restored
+    assert!(delete_user(&grantor, &mut conn).await);
+    assert_eq!(send(&client, Method::Post, &uri, &token, None).await.0, Status::BadRequest);
+    assert!(!is_initiated(EmergencyAccess::find_all_recoveries_initiated(&mut conn).await));
+}
@@ -168,6 +168,11 @@ pub fn static_files(filename: &str) -> Result<(ContentType, &'static [u8]), Erro
         "admin_diagnostics.js" => {
             Ok((ContentType::JavaScript, include_bytes!("../static/scripts/admin_diagnostics.js")))
         }
+        "admin_jobs.js" => Ok((ContentType::JavaScript, include_bytes!("../static/scripts/admin_jobs.js"))),
+        "admin_connections.js" => {
+            Ok((ContentType::JavaScript, include_bytes!("../static/scripts/admin_connections.js")))
+        }
+        "admin_templates.js" => Ok((ContentType::JavaScript, include_bytes!("../static/scripts/admin_templates.js"))),
         "bootstrap.css" => Ok((ContentType::CSS, include_bytes!("../static/scripts/bootstrap.css"))),
         "bootstrap.bundle.js" => Ok((ContentType::JavaScript, include_bytes!("../static/scripts/bootstrap.bundle.js"))),
         "jdenticon-3.3.0.js" => Ok((ContentType::JavaScript, include_bytes!("../static/scripts/jdenticon-3.3.0.js"))),
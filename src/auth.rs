@@ -371,7 +371,9 @@ use rocket::{
 };
 
 use crate::db::{
-    models::{Collection, Device, User, UserOrgStatus, UserOrgType, UserOrganization, UserStampException},
+    models::{
+        Collection, Device, User, UserOrgPermissions, UserOrgStatus, UserOrgType, UserOrganization, UserStampException,
+    },
     DbConn,
 };
 
@@ -419,6 +421,7 @@ impl<'r> FromRequest<'r> for Host {
 pub struct ClientHeaders {
     pub device_type: i32,
     pub ip: ClientIp,
+    pub locale: Option<String>,
 }
 
 #[rocket::async_trait]
@@ -434,9 +437,12 @@ impl<'r> FromRequest<'r> for ClientHeaders {
         let device_type: i32 =
             request.headers().get_one("device-type").map(|d| d.parse().unwrap_or(14)).unwrap_or_else(|| 14);
 
+        let locale = request.headers().get_one("Accept-Language").and_then(crate::util::parse_accept_language);
+
         Outcome::Success(ClientHeaders {
             device_type,
             ip,
+            locale,
         })
     }
 }
@@ -610,12 +616,12 @@ impl<'r> FromRequest<'r> for OrgHeaders {
     }
 }
 
+/// Only allows Admins and Owners. The routes which need the member type or the client version use the
+/// permission based guards from `org_permission_headers!` instead, as Custom roles can call those as well.
 pub struct AdminHeaders {
     pub host: String,
     pub device: Device,
     pub user: User,
-    pub org_user_type: UserOrgType,
-    pub client_version: Option<String>,
     pub ip: ClientIp,
 }
 
@@ -625,14 +631,11 @@ impl<'r> FromRequest<'r> for AdminHeaders {
 
     async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
         let headers = try_outcome!(OrgHeaders::from_request(request).await);
-        let client_version = request.headers().get_one("Bitwarden-Client-Version").map(String::from);
         if headers.org_user_type >= UserOrgType::Admin {
             Outcome::Success(Self {
                 host: headers.host,
                 device: headers.device,
                 user: headers.user,
-                org_user_type: headers.org_user_type,
-                client_version,
                 ip: headers.ip,
             })
         } else {
@@ -652,6 +655,88 @@ impl From<AdminHeaders> for Headers {
     }
 }
 
+impl OrgHeaders {
+    /// Checks if the member has at least the given type, or has a Custom role which grants the permission
+    fn has_type_or_permission(&self, min_type: UserOrgType, permission: fn(&UserOrgPermissions) -> bool) -> bool {
+        self.org_user_type >= min_type
+            || (self.org_user_type == UserOrgType::Custom && permission(&self.org_user.permissions()))
+    }
+}
+
+/// Generates request guards which allow Admins and Owners, and members with a Custom role having a specific permission.
+/// They have the same fields as AdminHeaders, so they can be used in the same way.
+macro_rules! org_permission_headers {
+    ($( $(#[doc = $doc:literal])* $name:ident: $min_type:expr, $permission:expr, $err:literal; )+) => {
+        $(
+            $(#[doc = $doc])*
+            pub struct $name {
+                pub host: String,
+                pub device: Device,
+                pub user: User,
+                #[allow(dead_code)]
+                pub org_user_type: UserOrgType,
+                #[allow(dead_code)]
+                pub org_user: UserOrganization,
+                #[allow(dead_code)]
+                pub client_version: Option<String>,
+                pub ip: ClientIp,
+            }
+
+            #[rocket::async_trait]
+            impl<'r> FromRequest<'r> for $name {
+                type Error = &'static str;
+
+                async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+                    let headers = try_outcome!(OrgHeaders::from_request(request).await);
+                    let client_version = request.headers().get_one("Bitwarden-Client-Version").map(String::from);
+                    if headers.has_type_or_permission($min_type, $permission) {
+                        Outcome::Success(Self {
+                            host: headers.host,
+                            device: headers.device,
+                            user: headers.user,
+                            org_user_type: headers.org_user_type,
+                            org_user: headers.org_user,
+                            client_version,
+                            ip: headers.ip,
+                        })
+                    } else {
+                        err_handler!($err)
+                    }
+                }
+            }
+
+            impl From<$name> for Headers {
+                fn from(h: $name) -> Headers {
+                    Headers {
+                        host: h.host,
+                        device: h.device,
+                        user: h.user,
+                        ip: h.ip,
+                    }
+                }
+            }
+        )+
+    };
+}
+
+org_permission_headers! {
+    /// Used to view the members and groups of an organization, which Managers and all Custom roles need
+    ViewMembersHeaders: UserOrgType::Manager, UserOrgPermissions::has_any,
+        "You need to be a Manager, Admin or Owner, or have a Custom role with any permission to call this endpoint";
+    ManageUsersHeaders: UserOrgType::Admin, |p| p.manage_users,
+        "You need to be Admin or Owner, or have permission to manage users to call this endpoint";
+    ManageGroupsHeaders: UserOrgType::Admin, |p| p.manage_groups,
+        "You need to be Admin or Owner, or have permission to manage groups to call this endpoint";
+    ManagePoliciesHeaders: UserOrgType::Admin, |p| p.manage_policies,
+        "You need to be Admin or Owner, or have permission to manage policies to call this endpoint";
+    EventLogHeaders: UserOrgType::Admin, |p| p.access_event_logs,
+        "You need to be Admin or Owner, or have permission to access event logs to call this endpoint";
+    ImportExportHeaders: UserOrgType::Admin, |p| p.access_import_export,
+        "You need to be Admin or Owner, or have permission to import and export to call this endpoint";
+    AccountRecoveryHeaders: UserOrgType::Admin, |p| p.manage_reset_password,
+        "You need to be Admin or Owner, or have permission to manage account recovery to call this endpoint";
+}
+
 // col_id is usually the fourth path param ("/organizations/<org_id>/collections/<col_id>"),
 // but there could be cases where it is a query value.
 // First check the path, if this is not a valid uuid, try the query values.
@@ -671,7 +756,7 @@ fn get_col_id(request: &Request<'_>) -> Option<String> {
     None
 }
 
-/// The ManagerHeaders are used to check if you are at least a Manager
+/// The ManagerHeaders are used to check if you are at least a Manager (or have a Custom role which can manage all collections)
 /// and have access to the specific collection provided via the <col_id>/collections/collectionId.
 /// This does strict checking on the collection_id, ManagerHeadersLoose does not.
 pub struct ManagerHeaders {
@@ -687,7 +772,7 @@ impl<'r> FromRequest<'r> for ManagerHeaders {
 
     async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
         let headers = try_outcome!(OrgHeaders::from_request(request).await);
-        if headers.org_user_type >= UserOrgType::Manager {
+        if headers.has_type_or_permission(UserOrgType::Manager, |p| p.manage_all_collections) {
             match get_col_id(request) {
                 Some(col_id) => {
                     let mut conn = match DbConn::from_request(request).await {
@@ -725,6 +810,55 @@ impl From<ManagerHeaders> for Headers {
     }
 }
 
+/// The CollectionManagerHeaders are used to check if you can manage the collection provided via <col_id>/collectionId.
+/// Next to Managers with access to the collection, this allows members who got the `manage` flag for it.
+pub struct CollectionManagerHeaders {
+    pub host: String,
+    pub device: Device,
+    pub user: User,
+    pub ip: ClientIp,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for CollectionManagerHeaders {
+    type Error = &'static str;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let headers = try_outcome!(OrgHeaders::from_request(request).await);
+        match get_col_id(request) {
+            Some(col_id) => {
+                let mut conn = match DbConn::from_request(request).await {
+                    Outcome::Success(conn) => conn,
+                    _ => err_handler!("Error getting DB"),
+                };
+
+                if !Collection::can_manage_collection(&headers.org_user, &col_id, &mut conn).await {
+                    err_handler!("The current user can't manage this collection")
+                }
+            }
+            _ => err_handler!("Error getting the collection id"),
+        }
+
+        Outcome::Success(Self {
+            host: headers.host,
+            device: headers.device,
+            user: headers.user,
+            ip: headers.ip,
+        })
+    }
+}
+
+impl From<CollectionManagerHeaders> for Headers {
+    fn from(h: CollectionManagerHeaders) -> Headers {
+        Headers {
+            host: h.host,
+            device: h.device,
+            user: h.user,
+            ip: h.ip,
+        }
+    }
+}
+
 /// The ManagerHeadersLoose is used when you at least need to be a Manager,
 /// but there is no collection_id sent with the request (either in the path or as form data).
 pub struct ManagerHeadersLoose {
@@ -741,7 +875,7 @@ impl<'r> FromRequest<'r> for ManagerHeadersLoose {
 
     async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
         let headers = try_outcome!(OrgHeaders::from_request(request).await);
-        if headers.org_user_type >= UserOrgType::Manager {
+        if headers.has_type_or_permission(UserOrgType::Manager, |p| p.manage_all_collections) {
             Outcome::Success(Self {
                 host: headers.host,
                 device: headers.device,
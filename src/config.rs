@@ -238,6 +238,7 @@ macro_rules! make_config {
                     "domain_path",
                     "domain",
                     "helo_name",
+                    "mail_api_url",
                     "org_creation_users",
                     "signups_domains_whitelist",
                     "smtp_from",
@@ -377,6 +378,19 @@ make_config! {
     ws {
         /// Enable websocket notifications
         enable_websocket:       bool,   false,  def,    true;
+        /// Max WebSocket connections |> Maximum number of WebSocket connections of logged in users to this instance, further connections are rejected. Set to 0 for no limit.
+        ws_max_connections:     u32,    false,  def,    0;
+        /// Max WebSocket connections per user |> Maximum number of WebSocket connections per user to this instance, further connections are rejected. Set to 0 for no limit.
+        ws_max_connections_per_user: u32, false, def,   0;
+        /// WebSocket broadcast backend |> How WebSocket notifications reach clients connected to other instances which share the same database.
+        /// `none` only notifies the clients connected to this instance. `database` stores the notifications in the database, from where the other instances read them.
+        /// On PostgreSQL the other instances are notified through LISTEN/NOTIFY, on MySQL and SQLite they poll the database.
+        ws_broadcast_backend:   String, false,  def,    "none".to_string();
+        /// WebSocket broadcast poll interval |> How often (in ms) the `database` broadcast backend polls for notifications sent by other instances on MySQL and SQLite.
+        ws_broadcast_poll_interval_ms: u64, false, def, 1000;
+        /// Notification batch window |> When a user's ciphers change in bulk, e.g. during an import, only the first change within this window (in ms) is notified right away.
+        /// The rest is merged into a single sync message for WebSockets and push once the window closes. Set to 0 to notify every change separately.
+        notification_batch_window_ms: u64, true, def,   2000;
     },
     push {
         /// Enable push notifications
@@ -418,6 +432,30 @@ make_config! {
         /// Duo Auth context cleanup schedule |> Cron schedule of the job that cleans expired Duo contexts from the database. Does nothing if Duo MFA is disabled or set to use the legacy iframe prompt.
         /// Defaults to once every minute. Set blank to disable this job.
         duo_context_purge_schedule:   String, false,  def,    "30 * * * * *".to_string();
+        /// Invitation cleanup schedule |> Cron schedule of the job that removes expired organization invitations. Does nothing unless `EXPIRED_INVITATIONS_PURGE_DAYS` is set.
+        /// Defaults to hourly (20 minutes after the hour). Set blank to disable this job.
+        invitation_cleanup_schedule: String, false, def,   "0 20 * * * *".to_string();
+        /// Account lifecycle schedule |> Cron schedule of the job that warns, disables and deletes unverified or inactive accounts.
+        /// Does nothing unless `UNVERIFIED_ACCOUNT_DAYS` or `INACTIVE_ACCOUNT_DAYS` is set. Defaults to daily. Set blank to disable this job.
+        account_lifecycle_schedule: String, false, def,    "0 20 2 * * *".to_string();
+        /// Security digest schedule |> Cron schedule of the job that sends the weekly security digest to the users who opted in to it.
+        /// Only runs when mail is enabled. Defaults to Mondays at 08:00. Set blank to disable this job.
+        security_digest_schedule: String, false, def,      "0 0 8 * * Mon".to_string();
+        /// Organization domain verification schedule |> Cron schedule of the job that checks the DNS TXT records of the organization domains which are not verified yet.
+        /// Each domain is checked at most 72 times, after that it can only be verified manually.
+        /// Defaults to hourly (40 minutes after the hour). Set blank to disable this job.
+        org_domain_verification_schedule: String, false, def, "0 40 * * * *".to_string();
+        /// Organization deletion purge schedule |> Cron schedule of the job that permanently deletes the organizations whose deletion grace period has passed.
+        /// Defaults to hourly (50 minutes after the hour). Set blank to disable this job.
+        org_deletion_purge_schedule: String, false, def, "0 50 * * * *".to_string();
+        /// User deletion purge schedule |> Cron schedule of the job that permanently deletes the accounts whose deletion retention period has passed.
+        /// Defaults to hourly (55 minutes after the hour). Set blank to disable this job.
+        user_deletion_purge_schedule: String, false, def, "0 55 * * * *".to_string();
+        /// Job history retention (days) |> Number of days to keep the run history of the scheduled jobs, as shown on the admin Jobs page.
+        job_history_days_retain:   i64,    false,  def,    7;
+        /// Job lease duration (seconds) |> When multiple instances share the same database, each job execution is claimed by a single instance using a lease stored in the database.
+        /// This is the maximum time a lease is held. If an instance stops while running a job, other instances can only run that job again once the lease has expired, so it should be longer than the longest running job.
+        job_lease_seconds:         u64,    false,  def,    3600;
     },
 
     /// General settings
@@ -485,6 +523,9 @@ make_config! {
         /// Invitation token expiration time (in hours) |> The number of hours after which an organization invite token, emergency access invite token,
         /// email verification token and deletion request token will expire (must be at least 1)
         invitation_expiration_hours: u32, false, def, 120;
+        /// Expired invitations purge (days) |> Number of days after their expiration after which organization invitations which were never accepted are removed.
+        /// If unset, expired invitations are kept and shown as expired, so they can be re-sent.
+        expired_invitations_purge_days: i64, true, option;
         /// Enable emergency access |> Controls whether users can enable emergency access to their accounts. This setting applies globally to all users.
         emergency_access_allowed:    bool,   true,   def,    true;
         /// Allow email change |> Controls whether users can change their email. This setting applies globally to all users.
@@ -498,6 +539,9 @@ make_config! {
         /// if SMTP service is not configured. Not recommended for publicly-accessible instances as this
         /// provides unauthenticated access to potentially sensitive data.
         show_password_hint:     bool,   true,   def,    false;
+        /// Require encrypted password hints |> Only mail password hints to users who uploaded an OpenPGP public key.
+        /// Other users are told that a hint exists, but not what it is.
+        password_hints_require_pgp: bool, true,  def,    false;
 
         /// Admin token/Argon2 PHC |> The plain text token or Argon2 PHC string used to authenticate in this very same page. Changing it here will not deauthorize the current session!
         admin_token:            Pass,   true,   option;
@@ -507,6 +551,23 @@ make_config! {
 
         /// Events days retain |> Number of days to retain events stored in the database. If unset, events are kept indefinitely.
         events_days_retain:     i64,    false,   option;
+
+        /// Unverified account days |> Number of days after which an account with an unverified email address is considered stale,
+        /// and gets warned, disabled and optionally deleted by the account lifecycle job. Only used when mail is enabled. If unset, unverified accounts are kept.
+        unverified_account_days: i64,   true,   option;
+        /// Inactive account days |> Number of days without any activity after which an account is considered stale,
+        /// and gets warned, disabled and optionally deleted by the account lifecycle job. Only used when mail is enabled. If unset, inactive accounts are kept.
+        inactive_account_days:  i64,    true,   option;
+        /// Account lifecycle warning days |> Number of days between the warning email and disabling a stale account.
+        account_lifecycle_warning_days: i64, true, def, 14;
+        /// Account lifecycle delete days |> Number of days after which an account disabled by the account lifecycle job is deleted. If unset, these accounts are never deleted.
+        account_lifecycle_delete_days: i64, true, option;
+        /// Organization deletion grace days |> Number of days a deleted organization is kept before it is permanently deleted, during which its owners can restore it.
+        /// Set to 0 to delete organizations immediately.
+        org_deletion_grace_days: i64,   true,   def,    7;
+        /// User deletion retention days |> Number of days a deleted account is kept before it is permanently deleted, during which an admin can restore it.
+        /// Set to 0 to delete accounts immediately.
+        user_deletion_retention_days: i64, true, def,   7;
     },
 
     /// Advanced settings
@@ -552,6 +613,10 @@ make_config! {
         /// Useful to secure your internal environment: See https://en.wikipedia.org/wiki/Reserved_IP_addresses for a list of IPs which it will block
         http_request_block_non_global_ips:  bool,   true,   auto, |c| c.icon_blacklist_non_global_ips;
 
+        /// Domain verification DNS server |> The DNS server used to look up the TXT records of organization domains, as `ip` or `ip:port`.
+        /// If unset, the DNS servers of the system are used.
+        org_domain_dns_server:  String, true,   option;
+
         /// Disable Two-Factor remember |> Enabling this would force the users to use a second factor to login every time.
         /// Note that the checkbox would still be present, but ignored.
         disable_2fa_remember:   bool,   true,   def,    false;
@@ -663,6 +728,10 @@ make_config! {
         use_sendmail:                  bool,   true,   def,     false;
         /// Sendmail Command |> Which sendmail command to use. The one found in the $PATH is used if not specified.
         sendmail_command:              String, true,   option;
+        /// Mail API URL |> Send mails as a JSON POST request to this HTTP mail API instead of using SMTP or sendmail. The mails are not DKIM signed.
+        mail_api_url:                  String, true,   option;
+        /// Mail API token |> Bearer token sent in the Authorization header of the requests to the mail API
+        mail_api_token:                Pass,   true,   option;
         /// Host
         smtp_host:                     String, true,   option;
         /// DEPRECATED smtp_ssl |> DEPRECATED - Please use SMTP_SECURITY
@@ -683,12 +752,28 @@ make_config! {
         smtp_password:                 Pass,   true,   option;
         /// SMTP Auth mechanism |> Defaults for SSL is "Plain" and "Login" and nothing for Non-SSL connections. Possible values: ["Plain", "Login", "Xoauth2"]. Multiple options need to be separated by a comma ','.
         smtp_auth_mechanism:           String, true,   option;
+        /// SMTP OAuth2 token URL |> The OAuth2 token endpoint of the mail provider. When set, SMTP authenticates with XOAUTH2 using SMTP_USERNAME and an access token instead of SMTP_PASSWORD.
+        smtp_oauth2_token_url:         String, true,   option;
+        /// SMTP OAuth2 client ID
+        smtp_oauth2_client_id:         String, true,   option;
+        /// SMTP OAuth2 client secret
+        smtp_oauth2_client_secret:     Pass,   true,   option;
+        /// SMTP OAuth2 refresh token |> When set, access tokens are obtained with the refresh token grant, otherwise with the client credentials grant
+        smtp_oauth2_refresh_token:     Pass,   true,   option;
+        /// SMTP OAuth2 scope |> The scope to request, for example "https://outlook.office365.com/.default" or "https://mail.google.com/"
+        smtp_oauth2_scope:             String, true,   option;
         /// SMTP connection timeout |> Number of seconds when to stop trying to connect to the SMTP server
         smtp_timeout:                  u64,    true,   def,     15;
         /// Server name sent during HELO |> By default this value should be is on the machine's hostname, but might need to be changed in case it trips some anti-spam filters
         helo_name:                     String, true,   option;
         /// Embed images as email attachments.
         smtp_embed_images:             bool, true, def, true;
+        /// DKIM selector |> The selector under which the DKIM public key is published, as `<selector>._domainkey.<domain>`. Setting it together with a private key file enables DKIM signing for both SMTP and sendmail.
+        smtp_dkim_selector:            String, true,   option;
+        /// DKIM domain |> The domain the mails are signed for. Defaults to the domain of the From Address.
+        smtp_dkim_domain:              String, true,   option;
+        /// DKIM private key file |> Path to the PEM file with the RSA or Ed25519 private key used to sign the mails
+        smtp_dkim_private_key:         String, true,   option;
         /// _smtp_img_src
         _smtp_img_src:                 String, false, gen, |c| generate_smtp_img_src(c.smtp_embed_images, &c.domain);
         /// Enable SMTP debugging (Know the risks!) |> DANGEROUS: Enabling this will output very detailed SMTP messages. This could contain sensitive information like passwords and usernames! Only enable this during troubleshooting!
@@ -702,7 +787,7 @@ make_config! {
     /// Email 2FA Settings
     email_2fa: _enable_email_2fa {
         /// Enabled |> Disabling will prevent users from setting up new email 2FA and using existing email 2FA configured
-        _enable_email_2fa:      bool,   true,   auto,    |c| c._enable_smtp && (c.smtp_host.is_some() || c.use_sendmail);
+        _enable_email_2fa:      bool,   true,   auto,    |c| c._enable_smtp && (c.smtp_host.is_some() || c.use_sendmail || c.mail_api_url.is_some());
         /// Email token size |> Number of digits in an email 2FA token (min: 6, max: 255). Note that the Bitwarden clients are hardcoded to mention 6 digit codes regardless of this setting.
         email_token_size:       u8,     true,   def,      6;
         /// Token expiration time |> Maximum time in seconds a token is valid. The time the user has to open email client and copy token.
@@ -714,6 +799,22 @@ make_config! {
         /// Auto-enable 2FA (Know the risks!) |> Automatically setup email 2FA as fallback provider when needed
         email_2fa_auto_fallback: bool,  true,   def,      false;
     },
+
+    /// Notification defaults
+    notifications {
+        /// New device logins |> Send a mail when a new device logs in, for users who have not changed this in their notification preferences. Ignored when REQUIRE_DEVICE_EMAIL is enabled, in which case the mail is always sent.
+        notify_new_device_logged_in:       bool, true, def, true;
+        /// Incomplete 2FA logins |> Send a mail when a login was not completed with a second factor, for users who have not changed this in their notification preferences
+        notify_incomplete_2fa_login:       bool, true, def, true;
+        /// Organization membership updates |> Send a mail when an invited user joins an organization or a membership gets confirmed, for users who have not changed this in their notification preferences
+        notify_org_membership_updates:     bool, true, def, true;
+        /// Emergency access updates |> Send a mail when emergency access invites are accepted or confirmed and recoveries are approved or rejected, for users who have not changed this in their notification preferences
+        notify_emergency_access_updates:   bool, true, def, true;
+        /// Emergency access reminders |> Send a reminder mail the day before a pending emergency access recovery is granted automatically, for users who have not changed this in their notification preferences
+        notify_emergency_access_reminders: bool, true, def, true;
+        /// Weekly security digest |> Send the weekly security digest, for users who have not changed this in their notification preferences. Also needs SECURITY_DIGEST_SCHEDULE.
+        notify_security_digest:            bool, true, def, false;
+    },
 }
 
 fn validate_config(cfg: &ConfigItems) -> Result<(), Error> {
@@ -769,6 +870,16 @@ fn validate_config(cfg: &ConfigItems) -> Result<(), Error> {
         }
     }
 
+    match cfg.ws_broadcast_backend.as_str() {
+        "none" => (),
+        "database" => {
+            if cfg.ws_broadcast_poll_interval_ms < 100 {
+                err!("`WS_BROADCAST_POLL_INTERVAL_MS` has a minimum of 100 ms")
+            }
+        }
+        _ => err!("`WS_BROADCAST_BACKEND` must be either 'none' or 'database'"),
+    }
+
     if cfg.push_enabled && (cfg.push_installation_id == String::new() || cfg.push_installation_key == String::new()) {
         err!(
             "Misconfigured Push Notification service\n\
@@ -892,26 +1003,67 @@ fn validate_config(cfg: &ConfigItems) -> Result<(), Error> {
                     }
                 }
             }
-        } else {
+        }
+
+        if let Some(api_url) = &cfg.mail_api_url {
+            if cfg.use_sendmail {
+                err!("`MAIL_API_URL` and `USE_SENDMAIL` can't be used at the same time")
+            }
+            if cfg.smtp_host.is_some() {
+                err!("`MAIL_API_URL` and `SMTP_HOST` can't be used at the same time")
+            }
+            match Url::parse(api_url) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") => (),
+                _ => err!("`MAIL_API_URL` needs to be a valid http or https URL"),
+            }
+            if cfg.smtp_from.is_empty() {
+                err!("`SMTP_FROM` needs to be set to send mail through the mail API")
+            }
+            if cfg.smtp_dkim_selector.is_some() {
+                err!("DKIM signing is not supported with `MAIL_API_URL`, the mail API needs to sign the mails instead")
+            }
+        } else if !cfg.use_sendmail {
             if cfg.smtp_host.is_some() == cfg.smtp_from.is_empty() {
                 err!("Both `SMTP_HOST` and `SMTP_FROM` need to be set for email support without `USE_SENDMAIL`")
             }
 
-            if cfg.smtp_username.is_some() != cfg.smtp_password.is_some() {
+            if cfg.smtp_oauth2_token_url.is_none() && cfg.smtp_username.is_some() != cfg.smtp_password.is_some() {
                 err!("Both `SMTP_USERNAME` and `SMTP_PASSWORD` need to be set to enable email authentication without `USE_SENDMAIL`")
             }
         }
 
-        if (cfg.smtp_host.is_some() || cfg.use_sendmail) && !cfg.smtp_from.contains('@') {
+        if (cfg.smtp_host.is_some() || cfg.use_sendmail || cfg.mail_api_url.is_some()) && !cfg.smtp_from.contains('@') {
             err!("SMTP_FROM does not contain a mandatory @ sign")
         }
 
+        if let Some(token_url) = &cfg.smtp_oauth2_token_url {
+            if Url::parse(token_url).is_err() {
+                err!("`SMTP_OAUTH2_TOKEN_URL` needs to be a valid URL")
+            }
+            if cfg.smtp_username.is_none() || cfg.smtp_oauth2_client_id.is_none() {
+                err!("Both `SMTP_USERNAME` and `SMTP_OAUTH2_CLIENT_ID` need to be set to use SMTP OAuth2")
+            }
+            if cfg.smtp_oauth2_refresh_token.is_none() && cfg.smtp_oauth2_client_secret.is_none() {
+                err!("`SMTP_OAUTH2_CLIENT_SECRET` needs to be set to use SMTP OAuth2 without a refresh token")
+            }
+        }
+
+        match (&cfg.smtp_dkim_selector, &cfg.smtp_dkim_private_key) {
+            (Some(_), Some(key_file)) => {
+                if let Err(e) = crate::mail::read_dkim_signing_key(key_file) {
+                    err!(format!("`SMTP_DKIM_PRIVATE_KEY` is invalid: {e:?}"))
+                }
+            }
+            (None, None) => (),
+            _ => err!("Both `SMTP_DKIM_SELECTOR` and `SMTP_DKIM_PRIVATE_KEY` need to be set to enable DKIM signing"),
+        }
+
         if cfg._enable_email_2fa && cfg.email_token_size < 6 {
             err!("`EMAIL_TOKEN_SIZE` has a minimum size of 6")
         }
     }
 
-    if cfg._enable_email_2fa && !(cfg.smtp_host.is_some() || cfg.use_sendmail) {
+    if cfg._enable_email_2fa && !(cfg.smtp_host.is_some() || cfg.use_sendmail || cfg.mail_api_url.is_some()) {
         err!("To enable email 2FA, a mail transport must be configured")
     }
 
@@ -958,36 +1110,62 @@ fn validate_config(cfg: &ConfigItems) -> Result<(), Error> {
     }
 
     // Validate schedule crontab format
-    if !cfg.send_purge_schedule.is_empty() && cfg.send_purge_schedule.parse::<Schedule>().is_err() {
-        err!("`SEND_PURGE_SCHEDULE` is not a valid cron expression")
+    let schedules = [
+        ("SEND_PURGE_SCHEDULE", &cfg.send_purge_schedule),
+        ("TRASH_PURGE_SCHEDULE", &cfg.trash_purge_schedule),
+        ("INCOMPLETE_2FA_SCHEDULE", &cfg.incomplete_2fa_schedule),
+        ("EMERGENCY_NOTIFICATION_REMINDER_SCHEDULE", &cfg.emergency_notification_reminder_schedule),
+        ("EMERGENCY_REQUEST_TIMEOUT_SCHEDULE", &cfg.emergency_request_timeout_schedule),
+        ("EVENT_CLEANUP_SCHEDULE", &cfg.event_cleanup_schedule),
+        ("ACCOUNT_LIFECYCLE_SCHEDULE", &cfg.account_lifecycle_schedule),
+        ("INVITATION_CLEANUP_SCHEDULE", &cfg.invitation_cleanup_schedule),
+        ("SECURITY_DIGEST_SCHEDULE", &cfg.security_digest_schedule),
+        ("ORG_DOMAIN_VERIFICATION_SCHEDULE", &cfg.org_domain_verification_schedule),
+        ("ORG_DELETION_PURGE_SCHEDULE", &cfg.org_deletion_purge_schedule),
+        ("USER_DELETION_PURGE_SCHEDULE", &cfg.user_deletion_purge_schedule),
+        ("AUTH_REQUEST_PURGE_SCHEDULE", &cfg.auth_request_purge_schedule),
+        ("DUO_CONTEXT_PURGE_SCHEDULE", &cfg.duo_context_purge_schedule),
+    ];
+    for (name, schedule) in schedules {
+        if let Err(e) = validate_schedule(schedule) {
+            err!(format!("`{name}` is not a valid cron expression: {e}"))
+        }
     }
 
-    if !cfg.trash_purge_schedule.is_empty() && cfg.trash_purge_schedule.parse::<Schedule>().is_err() {
-        err!("`TRASH_PURGE_SCHEDULE` is not a valid cron expression")
+    if let Some(dns_server) = &cfg.org_domain_dns_server {
+        if dns_server.parse::<std::net::SocketAddr>().is_err() && dns_server.parse::<std::net::IpAddr>().is_err() {
+            err!("`ORG_DOMAIN_DNS_SERVER` must be an IP address, optionally followed by a port")
+        }
     }
 
-    if !cfg.incomplete_2fa_schedule.is_empty() && cfg.incomplete_2fa_schedule.parse::<Schedule>().is_err() {
-        err!("`INCOMPLETE_2FA_SCHEDULE` is not a valid cron expression")
+    if cfg.job_history_days_retain < 1 {
+        err!("`JOB_HISTORY_DAYS_RETAIN` has a minimum of 1 day")
     }
 
-    if !cfg.emergency_notification_reminder_schedule.is_empty()
-        && cfg.emergency_notification_reminder_schedule.parse::<Schedule>().is_err()
-    {
-        err!("`EMERGENCY_NOTIFICATION_REMINDER_SCHEDULE` is not a valid cron expression")
+    for (name, days) in [
+        ("UNVERIFIED_ACCOUNT_DAYS", cfg.unverified_account_days),
+        ("INACTIVE_ACCOUNT_DAYS", cfg.inactive_account_days),
+        ("ACCOUNT_LIFECYCLE_WARNING_DAYS", Some(cfg.account_lifecycle_warning_days)),
+        ("ACCOUNT_LIFECYCLE_DELETE_DAYS", cfg.account_lifecycle_delete_days),
+        ("EXPIRED_INVITATIONS_PURGE_DAYS", cfg.expired_invitations_purge_days),
+    ] {
+        if let Some(days) = days {
+            if !(1..=36_500).contains(&days) {
+                err!(format!("`{name}` must be between 1 and 36500 days"))
+            }
+        }
     }
 
-    if !cfg.emergency_request_timeout_schedule.is_empty()
-        && cfg.emergency_request_timeout_schedule.parse::<Schedule>().is_err()
-    {
-        err!("`EMERGENCY_REQUEST_TIMEOUT_SCHEDULE` is not a valid cron expression")
+    if !(0..=36_500).contains(&cfg.org_deletion_grace_days) {
+        err!("`ORG_DELETION_GRACE_DAYS` must be between 0 and 36500 days")
     }
 
-    if !cfg.event_cleanup_schedule.is_empty() && cfg.event_cleanup_schedule.parse::<Schedule>().is_err() {
-        err!("`EVENT_CLEANUP_SCHEDULE` is not a valid cron expression")
+    if !(0..=36_500).contains(&cfg.user_deletion_retention_days) {
+        err!("`USER_DELETION_RETENTION_DAYS` must be between 0 and 36500 days")
     }
 
-    if !cfg.auth_request_purge_schedule.is_empty() && cfg.auth_request_purge_schedule.parse::<Schedule>().is_err() {
-        err!("`AUTH_REQUEST_PURGE_SCHEDULE` is not a valid cron expression")
+    if cfg.job_lease_seconds < 60 {
+        err!("`JOB_LEASE_SECONDS` has a minimum of 60 seconds")
     }
 
     if !cfg.disable_admin_token {
@@ -1015,6 +1193,14 @@ fn validate_config(cfg: &ConfigItems) -> Result<(), Error> {
     Ok(())
 }
 
+/// Validates a job schedule, an empty schedule disables the job and is always valid.
+fn validate_schedule(schedule: &str) -> Result<(), String> {
+    if schedule.is_empty() {
+        return Ok(());
+    }
+    schedule.parse::<Schedule>().map(|_| ()).map_err(|e| e.to_string())
+}
+
 /// Extracts an RFC 6454 web origin from a URL.
 fn extract_url_origin(url: &str) -> String {
     match Url::parse(url) {
@@ -1224,7 +1410,7 @@ impl Config {
     }
     pub fn mail_enabled(&self) -> bool {
         let inner = &self.inner.read().unwrap().config;
-        inner._enable_smtp && (inner.smtp_host.is_some() || inner.use_sendmail)
+        inner._enable_smtp && (inner.smtp_host.is_some() || inner.use_sendmail || inner.mail_api_url.is_some())
     }
 
     pub fn get_duo_akey(&self) -> String {
@@ -1255,15 +1441,85 @@ impl Config {
         &self,
         name: &str,
         data: &T,
+    ) -> Result<String, crate::error::Error> {
+        self.render_localized_template(name, None, data)
+    }
+
+    /// Renders a template in the given locale, if a translation exists in the templates folder.
+    ///
+    /// For a template like `email/welcome` and the locale `pt-BR`, this tries `email/pt-BR/welcome`,
+    /// then `email/pt/welcome`, and falls back to the default English `email/welcome`.
+    pub fn render_localized_template<T: serde::ser::Serialize>(
+        &self,
+        name: &str,
+        locale: Option<&str>,
+        data: &T,
     ) -> Result<String, crate::error::Error> {
         if CONFIG.reload_templates() {
             warn!("RELOADING TEMPLATES");
             let hb = load_templates(CONFIG.templates_folder());
-            hb.render(name, data).map_err(Into::into)
+            hb.render(&localized_template_name(&hb, name, locale), data).map_err(Into::into)
         } else {
             let hb = &CONFIG.inner.read().unwrap().templates;
-            hb.render(name, data).map_err(Into::into)
+            hb.render(&localized_template_name(hb, name, locale), data).map_err(Into::into)
+        }
+    }
+
+    /// Renders `render_name` after registering `source` as the template `name`, without changing the loaded templates.
+    /// This is used to validate and preview template changes made in the admin panel.
+    pub fn render_template_with_source<T: serde::ser::Serialize>(
+        &self,
+        name: &str,
+        source: &str,
+        render_name: &str,
+        data: &T,
+    ) -> Result<String, crate::error::Error> {
+        let mut hb = if CONFIG.reload_templates() {
+            load_templates(CONFIG.templates_folder())
+        } else {
+            CONFIG.inner.read().unwrap().templates.clone()
+        };
+        let invalid = |e: &dyn std::fmt::Display| {
+            let msg = format!("Invalid template: {e}");
+            crate::error::Error::new(msg.clone(), msg)
+        };
+        hb.register_template_string(name, source).map_err(|e| invalid(&e))?;
+        hb.render(render_name, data).map_err(|e| invalid(&e))
+    }
+
+    fn template_override_path(&self, name: &str) -> std::path::PathBuf {
+        std::path::Path::new(&self.templates_folder()).join(format!("{name}.hbs"))
+    }
+
+    /// Returns the source of the template override in the templates folder, if there is one.
+    pub fn template_override(&self, name: &str) -> Option<String> {
+        std::fs::read_to_string(self.template_override_path(name)).ok()
+    }
+
+    /// Writes a template override into the templates folder and reloads the templates.
+    pub fn save_template_override(&self, name: &str, source: &str) -> Result<(), crate::error::Error> {
+        let path = self.template_override_path(name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
         }
+        std::fs::write(path, source)?;
+        self.refresh_templates();
+        Ok(())
+    }
+
+    /// Removes a template override from the templates folder and reloads the templates.
+    pub fn remove_template_override(&self, name: &str) -> Result<(), crate::error::Error> {
+        let path = self.template_override_path(name);
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        self.refresh_templates();
+        Ok(())
+    }
+
+    fn refresh_templates(&self) {
+        let templates = load_templates(self.templates_folder());
+        self.inner.write().unwrap().templates = templates;
     }
 
     pub fn set_rocket_shutdown_handle(&self, handle: rocket::Shutdown) {
@@ -1284,6 +1540,75 @@ use handlebars::{
     Renderable,
 };
 
+macro_rules! email_templates {
+    (partials: [$($partial:literal),+ $(,)?], mails: [$($name:literal),+ $(,)?]) => {
+        &[
+            $((
+                concat!("email/", $partial),
+                include_str!(concat!("static/templates/email/", $partial, ".hbs")),
+            ),)+
+            $(
+                (concat!("email/", $name), include_str!(concat!("static/templates/email/", $name, ".hbs"))),
+                (
+                    concat!("email/", $name, ".html"),
+                    include_str!(concat!("static/templates/email/", $name, ".html.hbs")),
+                ),
+            )+
+        ]
+    };
+}
+
+/// The built-in email templates and partials, as `(name, source)` pairs.
+/// Every mail has a text template and an `.html` variant, both of them can be overridden in the templates folder.
+pub const EMAIL_TEMPLATES: &[(&str, &str)] = email_templates!(
+    partials: ["email_header", "email_footer", "email_footer_text"],
+    mails: [
+        "account_deletion_scheduled",
+        "account_lifecycle_warning",
+        "account_purged",
+        "account_restored",
+        "admin_reset_password",
+        "change_email",
+        "delete_account",
+        "emergency_access_invite_accepted",
+        "emergency_access_invite_confirmed",
+        "emergency_access_recovery_approved",
+        "emergency_access_recovery_initiated",
+        "emergency_access_recovery_rejected",
+        "emergency_access_recovery_reminder",
+        "emergency_access_recovery_timed_out",
+        "incomplete_2fa_login",
+        "invite_accepted",
+        "invite_confirmed",
+        "new_device_logged_in",
+        "org_deleted",
+        "org_deletion_restored",
+        "org_deletion_scheduled",
+        "org_transfer_completed",
+        "org_transfer_requested",
+        "pgp_key_unusable",
+        "protected_action",
+        "pw_hint_none",
+        "pw_hint_some",
+        "pw_hint_withheld",
+        "security_digest",
+        "send_2fa_removed_from_org",
+        "send_emergency_access_invite",
+        "send_org_invite",
+        "send_single_org_removed_from_org",
+        "smtp_test",
+        "twofactor_email",
+        "verify_email",
+        "welcome_must_verify",
+        "welcome",
+    ]
+);
+
+/// Whether the given email template is only a partial included by the other templates.
+pub fn is_email_partial(name: &str) -> bool {
+    matches!(name, "email/email_header" | "email/email_footer" | "email/email_footer_text")
+}
+
 fn load_templates<P>(path: P) -> Handlebars<'static>
 where
     P: AsRef<std::path::Path>,
@@ -1300,50 +1625,23 @@ where
             let template = include_str!(concat!("static/templates/", $name, ".hbs"));
             hb.register_template_string($name, template).unwrap();
         }};
-        ($name:expr, $ext:expr) => {{
-            reg!($name);
-            reg!(concat!($name, $ext));
-        }};
     }
 
     // First register default templates here
-    reg!("email/email_header");
-    reg!("email/email_footer");
-    reg!("email/email_footer_text");
-
-    reg!("email/admin_reset_password", ".html");
-    reg!("email/change_email", ".html");
-    reg!("email/delete_account", ".html");
-    reg!("email/emergency_access_invite_accepted", ".html");
-    reg!("email/emergency_access_invite_confirmed", ".html");
-    reg!("email/emergency_access_recovery_approved", ".html");
-    reg!("email/emergency_access_recovery_initiated", ".html");
-    reg!("email/emergency_access_recovery_rejected", ".html");
-    reg!("email/emergency_access_recovery_reminder", ".html");
-    reg!("email/emergency_access_recovery_timed_out", ".html");
-    reg!("email/incomplete_2fa_login", ".html");
-    reg!("email/invite_accepted", ".html");
-    reg!("email/invite_confirmed", ".html");
-    reg!("email/new_device_logged_in", ".html");
-    reg!("email/protected_action", ".html");
-    reg!("email/pw_hint_none", ".html");
-    reg!("email/pw_hint_some", ".html");
-    reg!("email/send_2fa_removed_from_org", ".html");
-    reg!("email/send_emergency_access_invite", ".html");
-    reg!("email/send_org_invite", ".html");
-    reg!("email/send_single_org_removed_from_org", ".html");
-    reg!("email/smtp_test", ".html");
-    reg!("email/twofactor_email", ".html");
-    reg!("email/verify_email", ".html");
-    reg!("email/welcome_must_verify", ".html");
-    reg!("email/welcome", ".html");
+    for (name, template) in EMAIL_TEMPLATES {
+        hb.register_template_string(name, template).unwrap();
+    }
 
     reg!("admin/base");
     reg!("admin/login");
     reg!("admin/settings");
     reg!("admin/users");
     reg!("admin/organizations");
+    reg!("admin/organization_security");
     reg!("admin/diagnostics");
+    reg!("admin/jobs");
+    reg!("admin/connections");
+    reg!("admin/templates");
 
     reg!("404");
 
@@ -1355,6 +1653,25 @@ where
     hb
 }
 
+fn localized_template_name(hb: &Handlebars<'_>, name: &str, locale: Option<&str>) -> String {
+    let (Some(locale), Some((dir, file))) = (locale, name.rsplit_once('/')) else {
+        return name.to_string();
+    };
+
+    // Drop the subtags one at a time, so `zh-Hant-TW` falls back to `zh-Hant` and then to `zh`
+    let mut candidates = vec![locale];
+    let mut rest = locale;
+    while let Some((shorter, _)) = rest.rsplit_once('-') {
+        candidates.push(shorter);
+        rest = shorter;
+    }
+    candidates
+        .iter()
+        .map(|l| format!("{dir}/{l}/{file}"))
+        .find(|localized| hb.has_template(localized))
+        .unwrap_or_else(|| name.to_string())
+}
+
 fn case_helper<'reg, 'rc>(
     h: &Helper<'rc>,
     r: &'reg Handlebars<'_>,
@@ -139,10 +139,14 @@ impl AuthRequest {
         ct_eq(&self.access_code, access_code)
     }
 
-    pub async fn purge_expired_auth_requests(conn: &mut DbConn) {
+    pub async fn purge_expired_auth_requests(conn: &mut DbConn) -> usize {
         let expiry_time = Utc::now().naive_utc() - chrono::TimeDelta::try_minutes(5).unwrap(); //after 5 minutes, clients reject the request
+        let mut purged = 0;
         for auth_request in Self::find_created_before(&expiry_time, conn).await {
-            auth_request.delete(conn).await.ok();
+            if auth_request.delete(conn).await.is_ok() {
+                purged += 1;
+            }
         }
+        purged
     }
 }
@@ -4,7 +4,8 @@ use chrono::{NaiveDateTime, TimeDelta, Utc};
 use serde_json::Value;
 
 use super::{
-    Attachment, CollectionCipher, Favorite, FolderCipher, Group, User, UserOrgStatus, UserOrgType, UserOrganization,
+    Attachment, CollectionCipher, Favorite, FolderCipher, Group, Organization, User, UserOrgStatus, UserOrgType,
+    UserOrganization,
 };
 
 use crate::api::core::{CipherData, CipherSyncData, CipherSyncType};
@@ -383,14 +384,18 @@ impl Cipher {
     }
 
     /// Purge all ciphers that are old enough to be auto-deleted.
-    pub async fn purge_trash(conn: &mut DbConn) {
+    pub async fn purge_trash(conn: &mut DbConn) -> usize {
+        let mut purged = 0;
         if let Some(auto_delete_days) = CONFIG.trash_auto_delete_days() {
             let now = Utc::now().naive_utc();
             let dt = now - TimeDelta::try_days(auto_delete_days).unwrap();
             for cipher in Self::find_deleted_before(&dt, conn).await {
-                cipher.delete(conn).await.ok();
+                if cipher.delete(conn).await.is_ok() {
+                    purged += 1;
+                }
             }
         }
+        purged
     }
 
     pub async fn move_to_folder(&self, folder_uuid: Option<String>, user_uuid: &str, conn: &mut DbConn) -> EmptyResult {
@@ -425,6 +430,17 @@ impl Cipher {
         self.user_uuid.is_some() && self.user_uuid.as_ref().unwrap() == user_uuid
     }
 
+    /// Returns whether this cipher is owned by an org which is scheduled for deletion.
+    async fn is_in_pending_deletion_org(&self, cipher_sync_data: Option<&CipherSyncData>, conn: &mut DbConn) -> bool {
+        if let Some(ref org_uuid) = self.organization_uuid {
+            if let Some(cipher_sync_data) = cipher_sync_data {
+                return cipher_sync_data.pending_deletion_organizations.contains(org_uuid);
+            }
+            return Organization::find_by_uuid(org_uuid, conn).await.is_some_and(|org| org.is_pending_deletion());
+        }
+        false
+    }
+
     /// Returns whether this cipher is owned by an org in which the user has full access.
     async fn is_in_full_access_org(
         &self,
@@ -434,11 +450,12 @@ impl Cipher {
     ) -> bool {
         if let Some(ref org_uuid) = self.organization_uuid {
             if let Some(cipher_sync_data) = cipher_sync_data {
-                if let Some(cached_user_org) = cipher_sync_data.user_organizations.get(org_uuid) {
-                    return cached_user_org.has_full_access();
-                }
-            } else if let Some(user_org) = UserOrganization::find_by_user_and_org(user_uuid, org_uuid, conn).await {
-                return user_org.has_full_access();
+                return cipher_sync_data.user_full_access_for_organizations.contains(org_uuid);
+            } else if let (Some(org), Some(user_org)) = (
+                Organization::find_by_uuid(org_uuid, conn).await,
+                UserOrganization::find_by_user_and_org(user_uuid, org_uuid, conn).await,
+            ) {
+                return org.has_full_item_access(&user_org);
             }
         }
         false
@@ -477,26 +494,33 @@ impl Cipher {
     ) -> Option<(bool, bool)> {
         // Check whether this cipher is directly owned by the user, or is in
         // a collection that the user has full access to. If so, there are no
-        // access restrictions.
-        if self.is_owned_by_user(user_uuid)
-            || self.is_in_full_access_org(user_uuid, cipher_sync_data, conn).await
-            || self.is_in_full_access_group(user_uuid, cipher_sync_data, conn).await
-        {
+        // access restrictions, unless the organization doesn't allow members to edit items.
+        if self.is_owned_by_user(user_uuid) {
+            return Some((false, false));
+        }
+        // Organizations which are scheduled for deletion are disabled, until one of the owners restores them
+        if self.is_in_pending_deletion_org(cipher_sync_data, conn).await {
+            return None;
+        }
+        let full_access = self.is_in_full_access_org(user_uuid, cipher_sync_data, conn).await
+            || self.is_in_full_access_group(user_uuid, cipher_sync_data, conn).await;
+        let editing_restricted = self.is_item_editing_restricted(user_uuid, cipher_sync_data, conn).await;
+        if full_access && !editing_restricted {
             return Some((false, false));
         }
 
         let rows = if let Some(cipher_sync_data) = cipher_sync_data {
-            let mut rows: Vec<(bool, bool)> = Vec::new();
+            let mut rows: Vec<(bool, bool, bool)> = Vec::new();
             if let Some(collections) = cipher_sync_data.cipher_collections.get(&self.uuid) {
                 for collection in collections {
                     //User permissions
                     if let Some(uc) = cipher_sync_data.user_collections.get(collection) {
-                        rows.push((uc.read_only, uc.hide_passwords));
+                        rows.push((uc.read_only, uc.hide_passwords, uc.manage));
                     }
 
                     //Group permissions
                     if let Some(cg) = cipher_sync_data.user_collections_groups.get(collection) {
-                        rows.push((cg.read_only, cg.hide_passwords));
+                        rows.push((cg.read_only, cg.hide_passwords, cg.manage));
                     }
                 }
             }
@@ -507,6 +531,11 @@ impl Cipher {
             access_flags
         };
 
+        if full_access {
+            // Only the collections the user manages give write access.
+            return Some((!rows.iter().any(|(_, _, manage)| *manage), false));
+        }
+
         if rows.is_empty() {
             // This cipher isn't in any collections accessible to the user.
             return None;
@@ -521,17 +550,40 @@ impl Cipher {
         // and `hide_passwords` columns. This could ideally be done as part of the
         // query, but Diesel doesn't support a min() or bool_and() function on
         // booleans and this behavior isn't portable anyway.
+        // If the organization doesn't allow members to edit items, only the collections they manage give write access.
         let mut read_only = true;
         let mut hide_passwords = true;
-        for (ro, hp) in rows.iter() {
-            read_only &= ro;
+        for (ro, hp, manage) in rows.iter() {
+            read_only &= *ro || (editing_restricted && !manage);
             hide_passwords &= hp;
         }
 
         Some((read_only, hide_passwords))
     }
 
-    async fn get_user_collections_access_flags(&self, user_uuid: &str, conn: &mut DbConn) -> Vec<(bool, bool)> {
+    /// Returns whether the organization of this cipher doesn't allow the user to edit its items.
+    async fn is_item_editing_restricted(
+        &self,
+        user_uuid: &str,
+        cipher_sync_data: Option<&CipherSyncData>,
+        conn: &mut DbConn,
+    ) -> bool {
+        let Some(ref org_uuid) = self.organization_uuid else {
+            return false;
+        };
+        if let Some(cipher_sync_data) = cipher_sync_data {
+            return cipher_sync_data.item_editing_restricted_organizations.contains(org_uuid);
+        }
+        match (
+            Organization::find_by_uuid(org_uuid, conn).await,
+            UserOrganization::find_by_user_and_org(user_uuid, org_uuid, conn).await,
+        ) {
+            (Some(org), Some(user_org)) => org.restricts_item_editing(&user_org),
+            _ => false,
+        }
+    }
+
+    async fn get_user_collections_access_flags(&self, user_uuid: &str, conn: &mut DbConn) -> Vec<(bool, bool, bool)> {
         db_run! {conn: {
             // Check whether this cipher is in any collections accessible to the
             // user. If so, retrieve the access flags for each collection.
@@ -542,13 +594,13 @@ impl Cipher {
                 .inner_join(users_collections::table.on(
                     ciphers_collections::collection_uuid.eq(users_collections::collection_uuid)
                         .and(users_collections::user_uuid.eq(user_uuid))))
-                .select((users_collections::read_only, users_collections::hide_passwords))
-                .load::<(bool, bool)>(conn)
+                .select((users_collections::read_only, users_collections::hide_passwords, users_collections::manage))
+                .load::<(bool, bool, bool)>(conn)
                 .expect("Error getting user access restrictions")
         }}
     }
 
-    async fn get_group_collections_access_flags(&self, user_uuid: &str, conn: &mut DbConn) -> Vec<(bool, bool)> {
+    async fn get_group_collections_access_flags(&self, user_uuid: &str, conn: &mut DbConn) -> Vec<(bool, bool, bool)> {
         if !CONFIG.org_groups_enabled() {
             return Vec::new();
         }
@@ -568,8 +620,8 @@ impl Cipher {
                     users_organizations::uuid.eq(groups_users::users_organizations_uuid)
                 ))
                 .filter(users_organizations::user_uuid.eq(user_uuid))
-                .select((collections_groups::read_only, collections_groups::hide_passwords))
-                .load::<(bool, bool)>(conn)
+                .select((collections_groups::read_only, collections_groups::hide_passwords, collections_groups::manage))
+                .load::<(bool, bool, bool)>(conn)
                 .expect("Error getting group access restrictions")
         }}
     }
@@ -643,6 +695,10 @@ impl Cipher {
                             ciphers::organization_uuid.eq(users_organizations::org_uuid.nullable())
                             .and(users_organizations::user_uuid.eq(user_uuid))
                             .and(users_organizations::status.eq(UserOrgStatus::Confirmed as i32))
+                            // Organizations scheduled for deletion are disabled
+                            .and(users_organizations::org_uuid.ne_all(
+                                organizations::table.filter(organizations::deleted_at.is_not_null()).select(organizations::uuid)
+                                ))
                             ))
                     .left_join(users_collections::table.on(
                             ciphers_collections::collection_uuid.eq(users_collections::collection_uuid)
@@ -670,6 +726,9 @@ impl Cipher {
                 if !visible_only {
                     query = query.or_filter(
                         users_organizations::atype.le(UserOrgType::Admin as i32) // Org admin/owner
+                        .and(users_organizations::org_uuid.eq_any(
+                            organizations::table.filter(organizations::allow_admin_access_to_all_collection_items.eq(true)).select(organizations::uuid)
+                            ))
                         );
                 }
 
@@ -688,6 +747,10 @@ impl Cipher {
                             ciphers::organization_uuid.eq(users_organizations::org_uuid.nullable())
                             .and(users_organizations::user_uuid.eq(user_uuid))
                             .and(users_organizations::status.eq(UserOrgStatus::Confirmed as i32))
+                            // Organizations scheduled for deletion are disabled
+                            .and(users_organizations::org_uuid.ne_all(
+                                organizations::table.filter(organizations::deleted_at.is_not_null()).select(organizations::uuid)
+                                ))
                             ))
                     .left_join(users_collections::table.on(
                             ciphers_collections::collection_uuid.eq(users_collections::collection_uuid)
@@ -702,6 +765,9 @@ impl Cipher {
                     if !visible_only {
                         query = query.or_filter(
                             users_organizations::atype.le(UserOrgType::Admin as i32) // Org admin/owner
+                            .and(users_organizations::org_uuid.eq_any(
+                                organizations::table.filter(organizations::allow_admin_access_to_all_collection_items.eq(true)).select(organizations::uuid)
+                                ))
                             );
                     }
 
@@ -1,6 +1,6 @@
 use serde_json::Value;
 
-use super::{CollectionGroup, GroupUser, User, UserOrgStatus, UserOrgType, UserOrganization};
+use super::{CollectionGroup, GroupUser, Organization, User, UserOrgStatus, UserOrgType, UserOrganization};
 use crate::CONFIG;
 
 db_object! {
@@ -22,6 +22,7 @@ db_object! {
         pub collection_uuid: String,
         pub read_only: bool,
         pub hide_passwords: bool,
+        pub manage: bool,
     }
 
     #[derive(Identifiable, Queryable, Insertable)]
@@ -78,39 +79,80 @@ impl Collection {
         cipher_sync_data: Option<&crate::api::core::CipherSyncData>,
         conn: &mut DbConn,
     ) -> Value {
-        let (read_only, hide_passwords) = if let Some(cipher_sync_data) = cipher_sync_data {
+        let (read_only, hide_passwords, manage) = if let Some(cipher_sync_data) = cipher_sync_data {
             match cipher_sync_data.user_organizations.get(&self.org_uuid) {
-                Some(uo) if uo.has_full_access() => (false, false),
-                Some(_) => {
-                    if let Some(uc) = cipher_sync_data.user_collections.get(&self.uuid) {
-                        (uc.read_only, uc.hide_passwords)
-                    } else if let Some(cg) = cipher_sync_data.user_collections_groups.get(&self.uuid) {
-                        (cg.read_only, cg.hide_passwords)
-                    } else {
-                        (false, false)
-                    }
+                Some(uo) => {
+                    // The same check as `can_manage_collection`
+                    let is_manager = uo.atype >= UserOrgType::Manager || uo.permissions().manage_all_collections;
+                    let (read_only, hide_passwords, manage) =
+                        if cipher_sync_data.user_full_access_for_organizations.contains(&self.org_uuid) {
+                            (false, false, is_manager)
+                        } else if let Some(uc) = cipher_sync_data.user_collections.get(&self.uuid) {
+                            (uc.read_only, uc.hide_passwords, uc.manage || is_manager)
+                        } else if let Some(cg) = cipher_sync_data.user_collections_groups.get(&self.uuid) {
+                            (cg.read_only, cg.hide_passwords, cg.manage || is_manager)
+                        } else {
+                            (false, false, is_manager)
+                        };
+                    // Only the members who can manage the collection can edit its items when item editing is restricted
+                    let editing_restricted =
+                        cipher_sync_data.item_editing_restricted_organizations.contains(&self.org_uuid);
+                    (read_only || (editing_restricted && !manage), hide_passwords, manage)
                 }
-                _ => (true, true),
+                _ => (true, true, false),
             }
         } else {
-            (!self.is_writable_by_user(user_uuid, conn).await, self.hide_passwords_for_user(user_uuid, conn).await)
+            let (manage, editing_restricted) =
+                match UserOrganization::find_by_user_and_org(user_uuid, &self.org_uuid, conn).await {
+                    Some(uo) => (
+                        Self::can_manage_collection(&uo, &self.uuid, conn).await,
+                        Organization::find_by_uuid(&self.org_uuid, conn)
+                            .await
+                            .is_some_and(|org| org.restricts_item_editing(&uo)),
+                    ),
+                    None => (false, false),
+                };
+            (
+                !self.is_writable_by_user(user_uuid, conn).await || (editing_restricted && !manage),
+                self.hide_passwords_for_user(user_uuid, conn).await,
+                manage,
+            )
         };
 
         let mut json_object = self.to_json();
         json_object["object"] = json!("collectionDetails");
         json_object["readOnly"] = json!(read_only);
         json_object["hidePasswords"] = json!(hide_passwords);
+        json_object["manage"] = json!(manage);
         json_object
     }
 
     pub async fn can_access_collection(org_user: &UserOrganization, col_id: &str, conn: &mut DbConn) -> bool {
         org_user.has_status(UserOrgStatus::Confirmed)
-            && (org_user.has_full_access()
+            && (org_user.permissions().manage_all_collections
+                || Organization::find_by_uuid(&org_user.org_uuid, conn)
+                    .await
+                    .is_some_and(|org| org.has_full_item_access(org_user))
                 || CollectionUser::has_access_to_collection_by_user(col_id, &org_user.user_uuid, conn).await
                 || (CONFIG.org_groups_enabled()
                     && (GroupUser::has_full_access_by_member(&org_user.org_uuid, &org_user.uuid, conn).await
                         || GroupUser::has_access_to_collection_by_member(col_id, &org_user.uuid, conn).await)))
     }
+
+    /// Checks if the member can manage the collection, which allows changing its settings and who has access to it.
+    /// Managers and above can manage every collection they have access to, other members need the `manage` flag.
+    pub async fn can_manage_collection(org_user: &UserOrganization, col_id: &str, conn: &mut DbConn) -> bool {
+        if org_user.atype >= UserOrgType::Manager || org_user.permissions().manage_all_collections {
+            return Self::can_access_collection(org_user, col_id, conn).await;
+        }
+
+        org_user.has_status(UserOrgStatus::Confirmed)
+            && (CollectionUser::find_by_collection_and_user(col_id, &org_user.user_uuid, conn)
+                .await
+                .is_some_and(|collection_user| collection_user.manage)
+                || (CONFIG.org_groups_enabled()
+                    && GroupUser::has_manage_access_to_collection_by_member(col_id, &org_user.uuid, conn).await))
+    }
 }
 
 use crate::db::DbConn;
@@ -218,6 +260,11 @@ impl Collection {
                 .filter(
                     users_organizations::status.eq(UserOrgStatus::Confirmed as i32)
                 )
+                .filter( // Organizations scheduled for deletion are disabled
+                    users_organizations::org_uuid.ne_all(
+                        organizations::table.filter(organizations::deleted_at.is_not_null()).select(organizations::uuid)
+                    )
+                )
                 .filter(
                     users_collections::user_uuid.eq(user_uuid).or( // Directly accessed collection
                         users_organizations::access_all.eq(true) // access_all in Organization
@@ -249,6 +296,11 @@ impl Collection {
                 .filter(
                     users_organizations::status.eq(UserOrgStatus::Confirmed as i32)
                 )
+                .filter( // Organizations scheduled for deletion are disabled
+                    users_organizations::org_uuid.ne_all(
+                        organizations::table.filter(organizations::deleted_at.is_not_null()).select(organizations::uuid)
+                    )
+                )
                 .filter(
                     users_collections::user_uuid.eq(user_uuid).or( // Directly accessed collection
                         users_organizations::access_all.eq(true) // access_all in Organization
@@ -379,6 +431,7 @@ impl Collection {
                         collections::org_uuid.eq(users_organizations::org_uuid)
                         .and(users_organizations::user_uuid.eq(user_uuid.clone()))
                     ))
+                    .inner_join(organizations::table.on(organizations::uuid.eq(collections::org_uuid)))
                     .left_join(users_collections::table.on(
                         users_collections::collection_uuid.eq(collections::uuid)
                         .and(users_collections::user_uuid.eq(user_uuid))
@@ -393,7 +446,8 @@ impl Collection {
                         collections_groups::groups_uuid.eq(groups_users::groups_uuid)
                         .and(collections_groups::collections_uuid.eq(collections::uuid))
                     ))
-                    .filter(users_organizations::atype.le(UserOrgType::Admin as i32) // Org admin or owner
+                    .filter(users_organizations::atype.le(UserOrgType::Admin as i32) // Org admin or owner, when allowed to access all items
+                            .and(organizations::allow_admin_access_to_all_collection_items.eq(true))
                         .or(users_organizations::access_all.eq(true)) // access_all via membership
                         .or(users_collections::collection_uuid.eq(&self.uuid) // write access given to collection
                             .and(users_collections::read_only.eq(false)))
@@ -414,11 +468,13 @@ impl Collection {
                         collections::org_uuid.eq(users_organizations::org_uuid)
                         .and(users_organizations::user_uuid.eq(user_uuid.clone()))
                     ))
+                    .inner_join(organizations::table.on(organizations::uuid.eq(collections::org_uuid)))
                     .left_join(users_collections::table.on(
                         users_collections::collection_uuid.eq(collections::uuid)
                         .and(users_collections::user_uuid.eq(user_uuid))
                     ))
-                    .filter(users_organizations::atype.le(UserOrgType::Admin as i32) // Org admin or owner
+                    .filter(users_organizations::atype.le(UserOrgType::Admin as i32) // Org admin or owner, when allowed to access all items
+                            .and(organizations::allow_admin_access_to_all_collection_items.eq(true))
                         .or(users_organizations::access_all.eq(true)) // access_all via membership
                         .or(users_collections::collection_uuid.eq(&self.uuid) // write access given to collection
                             .and(users_collections::read_only.eq(false)))
@@ -498,8 +554,11 @@ impl CollectionUser {
             users_collections::table
                 .inner_join(collections::table.on(collections::uuid.eq(users_collections::collection_uuid)))
                 .filter(collections::org_uuid.eq(org_uuid))
-                .inner_join(users_organizations::table.on(users_organizations::user_uuid.eq(users_collections::user_uuid)))
-                .select((users_organizations::uuid, users_collections::collection_uuid, users_collections::read_only, users_collections::hide_passwords))
+                .inner_join(users_organizations::table.on(
+                    users_organizations::user_uuid.eq(users_collections::user_uuid)
+                        .and(users_organizations::org_uuid.eq(collections::org_uuid))
+                ))
+                .select((users_organizations::uuid, users_collections::collection_uuid, users_collections::read_only, users_collections::hide_passwords, users_collections::manage))
                 .load::<CollectionUserDb>(conn)
                 .expect("Error loading users_collections")
                 .from_db()
@@ -511,6 +570,7 @@ impl CollectionUser {
         collection_uuid: &str,
         read_only: bool,
         hide_passwords: bool,
+        manage: bool,
         conn: &mut DbConn,
     ) -> EmptyResult {
         User::update_uuid_revision(user_uuid, conn).await;
@@ -523,6 +583,7 @@ impl CollectionUser {
                         users_collections::collection_uuid.eq(collection_uuid),
                         users_collections::read_only.eq(read_only),
                         users_collections::hide_passwords.eq(hide_passwords),
+                        users_collections::manage.eq(manage),
                     ))
                     .execute(conn)
                 {
@@ -537,6 +598,7 @@ impl CollectionUser {
                                 users_collections::collection_uuid.eq(collection_uuid),
                                 users_collections::read_only.eq(read_only),
                                 users_collections::hide_passwords.eq(hide_passwords),
+                                users_collections::manage.eq(manage),
                             ))
                             .execute(conn)
                             .map_res("Error adding user to collection")
@@ -551,12 +613,14 @@ impl CollectionUser {
                         users_collections::collection_uuid.eq(collection_uuid),
                         users_collections::read_only.eq(read_only),
                         users_collections::hide_passwords.eq(hide_passwords),
+                        users_collections::manage.eq(manage),
                     ))
                     .on_conflict((users_collections::user_uuid, users_collections::collection_uuid))
                     .do_update()
                     .set((
                         users_collections::read_only.eq(read_only),
                         users_collections::hide_passwords.eq(hide_passwords),
+                        users_collections::manage.eq(manage),
                     ))
                     .execute(conn)
                     .map_res("Error adding user to collection")
@@ -597,7 +661,7 @@ impl CollectionUser {
             users_collections::table
                 .filter(users_collections::collection_uuid.eq(collection_uuid))
                 .inner_join(users_organizations::table.on(users_organizations::user_uuid.eq(users_collections::user_uuid)))
-                .select((users_organizations::uuid, users_collections::collection_uuid, users_collections::read_only, users_collections::hide_passwords))
+                .select((users_organizations::uuid, users_collections::collection_uuid, users_collections::read_only, users_collections::hide_passwords, users_collections::manage))
                 .load::<CollectionUserDb>(conn)
                 .expect("Error loading users_collections")
                 .from_db()
@@ -726,6 +790,21 @@ impl CollectionCipher {
         }}
     }
 
+    /// Returns the number of items in each collection of the organization, not counting the ones in the trash
+    pub async fn count_by_organization(org_uuid: &str, conn: &mut DbConn) -> Vec<(String, i64)> {
+        db_run! { conn: {
+            ciphers_collections::table
+                .inner_join(collections::table.on(collections::uuid.eq(ciphers_collections::collection_uuid)))
+                .inner_join(ciphers::table.on(ciphers::uuid.eq(ciphers_collections::cipher_uuid)))
+                .filter(collections::org_uuid.eq(org_uuid))
+                .filter(ciphers::deleted_at.is_null())
+                .group_by(ciphers_collections::collection_uuid)
+                .select((ciphers_collections::collection_uuid, diesel::dsl::count_star()))
+                .load::<(String, i64)>(conn)
+                .expect("Error counting items of collections")
+        }}
+    }
+
     pub async fn update_users_revision(collection_uuid: &str, conn: &mut DbConn) {
         if let Some(collection) = Collection::find_by_uuid(collection_uuid, conn).await {
             collection.update_users_revision(conn).await;
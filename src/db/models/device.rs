@@ -219,6 +219,30 @@ impl Device {
         }}
     }
 
+    /// Returns the time of the most recently active device of every user
+    pub async fn find_last_active_all(conn: &mut DbConn) -> Vec<(String, Option<NaiveDateTime>)> {
+        db_run! { conn: {
+            devices::table
+                .group_by(devices::user_uuid)
+                .select((devices::user_uuid, diesel::dsl::max(devices::updated_at)))
+                .load::<(String, Option<NaiveDateTime>)>(conn)
+                .expect("Error loading the last active devices")
+        }}
+    }
+
+    /// Returns the time of the most recently active device of every member of the organization
+    pub async fn find_last_active_by_org(org_uuid: &str, conn: &mut DbConn) -> Vec<(String, Option<NaiveDateTime>)> {
+        db_run! { conn: {
+            devices::table
+                .inner_join(users_organizations::table.on(users_organizations::user_uuid.eq(devices::user_uuid)))
+                .filter(users_organizations::org_uuid.eq(org_uuid))
+                .group_by(devices::user_uuid)
+                .select((devices::user_uuid, diesel::dsl::max(devices::updated_at)))
+                .load::<(String, Option<NaiveDateTime>)>(conn)
+                .expect("Error loading the last active devices of the organization")
+        }}
+    }
+
     pub async fn find_push_devices_by_user(user_uuid: &str, conn: &mut DbConn) -> Vec<Self> {
         db_run! { conn: {
             devices::table
@@ -253,11 +253,22 @@ impl EmergencyAccess {
         }}
     }
 
+    /// Returns the initiated recoveries, except those of deleted accounts which are on hold until the account is restored
     pub async fn find_all_recoveries_initiated(conn: &mut DbConn) -> Vec<Self> {
         db_run! { conn: {
             emergency_access::table
                 .filter(emergency_access::status.eq(EmergencyAccessStatus::RecoveryInitiated as i32))
                 .filter(emergency_access::recovery_initiated_at.is_not_null())
+                .filter(
+                    emergency_access::grantor_uuid.ne_all(
+                        users::table.filter(users::deleted_at.is_not_null()).select(users::uuid)
+                    )
+                )
+                .filter(
+                    emergency_access::grantee_uuid.ne_all(
+                        users::table.filter(users::deleted_at.is_not_null()).select(users::uuid.nullable())
+                    )
+                )
                 .load::<EmergencyAccessDb>(conn).expect("Error loading emergency_access").from_db()
         }}
     }
@@ -49,6 +49,16 @@ pub enum EventType {
     UserClientExportedVault = 1007,
     // UserUpdatedTempPassword = 1008, // Not supported
     // UserMigratedKeyToKeyConnector = 1009, // Not supported
+    // Not upstream, logged by the account lifecycle job
+    UserLifecycleWarned = 1090,
+    UserLifecycleDisabled = 1091,
+    UserLifecycleDeleted = 1092,
+    // Not upstream, logged for the owner of a Send when it is accessed
+    UserSendAccessed = 1093,
+    // Not upstream, logged when an account is deleted, restored by an admin and finally purged
+    UserDeleted = 1094,
+    UserRestored = 1095,
+    UserPurged = 1096,
 
     // Cipher
     CipherCreated = 1100,
@@ -314,7 +324,40 @@ impl Event {
         }}
     }
 
-    pub async fn clean_events(conn: &mut DbConn) -> EmptyResult {
+    /// Returns the user's own events since the given date, which are stored without an organization.
+    pub async fn find_by_user_since(user_uuid: &str, since: &NaiveDateTime, conn: &mut DbConn) -> Vec<Self> {
+        db_run! { conn: {
+            event::table
+                .filter(event::user_uuid.eq(user_uuid))
+                .filter(event::org_uuid.is_null())
+                .filter(event::event_date.ge(since))
+                .order_by(event::event_date.asc())
+                .load::<EventDb>(conn)
+                .expect("Error filtering events")
+                .from_db()
+        }}
+    }
+
+    /// Returns the membership events (invited, confirmed, updated, ...) of the given memberships since the given date.
+    pub async fn find_by_org_users_since(
+        org_user_uuids: &[String],
+        since: &NaiveDateTime,
+        conn: &mut DbConn,
+    ) -> Vec<Self> {
+        db_run! { conn: {
+            event::table
+                .filter(event::org_user_uuid.eq_any(org_user_uuids))
+                .filter(event::event_type.between(1500, 1599))
+                .filter(event::event_date.ge(since))
+                .order_by(event::event_date.asc())
+                .load::<EventDb>(conn)
+                .expect("Error filtering events")
+                .from_db()
+        }}
+    }
+
+    /// Returns the number of events that were removed.
+    pub async fn clean_events(conn: &mut DbConn) -> Result<usize, crate::Error> {
         if let Some(days_to_retain) = CONFIG.events_days_retain() {
             let dt = Utc::now().naive_utc() - TimeDelta::try_days(days_to_retain).unwrap();
             db_run! { conn: {
@@ -323,7 +366,7 @@ impl Event {
                 .map_res("Error cleaning old events")
             }}
         } else {
-            Ok(())
+            Ok(0)
         }
     }
 }
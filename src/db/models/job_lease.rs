@@ -0,0 +1,117 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+use crate::{api::EmptyResult, db::DbConn, error::MapResult};
+
+db_object! {
+    #[derive(Identifiable, Queryable, Insertable)]
+    #[diesel(table_name = job_leases)]
+    #[diesel(primary_key(job_name))]
+    pub struct JobLease {
+        pub job_name: String,
+        pub holder: String,
+        pub locked_until: NaiveDateTime,
+        pub last_slot: NaiveDateTime,
+    }
+}
+
+/// Database methods
+impl JobLease {
+    /// Tries to claim the lease of a job for the execution scheduled at `slot`.
+    ///
+    /// The lease is only granted when no other instance currently holds it and the given slot has not been claimed yet.
+    /// Both checks are done in a single `UPDATE`, so when multiple instances race for the same slot, only one of them wins.
+    /// If an instance dies while holding the lease, it becomes available again once `locked_until` has passed.
+    pub async fn try_acquire(
+        job_name: &str,
+        holder: &str,
+        slot: &NaiveDateTime,
+        locked_until: &NaiveDateTime,
+        conn: &mut DbConn,
+    ) -> Result<bool, crate::Error> {
+        Self::create_if_missing(job_name, conn).await?;
+
+        let now = Utc::now().naive_utc();
+        db_run! { conn: {
+            diesel::update(
+                job_leases::table
+                    .filter(job_leases::job_name.eq(job_name))
+                    .filter(job_leases::locked_until.lt(now))
+                    .filter(job_leases::last_slot.lt(slot)),
+            )
+            .set((
+                job_leases::holder.eq(holder),
+                job_leases::locked_until.eq(locked_until),
+                job_leases::last_slot.eq(slot),
+            ))
+            .execute(conn)
+            .map(|updated| updated == 1)
+            .map_res("Error acquiring job lease")
+        }}
+    }
+
+    /// Tries to claim the lease of a job for a manual run.
+    ///
+    /// This only checks that no other instance currently holds the lease. The last claimed slot is left alone,
+    /// so a manual run never suppresses a scheduled execution, even one which is running late.
+    pub async fn try_acquire_manual(
+        job_name: &str,
+        holder: &str,
+        locked_until: &NaiveDateTime,
+        conn: &mut DbConn,
+    ) -> Result<bool, crate::Error> {
+        Self::create_if_missing(job_name, conn).await?;
+
+        let now = Utc::now().naive_utc();
+        db_run! { conn: {
+            diesel::update(
+                job_leases::table
+                    .filter(job_leases::job_name.eq(job_name))
+                    .filter(job_leases::locked_until.lt(now)),
+            )
+            .set((job_leases::holder.eq(holder), job_leases::locked_until.eq(locked_until)))
+            .execute(conn)
+            .map(|updated| updated == 1)
+            .map_res("Error acquiring job lease")
+        }}
+    }
+
+    /// Releases the lease of a job, but only if it is still held by `holder`.
+    pub async fn release(job_name: &str, holder: &str, conn: &mut DbConn) -> EmptyResult {
+        let now = Utc::now().naive_utc();
+        db_run! { conn: {
+            diesel::update(
+                job_leases::table
+                    .filter(job_leases::job_name.eq(job_name))
+                    .filter(job_leases::holder.eq(holder)),
+            )
+            .set(job_leases::locked_until.eq(now))
+            .execute(conn)
+            .map_res("Error releasing job lease")
+        }}
+    }
+
+    async fn create_if_missing(job_name: &str, conn: &mut DbConn) -> EmptyResult {
+        let lease = Self {
+            job_name: job_name.to_string(),
+            holder: String::new(),
+            locked_until: DateTime::UNIX_EPOCH.naive_utc(),
+            last_slot: DateTime::UNIX_EPOCH.naive_utc(),
+        };
+
+        db_run! { conn:
+            sqlite, mysql {
+                diesel::insert_or_ignore_into(job_leases::table)
+                    .values(JobLeaseDb::to_db(&lease))
+                    .execute(conn)
+                    .map_res("Error creating job lease")
+            }
+            postgresql {
+                diesel::insert_into(job_leases::table)
+                    .values(JobLeaseDb::to_db(&lease))
+                    .on_conflict_do_nothing()
+                    .execute(conn)
+                    .map_res("Error creating job lease")
+            }
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use chrono::{NaiveDateTime, Utc};
+use serde_json::Value;
+
+use crate::{api::EmptyResult, db::DbConn, error::MapResult, util::format_naive_datetime_local};
+
+db_object! {
+    #[derive(Identifiable, Queryable, Insertable, AsChangeset)]
+    #[diesel(table_name = job_runs)]
+    #[diesel(treat_none_as_null = true)]
+    #[diesel(primary_key(uuid))]
+    pub struct JobRun {
+        pub uuid: String,
+        pub job_name: String,
+        pub manual: bool,
+        pub started_at: NaiveDateTime,
+        pub finished_at: Option<NaiveDateTime>,
+        pub status: i32, // JobRunStatus
+        pub items_affected: Option<i64>,
+        pub error: Option<String>,
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum JobRunStatus {
+    Running = 0,
+    Succeeded = 1,
+    Failed = 2,
+}
+
+impl JobRunStatus {
+    pub fn from_i32(status: i32) -> Option<Self> {
+        match status {
+            0 => Some(Self::Running),
+            1 => Some(Self::Succeeded),
+            2 => Some(Self::Failed),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Running => "Running",
+            Self::Succeeded => "Succeeded",
+            Self::Failed => "Failed",
+        }
+    }
+}
+
+/// Local methods
+impl JobRun {
+    pub fn new(job_name: &str, manual: bool) -> Self {
+        Self {
+            uuid: crate::util::get_uuid(),
+            job_name: job_name.to_string(),
+            manual,
+            started_at: Utc::now().naive_utc(),
+            finished_at: None,
+            status: JobRunStatus::Running as i32,
+            items_affected: None,
+            error: None,
+        }
+    }
+
+    pub fn finish(&mut self, result: &Result<usize, String>) {
+        self.finished_at = Some(Utc::now().naive_utc());
+        match result {
+            Ok(items) => {
+                self.status = JobRunStatus::Succeeded as i32;
+                self.items_affected = Some(*items as i64);
+            }
+            Err(e) => {
+                self.status = JobRunStatus::Failed as i32;
+                self.error = Some(e.clone());
+            }
+        }
+    }
+
+    pub fn to_json(&self, dt_fmt: &str) -> Value {
+        let status = JobRunStatus::from_i32(self.status).map(JobRunStatus::as_str).unwrap_or("Unknown");
+        let duration =
+            self.finished_at.map(|f| format!("{:.2}s", (f - self.started_at).num_milliseconds() as f64 / 1000.0));
+
+        json!({
+            "id": self.uuid,
+            "job_name": self.job_name,
+            "manual": self.manual,
+            "started_at": format_naive_datetime_local(&self.started_at, dt_fmt),
+            "finished_at": self.finished_at.map(|f| format_naive_datetime_local(&f, dt_fmt)),
+            "duration": duration,
+            "status": status,
+            "items_affected": self.items_affected,
+            "error": self.error,
+        })
+    }
+}
+
+/// Database methods
+impl JobRun {
+    pub async fn save(&self, conn: &mut DbConn) -> EmptyResult {
+        db_run! { conn:
+            sqlite, mysql {
+                diesel::replace_into(job_runs::table)
+                    .values(JobRunDb::to_db(self))
+                    .execute(conn)
+                    .map_res("Error saving job run")
+            }
+            postgresql {
+                let value = JobRunDb::to_db(self);
+                diesel::insert_into(job_runs::table)
+                    .values(&value)
+                    .on_conflict(job_runs::uuid)
+                    .do_update()
+                    .set(&value)
+                    .execute(conn)
+                    .map_res("Error saving job run")
+            }
+        }
+    }
+
+    pub async fn find_recent(limit: i64, conn: &mut DbConn) -> Vec<Self> {
+        db_run! { conn: {
+            job_runs::table
+                .order_by(job_runs::started_at.desc())
+                .limit(limit)
+                .load::<JobRunDb>(conn)
+                .expect("Error loading job runs")
+                .from_db()
+        }}
+    }
+
+    pub async fn find_last_by_job(job_name: &str, conn: &mut DbConn) -> Option<Self> {
+        db_run! { conn: {
+            job_runs::table
+                .filter(job_runs::job_name.eq(job_name))
+                .order_by(job_runs::started_at.desc())
+                .first::<JobRunDb>(conn)
+                .ok()
+                .from_db()
+        }}
+    }
+
+    pub async fn delete_started_before(dt: &NaiveDateTime, conn: &mut DbConn) -> EmptyResult {
+        db_run! { conn: {
+            diesel::delete(job_runs::table.filter(job_runs::started_at.lt(dt)))
+                .execute(conn)
+                .map_res("Error deleting old job runs")
+        }}
+    }
+}
@@ -8,6 +8,10 @@ mod event;
 mod favorite;
 mod folder;
 mod group;
+mod job_lease;
+mod job_run;
+mod notification_preference;
+mod org_domain;
 mod org_policy;
 mod organization;
 mod send;
@@ -15,6 +19,7 @@ mod two_factor;
 mod two_factor_duo_context;
 mod two_factor_incomplete;
 mod user;
+mod ws_broadcast;
 
 pub use self::attachment::Attachment;
 pub use self::auth_request::AuthRequest;
@@ -26,10 +31,17 @@ pub use self::event::{Event, EventType};
 pub use self::favorite::Favorite;
 pub use self::folder::{Folder, FolderCipher};
 pub use self::group::{CollectionGroup, Group, GroupUser};
+pub use self::job_lease::JobLease;
+pub use self::job_run::JobRun;
+pub use self::notification_preference::{NotificationCategory, NotificationPreference};
+pub use self::org_domain::{email_domain, AutoEnrollCollection, OrganizationDomain};
 pub use self::org_policy::{OrgPolicy, OrgPolicyErr, OrgPolicyType};
-pub use self::organization::{Organization, OrganizationApiKey, UserOrgStatus, UserOrgType, UserOrganization};
+pub use self::organization::{
+    Organization, OrganizationApiKey, UserOrgPermissions, UserOrgStatus, UserOrgType, UserOrganization,
+};
 pub use self::send::{Send, SendType};
 pub use self::two_factor::{TwoFactor, TwoFactorType};
 pub use self::two_factor_duo_context::TwoFactorDuoContext;
 pub use self::two_factor_incomplete::TwoFactorIncomplete;
-pub use self::user::{Invitation, User, UserKdfType, UserStampException};
+pub use self::user::{Invitation, User, UserKdfType, UserLifecycleAction, UserStampException};
+pub use self::ws_broadcast::WsBroadcast;
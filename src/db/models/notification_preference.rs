@@ -0,0 +1,146 @@
+use serde_json::Value;
+
+use crate::{api::EmptyResult, db::DbConn, error::MapResult, CONFIG};
+
+db_object! {
+    #[derive(Identifiable, Queryable, Insertable, AsChangeset)]
+    #[diesel(table_name = notification_preferences)]
+    #[diesel(treat_none_as_null = true)]
+    #[diesel(primary_key(user_uuid))]
+    pub struct NotificationPreference {
+        pub user_uuid: String,
+        // A `None` value means the user hasn't made a choice, in which case the admin configured default is used
+        pub new_device_logged_in: Option<bool>,
+        pub incomplete_2fa_login: Option<bool>,
+        pub org_membership_updates: Option<bool>,
+        pub emergency_access_updates: Option<bool>,
+        pub emergency_access_reminders: Option<bool>,
+        pub security_digest: Option<bool>,
+    }
+}
+
+/// The optional mails a user can opt out of.
+/// Mails which are needed to use or secure an account, like email verification and 2FA codes, are always sent.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum NotificationCategory {
+    NewDeviceLoggedIn,
+    Incomplete2faLogin,
+    OrgMembershipUpdates,
+    EmergencyAccessUpdates,
+    EmergencyAccessReminders,
+    SecurityDigest,
+}
+
+impl NotificationCategory {
+    pub fn default_enabled(self) -> bool {
+        match self {
+            Self::NewDeviceLoggedIn => CONFIG.notify_new_device_logged_in(),
+            Self::Incomplete2faLogin => CONFIG.notify_incomplete_2fa_login(),
+            Self::OrgMembershipUpdates => CONFIG.notify_org_membership_updates(),
+            Self::EmergencyAccessUpdates => CONFIG.notify_emergency_access_updates(),
+            Self::EmergencyAccessReminders => CONFIG.notify_emergency_access_reminders(),
+            Self::SecurityDigest => CONFIG.notify_security_digest(),
+        }
+    }
+}
+
+/// Local methods
+impl NotificationPreference {
+    pub fn new(user_uuid: String) -> Self {
+        Self {
+            user_uuid,
+            new_device_logged_in: None,
+            incomplete_2fa_login: None,
+            org_membership_updates: None,
+            emergency_access_updates: None,
+            emergency_access_reminders: None,
+            security_digest: None,
+        }
+    }
+
+    fn get(&self, category: NotificationCategory) -> Option<bool> {
+        match category {
+            NotificationCategory::NewDeviceLoggedIn => self.new_device_logged_in,
+            NotificationCategory::Incomplete2faLogin => self.incomplete_2fa_login,
+            NotificationCategory::OrgMembershipUpdates => self.org_membership_updates,
+            NotificationCategory::EmergencyAccessUpdates => self.emergency_access_updates,
+            NotificationCategory::EmergencyAccessReminders => self.emergency_access_reminders,
+            NotificationCategory::SecurityDigest => self.security_digest,
+        }
+    }
+
+    pub fn is_enabled(&self, category: NotificationCategory) -> bool {
+        self.get(category).unwrap_or_else(|| category.default_enabled())
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "newDeviceLoggedIn": self.is_enabled(NotificationCategory::NewDeviceLoggedIn),
+            "incomplete2faLogin": self.is_enabled(NotificationCategory::Incomplete2faLogin),
+            "orgMembershipUpdates": self.is_enabled(NotificationCategory::OrgMembershipUpdates),
+            "emergencyAccessUpdates": self.is_enabled(NotificationCategory::EmergencyAccessUpdates),
+            "emergencyAccessReminders": self.is_enabled(NotificationCategory::EmergencyAccessReminders),
+            "securityDigest": self.is_enabled(NotificationCategory::SecurityDigest),
+            "object": "notificationPreferences",
+        })
+    }
+}
+
+/// Database methods
+impl NotificationPreference {
+    pub async fn save(&self, conn: &mut DbConn) -> EmptyResult {
+        db_run! { conn:
+            sqlite, mysql {
+                diesel::replace_into(notification_preferences::table)
+                    .values(NotificationPreferenceDb::to_db(self))
+                    .execute(conn)
+                    .map_res("Error saving notification preferences")
+            }
+            postgresql {
+                let value = NotificationPreferenceDb::to_db(self);
+                diesel::insert_into(notification_preferences::table)
+                    .values(&value)
+                    .on_conflict(notification_preferences::user_uuid)
+                    .do_update()
+                    .set(&value)
+                    .execute(conn)
+                    .map_res("Error saving notification preferences")
+            }
+        }
+    }
+
+    pub async fn delete_all_by_user(user_uuid: &str, conn: &mut DbConn) -> EmptyResult {
+        db_run! { conn: {
+            diesel::delete(notification_preferences::table.filter(notification_preferences::user_uuid.eq(user_uuid)))
+                .execute(conn)
+                .map_res("Error deleting notification preferences")
+        }}
+    }
+
+    pub async fn find_all(conn: &mut DbConn) -> Vec<Self> {
+        db_run! { conn: {
+            notification_preferences::table
+                .load::<NotificationPreferenceDb>(conn)
+                .expect("Error loading notification preferences")
+                .from_db()
+        }}
+    }
+
+    pub async fn find_by_user(user_uuid: &str, conn: &mut DbConn) -> Option<Self> {
+        db_run! { conn: {
+            notification_preferences::table
+                .filter(notification_preferences::user_uuid.eq(user_uuid))
+                .first::<NotificationPreferenceDb>(conn)
+                .ok()
+                .from_db()
+        }}
+    }
+
+    /// Returns the stored preferences of a user, or a record which uses the defaults for everything
+    pub async fn find_or_default_by_user(user_uuid: &str, conn: &mut DbConn) -> Self {
+        match Self::find_by_user(user_uuid, conn).await {
+            Some(prefs) => prefs,
+            None => Self::new(user_uuid.to_string()),
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use chrono::{NaiveDateTime, Utc};
+use serde_json::Value;
+
+use crate::{api::EmptyResult, db::DbConn, error::MapResult, util::format_date};
+
+use super::UserOrgType;
+
+db_object! {
+    #[derive(Identifiable, Queryable, Insertable, AsChangeset)]
+    #[diesel(table_name = organization_domains)]
+    #[diesel(treat_none_as_null = true)]
+    #[diesel(primary_key(uuid))]
+    pub struct OrganizationDomain {
+        pub uuid: String,
+        pub org_uuid: String,
+        pub domain_name: String,
+        // The complete value of the TXT record the organization needs to publish on the domain
+        pub txt: String,
+        pub creation_date: NaiveDateTime,
+        pub last_checked_at: Option<NaiveDateTime>,
+        pub verified_at: Option<NaiveDateTime>,
+        pub job_run_count: i32,
+        // New users with an email address in this domain can join the organization automatically
+        pub auto_enroll: bool,
+        pub auto_enroll_type: i32,
+        pub auto_enroll_groups: Option<String>,
+        pub auto_enroll_collections: Option<String>,
+    }
+}
+
+/// A collection automatically enrolled members get access to
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AutoEnrollCollection {
+    pub id: String,
+    #[serde(default)]
+    pub read_only: bool,
+    #[serde(default)]
+    pub hide_passwords: bool,
+    #[serde(default)]
+    pub manage: bool,
+}
+
+/// Local methods
+impl OrganizationDomain {
+    pub fn new(org_uuid: String, domain_name: String) -> Self {
+        Self {
+            uuid: crate::util::get_uuid(),
+            org_uuid,
+            domain_name: domain_name.to_lowercase(),
+            txt: format!("bw={}", crate::crypto::generate_id::<32>()),
+            creation_date: Utc::now().naive_utc(),
+            last_checked_at: None,
+            verified_at: None,
+            job_run_count: 0,
+            auto_enroll: false,
+            auto_enroll_type: UserOrgType::User as i32,
+            auto_enroll_groups: None,
+            auto_enroll_collections: None,
+        }
+    }
+
+    pub fn is_verified(&self) -> bool {
+        self.verified_at.is_some()
+    }
+
+    /// Records the outcome of a verification attempt
+    pub fn checked(&mut self, verified: bool) {
+        let now = Utc::now().naive_utc();
+        self.last_checked_at = Some(now);
+        if verified && self.verified_at.is_none() {
+            self.verified_at = Some(now);
+        }
+    }
+
+    pub fn auto_enroll_groups(&self) -> Vec<String> {
+        self.auto_enroll_groups.as_deref().and_then(|g| serde_json::from_str(g).ok()).unwrap_or_default()
+    }
+
+    pub fn auto_enroll_collections(&self) -> Vec<AutoEnrollCollection> {
+        self.auto_enroll_collections.as_deref().and_then(|c| serde_json::from_str(c).ok()).unwrap_or_default()
+    }
+
+    pub fn set_auto_enroll(
+        &mut self,
+        enabled: bool,
+        atype: i32,
+        groups: &[String],
+        collections: &[AutoEnrollCollection],
+    ) {
+        self.auto_enroll = enabled;
+        self.auto_enroll_type = atype;
+        self.auto_enroll_groups = serde_json::to_string(groups).ok();
+        self.auto_enroll_collections = serde_json::to_string(collections).ok();
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "id": self.uuid,
+            "organizationId": self.org_uuid,
+            "txt": self.txt,
+            "domainName": self.domain_name,
+            "creationDate": format_date(&self.creation_date),
+            "lastCheckedDate": self.last_checked_at.as_ref().map(format_date),
+            "verifiedDate": self.verified_at.as_ref().map(format_date),
+            "jobRunCount": self.job_run_count,
+            "autoEnroll": {
+                "enabled": self.auto_enroll,
+                "type": self.auto_enroll_type,
+                "groups": self.auto_enroll_groups(),
+                "collections": self.auto_enroll_collections(),
+            },
+            "object": "organizationDomain",
+        })
+    }
+}
+
+/// Returns the domain part of an email address, in lowercase
+pub fn email_domain(email: &str) -> Option<String> {
+    email.rsplit_once('@').map(|(_, domain)| domain.to_lowercase()).filter(|d| !d.is_empty())
+}
+
+/// Database methods
+impl OrganizationDomain {
+    pub async fn save(&self, conn: &mut DbConn) -> EmptyResult {
+        db_run! { conn:
+            sqlite, mysql {
+                diesel::replace_into(organization_domains::table)
+                    .values(OrganizationDomainDb::to_db(self))
+                    .execute(conn)
+                    .map_res("Error saving organization domain")
+            }
+            postgresql {
+                let value = OrganizationDomainDb::to_db(self);
+                diesel::insert_into(organization_domains::table)
+                    .values(&value)
+                    .on_conflict(organization_domains::uuid)
+                    .do_update()
+                    .set(&value)
+                    .execute(conn)
+                    .map_res("Error saving organization domain")
+            }
+        }
+    }
+
+    pub async fn delete(self, conn: &mut DbConn) -> EmptyResult {
+        db_run! { conn: {
+            diesel::delete(organization_domains::table.filter(organization_domains::uuid.eq(self.uuid)))
+                .execute(conn)
+                .map_res("Error deleting organization domain")
+        }}
+    }
+
+    pub async fn delete_all_by_organization(org_uuid: &str, conn: &mut DbConn) -> EmptyResult {
+        db_run! { conn: {
+            diesel::delete(organization_domains::table.filter(organization_domains::org_uuid.eq(org_uuid)))
+                .execute(conn)
+                .map_res("Error deleting organization domains")
+        }}
+    }
+
+    pub async fn find_by_uuid_and_org(uuid: &str, org_uuid: &str, conn: &mut DbConn) -> Option<Self> {
+        db_run! { conn: {
+            organization_domains::table
+                .filter(organization_domains::uuid.eq(uuid))
+                .filter(organization_domains::org_uuid.eq(org_uuid))
+                .first::<OrganizationDomainDb>(conn)
+                .ok()
+                .from_db()
+        }}
+    }
+
+    pub async fn find_by_org_and_domain(org_uuid: &str, domain_name: &str, conn: &mut DbConn) -> Option<Self> {
+        db_run! { conn: {
+            organization_domains::table
+                .filter(organization_domains::org_uuid.eq(org_uuid))
+                .filter(organization_domains::domain_name.eq(domain_name.to_lowercase()))
+                .first::<OrganizationDomainDb>(conn)
+                .ok()
+                .from_db()
+        }}
+    }
+
+    pub async fn find_by_org(org_uuid: &str, conn: &mut DbConn) -> Vec<Self> {
+        db_run! { conn: {
+            organization_domains::table
+                .filter(organization_domains::org_uuid.eq(org_uuid))
+                .order(organization_domains::domain_name)
+                .load::<OrganizationDomainDb>(conn)
+                .expect("Error loading organization domains")
+                .from_db()
+        }}
+    }
+
+    /// Returns the domains of all organizations which still need to be verified and were checked less than `max_job_runs` times
+    pub async fn find_unverified(max_job_runs: i32, conn: &mut DbConn) -> Vec<Self> {
+        db_run! { conn: {
+            organization_domains::table
+                .filter(organization_domains::verified_at.is_null())
+                .filter(organization_domains::job_run_count.lt(max_job_runs))
+                .load::<OrganizationDomainDb>(conn)
+                .expect("Error loading organization domains")
+                .from_db()
+        }}
+    }
+
+    /// Returns the verified claims on the given domain, normally there is only one
+    pub async fn find_verified_by_domain(domain_name: &str, conn: &mut DbConn) -> Vec<Self> {
+        db_run! { conn: {
+            organization_domains::table
+                .filter(organization_domains::domain_name.eq(domain_name.to_lowercase()))
+                .filter(organization_domains::verified_at.is_not_null())
+                .load::<OrganizationDomainDb>(conn)
+                .expect("Error loading organization domains")
+                .from_db()
+        }}
+    }
+
+    /// Returns the verified domains which automatically enroll new users with an address in the given domain
+    pub async fn find_auto_enroll_by_domain(domain_name: &str, conn: &mut DbConn) -> Vec<Self> {
+        db_run! { conn: {
+            organization_domains::table
+                .filter(organization_domains::domain_name.eq(domain_name.to_lowercase()))
+                .filter(organization_domains::verified_at.is_not_null())
+                .filter(organization_domains::auto_enroll.eq(true))
+                .load::<OrganizationDomainDb>(conn)
+                .expect("Error loading organization domains")
+                .from_db()
+        }}
+    }
+}
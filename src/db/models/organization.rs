@@ -1,14 +1,16 @@
-use chrono::{NaiveDateTime, Utc};
+use chrono::{NaiveDateTime, TimeDelta, Utc};
 use num_traits::FromPrimitive;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::cmp::Ordering;
 
 use super::{CollectionUser, Group, GroupUser, OrgPolicy, OrgPolicyType, TwoFactor, User};
-use crate::CONFIG;
+use crate::{util::format_date, CONFIG};
 
 db_object! {
     #[derive(Identifiable, Queryable, Insertable, AsChangeset)]
     #[diesel(table_name = organizations)]
+    #[diesel(treat_none_as_null = true)]
     #[diesel(primary_key(uuid))]
     pub struct Organization {
         pub uuid: String,
@@ -16,6 +18,16 @@ db_object! {
         pub billing_email: String,
         pub private_key: Option<String>,
         pub public_key: Option<String>,
+        pub limit_collection_creation_deletion: bool,
+        pub allow_admin_access_to_all_collection_items: bool,
+        pub members_can_edit_items: bool,
+        pub limit_invitations_to_verified_domains: bool,
+        // Set when the organization is scheduled for deletion, it is purged once the grace period has passed
+        pub deleted_at: Option<NaiveDateTime>,
+        // A pending ownership transfer, waiting for the confirmation of the new owner
+        pub transfer_org_user_uuid: Option<String>,
+        pub transfer_initiator_uuid: Option<String>,
+        pub transfer_date: Option<NaiveDateTime>,
     }
 
     #[derive(Identifiable, Queryable, Insertable, AsChangeset)]
@@ -32,6 +44,8 @@ db_object! {
         pub atype: i32,
         pub reset_password_key: Option<String>,
         pub external_id: Option<String>,
+        pub invited_at: Option<NaiveDateTime>,
+        pub permissions: Option<String>,
     }
 
     #[derive(Identifiable, Queryable, Insertable, AsChangeset)]
@@ -60,6 +74,7 @@ pub enum UserOrgType {
     Admin = 1,
     User = 2,
     Manager = 3,
+    Custom = 4,
 }
 
 impl UserOrgType {
@@ -69,19 +84,69 @@ impl UserOrgType {
             "1" | "Admin" => Some(UserOrgType::Admin),
             "2" | "User" => Some(UserOrgType::User),
             "3" | "Manager" => Some(UserOrgType::Manager),
+            "4" | "Custom" => Some(UserOrgType::Custom),
             _ => None,
         }
     }
 }
 
+// The permissions of a member with the `Custom` type, stored as JSON on the membership.
+// Only the flags we are able to enforce are supported, the other ones the clients know about are always false.
+// https://github.com/bitwarden/server/blob/13d1e74d6960cf0d042620b72d85bf583a4236f7/src/Core/Models/Data/Permissions.cs
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct UserOrgPermissions {
+    pub access_event_logs: bool,
+    pub access_import_export: bool,
+    pub manage_groups: bool,
+    pub manage_users: bool,
+    pub manage_policies: bool,
+    // The clients have separate flags to create, edit and delete any collection, we treat them as one
+    #[serde(rename = "editAnyCollection")]
+    pub manage_all_collections: bool,
+    pub manage_reset_password: bool,
+}
+
+impl UserOrgPermissions {
+    pub fn has_any(&self) -> bool {
+        self.access_event_logs
+            || self.access_import_export
+            || self.manage_groups
+            || self.manage_users
+            || self.manage_policies
+            || self.manage_all_collections
+            || self.manage_reset_password
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "accessEventLogs": self.access_event_logs,
+            "accessImportExport": self.access_import_export,
+            "accessReports": false,
+            "createNewCollections": self.manage_all_collections,
+            "editAnyCollection": self.manage_all_collections,
+            "deleteAnyCollection": self.manage_all_collections,
+            "editAssignedCollections": false,
+            "deleteAssignedCollections": false,
+            "manageGroups": self.manage_groups,
+            "managePolicies": self.manage_policies,
+            "manageSso": false, // Not supported
+            "manageUsers": self.manage_users,
+            "manageResetPassword": self.manage_reset_password,
+            "manageScim": false // Not supported (Not AGPLv3 Licensed)
+        })
+    }
+}
+
 impl Ord for UserOrgType {
     fn cmp(&self, other: &UserOrgType) -> Ordering {
         // For easy comparison, map each variant to an access level (where 0 is lowest).
-        static ACCESS_LEVEL: [i32; 4] = [
-            3, // Owner
-            2, // Admin
+        static ACCESS_LEVEL: [i32; 5] = [
+            4, // Owner
+            3, // Admin
             0, // User
-            1, // Manager
+            2, // Manager
+            1, // Custom
         ];
         ACCESS_LEVEL[*self as usize].cmp(&ACCESS_LEVEL[*other as usize])
     }
@@ -148,6 +213,14 @@ impl Organization {
             billing_email,
             private_key,
             public_key,
+            limit_collection_creation_deletion: false,
+            allow_admin_access_to_all_collection_items: true,
+            members_can_edit_items: true,
+            limit_invitations_to_verified_domains: false,
+            deleted_at: None,
+            transfer_org_user_uuid: None,
+            transfer_initiator_uuid: None,
+            transfer_date: None,
         }
     }
     // https://github.com/bitwarden/server/blob/13d1e74d6960cf0d042620b72d85bf583a4236f7/src/Api/Models/Response/Organizations/OrganizationResponseModel.cs
@@ -161,7 +234,7 @@ impl Organization {
             "maxCollections": null,
             "maxStorageGb": i16::MAX, // The value doesn't matter, we don't check server-side
             "use2fa": true,
-            "useCustomPermissions": false,
+            "useCustomPermissions": true,
             "useDirectory": false, // Is supported, but this value isn't checked anywhere (yet)
             "useEvents": CONFIG.org_events_enabled(),
             "useGroups": CONFIG.org_groups_enabled(),
@@ -185,9 +258,66 @@ impl Organization {
             "billingEmail": self.billing_email,
             "planType": 6, // Custom plan
             "usersGetPremium": true,
+            "limitCollectionCreationDeletion": self.limit_collection_creation_deletion,
+            "allowAdminAccessToAllCollectionItems": self.allow_admin_access_to_all_collection_items,
+            "membersCanEditItems": self.members_can_edit_items,
+            "limitInvitationsToVerifiedDomains": self.limit_invitations_to_verified_domains,
+            "deletionDate": self.purge_date().as_ref().map(format_date),
             "object": "organization",
         })
     }
+
+    pub fn is_pending_deletion(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    /// The date on which an organization scheduled for deletion gets purged
+    pub fn purge_date(&self) -> Option<NaiveDateTime> {
+        self.deleted_at.map(|d| d + TimeDelta::try_days(CONFIG.org_deletion_grace_days()).unwrap())
+    }
+
+    pub fn set_transfer(&mut self, org_user_uuid: &str, initiator_uuid: &str) {
+        self.transfer_org_user_uuid = Some(org_user_uuid.to_string());
+        self.transfer_initiator_uuid = Some(initiator_uuid.to_string());
+        self.transfer_date = Some(Utc::now().naive_utc());
+    }
+
+    pub fn clear_transfer(&mut self) {
+        self.transfer_org_user_uuid = None;
+        self.transfer_initiator_uuid = None;
+        self.transfer_date = None;
+    }
+
+    pub fn transfer_to_json(&self) -> Value {
+        json!({
+            "organizationId": self.uuid,
+            "newOwnerId": self.transfer_org_user_uuid,
+            "initiatorId": self.transfer_initiator_uuid,
+            "creationDate": self.transfer_date.as_ref().map(format_date),
+            "object": "organizationOwnershipTransfer",
+        })
+    }
+
+    /// Owners and Admins only have access to all items if the organization allows it, other members need `access_all`
+    pub fn has_full_item_access(&self, user_org: &UserOrganization) -> bool {
+        user_org.has_status(UserOrgStatus::Confirmed)
+            && (user_org.access_all
+                || (user_org.atype >= UserOrgType::Admin && self.allow_admin_access_to_all_collection_items))
+    }
+
+    /// Members who can't manage collections may be limited to read-only access to the items of the organization
+    pub fn restricts_item_editing(&self, user_org: &UserOrganization) -> bool {
+        !self.members_can_edit_items
+            && user_org.atype < UserOrgType::Manager
+            && !user_org.permissions().manage_all_collections
+    }
+
+    /// Creating and deleting collections can be limited to Owners, Admins and Custom roles which can manage all collections
+    pub fn can_create_delete_collections(&self, org_user: &UserOrganization) -> bool {
+        !self.limit_collection_creation_deletion
+            || org_user.atype >= UserOrgType::Admin
+            || org_user.permissions().manage_all_collections
+    }
 }
 
 // Used to either subtract or add to the current status
@@ -210,9 +340,37 @@ impl UserOrganization {
             atype: UserOrgType::User as i32,
             reset_password_key: None,
             external_id: None,
+            invited_at: Some(Utc::now().naive_utc()),
+            permissions: None,
         }
     }
 
+    /// The custom permissions of this member, which are only granted when the member has the `Custom` type
+    pub fn permissions(&self) -> UserOrgPermissions {
+        if self.atype != UserOrgType::Custom {
+            return UserOrgPermissions::default();
+        }
+        self.permissions.as_deref().and_then(|p| serde_json::from_str(p).ok()).unwrap_or_default()
+    }
+
+    pub fn set_permissions(&mut self, permissions: Option<UserOrgPermissions>) {
+        self.permissions = match permissions {
+            Some(permissions) if self.atype == UserOrgType::Custom => serde_json::to_string(&permissions).ok(),
+            _ => None,
+        };
+    }
+
+    /// Whether this is an invitation which was not accepted within `INVITATION_EXPIRATION_HOURS`
+    pub fn is_invitation_expired(&self) -> bool {
+        self.status == UserOrgStatus::Invited as i32
+            && self.invited_at.is_some_and(|invited_at| invited_at < Self::invitation_expiration_cutoff())
+    }
+
+    /// Invitations sent before this date are expired
+    pub fn invitation_expiration_cutoff() -> NaiveDateTime {
+        Utc::now().naive_utc() - TimeDelta::try_hours(CONFIG.invitation_expiration_hours().into()).unwrap()
+    }
+
     pub fn restore(&mut self) -> bool {
         if self.status < UserOrgStatus::Invited as i32 {
             self.status += ACTIVATE_REVOKE_DIFF;
@@ -309,7 +467,7 @@ impl Organization {
     }
 
     pub async fn delete(self, conn: &mut DbConn) -> EmptyResult {
-        use super::{Cipher, Collection};
+        use super::{Cipher, Collection, OrganizationDomain};
 
         Cipher::delete_all_by_organization(&self.uuid, conn).await?;
         Collection::delete_all_by_organization(&self.uuid, conn).await?;
@@ -317,6 +475,7 @@ impl Organization {
         OrgPolicy::delete_all_by_organization(&self.uuid, conn).await?;
         Group::delete_all_by_organization(&self.uuid, conn).await?;
         OrganizationApiKey::delete_all_by_organization(&self.uuid, conn).await?;
+        OrganizationDomain::delete_all_by_organization(&self.uuid, conn).await?;
 
         db_run! { conn: {
             diesel::delete(organizations::table.filter(organizations::uuid.eq(self.uuid)))
@@ -334,36 +493,53 @@ impl Organization {
         }}
     }
 
+    pub async fn find_by_uuids(uuids: &[String], conn: &mut DbConn) -> Vec<Self> {
+        db_run! { conn: {
+            organizations::table
+                .filter(organizations::uuid.eq_any(uuids))
+                .load::<OrganizationDb>(conn)
+                .expect("Error loading organizations")
+                .from_db()
+        }}
+    }
+
     pub async fn get_all(conn: &mut DbConn) -> Vec<Self> {
         db_run! { conn: {
             organizations::table.load::<OrganizationDb>(conn).expect("Error loading organizations").from_db()
         }}
     }
+
+    /// Cancels the pending ownership transfer of the organization when the member who started it, or who would receive it,
+    /// changes role or leaves the organization
+    pub async fn cancel_transfer_of_member(user_org: &UserOrganization, conn: &mut DbConn) -> EmptyResult {
+        let Some(mut org) = Self::find_by_uuid(&user_org.org_uuid, conn).await else {
+            return Ok(());
+        };
+        if org.transfer_initiator_uuid.as_ref() == Some(&user_org.user_uuid)
+            || org.transfer_org_user_uuid.as_ref() == Some(&user_org.uuid)
+        {
+            org.clear_transfer();
+            org.save(conn).await?;
+        }
+        Ok(())
+    }
+
+    /// Returns the organizations scheduled for deletion before the given date
+    pub async fn find_deleted_before(dt: &NaiveDateTime, conn: &mut DbConn) -> Vec<Self> {
+        db_run! { conn: {
+            organizations::table
+                .filter(organizations::deleted_at.le(dt))
+                .load::<OrganizationDb>(conn)
+                .expect("Error loading organizations")
+                .from_db()
+        }}
+    }
 }
 
 impl UserOrganization {
     pub async fn to_json(&self, conn: &mut DbConn) -> Value {
         let org = Organization::find_by_uuid(&self.org_uuid, conn).await.unwrap();
 
-        let permissions = json!({
-                // TODO: Add support for Custom User Roles
-                // See: https://bitwarden.com/help/article/user-types-access-control/#custom-role
-                "accessEventLogs": false,
-                "accessImportExport": false,
-                "accessReports": false,
-                "createNewCollections": false,
-                "editAnyCollection": false,
-                "deleteAnyCollection": false,
-                "editAssignedCollections": false,
-                "deleteAssignedCollections": false,
-                "manageGroups": false,
-                "managePolicies": false,
-                "manageSso": false, // Not supported
-                "manageUsers": false,
-                "manageResetPassword": false,
-                "manageScim": false // Not supported (Not AGPLv3 Licensed)
-        });
-
         // https://github.com/bitwarden/server/blob/13d1e74d6960cf0d042620b72d85bf583a4236f7/src/Api/Models/Response/ProfileOrganizationResponseModel.cs
         json!({
             "id": self.org_uuid,
@@ -390,7 +566,7 @@ impl UserOrganization {
             "useKeyConnector": false,
             "useSecretsManager": false,
             "usePasswordManager": true,
-            "useCustomPermissions": false,
+            "useCustomPermissions": true,
             "useActivateAutofillPolicy": false,
 
             "organizationUserId": self.uuid,
@@ -407,11 +583,12 @@ impl UserOrganization {
             "familySponsorshipValidUntil": null,
             "familySponsorshipToDelete": null,
             "accessSecretsManager": false,
-            "limitCollectionCreationDeletion": true,
-            "allowAdminAccessToAllCollectionItems": true,
+            "limitCollectionCreationDeletion": org.limit_collection_creation_deletion,
+            "allowAdminAccessToAllCollectionItems": org.allow_admin_access_to_all_collection_items,
+            "membersCanEditItems": org.members_can_edit_items,
             "flexibleCollections": false,
 
-            "permissions": permissions,
+            "permissions": self.permissions().to_json(),
 
             "maxStorageGb": i16::MAX, // The value doesn't matter, we don't check server-side
 
@@ -420,7 +597,7 @@ impl UserOrganization {
             "key": self.akey,
             "status": self.status,
             "type": self.atype,
-            "enabled": true,
+            "enabled": !org.is_pending_deletion(),
 
             "object": "profileOrganization",
         })
@@ -461,6 +638,7 @@ impl UserOrganization {
                         "id": cu.collection_uuid,
                         "readOnly": cu.read_only,
                         "hidePasswords": cu.hide_passwords,
+                        "manage": cu.manage,
                     })
                 })
                 .collect()
@@ -480,8 +658,10 @@ impl UserOrganization {
             "status": status,
             "type": self.atype,
             "accessAll": self.access_all,
+            "permissions": self.permissions().to_json(),
             "twoFactorEnabled": twofactor_enabled,
             "resetPasswordEnrolled": self.reset_password_key.is_some(),
+            "invitationExpired": self.is_invitation_expired(),
 
             "object": "organizationUserUserDetails",
         })
@@ -492,6 +672,7 @@ impl UserOrganization {
             "id": self.uuid,
             "readOnly": col_user.read_only,
             "hidePasswords": col_user.hide_passwords,
+            "manage": col_user.manage,
         })
     }
 
@@ -508,6 +689,7 @@ impl UserOrganization {
                         "id": c.collection_uuid,
                         "readOnly": c.read_only,
                         "hidePasswords": c.hide_passwords,
+                        "manage": c.manage,
                     })
                 })
                 .collect()
@@ -528,6 +710,7 @@ impl UserOrganization {
             "status": status,
             "type": self.atype,
             "accessAll": self.access_all,
+            "permissions": self.permissions().to_json(),
             "collections": coll_uuids,
 
             "object": "organizationUserDetails",
@@ -612,10 +795,6 @@ impl UserOrganization {
         self.atype == user_type as i32
     }
 
-    pub fn has_full_access(&self) -> bool {
-        (self.access_all || self.atype >= UserOrgType::Admin) && self.has_status(UserOrgStatus::Confirmed)
-    }
-
     pub async fn find_by_uuid(uuid: &str, conn: &mut DbConn) -> Option<Self> {
         db_run! { conn: {
             users_organizations::table
@@ -664,6 +843,18 @@ impl UserOrganization {
         }}
     }
 
+    /// Returns the uuids of the users who are an owner of any organization, in any state
+    pub async fn find_owner_user_uuids(conn: &mut DbConn) -> Vec<String> {
+        db_run! { conn: {
+            users_organizations::table
+                .filter(users_organizations::atype.eq(UserOrgType::Owner as i32))
+                .select(users_organizations::user_uuid)
+                .distinct()
+                .load::<String>(conn)
+                .unwrap_or_default()
+        }}
+    }
+
     pub async fn count_accepted_and_confirmed_by_user(user_uuid: &str, conn: &mut DbConn) -> i64 {
         db_run! { conn: {
             users_organizations::table
@@ -675,10 +866,31 @@ impl UserOrganization {
         }}
     }
 
+    /// Finds the invitations which were sent before the given date, optionally limited to one organization
+    pub async fn find_invited_before(dt: &NaiveDateTime, org_uuid: Option<&str>, conn: &mut DbConn) -> Vec<Self> {
+        db_run! { conn: {
+            let mut query = users_organizations::table
+                .filter(users_organizations::status.eq(UserOrgStatus::Invited as i32))
+                .filter(users_organizations::invited_at.lt(dt))
+                .into_boxed();
+            if let Some(org_uuid) = org_uuid {
+                query = query.filter(users_organizations::org_uuid.eq(org_uuid));
+            }
+            query
+                .load::<UserOrganizationDb>(conn)
+                .expect("Error loading user organizations")
+                .from_db()
+        }}
+    }
+
+    /// Returns the members of the organization, in any state. Members whose account is deleted are left out.
     pub async fn find_by_org(org_uuid: &str, conn: &mut DbConn) -> Vec<Self> {
         db_run! { conn: {
             users_organizations::table
+                .inner_join(users::table.on(users::uuid.eq(users_organizations::user_uuid)))
                 .filter(users_organizations::org_uuid.eq(org_uuid))
+                .filter(users::deleted_at.is_null())
+                .select(users_organizations::all_columns)
                 .load::<UserOrganizationDb>(conn)
                 .expect("Error loading user organizations").from_db()
         }}
@@ -687,8 +899,11 @@ impl UserOrganization {
     pub async fn find_confirmed_by_org(org_uuid: &str, conn: &mut DbConn) -> Vec<Self> {
         db_run! { conn: {
             users_organizations::table
+                .inner_join(users::table.on(users::uuid.eq(users_organizations::user_uuid)))
                 .filter(users_organizations::org_uuid.eq(org_uuid))
                 .filter(users_organizations::status.eq(UserOrgStatus::Confirmed as i32))
+                .filter(users::deleted_at.is_null())
+                .select(users_organizations::all_columns)
                 .load::<UserOrganizationDb>(conn)
                 .unwrap_or_default().from_db()
         }}
@@ -697,7 +912,9 @@ impl UserOrganization {
     pub async fn count_by_org(org_uuid: &str, conn: &mut DbConn) -> i64 {
         db_run! { conn: {
             users_organizations::table
+                .inner_join(users::table.on(users::uuid.eq(users_organizations::user_uuid)))
                 .filter(users_organizations::org_uuid.eq(org_uuid))
+                .filter(users::deleted_at.is_null())
                 .count()
                 .first::<i64>(conn)
                 .ok()
@@ -708,8 +925,11 @@ impl UserOrganization {
     pub async fn find_by_org_and_type(org_uuid: &str, atype: UserOrgType, conn: &mut DbConn) -> Vec<Self> {
         db_run! { conn: {
             users_organizations::table
+                .inner_join(users::table.on(users::uuid.eq(users_organizations::user_uuid)))
                 .filter(users_organizations::org_uuid.eq(org_uuid))
                 .filter(users_organizations::atype.eq(atype as i32))
+                .filter(users::deleted_at.is_null())
+                .select(users_organizations::all_columns)
                 .load::<UserOrganizationDb>(conn)
                 .expect("Error loading user organizations").from_db()
         }}
@@ -718,9 +938,11 @@ impl UserOrganization {
     pub async fn count_confirmed_by_org_and_type(org_uuid: &str, atype: UserOrgType, conn: &mut DbConn) -> i64 {
         db_run! { conn: {
             users_organizations::table
+                .inner_join(users::table.on(users::uuid.eq(users_organizations::user_uuid)))
                 .filter(users_organizations::org_uuid.eq(org_uuid))
                 .filter(users_organizations::atype.eq(atype as i32))
                 .filter(users_organizations::status.eq(UserOrgStatus::Confirmed as i32))
+                .filter(users::deleted_at.is_null())
                 .count()
                 .first::<i64>(conn)
                 .unwrap_or(0)
@@ -926,6 +1148,61 @@ mod tests {
     fn partial_cmp_UserOrgType() {
         assert!(UserOrgType::Owner > UserOrgType::Admin);
         assert!(UserOrgType::Admin > UserOrgType::Manager);
-        assert!(UserOrgType::Manager > UserOrgType::User);
+        assert!(UserOrgType::Manager > UserOrgType::Custom);
+        assert!(UserOrgType::Custom > UserOrgType::User);
+    }
+
+    #[test]
+    fn custom_permissions() {
+        let mut user_org = UserOrganization::new("user".to_string(), "org".to_string());
+        user_org.atype = UserOrgType::Custom as i32;
+
+        // The clients send more flags than we support, and use a different name for managing collections
+        let permissions: UserOrgPermissions = serde_json::from_value(json!({
+            "manageGroups": true,
+            "editAnyCollection": true,
+            "accessReports": true,
+        }))
+        .unwrap();
+        assert!(permissions.has_any());
+        user_org.set_permissions(Some(permissions));
+
+        let permissions = user_org.permissions();
+        assert!(permissions.manage_groups && permissions.manage_all_collections);
+        assert!(!permissions.manage_users && !permissions.access_event_logs);
+        let json = permissions.to_json();
+        assert_eq!(json["createNewCollections"], true);
+        assert_eq!(json["deleteAnyCollection"], true);
+        assert_eq!(json["accessReports"], false);
+
+        // Invalid stored permissions don't grant anything
+        user_org.permissions = Some("not json".to_string());
+        assert!(!user_org.permissions().has_any());
+    }
+
+    #[test]
+    fn custom_permissions_only_for_custom_type() {
+        let all = UserOrgPermissions {
+            access_event_logs: true,
+            access_import_export: true,
+            manage_groups: true,
+            manage_users: true,
+            manage_policies: true,
+            manage_all_collections: true,
+            manage_reset_password: true,
+        };
+
+        for atype in [UserOrgType::Owner, UserOrgType::Admin, UserOrgType::Manager, UserOrgType::User] {
+            let mut user_org = UserOrganization::new("user".to_string(), "org".to_string());
+            user_org.atype = atype as i32;
+            user_org.set_permissions(Some(all.clone()));
+            assert_eq!(user_org.permissions, None);
+
+            // Permissions which were stored while the member had the Custom type are ignored as well
+            user_org.permissions = serde_json::to_string(&all).ok();
+            assert!(!user_org.permissions().has_any());
+        }
+
+        assert!(!UserOrgPermissions::default().has_any());
     }
 }
@@ -237,10 +237,15 @@ impl Send {
     }
 
     /// Purge all sends that are past their deletion date.
-    pub async fn purge(conn: &mut DbConn) {
+    /// Returns the number of sends that were deleted.
+    pub async fn purge(conn: &mut DbConn) -> usize {
+        let mut purged = 0;
         for send in Self::find_by_past_deletion_date(conn).await {
-            send.delete(conn).await.ok();
+            if send.delete(conn).await.is_ok() {
+                purged += 1;
+            }
         }
+        purged
     }
 
     pub async fn update_users_revision(&self, conn: &mut DbConn) -> Vec<String> {
@@ -257,6 +262,14 @@ impl Send {
         user_uuids
     }
 
+    /// The Sends of a deleted account can't be accessed until the account is restored
+    pub async fn is_owner_deleted(&self, conn: &mut DbConn) -> bool {
+        match &self.user_uuid {
+            Some(user_uuid) => User::find_by_uuid(user_uuid, conn).await.is_some_and(|user| user.is_deleted()),
+            None => false,
+        }
+    }
+
     pub async fn delete_all_by_user(user_uuid: &str, conn: &mut DbConn) -> EmptyResult {
         for send in Self::find_by_user(user_uuid, conn).await {
             send.delete(conn).await?;
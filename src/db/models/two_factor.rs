@@ -129,6 +129,21 @@ impl TwoFactor {
         }}
     }
 
+    /// Returns the enabled providers of all the members of the organization
+    pub async fn find_enabled_by_org(org_uuid: &str, conn: &mut DbConn) -> Vec<Self> {
+        db_run! { conn: {
+            twofactor::table
+                .inner_join(users_organizations::table.on(users_organizations::user_uuid.eq(twofactor::user_uuid)))
+                .filter(users_organizations::org_uuid.eq(org_uuid))
+                .filter(twofactor::enabled.eq(true))
+                .filter(twofactor::atype.lt(1000)) // Filter implementation types
+                .select(twofactor::all_columns)
+                .load::<TwoFactorDb>(conn)
+                .expect("Error loading twofactor")
+                .from_db()
+        }}
+    }
+
     pub async fn find_by_user_and_type(user_uuid: &str, atype: i32, conn: &mut DbConn) -> Option<Self> {
         db_run! { conn: {
             twofactor::table
@@ -76,9 +76,13 @@ impl TwoFactorDuoContext {
         }
     }
 
-    pub async fn purge_expired_duo_contexts(conn: &mut DbConn) {
+    pub async fn purge_expired_duo_contexts(conn: &mut DbConn) -> usize {
+        let mut purged = 0;
         for context in Self::find_expired(conn).await {
-            context.delete(conn).await.ok();
+            if context.delete(conn).await.is_ok() {
+                purged += 1;
+            }
         }
+        purged
     }
 }
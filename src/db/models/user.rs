@@ -52,6 +52,18 @@ db_object! {
         pub avatar_color: Option<String>,
 
         pub external_id: Option<String>, // Todo: Needs to be removed in the future, this is not used anymore.
+
+        pub lifecycle_warned_at: Option<NaiveDateTime>,
+        pub lifecycle_disabled_at: Option<NaiveDateTime>,
+
+        pub locale: Option<String>,
+
+        pub pgp_public_key: Option<String>,
+        // When the user was told their OpenPGP public key can't be used anymore, this is reset when the key changes
+        pub pgp_key_unusable_notified_at: Option<NaiveDateTime>,
+
+        // Set when the account is deleted, it is purged once the retention period has passed
+        pub deleted_at: Option<NaiveDateTime>,
     }
 
     #[derive(Identifiable, Queryable, Insertable)]
@@ -73,6 +85,24 @@ enum UserStatus {
     _Disabled = 2,
 }
 
+/// The actions taken by the account lifecycle job on unverified or inactive accounts, in this order.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum UserLifecycleAction {
+    Warn,
+    Disable,
+    Delete,
+}
+
+impl UserLifecycleAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Warn => "Warning email",
+            Self::Disable => "Disable",
+            Self::Delete => "Delete",
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct UserStampException {
     pub routes: Vec<String>,
@@ -130,6 +160,15 @@ impl User {
             avatar_color: None,
 
             external_id: None, // Todo: Needs to be removed in the future, this is not used anymore.
+
+            lifecycle_warned_at: None,
+            lifecycle_disabled_at: None,
+
+            locale: None,
+
+            pgp_public_key: None,
+            pgp_key_unusable_notified_at: None,
+            deleted_at: None,
         }
     }
 
@@ -211,11 +250,90 @@ impl User {
     pub fn reset_stamp_exception(&mut self) {
         self.stamp_exception = None;
     }
+
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    /// The date on which a deleted account gets purged
+    pub fn purge_date(&self) -> Option<NaiveDateTime> {
+        self.deleted_at.map(|d| d + TimeDelta::try_days(CONFIG.user_deletion_retention_days()).unwrap())
+    }
+
+    /// Returns the next action the account lifecycle job will take on this account, and the date it is due.
+    /// `last_active` is the last time the user was active, see [`User::last_active`].
+    /// Note that organization owners are never acted upon, this needs to be checked separately.
+    pub fn next_lifecycle_action(
+        &self,
+        last_active: Option<NaiveDateTime>,
+    ) -> Option<(UserLifecycleAction, NaiveDateTime)> {
+        self.lifecycle_action(last_active, &LifecycleSettings::from_config())
+    }
+
+    fn lifecycle_action(
+        &self,
+        last_active: Option<NaiveDateTime>,
+        settings: &LifecycleSettings,
+    ) -> Option<(UserLifecycleAction, NaiveDateTime)> {
+        // Invited users did not create their account yet, and deleted accounts are purged separately
+        if self.password_hash.is_empty() || self.is_deleted() {
+            return None;
+        }
+
+        if let Some(disabled_at) = self.lifecycle_disabled_at {
+            let days = settings.delete_days?;
+            return Some((UserLifecycleAction::Delete, disabled_at + TimeDelta::try_days(days).unwrap()));
+        }
+
+        // Leave accounts which were disabled by an admin alone,
+        // and never disable an account without warning the user first, which needs mail
+        if !self.enabled || !settings.mail_enabled {
+            return None;
+        }
+
+        let unverified_stale_at = match (settings.unverified_days, self.verified_at) {
+            (Some(days), None) => Some(self.created_at + TimeDelta::try_days(days).unwrap()),
+            _ => None,
+        };
+        let inactive_stale_at = settings
+            .inactive_days
+            .map(|days| last_active.unwrap_or(self.created_at) + TimeDelta::try_days(days).unwrap());
+        let stale_at = [unverified_stale_at, inactive_stale_at].into_iter().flatten().min()?;
+
+        // A warning which was sent before the account became stale again, for example because the user logged in since, doesn't count
+        match self.lifecycle_warned_at {
+            Some(warned_at) if warned_at >= stale_at => {
+                Some((UserLifecycleAction::Disable, warned_at + TimeDelta::try_days(settings.warning_days).unwrap()))
+            }
+            _ => Some((UserLifecycleAction::Warn, stale_at)),
+        }
+    }
+}
+
+/// The configuration used by [`User::next_lifecycle_action`]
+struct LifecycleSettings {
+    mail_enabled: bool,
+    unverified_days: Option<i64>,
+    inactive_days: Option<i64>,
+    warning_days: i64,
+    delete_days: Option<i64>,
+}
+
+impl LifecycleSettings {
+    fn from_config() -> Self {
+        Self {
+            mail_enabled: CONFIG.mail_enabled(),
+            unverified_days: CONFIG.unverified_account_days(),
+            inactive_days: CONFIG.inactive_account_days(),
+            warning_days: CONFIG.account_lifecycle_warning_days(),
+            delete_days: CONFIG.account_lifecycle_delete_days(),
+        }
+    }
 }
 
 use super::{
-    Cipher, Device, EmergencyAccess, Favorite, Folder, Send, TwoFactor, TwoFactorIncomplete, UserOrgType,
-    UserOrganization,
+    Cipher, Device, EmergencyAccess, Favorite, Folder, NotificationPreference, Send, TwoFactor, TwoFactorIncomplete,
+    UserOrgType, UserOrganization,
 };
 use crate::db::DbConn;
 
@@ -248,7 +366,7 @@ impl User {
             "premium": true,
             "premiumFromOrganization": false,
             "masterPasswordHint": self.password_hint,
-            "culture": "en-US",
+            "culture": self.locale.as_deref().unwrap_or("en-US"),
             "twoFactorEnabled": twofactor_enabled,
             "key": self.akey,
             "privateKey": self.private_key,
@@ -303,10 +421,18 @@ impl User {
 
     pub async fn delete(self, conn: &mut DbConn) -> EmptyResult {
         for user_org in UserOrganization::find_confirmed_by_user(&self.uuid, conn).await {
-            if user_org.atype == UserOrgType::Owner
-                && UserOrganization::count_confirmed_by_org_and_type(&user_org.org_uuid, UserOrgType::Owner, conn).await
-                    <= 1
-            {
+            if user_org.atype != UserOrgType::Owner {
+                continue;
+            }
+            // Deleted accounts aren't counted as owners, so only an account which isn't deleted yet counts itself
+            let owners =
+                UserOrganization::count_confirmed_by_org_and_type(&user_org.org_uuid, UserOrgType::Owner, conn).await;
+            let other_owners = if self.is_deleted() {
+                owners
+            } else {
+                owners - 1
+            };
+            if other_owners < 1 {
                 err!("Can't delete last owner")
             }
         }
@@ -321,6 +447,7 @@ impl User {
         Device::delete_all_by_user(&self.uuid, conn).await?;
         TwoFactor::delete_all_by_user(&self.uuid, conn).await?;
         TwoFactorIncomplete::delete_all_by_user(&self.uuid, conn).await?;
+        NotificationPreference::delete_all_by_user(&self.uuid, conn).await?;
         Invitation::take(&self.email, conn).await; // Delete invitation if any
 
         db_run! {conn: {
@@ -366,6 +493,15 @@ impl User {
         }}
     }
 
+    pub async fn set_pgp_key_unusable_notified(uuid: &str, date: &NaiveDateTime, conn: &mut DbConn) -> EmptyResult {
+        db_run! {conn: {
+            diesel::update(users::table.filter(users::uuid.eq(uuid)))
+                .set(users::pgp_key_unusable_notified_at.eq(date))
+                .execute(conn)
+                .map_res("Error updating user")
+        }}
+    }
+
     pub async fn find_by_mail(mail: &str, conn: &mut DbConn) -> Option<Self> {
         let lower_mail = mail.to_lowercase();
         db_run! {conn: {
@@ -389,6 +525,31 @@ impl User {
         }}
     }
 
+    /// Returns the accounts which were deleted before the given date
+    pub async fn find_deleted_before(dt: &NaiveDateTime, conn: &mut DbConn) -> Vec<Self> {
+        db_run! {conn: {
+            users::table
+                .filter(users::deleted_at.le(dt))
+                .load::<UserDb>(conn)
+                .expect("Error loading users")
+                .from_db()
+        }}
+    }
+
+    /// Returns all the users which are a member of the organization, in any state. Deleted accounts are left out.
+    pub async fn find_by_org(org_uuid: &str, conn: &mut DbConn) -> Vec<Self> {
+        db_run! {conn: {
+            users::table
+                .inner_join(users_organizations::table.on(users_organizations::user_uuid.eq(users::uuid)))
+                .filter(users_organizations::org_uuid.eq(org_uuid))
+                .filter(users::deleted_at.is_null())
+                .select(users::all_columns)
+                .load::<UserDb>(conn)
+                .expect("Error loading users of organization")
+                .from_db()
+        }}
+    }
+
     pub async fn last_active(&self, conn: &mut DbConn) -> Option<NaiveDateTime> {
         match Device::find_latest_active_by_user(&self.uuid, conn).await {
             Some(device) => Some(device.updated_at),
@@ -456,3 +617,111 @@ impl Invitation {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> LifecycleSettings {
+        LifecycleSettings {
+            mail_enabled: true,
+            unverified_days: Some(7),
+            inactive_days: Some(90),
+            warning_days: 14,
+            delete_days: Some(30),
+        }
+    }
+
+    // A verified account with a password, created at a fixed date
+    fn user() -> User {
+        crate::api::tests::init_test_config();
+        let created_at = NaiveDateTime::parse_from_str("2024-01-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let mut user = User::new(String::from("user@example.com"));
+        user.password_hash = vec![1];
+        user.created_at = created_at;
+        user.verified_at = Some(created_at);
+        user.lifecycle_warned_at = None;
+        user.lifecycle_disabled_at = None;
+        user.enabled = true;
+        user
+    }
+
+    #[test]
+    fn lifecycle_action_warns_stale_accounts() {
+        let user = user();
+        let last_active = user.created_at + TimeDelta::try_days(10).unwrap();
+
+        let (action, due) = user.lifecycle_action(Some(last_active), &settings()).unwrap();
+        assert_eq!(action, UserLifecycleAction::Warn);
+        assert_eq!(due, last_active + TimeDelta::try_days(90).unwrap());
+
+        let mut unverified = user;
+        unverified.verified_at = None;
+        let (action, due) = unverified.lifecycle_action(Some(last_active), &settings()).unwrap();
+        assert_eq!(action, UserLifecycleAction::Warn);
+        assert_eq!(due, unverified.created_at + TimeDelta::try_days(7).unwrap());
+    }
+
+    #[test]
+    fn lifecycle_action_disables_after_warning() {
+        let mut user = user();
+        let warned_at = user.created_at + TimeDelta::try_days(100).unwrap();
+        user.lifecycle_warned_at = Some(warned_at);
+
+        let (action, due) = user.lifecycle_action(None, &settings()).unwrap();
+        assert_eq!(action, UserLifecycleAction::Disable);
+        assert_eq!(due, warned_at + TimeDelta::try_days(14).unwrap());
+
+        // The user was active again after the warning, so a new warning is needed
+        let last_active = warned_at + TimeDelta::try_days(1).unwrap();
+        let (action, _) = user.lifecycle_action(Some(last_active), &settings()).unwrap();
+        assert_eq!(action, UserLifecycleAction::Warn);
+    }
+
+    #[test]
+    fn lifecycle_action_deletes_disabled_accounts() {
+        let mut user = user();
+        let disabled_at = user.created_at + TimeDelta::try_days(120).unwrap();
+        user.enabled = false;
+        user.lifecycle_disabled_at = Some(disabled_at);
+
+        let (action, due) = user.lifecycle_action(None, &settings()).unwrap();
+        assert_eq!(action, UserLifecycleAction::Delete);
+        assert_eq!(due, disabled_at + TimeDelta::try_days(30).unwrap());
+
+        let keep = LifecycleSettings {
+            delete_days: None,
+            ..settings()
+        };
+        assert_eq!(user.lifecycle_action(None, &keep), None);
+    }
+
+    #[test]
+    fn lifecycle_action_skips_accounts() {
+        let mut invited = user();
+        invited.password_hash = Vec::new();
+        assert_eq!(invited.lifecycle_action(None, &settings()), None);
+
+        let mut disabled_by_admin = user();
+        disabled_by_admin.enabled = false;
+        assert_eq!(disabled_by_admin.lifecycle_action(None, &settings()), None);
+
+        let mut deleted = user();
+        deleted.deleted_at = Some(deleted.created_at);
+        assert_eq!(deleted.lifecycle_action(None, &settings()), None);
+
+        // Stale accounts can't be warned without mail, so they are never disabled
+        let no_mail = LifecycleSettings {
+            mail_enabled: false,
+            ..settings()
+        };
+        assert_eq!(user().lifecycle_action(None, &no_mail), None);
+
+        let nothing = LifecycleSettings {
+            unverified_days: None,
+            inactive_days: None,
+            ..settings()
+        };
+        assert_eq!(user().lifecycle_action(None, &nothing), None);
+    }
+}
@@ -0,0 +1,92 @@
+use chrono::{NaiveDateTime, Utc};
+
+use crate::{api::EmptyResult, db::DbConn, error::MapResult, util::INSTANCE_ID};
+
+db_object! {
+    #[derive(Identifiable, Queryable)]
+    #[diesel(table_name = ws_broadcasts)]
+    #[diesel(primary_key(id))]
+    pub struct WsBroadcast {
+        // Assigned by the database, the other instances use it to keep track of the broadcasts they have seen
+        pub id: i64,
+        pub origin: String,
+        pub anonymous: bool,
+        pub target: String,
+        pub data: Vec<u8>,
+        pub created_at: NaiveDateTime,
+    }
+}
+
+/// Database methods
+impl WsBroadcast {
+    /// Stores a broadcast of an already serialized WebSocket message.
+    /// The `target` is a user uuid, or the auth request uuid for anonymous subscriptions.
+    /// On PostgreSQL the instances listening on the `ws_broadcasts` channel are notified as well.
+    /// The message itself isn't part of the notification, it could be larger than what NOTIFY allows.
+    pub async fn create(anonymous: bool, target: &str, data: &[u8], conn: &mut DbConn) -> EmptyResult {
+        let now = Utc::now().naive_utc();
+        db_run! { conn:
+            sqlite, mysql {
+                diesel::insert_into(ws_broadcasts::table)
+                    .values((
+                        ws_broadcasts::origin.eq(&*INSTANCE_ID),
+                        ws_broadcasts::anonymous.eq(anonymous),
+                        ws_broadcasts::target.eq(target),
+                        ws_broadcasts::data.eq(data),
+                        ws_broadcasts::created_at.eq(now),
+                    ))
+                    .execute(conn)
+                    .map_res("Error saving WebSocket broadcast")
+            }
+            postgresql {
+                diesel::insert_into(ws_broadcasts::table)
+                    .values((
+                        ws_broadcasts::origin.eq(&*INSTANCE_ID),
+                        ws_broadcasts::anonymous.eq(anonymous),
+                        ws_broadcasts::target.eq(target),
+                        ws_broadcasts::data.eq(data),
+                        ws_broadcasts::created_at.eq(now),
+                    ))
+                    .execute(conn)
+                    .map_res("Error saving WebSocket broadcast")?;
+                diesel::sql_query("NOTIFY ws_broadcasts")
+                    .execute(conn)
+                    .map_res("Error notifying WebSocket broadcast")
+            }
+        }
+    }
+
+    /// Returns the id of the most recent broadcast, or 0 if there are none
+    pub async fn find_last_id(conn: &mut DbConn) -> i64 {
+        db_run! { conn: {
+            ws_broadcasts::table
+                .select(diesel::dsl::max(ws_broadcasts::id))
+                .first::<Option<i64>>(conn)
+                .ok()
+                .flatten()
+                .unwrap_or(0)
+        }}
+    }
+
+    /// Finds the broadcasts created by other instances with an id above the given one, in the order they were created.
+    /// Unlike most lookups this returns the error, the caller keeps polling and shouldn't stop on a transient one.
+    pub async fn find_from_others_after(id: i64, conn: &mut DbConn) -> Result<Vec<Self>, crate::Error> {
+        db_run! { conn: {
+            ws_broadcasts::table
+                .filter(ws_broadcasts::id.gt(id))
+                .filter(ws_broadcasts::origin.ne(&*INSTANCE_ID))
+                .order_by(ws_broadcasts::id.asc())
+                .load::<WsBroadcastDb>(conn)
+                .map_res("Error loading WebSocket broadcasts")
+                .map(|broadcasts| broadcasts.from_db())
+        }}
+    }
+
+    pub async fn delete_created_before(dt: &NaiveDateTime, conn: &mut DbConn) -> EmptyResult {
+        db_run! { conn: {
+            diesel::delete(ws_broadcasts::table.filter(ws_broadcasts::created_at.lt(dt)))
+                .execute(conn)
+                .map_res("Error deleting old WebSocket broadcasts")
+        }}
+    }
+}
@@ -126,6 +126,14 @@ table! {
         billing_email -> Text,
         private_key -> Nullable<Text>,
         public_key -> Nullable<Text>,
+        limit_collection_creation_deletion -> Bool,
+        allow_admin_access_to_all_collection_items -> Bool,
+        members_can_edit_items -> Bool,
+        limit_invitations_to_verified_domains -> Bool,
+        deleted_at -> Nullable<Timestamp>,
+        transfer_org_user_uuid -> Nullable<Text>,
+        transfer_initiator_uuid -> Nullable<Text>,
+        transfer_date -> Nullable<Timestamp>,
     }
 }
 
@@ -216,6 +224,12 @@ table! {
         api_key -> Nullable<Text>,
         avatar_color -> Nullable<Text>,
         external_id -> Nullable<Text>,
+        lifecycle_warned_at -> Nullable<Timestamp>,
+        lifecycle_disabled_at -> Nullable<Timestamp>,
+        locale -> Nullable<Text>,
+        pgp_public_key -> Nullable<Text>,
+        pgp_key_unusable_notified_at -> Nullable<Timestamp>,
+        deleted_at -> Nullable<Timestamp>,
     }
 }
 
@@ -225,6 +239,7 @@ table! {
         collection_uuid -> Text,
         read_only -> Bool,
         hide_passwords -> Bool,
+        manage -> Bool,
     }
 }
 
@@ -239,6 +254,25 @@ table! {
         atype -> Integer,
         reset_password_key -> Nullable<Text>,
         external_id -> Nullable<Text>,
+        invited_at -> Nullable<Timestamp>,
+        permissions -> Nullable<Text>,
+    }
+}
+
+table! {
+    organization_domains (uuid) {
+        uuid -> Text,
+        org_uuid -> Text,
+        domain_name -> Text,
+        txt -> Text,
+        creation_date -> Timestamp,
+        last_checked_at -> Nullable<Timestamp>,
+        verified_at -> Nullable<Timestamp>,
+        job_run_count -> Integer,
+        auto_enroll -> Bool,
+        auto_enroll_type -> Integer,
+        auto_enroll_groups -> Nullable<Text>,
+        auto_enroll_collections -> Nullable<Text>,
     }
 }
 
@@ -294,6 +328,7 @@ table! {
         groups_uuid -> Text,
         read_only -> Bool,
         hide_passwords -> Bool,
+        manage -> Bool,
     }
 }
 
@@ -317,6 +352,51 @@ table! {
     }
 }
 
+table! {
+    job_leases (job_name) {
+        job_name -> Text,
+        holder -> Text,
+        locked_until -> Timestamp,
+        last_slot -> Timestamp,
+    }
+}
+
+table! {
+    job_runs (uuid) {
+        uuid -> Text,
+        job_name -> Text,
+        manual -> Bool,
+        started_at -> Timestamp,
+        finished_at -> Nullable<Timestamp>,
+        status -> Integer,
+        items_affected -> Nullable<BigInt>,
+        error -> Nullable<Text>,
+    }
+}
+
+table! {
+    notification_preferences (user_uuid) {
+        user_uuid -> Text,
+        new_device_logged_in -> Nullable<Bool>,
+        incomplete_2fa_login -> Nullable<Bool>,
+        org_membership_updates -> Nullable<Bool>,
+        emergency_access_updates -> Nullable<Bool>,
+        emergency_access_reminders -> Nullable<Bool>,
+        security_digest -> Nullable<Bool>,
+    }
+}
+
+table! {
+    ws_broadcasts (id) {
+        id -> BigInt,
+        origin -> Text,
+        anonymous -> Bool,
+        target -> Text,
+        data -> Binary,
+        created_at -> Timestamp,
+    }
+}
+
 joinable!(attachments -> ciphers (cipher_uuid));
 joinable!(ciphers -> organizations (organization_uuid));
 joinable!(ciphers -> users (user_uuid));
@@ -345,6 +425,8 @@ joinable!(collections_groups -> collections (collections_uuid));
 joinable!(collections_groups -> groups (groups_uuid));
 joinable!(event -> users_organizations (uuid));
 joinable!(auth_requests -> users (user_uuid));
+joinable!(notification_preferences -> users (user_uuid));
+joinable!(organization_domains -> organizations (org_uuid));
 
 allow_tables_to_appear_in_same_query!(
     attachments,
@@ -369,4 +451,9 @@ allow_tables_to_appear_in_same_query!(
     collections_groups,
     event,
     auth_requests,
+    job_leases,
+    job_runs,
+    notification_preferences,
+    ws_broadcasts,
+    organization_domains,
 );
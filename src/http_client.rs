@@ -32,10 +32,29 @@ pub fn make_http_request(method: reqwest::Method, url: &str) -> Result<reqwest::
     Ok(INSTANCE.request(method, url))
 }
 
-pub fn get_reqwest_client_builder() -> ClientBuilder {
+/// Like [`make_http_request`], but for endpoints configured by the admin, like the mail API, which may be on the local network.
+/// These are not subject to the `HTTP_REQUEST_BLOCK_*` settings, so redirects are never followed.
+pub fn make_trusted_http_request(method: reqwest::Method, url: &str) -> Result<reqwest::RequestBuilder, crate::Error> {
+    let Ok(url) = url::Url::parse(url) else {
+        err!("Invalid URL");
+    };
+
+    static INSTANCE: Lazy<Client> = Lazy::new(|| {
+        get_base_client_builder().redirect(reqwest::redirect::Policy::none()).build().expect("Failed to build client")
+    });
+
+    Ok(INSTANCE.request(method, url))
+}
+
+/// The defaults shared by all the clients
+fn get_base_client_builder() -> ClientBuilder {
     let mut headers = header::HeaderMap::new();
     headers.insert(header::USER_AGENT, header::HeaderValue::from_static("Vaultwarden"));
 
+    Client::builder().default_headers(headers).timeout(Duration::from_secs(10))
+}
+
+pub fn get_reqwest_client_builder() -> ClientBuilder {
     let redirect_policy = reqwest::redirect::Policy::custom(|attempt| {
         if attempt.previous().len() >= 5 {
             return attempt.error("Too many redirects");
@@ -52,11 +71,7 @@ pub fn get_reqwest_client_builder() -> ClientBuilder {
         attempt.follow()
     });
 
-    Client::builder()
-        .default_headers(headers)
-        .redirect(redirect_policy)
-        .dns_resolver(CustomDnsResolver::instance())
-        .timeout(Duration::from_secs(10))
+    get_base_client_builder().redirect(redirect_policy).dns_resolver(CustomDnsResolver::instance())
 }
 
 pub fn should_block_address(domain_or_ip: &str) -> bool {
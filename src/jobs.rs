@@ -0,0 +1,304 @@
+//
+// Scheduled jobs
+//
+use std::{collections::HashSet, sync::Mutex};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use job_scheduler_ng::Schedule;
+use once_cell::sync::Lazy;
+
+use crate::{
+    api,
+    db::{
+        models::{JobLease, JobRun},
+        DbPool,
+    },
+    error::Error,
+    util::INSTANCE_ID,
+    CONFIG,
+};
+
+/// The result of a job run, containing the number of items the job acted upon.
+pub type JobResult = Result<usize, Error>;
+
+/// Names of the jobs currently running within this instance.
+/// Used to prevent a manual run from overlapping with a scheduled one (or vice versa).
+static RUNNING_JOBS: Lazy<Mutex<HashSet<&'static str>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ScheduledJob {
+    SendPurge,
+    TrashPurge,
+    Incomplete2faNotifications,
+    EmergencyRequestTimeout,
+    EmergencyNotificationReminder,
+    AuthRequestPurge,
+    DuoContextPurge,
+    EventCleanup,
+    AccountLifecycle,
+    InvitationCleanup,
+    SecurityDigest,
+    OrgDomainVerification,
+    OrgDeletionPurge,
+    UserDeletionPurge,
+}
+
+impl ScheduledJob {
+    /// All the known jobs, in the order in which they are added to the scheduler.
+    /// Note that the scheduler checks jobs in this order, so if two jobs are both eligible to run at a given
+    /// tick, the one listed first will run first.
+    pub const ALL: [Self; 14] = [
+        Self::SendPurge,
+        Self::TrashPurge,
+        Self::Incomplete2faNotifications,
+        // This job should run before the emergency access reminders job to avoid
+        // sending reminders for requests that are about to be granted anyway.
+        Self::EmergencyRequestTimeout,
+        Self::EmergencyNotificationReminder,
+        Self::AuthRequestPurge,
+        Self::DuoContextPurge,
+        Self::EventCleanup,
+        Self::AccountLifecycle,
+        Self::InvitationCleanup,
+        Self::SecurityDigest,
+        Self::OrgDomainVerification,
+        Self::OrgDeletionPurge,
+        Self::UserDeletionPurge,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|j| j.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::SendPurge => "send_purge",
+            Self::TrashPurge => "trash_purge",
+            Self::Incomplete2faNotifications => "incomplete_2fa",
+            Self::EmergencyRequestTimeout => "emergency_request_timeout",
+            Self::EmergencyNotificationReminder => "emergency_notification_reminder",
+            Self::AuthRequestPurge => "auth_request_purge",
+            Self::DuoContextPurge => "duo_context_purge",
+            Self::EventCleanup => "event_cleanup",
+            Self::AccountLifecycle => "account_lifecycle",
+            Self::InvitationCleanup => "invitation_cleanup",
+            Self::SecurityDigest => "security_digest",
+            Self::OrgDomainVerification => "org_domain_verification",
+            Self::OrgDeletionPurge => "org_deletion_purge",
+            Self::UserDeletionPurge => "user_deletion_purge",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::SendPurge => "Purge Sends that are past their deletion date",
+            Self::TrashPurge => "Permanently delete trashed items that are old enough to be auto-deleted",
+            Self::Incomplete2faNotifications => "Send email notifications about incomplete 2FA logins",
+            Self::EmergencyRequestTimeout => "Grant emergency access requests that have met the required wait time",
+            Self::EmergencyNotificationReminder => "Remind emergency access grantors about pending requests",
+            Self::AuthRequestPurge => "Purge expired auth requests",
+            Self::DuoContextPurge => "Purge expired Duo authentication contexts",
+            Self::EventCleanup => "Remove events older than the configured retention",
+            Self::AccountLifecycle => "Warn, disable and delete unverified or inactive accounts",
+            Self::InvitationCleanup => "Remove expired organization invitations",
+            Self::SecurityDigest => "Send the weekly security digest to users who opted in",
+            Self::OrgDomainVerification => "Verify the DNS TXT records of organization domains",
+            Self::OrgDeletionPurge => "Permanently delete organizations whose deletion grace period has passed",
+            Self::UserDeletionPurge => "Permanently delete accounts whose deletion retention period has passed",
+        }
+    }
+
+    /// The configured cron schedule, an empty string means the job is not scheduled.
+    pub fn schedule(self) -> String {
+        match self {
+            Self::SendPurge => CONFIG.send_purge_schedule(),
+            Self::TrashPurge => CONFIG.trash_purge_schedule(),
+            Self::Incomplete2faNotifications => CONFIG.incomplete_2fa_schedule(),
+            Self::EmergencyRequestTimeout => CONFIG.emergency_request_timeout_schedule(),
+            Self::EmergencyNotificationReminder => CONFIG.emergency_notification_reminder_schedule(),
+            Self::AuthRequestPurge => CONFIG.auth_request_purge_schedule(),
+            Self::DuoContextPurge => CONFIG.duo_context_purge_schedule(),
+            Self::EventCleanup => CONFIG.event_cleanup_schedule(),
+            Self::AccountLifecycle => CONFIG.account_lifecycle_schedule(),
+            Self::InvitationCleanup => CONFIG.invitation_cleanup_schedule(),
+            Self::SecurityDigest => CONFIG.security_digest_schedule(),
+            Self::OrgDomainVerification => CONFIG.org_domain_verification_schedule(),
+            Self::OrgDeletionPurge => CONFIG.org_deletion_purge_schedule(),
+            Self::UserDeletionPurge => CONFIG.user_deletion_purge_schedule(),
+        }
+    }
+
+    /// Whether the job should be added to the scheduler.
+    pub fn is_scheduled(self) -> bool {
+        if self.schedule().is_empty() {
+            return false;
+        }
+
+        match self {
+            Self::DuoContextPurge => CONFIG._enable_duo() && !CONFIG.duo_use_iframe(),
+            Self::EventCleanup => CONFIG.org_events_enabled() && CONFIG.events_days_retain().is_some(),
+            Self::AccountLifecycle => {
+                CONFIG.unverified_account_days().is_some() || CONFIG.inactive_account_days().is_some()
+            }
+            Self::SecurityDigest => CONFIG.mail_enabled(),
+            _ => true,
+        }
+    }
+
+    /// The next time this job will be triggered by the scheduler, if it is scheduled at all.
+    pub fn next_run(self) -> Option<DateTime<Utc>> {
+        if !self.is_scheduled() {
+            return None;
+        }
+        self.schedule().parse::<Schedule>().ok()?.upcoming(Utc).next()
+    }
+
+    /// The most recent time at which this job was due according to its schedule.
+    /// Every instance computes the same value for a given trigger, which is what the job lease is claimed for.
+    fn current_slot(self) -> Option<DateTime<Utc>> {
+        let schedule = self.schedule().parse::<Schedule>().ok()?;
+        // `next_back` returns the last occurrence strictly before the given time, so add a second to include `now`
+        schedule.after(&(Utc::now() + TimeDelta::try_seconds(1).unwrap())).next_back()
+    }
+
+    async fn execute(self, pool: DbPool) -> JobResult {
+        match self {
+            Self::SendPurge => api::purge_sends(pool).await,
+            Self::TrashPurge => api::purge_trashed_ciphers(pool).await,
+            Self::Incomplete2faNotifications => api::send_incomplete_2fa_notifications(pool).await,
+            Self::EmergencyRequestTimeout => api::emergency_request_timeout_job(pool).await,
+            Self::EmergencyNotificationReminder => api::emergency_notification_reminder_job(pool).await,
+            Self::AuthRequestPurge => api::purge_auth_requests(pool).await,
+            Self::DuoContextPurge => api::core::two_factor::duo_oidc::purge_duo_contexts(pool).await,
+            Self::EventCleanup => api::event_cleanup_job(pool).await,
+            Self::AccountLifecycle => api::account_lifecycle_job(pool).await,
+            Self::InvitationCleanup => api::purge_expired_invitations(pool).await,
+            Self::SecurityDigest => api::security_digest_job(pool).await,
+            Self::OrgDomainVerification => api::org_domain_verification_job(pool).await,
+            Self::OrgDeletionPurge => api::org_deletion_purge_job(pool).await,
+            Self::UserDeletionPurge => api::user_deletion_purge_job(pool).await,
+        }
+    }
+}
+
+/// Runs the given job and records the outcome in the job history.
+/// Returns `None` when the job was not started because it is already running, either on this instance
+/// or on another instance sharing the same database.
+pub async fn run_job(job: ScheduledJob, pool: DbPool, manual: bool) -> Option<JobRun> {
+    if !RUNNING_JOBS.lock().unwrap().insert(job.name()) {
+        warn!("Job `{}` is still running, skipping this run", job.name());
+        return None;
+    }
+
+    // A manual run is not tied to a scheduled trigger, so it doesn't claim a slot
+    let slot = if manual {
+        None
+    } else {
+        match job.current_slot() {
+            Some(slot) => Some(slot),
+            None => {
+                error!("Unable to determine the scheduled time of job `{}`", job.name());
+                RUNNING_JOBS.lock().unwrap().remove(job.name());
+                return None;
+            }
+        }
+    };
+    if !acquire_lease(job, slot, &pool).await {
+        RUNNING_JOBS.lock().unwrap().remove(job.name());
+        return None;
+    }
+
+    let mut run = JobRun::new(job.name(), manual);
+    save_job_run(&run, &pool).await;
+
+    // Run the job in its own task, this way a panic inside of a job is caught and recorded as a failure.
+    let result = match tokio::spawn(job.execute(pool.clone())).await {
+        Ok(Ok(items)) => Ok(items),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(e) => match e.try_into_panic() {
+            Ok(panic) => Err(match panic.downcast_ref::<&'static str>() {
+                Some(s) => format!("Job panicked: {s}"),
+                None => match panic.downcast_ref::<String>() {
+                    Some(s) => format!("Job panicked: {s}"),
+                    None => String::from("Job panicked"),
+                },
+            }),
+            Err(_) => Err(String::from("Job was cancelled")),
+        },
+    };
+
+    release_lease(job, &pool).await;
+    RUNNING_JOBS.lock().unwrap().remove(job.name());
+
+    match &result {
+        Ok(items) => debug!("Job `{}` finished, {items} item(s) affected", job.name()),
+        Err(e) => error!("Job `{}` failed: {e}", job.name()),
+    }
+
+    run.finish(&result);
+    save_job_run(&run, &pool).await;
+    Some(run)
+}
+
+async fn save_job_run(run: &JobRun, pool: &DbPool) {
+    let mut conn = match pool.get().await {
+        Ok(conn) => conn,
+        Err(e) => {
+            error!("Failed to get DB connection while saving job run of `{}`: {e:?}", run.job_name);
+            return;
+        }
+    };
+
+    if let Err(e) = run.save(&mut conn).await {
+        error!("Failed to save job run of `{}`: {e:?}", run.job_name);
+    }
+
+    // Only cleanup the history once the run is finished
+    if run.finished_at.is_some() {
+        let dt = Utc::now().naive_utc() - TimeDelta::try_days(CONFIG.job_history_days_retain()).unwrap();
+        JobRun::delete_started_before(&dt, &mut conn).await.ok();
+    }
+}
+
+/// Claims the lease of the job for the given slot, so that only one of the instances sharing the database runs it.
+/// Manual runs don't have a slot, they only need the lease to be free.
+async fn acquire_lease(job: ScheduledJob, slot: Option<DateTime<Utc>>, pool: &DbPool) -> bool {
+    let mut conn = match pool.get().await {
+        Ok(conn) => conn,
+        Err(e) => {
+            error!("Failed to get DB connection while acquiring the lease of job `{}`: {e:?}", job.name());
+            return false;
+        }
+    };
+
+    let locked_until = Utc::now() + TimeDelta::try_seconds(CONFIG.job_lease_seconds() as i64).unwrap();
+    let acquired = match slot {
+        Some(slot) => {
+            JobLease::try_acquire(job.name(), &INSTANCE_ID, &slot.naive_utc(), &locked_until.naive_utc(), &mut conn)
+                .await
+        }
+        None => JobLease::try_acquire_manual(job.name(), &INSTANCE_ID, &locked_until.naive_utc(), &mut conn).await,
+    };
+    match acquired {
+        Ok(true) => true,
+        Ok(false) => {
+            debug!("Job `{}` is already handled by another instance, skipping this run", job.name());
+            false
+        }
+        Err(e) => {
+            error!("Failed to acquire the lease of job `{}`: {e:?}", job.name());
+            false
+        }
+    }
+}
+
+async fn release_lease(job: ScheduledJob, pool: &DbPool) {
+    match pool.get().await {
+        Ok(mut conn) => {
+            if let Err(e) = JobLease::release(job.name(), &INSTANCE_ID, &mut conn).await {
+                error!("Failed to release the lease of job `{}`: {e:?}", job.name());
+            }
+        }
+        Err(e) => error!("Failed to get DB connection while releasing the lease of job `{}`: {e:?}", job.name()),
+    }
+}
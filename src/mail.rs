@@ -1,10 +1,23 @@
-use std::str::FromStr;
+use std::{
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Utc};
+use once_cell::sync::{Lazy, OnceCell};
 use percent_encoding::{percent_encode, NON_ALPHANUMERIC};
+use pgp::{
+    crypto::sym::SymmetricKeyAlgorithm, types::PublicKeyTrait, ArmorOptions, Deserializable, Message as PgpMessage,
+    SignedPublicKey, SignedPublicSubKey,
+};
 
 use lettre::{
-    message::{Attachment, Body, Mailbox, Message, MultiPart, SinglePart},
+    message::{
+        dkim::{DkimConfig, DkimSigningAlgorithm, DkimSigningKey},
+        header::{ContentDisposition, ContentType},
+        Attachment, Body, Mailbox, Message, MultiPart, SinglePart,
+    },
     transport::smtp::authentication::{Credentials, Mechanism as SmtpAuthMechanism},
     transport::smtp::client::{Tls, TlsParameters},
     transport::smtp::extension::ClientId,
@@ -17,10 +30,22 @@ use crate::{
         encode_jwt, generate_delete_claims, generate_emergency_access_invite_claims, generate_invite_claims,
         generate_verify_email_claims,
     },
+    db::{
+        models::{NotificationCategory, NotificationPreference, User},
+        DbPool,
+    },
     error::Error,
+    http_client::{make_http_request, make_trusted_http_request},
     CONFIG,
 };
 
+/// Used to look up and record whether the recipient of a mail was told that their OpenPGP public key is unusable
+static DB_POOL: OnceCell<DbPool> = OnceCell::new();
+
+pub fn set_db_pool(pool: DbPool) {
+    DB_POOL.set(pool).ok();
+}
+
 fn sendmail_transport() -> AsyncSendmailTransport<Tokio1Executor> {
     if let Some(command) = CONFIG.sendmail_command() {
         AsyncSendmailTransport::new_with_command(command)
@@ -29,8 +54,7 @@ fn sendmail_transport() -> AsyncSendmailTransport<Tokio1Executor> {
     }
 }
 
-fn smtp_transport() -> AsyncSmtpTransport<Tokio1Executor> {
-    use std::time::Duration;
+fn smtp_transport(oauth2_token: Option<String>) -> AsyncSmtpTransport<Tokio1Executor> {
     let host = CONFIG.smtp_host().unwrap();
 
     let smtp_client = AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host.as_str())
@@ -57,8 +81,9 @@ fn smtp_transport() -> AsyncSmtpTransport<Tokio1Executor> {
         smtp_client
     };
 
-    let smtp_client = match (CONFIG.smtp_username(), CONFIG.smtp_password()) {
-        (Some(user), Some(pass)) => smtp_client.credentials(Credentials::new(user, pass)),
+    let smtp_client = match (CONFIG.smtp_username(), CONFIG.smtp_password(), oauth2_token) {
+        (Some(user), _, Some(token)) => smtp_client.credentials(Credentials::new(user, token)),
+        (Some(user), Some(pass), None) => smtp_client.credentials(Credentials::new(user, pass)),
         _ => smtp_client,
     };
 
@@ -92,17 +117,152 @@ fn smtp_transport() -> AsyncSmtpTransport<Tokio1Executor> {
         _ => smtp_client,
     };
 
+    // OAuth2 access tokens can only be used with XOAUTH2
+    let smtp_client = if CONFIG.smtp_oauth2_token_url().is_some() {
+        smtp_client.authentication(vec![SmtpAuthMechanism::Xoauth2])
+    } else {
+        smtp_client
+    };
+
     smtp_client.build()
 }
 
-fn get_text(template_name: &'static str, data: serde_json::Value) -> Result<(String, String, String), Error> {
-    let (subject_html, body_html) = get_template(&format!("{template_name}.html"), &data)?;
-    let (_subject_text, body_text) = get_template(template_name, &data)?;
+struct SmtpOAuth2Token {
+    access_token: String,
+    expires_at: Instant,
+    /// The latest refresh token, some providers rotate it on every refresh
+    refresh_token: Option<String>,
+    /// The settings the token was requested with, it's not used anymore once they are changed
+    settings: SmtpOAuth2Settings,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+struct SmtpOAuth2Settings {
+    token_url: String,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    scope: Option<String>,
+    refresh_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OAuth2TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+    refresh_token: Option<String>,
+}
+
+static SMTP_OAUTH2_TOKEN: Lazy<tokio::sync::Mutex<Option<SmtpOAuth2Token>>> =
+    Lazy::new(|| tokio::sync::Mutex::new(None));
+
+/// Returns a cached OAuth2 access token for SMTP, requesting a new one when it expires within the next five minutes.
+/// Uses the refresh token grant when `SMTP_OAUTH2_REFRESH_TOKEN` is set, and the client credentials grant otherwise.
+async fn smtp_oauth2_access_token() -> Result<String, Error> {
+    let Some(token_url) = CONFIG.smtp_oauth2_token_url() else {
+        err!("SMTP OAuth2 is not configured")
+    };
+
+    let settings = SmtpOAuth2Settings {
+        token_url,
+        client_id: CONFIG.smtp_oauth2_client_id(),
+        client_secret: CONFIG.smtp_oauth2_client_secret(),
+        scope: CONFIG.smtp_oauth2_scope(),
+        refresh_token: CONFIG.smtp_oauth2_refresh_token(),
+    };
+
+    let mut cached = SMTP_OAUTH2_TOKEN.lock().await;
+    // A token requested with other settings is dropped, including the refresh token it was rotated to
+    if cached.as_ref().is_some_and(|token| token.settings != settings) {
+        *cached = None;
+    }
+    if let Some(token) = cached.as_ref() {
+        if token.expires_at > Instant::now() + Duration::from_secs(300) {
+            return Ok(token.access_token.clone());
+        }
+    }
+
+    let refresh_token = match cached.as_ref() {
+        Some(token) => token.refresh_token.clone(),
+        None => settings.refresh_token.clone(),
+    };
+
+    let mut form = vec![("client_id", settings.client_id.clone().unwrap_or_default())];
+    match &refresh_token {
+        Some(refresh_token) => {
+            form.push(("grant_type", String::from("refresh_token")));
+            form.push(("refresh_token", refresh_token.clone()));
+        }
+        None => form.push(("grant_type", String::from("client_credentials"))),
+    }
+    if let Some(secret) = &settings.client_secret {
+        form.push(("client_secret", secret.clone()));
+    }
+    if let Some(scope) = &settings.scope {
+        form.push(("scope", scope.clone()));
+    }
+
+    let res = match make_http_request(reqwest::Method::POST, &settings.token_url)?.form(&form).send().await {
+        Ok(res) => res,
+        Err(e) => err!(format!("SMTP OAuth2 token request failed: {e}")),
+    };
+    if !res.status().is_success() {
+        let status = res.status();
+        let body = res.text().await.unwrap_or_default();
+        debug!("SMTP OAuth2 token error response: {body}");
+        err!(format!("SMTP OAuth2 token request failed with status {status}"))
+    }
+    let token: OAuth2TokenResponse = match res.json().await {
+        Ok(token) => token,
+        Err(e) => err!(format!("SMTP OAuth2 token response is invalid: {e}")),
+    };
+
+    let access_token = token.access_token.clone();
+    *cached = Some(SmtpOAuth2Token {
+        access_token: token.access_token,
+        expires_at: Instant::now() + Duration::from_secs(token.expires_in.unwrap_or(3600)),
+        refresh_token: token.refresh_token.or(refresh_token),
+        settings,
+    });
+    Ok(access_token)
+}
+
+/// Forces a new SMTP OAuth2 access token to be requested for the next mail.
+async fn expire_smtp_oauth2_token() {
+    if let Some(token) = SMTP_OAUTH2_TOKEN.lock().await.as_mut() {
+        token.expires_at = Instant::now();
+    }
+}
+
+/// Checks the notification preferences of the recipient for one of the optional mails.
+/// New device mails can't be turned off when the admin requires them.
+fn notification_enabled(prefs: &NotificationPreference, category: NotificationCategory) -> bool {
+    if category == NotificationCategory::NewDeviceLoggedIn && CONFIG.require_device_email() {
+        return true;
+    }
+    prefs.is_enabled(category)
+}
+
+fn get_text(
+    template_name: &str,
+    locale: Option<&str>,
+    data: serde_json::Value,
+) -> Result<(String, String, String), Error> {
+    let (subject_html, body_html) = get_template(&format!("{template_name}.html"), locale, &data)?;
+    let (_subject_text, body_text) = get_template(template_name, locale, &data)?;
     Ok((subject_html, body_html, body_text))
 }
 
-fn get_template(template_name: &str, data: &serde_json::Value) -> Result<(String, String), Error> {
-    let text = CONFIG.render_template(template_name, data)?;
+/// Renders an email template in the recipient's language, the first part of the template is the subject
+fn get_template(
+    template_name: &str,
+    locale: Option<&str>,
+    data: &serde_json::Value,
+) -> Result<(String, String), Error> {
+    let text = CONFIG.render_localized_template(template_name, locale, data)?;
+    split_subject(&text)
+}
+
+fn split_subject(text: &str) -> Result<(String, String), Error> {
     let mut text_split = text.split("<!---------------->");
 
     let subject = match text_split.next() {
@@ -118,8 +278,25 @@ fn get_template(template_name: &str, data: &serde_json::Value) -> Result<(String
     Ok((subject, body))
 }
 
-pub async fn send_password_hint(address: &str, hint: Option<String>) -> EmptyResult {
-    let template_name = if hint.is_some() {
+pub async fn send_password_hint(
+    address: &str,
+    locale: Option<&str>,
+    pgp_key: Option<&str>,
+    hint: Option<String>,
+) -> EmptyResult {
+    // Without a usable key to encrypt to, users only get to know a hint exists when hints may only be sent encrypted
+    let withheld = hint.is_some()
+        && !pgp_key.is_some_and(|key| read_pgp_public_key(key).is_ok())
+        && CONFIG.password_hints_require_pgp();
+    let hint = if withheld {
+        None
+    } else {
+        hint
+    };
+
+    let template_name = if withheld {
+        "email/pw_hint_withheld"
+    } else if hint.is_some() {
         "email/pw_hint_some"
     } else {
         "email/pw_hint_none"
@@ -127,6 +304,7 @@ pub async fn send_password_hint(address: &str, hint: Option<String>) -> EmptyRes
 
     let (subject, body_html, body_text) = get_text(
         template_name,
+        locale,
         json!({
             "url": CONFIG.domain(),
             "img_src": CONFIG._smtp_img_src(),
@@ -134,15 +312,21 @@ pub async fn send_password_hint(address: &str, hint: Option<String>) -> EmptyRes
         }),
     )?;
 
-    send_email(address, &subject, body_html, body_text).await
+    send_email_with_pgp_key(address, pgp_key, &subject, body_html, body_text).await
 }
 
-pub async fn send_delete_account(address: &str, uuid: &str) -> EmptyResult {
+pub async fn send_delete_account(
+    address: &str,
+    locale: Option<&str>,
+    pgp_key: Option<&str>,
+    uuid: &str,
+) -> EmptyResult {
     let claims = generate_delete_claims(uuid.to_string());
     let delete_token = encode_jwt(&claims);
 
     let (subject, body_html, body_text) = get_text(
         "email/delete_account",
+        locale,
         json!({
             "url": CONFIG.domain(),
             "img_src": CONFIG._smtp_img_src(),
@@ -152,15 +336,16 @@ pub async fn send_delete_account(address: &str, uuid: &str) -> EmptyResult {
         }),
     )?;
 
-    send_email(address, &subject, body_html, body_text).await
+    send_email_with_pgp_key(address, pgp_key, &subject, body_html, body_text).await
 }
 
-pub async fn send_verify_email(address: &str, uuid: &str) -> EmptyResult {
+pub async fn send_verify_email(address: &str, locale: Option<&str>, uuid: &str) -> EmptyResult {
     let claims = generate_verify_email_claims(uuid.to_string());
     let verify_email_token = encode_jwt(&claims);
 
     let (subject, body_html, body_text) = get_text(
         "email/verify_email",
+        locale,
         json!({
             "url": CONFIG.domain(),
             "img_src": CONFIG._smtp_img_src(),
@@ -173,9 +358,10 @@ pub async fn send_verify_email(address: &str, uuid: &str) -> EmptyResult {
     send_email(address, &subject, body_html, body_text).await
 }
 
-pub async fn send_welcome(address: &str) -> EmptyResult {
+pub async fn send_welcome(address: &str, locale: Option<&str>) -> EmptyResult {
     let (subject, body_html, body_text) = get_text(
         "email/welcome",
+        locale,
         json!({
             "url": CONFIG.domain(),
             "img_src": CONFIG._smtp_img_src(),
@@ -185,12 +371,13 @@ pub async fn send_welcome(address: &str) -> EmptyResult {
     send_email(address, &subject, body_html, body_text).await
 }
 
-pub async fn send_welcome_must_verify(address: &str, uuid: &str) -> EmptyResult {
+pub async fn send_welcome_must_verify(address: &str, locale: Option<&str>, uuid: &str) -> EmptyResult {
     let claims = generate_verify_email_claims(uuid.to_string());
     let verify_email_token = encode_jwt(&claims);
 
     let (subject, body_html, body_text) = get_text(
         "email/welcome_must_verify",
+        locale,
         json!({
             "url": CONFIG.domain(),
             "img_src": CONFIG._smtp_img_src(),
@@ -202,9 +389,10 @@ pub async fn send_welcome_must_verify(address: &str, uuid: &str) -> EmptyResult
     send_email(address, &subject, body_html, body_text).await
 }
 
-pub async fn send_2fa_removed_from_org(address: &str, org_name: &str) -> EmptyResult {
+pub async fn send_2fa_removed_from_org(address: &str, locale: Option<&str>, org_name: &str) -> EmptyResult {
     let (subject, body_html, body_text) = get_text(
         "email/send_2fa_removed_from_org",
+        locale,
         json!({
             "url": CONFIG.domain(),
             "img_src": CONFIG._smtp_img_src(),
@@ -215,13 +403,112 @@ pub async fn send_2fa_removed_from_org(address: &str, org_name: &str) -> EmptyRe
     send_email(address, &subject, body_html, body_text).await
 }
 
-pub async fn send_single_org_removed_from_org(address: &str, org_name: &str) -> EmptyResult {
+pub async fn send_single_org_removed_from_org(address: &str, locale: Option<&str>, org_name: &str) -> EmptyResult {
     let (subject, body_html, body_text) = get_text(
         "email/send_single_org_removed_from_org",
+        locale,
+        json!({
+            "url": CONFIG.domain(),
+            "img_src": CONFIG._smtp_img_src(),
+            "org_name": org_name,
+        }),
+    )?;
+
+    send_email(address, &subject, body_html, body_text).await
+}
+
+pub async fn send_org_deletion_scheduled(
+    address: &str,
+    locale: Option<&str>,
+    org_name: &str,
+    deleted_by: &str,
+    delete_date: &NaiveDateTime,
+) -> EmptyResult {
+    let (subject, body_html, body_text) = get_text(
+        "email/org_deletion_scheduled",
+        locale,
+        json!({
+            "url": CONFIG.domain(),
+            "img_src": CONFIG._smtp_img_src(),
+            "org_name": org_name,
+            "email": deleted_by,
+            "delete_date": crate::util::format_naive_datetime_local(delete_date, "%A, %B %_d, %Y"),
+        }),
+    )?;
+
+    send_email(address, &subject, body_html, body_text).await
+}
+
+pub async fn send_org_deletion_restored(
+    address: &str,
+    locale: Option<&str>,
+    org_name: &str,
+    restored_by: &str,
+) -> EmptyResult {
+    let (subject, body_html, body_text) = get_text(
+        "email/org_deletion_restored",
+        locale,
+        json!({
+            "url": CONFIG.domain(),
+            "img_src": CONFIG._smtp_img_src(),
+            "org_name": org_name,
+            "email": restored_by,
+        }),
+    )?;
+
+    send_email(address, &subject, body_html, body_text).await
+}
+
+pub async fn send_org_deleted(address: &str, locale: Option<&str>, org_name: &str) -> EmptyResult {
+    let (subject, body_html, body_text) = get_text(
+        "email/org_deleted",
+        locale,
+        json!({
+            "url": CONFIG.domain(),
+            "img_src": CONFIG._smtp_img_src(),
+            "org_name": org_name,
+        }),
+    )?;
+
+    send_email(address, &subject, body_html, body_text).await
+}
+
+pub async fn send_org_transfer_requested(
+    address: &str,
+    locale: Option<&str>,
+    org_name: &str,
+    initiator_email: &str,
+) -> EmptyResult {
+    let (subject, body_html, body_text) = get_text(
+        "email/org_transfer_requested",
+        locale,
+        json!({
+            "url": CONFIG.domain(),
+            "img_src": CONFIG._smtp_img_src(),
+            "org_name": org_name,
+            "email": initiator_email,
+        }),
+    )?;
+
+    send_email(address, &subject, body_html, body_text).await
+}
+
+pub async fn send_org_transfer_completed(
+    address: &str,
+    locale: Option<&str>,
+    org_name: &str,
+    new_owner_email: &str,
+    initiator_email: &str,
+) -> EmptyResult {
+    let (subject, body_html, body_text) = get_text(
+        "email/org_transfer_completed",
+        locale,
         json!({
             "url": CONFIG.domain(),
             "img_src": CONFIG._smtp_img_src(),
             "org_name": org_name,
+            "email": new_owner_email,
+            "initiator_email": initiator_email,
         }),
     )?;
 
@@ -230,6 +517,7 @@ pub async fn send_single_org_removed_from_org(address: &str, org_name: &str) ->
 
 pub async fn send_invite(
     address: &str,
+    locale: Option<&str>,
     uuid: &str,
     org_id: Option<String>,
     org_user_id: Option<String>,
@@ -247,6 +535,7 @@ pub async fn send_invite(
 
     let (subject, body_html, body_text) = get_text(
         "email/send_org_invite",
+        locale,
         json!({
             "url": CONFIG.domain(),
             "img_src": CONFIG._smtp_img_src(),
@@ -264,6 +553,8 @@ pub async fn send_invite(
 
 pub async fn send_emergency_access_invite(
     address: &str,
+    locale: Option<&str>,
+    pgp_key: Option<&str>,
     uuid: &str,
     emer_id: &str,
     grantor_name: &str,
@@ -281,6 +572,7 @@ pub async fn send_emergency_access_invite(
 
     let (subject, body_html, body_text) = get_text(
         "email/send_emergency_access_invite",
+        locale,
         json!({
             "url": CONFIG.domain(),
             "img_src": CONFIG._smtp_img_src(),
@@ -291,12 +583,23 @@ pub async fn send_emergency_access_invite(
         }),
     )?;
 
-    send_email(address, &subject, body_html, body_text).await
+    send_email_with_pgp_key(address, pgp_key, &subject, body_html, body_text).await
 }
 
-pub async fn send_emergency_access_invite_accepted(address: &str, grantee_email: &str) -> EmptyResult {
+pub async fn send_emergency_access_invite_accepted(
+    address: &str,
+    locale: Option<&str>,
+    pgp_key: Option<&str>,
+    grantee_email: &str,
+    prefs: &NotificationPreference,
+) -> EmptyResult {
+    if !notification_enabled(prefs, NotificationCategory::EmergencyAccessUpdates) {
+        return Ok(());
+    }
+
     let (subject, body_html, body_text) = get_text(
         "email/emergency_access_invite_accepted",
+        locale,
         json!({
             "url": CONFIG.domain(),
             "img_src": CONFIG._smtp_img_src(),
@@ -304,12 +607,23 @@ pub async fn send_emergency_access_invite_accepted(address: &str, grantee_email:
         }),
     )?;
 
-    send_email(address, &subject, body_html, body_text).await
+    send_email_with_pgp_key(address, pgp_key, &subject, body_html, body_text).await
 }
 
-pub async fn send_emergency_access_invite_confirmed(address: &str, grantor_name: &str) -> EmptyResult {
+pub async fn send_emergency_access_invite_confirmed(
+    address: &str,
+    locale: Option<&str>,
+    pgp_key: Option<&str>,
+    grantor_name: &str,
+    prefs: &NotificationPreference,
+) -> EmptyResult {
+    if !notification_enabled(prefs, NotificationCategory::EmergencyAccessUpdates) {
+        return Ok(());
+    }
+
     let (subject, body_html, body_text) = get_text(
         "email/emergency_access_invite_confirmed",
+        locale,
         json!({
             "url": CONFIG.domain(),
             "img_src": CONFIG._smtp_img_src(),
@@ -317,12 +631,23 @@ pub async fn send_emergency_access_invite_confirmed(address: &str, grantor_name:
         }),
     )?;
 
-    send_email(address, &subject, body_html, body_text).await
+    send_email_with_pgp_key(address, pgp_key, &subject, body_html, body_text).await
 }
 
-pub async fn send_emergency_access_recovery_approved(address: &str, grantor_name: &str) -> EmptyResult {
+pub async fn send_emergency_access_recovery_approved(
+    address: &str,
+    locale: Option<&str>,
+    pgp_key: Option<&str>,
+    grantor_name: &str,
+    prefs: &NotificationPreference,
+) -> EmptyResult {
+    if !notification_enabled(prefs, NotificationCategory::EmergencyAccessUpdates) {
+        return Ok(());
+    }
+
     let (subject, body_html, body_text) = get_text(
         "email/emergency_access_recovery_approved",
+        locale,
         json!({
             "url": CONFIG.domain(),
             "img_src": CONFIG._smtp_img_src(),
@@ -330,17 +655,20 @@ pub async fn send_emergency_access_recovery_approved(address: &str, grantor_name
         }),
     )?;
 
-    send_email(address, &subject, body_html, body_text).await
+    send_email_with_pgp_key(address, pgp_key, &subject, body_html, body_text).await
 }
 
 pub async fn send_emergency_access_recovery_initiated(
     address: &str,
+    locale: Option<&str>,
+    pgp_key: Option<&str>,
     grantee_name: &str,
     atype: &str,
     wait_time_days: &i32,
 ) -> EmptyResult {
     let (subject, body_html, body_text) = get_text(
         "email/emergency_access_recovery_initiated",
+        locale,
         json!({
             "url": CONFIG.domain(),
             "img_src": CONFIG._smtp_img_src(),
@@ -350,17 +678,25 @@ pub async fn send_emergency_access_recovery_initiated(
         }),
     )?;
 
-    send_email(address, &subject, body_html, body_text).await
+    send_email_with_pgp_key(address, pgp_key, &subject, body_html, body_text).await
 }
 
 pub async fn send_emergency_access_recovery_reminder(
     address: &str,
+    locale: Option<&str>,
+    pgp_key: Option<&str>,
     grantee_name: &str,
     atype: &str,
     days_left: &str,
+    prefs: &NotificationPreference,
 ) -> EmptyResult {
+    if !notification_enabled(prefs, NotificationCategory::EmergencyAccessReminders) {
+        return Ok(());
+    }
+
     let (subject, body_html, body_text) = get_text(
         "email/emergency_access_recovery_reminder",
+        locale,
         json!({
             "url": CONFIG.domain(),
             "img_src": CONFIG._smtp_img_src(),
@@ -370,12 +706,23 @@ pub async fn send_emergency_access_recovery_reminder(
         }),
     )?;
 
-    send_email(address, &subject, body_html, body_text).await
+    send_email_with_pgp_key(address, pgp_key, &subject, body_html, body_text).await
 }
 
-pub async fn send_emergency_access_recovery_rejected(address: &str, grantor_name: &str) -> EmptyResult {
+pub async fn send_emergency_access_recovery_rejected(
+    address: &str,
+    locale: Option<&str>,
+    pgp_key: Option<&str>,
+    grantor_name: &str,
+    prefs: &NotificationPreference,
+) -> EmptyResult {
+    if !notification_enabled(prefs, NotificationCategory::EmergencyAccessUpdates) {
+        return Ok(());
+    }
+
     let (subject, body_html, body_text) = get_text(
         "email/emergency_access_recovery_rejected",
+        locale,
         json!({
             "url": CONFIG.domain(),
             "img_src": CONFIG._smtp_img_src(),
@@ -383,12 +730,19 @@ pub async fn send_emergency_access_recovery_rejected(address: &str, grantor_name
         }),
     )?;
 
-    send_email(address, &subject, body_html, body_text).await
+    send_email_with_pgp_key(address, pgp_key, &subject, body_html, body_text).await
 }
 
-pub async fn send_emergency_access_recovery_timed_out(address: &str, grantee_name: &str, atype: &str) -> EmptyResult {
+pub async fn send_emergency_access_recovery_timed_out(
+    address: &str,
+    locale: Option<&str>,
+    pgp_key: Option<&str>,
+    grantee_name: &str,
+    atype: &str,
+) -> EmptyResult {
     let (subject, body_html, body_text) = get_text(
         "email/emergency_access_recovery_timed_out",
+        locale,
         json!({
             "url": CONFIG.domain(),
             "img_src": CONFIG._smtp_img_src(),
@@ -397,12 +751,23 @@ pub async fn send_emergency_access_recovery_timed_out(address: &str, grantee_nam
         }),
     )?;
 
-    send_email(address, &subject, body_html, body_text).await
+    send_email_with_pgp_key(address, pgp_key, &subject, body_html, body_text).await
 }
 
-pub async fn send_invite_accepted(new_user_email: &str, address: &str, org_name: &str) -> EmptyResult {
+pub async fn send_invite_accepted(
+    new_user_email: &str,
+    address: &str,
+    locale: Option<&str>,
+    org_name: &str,
+    prefs: &NotificationPreference,
+) -> EmptyResult {
+    if !notification_enabled(prefs, NotificationCategory::OrgMembershipUpdates) {
+        return Ok(());
+    }
+
     let (subject, body_html, body_text) = get_text(
         "email/invite_accepted",
+        locale,
         json!({
             "url": CONFIG.domain(),
             "img_src": CONFIG._smtp_img_src(),
@@ -414,9 +779,19 @@ pub async fn send_invite_accepted(new_user_email: &str, address: &str, org_name:
     send_email(address, &subject, body_html, body_text).await
 }
 
-pub async fn send_invite_confirmed(address: &str, org_name: &str) -> EmptyResult {
+pub async fn send_invite_confirmed(
+    address: &str,
+    locale: Option<&str>,
+    org_name: &str,
+    prefs: &NotificationPreference,
+) -> EmptyResult {
+    if !notification_enabled(prefs, NotificationCategory::OrgMembershipUpdates) {
+        return Ok(());
+    }
+
     let (subject, body_html, body_text) = get_text(
         "email/invite_confirmed",
+        locale,
         json!({
             "url": CONFIG.domain(),
             "img_src": CONFIG._smtp_img_src(),
@@ -427,13 +802,25 @@ pub async fn send_invite_confirmed(address: &str, org_name: &str) -> EmptyResult
     send_email(address, &subject, body_html, body_text).await
 }
 
-pub async fn send_new_device_logged_in(address: &str, ip: &str, dt: &NaiveDateTime, device: &str) -> EmptyResult {
+pub async fn send_new_device_logged_in(
+    address: &str,
+    locale: Option<&str>,
+    ip: &str,
+    dt: &NaiveDateTime,
+    device: &str,
+    prefs: &NotificationPreference,
+) -> EmptyResult {
+    if !notification_enabled(prefs, NotificationCategory::NewDeviceLoggedIn) {
+        return Ok(());
+    }
+
     use crate::util::upcase_first;
     let device = upcase_first(device);
 
     let fmt = "%A, %B %_d, %Y at %r %Z";
     let (subject, body_html, body_text) = get_text(
         "email/new_device_logged_in",
+        locale,
         json!({
             "url": CONFIG.domain(),
             "img_src": CONFIG._smtp_img_src(),
@@ -446,13 +833,25 @@ pub async fn send_new_device_logged_in(address: &str, ip: &str, dt: &NaiveDateTi
     send_email(address, &subject, body_html, body_text).await
 }
 
-pub async fn send_incomplete_2fa_login(address: &str, ip: &str, dt: &NaiveDateTime, device: &str) -> EmptyResult {
+pub async fn send_incomplete_2fa_login(
+    address: &str,
+    locale: Option<&str>,
+    ip: &str,
+    dt: &NaiveDateTime,
+    device: &str,
+    prefs: &NotificationPreference,
+) -> EmptyResult {
+    if !notification_enabled(prefs, NotificationCategory::Incomplete2faLogin) {
+        return Ok(());
+    }
+
     use crate::util::upcase_first;
     let device = upcase_first(device);
 
     let fmt = "%A, %B %_d, %Y at %r %Z";
     let (subject, body_html, body_text) = get_text(
         "email/incomplete_2fa_login",
+        locale,
         json!({
             "url": CONFIG.domain(),
             "img_src": CONFIG._smtp_img_src(),
@@ -466,47 +865,215 @@ pub async fn send_incomplete_2fa_login(address: &str, ip: &str, dt: &NaiveDateTi
     send_email(address, &subject, body_html, body_text).await
 }
 
-pub async fn send_token(address: &str, token: &str) -> EmptyResult {
+pub async fn send_account_lifecycle_warning(
+    address: &str,
+    locale: Option<&str>,
+    unverified: bool,
+    disable_date: &NaiveDateTime,
+    delete_days: Option<i64>,
+) -> EmptyResult {
     let (subject, body_html, body_text) = get_text(
-        "email/twofactor_email",
+        "email/account_lifecycle_warning",
+        locale,
         json!({
             "url": CONFIG.domain(),
             "img_src": CONFIG._smtp_img_src(),
-            "token": token,
+            "email": address,
+            "unverified": unverified,
+            "disable_date": crate::util::format_naive_datetime_local(disable_date, "%A, %B %_d, %Y"),
+            "delete_days": delete_days,
         }),
     )?;
 
     send_email(address, &subject, body_html, body_text).await
 }
 
-pub async fn send_change_email(address: &str, token: &str) -> EmptyResult {
+pub async fn send_account_deletion_scheduled(
+    address: &str,
+    locale: Option<&str>,
+    by_admin: bool,
+    delete_date: &NaiveDateTime,
+) -> EmptyResult {
     let (subject, body_html, body_text) = get_text(
-        "email/change_email",
+        "email/account_deletion_scheduled",
+        locale,
         json!({
             "url": CONFIG.domain(),
             "img_src": CONFIG._smtp_img_src(),
-            "token": token,
+            "email": address,
+            "by_admin": by_admin,
+            "delete_date": crate::util::format_naive_datetime_local(delete_date, "%A, %B %_d, %Y"),
         }),
     )?;
 
     send_email(address, &subject, body_html, body_text).await
 }
 
-pub async fn send_test(address: &str) -> EmptyResult {
+pub async fn send_account_restored(address: &str, locale: Option<&str>) -> EmptyResult {
     let (subject, body_html, body_text) = get_text(
-        "email/smtp_test",
+        "email/account_restored",
+        locale,
         json!({
             "url": CONFIG.domain(),
             "img_src": CONFIG._smtp_img_src(),
+            "email": address,
         }),
     )?;
 
     send_email(address, &subject, body_html, body_text).await
 }
 
-pub async fn send_admin_reset_password(address: &str, user_name: &str, org_name: &str) -> EmptyResult {
+pub async fn send_account_purged(address: &str, locale: Option<&str>) -> EmptyResult {
+    let (subject, body_html, body_text) = get_text(
+        "email/account_purged",
+        locale,
+        json!({
+            "url": CONFIG.domain(),
+            "img_src": CONFIG._smtp_img_src(),
+            "email": address,
+        }),
+    )?;
+
+    send_email(address, &subject, body_html, body_text).await
+}
+
+pub async fn send_token(address: &str, locale: Option<&str>, pgp_key: Option<&str>, token: &str) -> EmptyResult {
+    let (subject, body_html, body_text) = get_text(
+        "email/twofactor_email",
+        locale,
+        json!({
+            "url": CONFIG.domain(),
+            "img_src": CONFIG._smtp_img_src(),
+            "token": token,
+        }),
+    )?;
+
+    send_email_with_pgp_key(address, pgp_key, &subject, body_html, body_text).await
+}
+
+pub async fn send_change_email(address: &str, locale: Option<&str>, token: &str) -> EmptyResult {
+    let (subject, body_html, body_text) = get_text(
+        "email/change_email",
+        locale,
+        json!({
+            "url": CONFIG.domain(),
+            "img_src": CONFIG._smtp_img_src(),
+            "token": token,
+        }),
+    )?;
+
+    send_email(address, &subject, body_html, body_text).await
+}
+
+pub async fn send_security_digest(
+    address: &str,
+    locale: Option<&str>,
+    period_start: &NaiveDateTime,
+    period_end: &NaiveDateTime,
+    digest: serde_json::Value,
+) -> EmptyResult {
+    let fmt = "%B %_d, %Y";
+    let mut data = json!({
+        "url": CONFIG.domain(),
+        "img_src": CONFIG._smtp_img_src(),
+        "email": address,
+        "period_start": crate::util::format_naive_datetime_local(period_start, fmt),
+        "period_end": crate::util::format_naive_datetime_local(period_end, fmt),
+    });
+    if let (Some(data), serde_json::Value::Object(digest)) = (data.as_object_mut(), digest) {
+        data.extend(digest);
+    }
+
+    let (subject, body_html, body_text) = get_text("email/security_digest", locale, data)?;
+
+    send_email(address, &subject, body_html, body_text).await
+}
+
+pub async fn send_test(address: &str) -> EmptyResult {
+    // Always request a new OAuth2 token, so the test validates the current settings
+    expire_smtp_oauth2_token().await;
+    send_template_test(address, "email/smtp_test").await
+}
+
+/// Sends the given email template rendered with the sample data, used to test template changes from the admin panel.
+pub async fn send_template_test(address: &str, template_name: &str) -> EmptyResult {
+    let (subject, body_html, body_text) = get_text(template_name, None, template_sample_data())?;
+
+    send_email(address, &subject, body_html, body_text).await
+}
+
+/// Renders a preview of a mail with `source` in place of the template `template_name`, using the sample data.
+/// For the `.html` variant or a partial, the other parts of the mail are rendered with the currently loaded templates.
+/// Returns the subject, the html body and the text body.
+pub fn preview_template(template_name: &str, source: &str) -> Result<(String, String, String), Error> {
+    let mail_name = if crate::config::is_email_partial(template_name) {
+        "email/smtp_test"
+    } else {
+        template_name.strip_suffix(".html").unwrap_or(template_name)
+    };
+
+    let data = template_sample_data();
+    let html_name = format!("{mail_name}.html");
+    let (subject, body_html) =
+        split_subject(&CONFIG.render_template_with_source(template_name, source, &html_name, &data)?)?;
+    let (_subject_text, body_text) =
+        split_subject(&CONFIG.render_template_with_source(template_name, source, mail_name, &data)?)?;
+    Ok((subject, body_html, body_text))
+}
+
+/// Sample values for every variable used by the email templates.
+pub fn template_sample_data() -> serde_json::Value {
+    let now = Utc::now().naive_utc();
+    json!({
+        "url": CONFIG.domain(),
+        "img_src": CONFIG._smtp_img_src(),
+        "email": "user@example.com",
+        "hint": "The name of my first pet",
+        "token": "123456",
+        "user_id": "00000000-0000-0000-0000-000000000001",
+        "user_name": "Jane Doe",
+        "org_id": "00000000-0000-0000-0000-000000000002",
+        "org_user_id": "00000000-0000-0000-0000-000000000003",
+        "org_name": "Example Organization",
+        "org_name_encoded": "Example%20Organization",
+        "emer_id": "00000000-0000-0000-0000-000000000004",
+        "grantor_name": "Jane Doe",
+        "grantee_name": "John Doe",
+        "grantee_email": "john@example.com",
+        "initiator_email": "admin@example.com",
+        "atype": "view",
+        "wait_time_days": 7,
+        "days_left": "3",
+        "ip": "192.0.2.1",
+        "device": "Firefox",
+        "datetime": crate::util::format_naive_datetime_local(&now, "%A, %B %_d, %Y at %r %Z"),
+        "time_limit": CONFIG.incomplete_2fa_time_limit(),
+        "disable_date": crate::util::format_naive_datetime_local(&now, "%A, %B %_d, %Y"),
+        "delete_days": 30,
+        "pgp_error": "The OpenPGP public key has expired",
+        "delete_date": crate::util::format_naive_datetime_local(&(now + chrono::TimeDelta::try_days(7).unwrap()), "%A, %B %_d, %Y"),
+        "unverified": false,
+        "by_admin": false,
+        "period_start": crate::util::format_naive_datetime_local(&(now - chrono::TimeDelta::try_days(7).unwrap()), "%B %_d, %Y"),
+        "period_end": crate::util::format_naive_datetime_local(&now, "%B %_d, %Y"),
+        "new_devices": [{"name": "firefox", "type": "Firefox Extension", "date": "Mon, Oct  5 at 14:02 UTC"}],
+        "failed_logins": [{"date": "Tue, Oct  6 at 03:17 UTC", "ip": "198.51.100.7", "reason": "Wrong master password"}],
+        "twofactor_changes": [{"date": "Wed, Oct  7 at 09:45 UTC", "action": "Two-step login method added or updated"}],
+        "emergency_access": [{"name": "john@example.com", "role": "your emergency contact", "status": "Access requested", "date": "Thu, Oct  8 at 18:30 UTC"}],
+        "send_accesses": 3,
+        "org_changes": [{"org_name": "Example Organization", "action": "Your role or access was changed", "date": "Fri, Oct  9 at 11:05 UTC"}],
+    })
+}
+
+pub async fn send_admin_reset_password(
+    address: &str,
+    locale: Option<&str>,
+    user_name: &str,
+    org_name: &str,
+) -> EmptyResult {
     let (subject, body_html, body_text) = get_text(
         "email/admin_reset_password",
+        locale,
         json!({
             "url": CONFIG.domain(),
             "img_src": CONFIG._smtp_img_src(),
@@ -517,9 +1084,10 @@ pub async fn send_admin_reset_password(address: &str, user_name: &str, org_name:
     send_email(address, &subject, body_html, body_text).await
 }
 
-pub async fn send_protected_action_token(address: &str, token: &str) -> EmptyResult {
+pub async fn send_protected_action_token(address: &str, locale: Option<&str>, token: &str) -> EmptyResult {
     let (subject, body_html, body_text) = get_text(
         "email/protected_action",
+        locale,
         json!({
             "url": CONFIG.domain(),
             "img_src": CONFIG._smtp_img_src(),
@@ -549,7 +1117,13 @@ async fn send_with_selected_transport(email: Message) -> EmptyResult {
             }
         }
     } else {
-        match smtp_transport().send(email).await {
+        let oauth2_token = if CONFIG.smtp_oauth2_token_url().is_some() {
+            Some(smtp_oauth2_access_token().await?)
+        } else {
+            None
+        };
+
+        match smtp_transport(oauth2_token).send(email).await {
             Ok(_) => Ok(()),
             // Match some common errors and make them more user friendly
             Err(e) => {
@@ -565,6 +1139,8 @@ async fn send_with_selected_transport(email: Message) -> EmptyResult {
                     // Add a special check for 535 to add a more descriptive message
                     if msg.contains("(535)") {
                         msg = format!("{msg} - Authentication credentials invalid");
+                        // The access token might have been revoked, request a new one for the next try
+                        expire_smtp_oauth2_token().await;
                     }
                     err!(format!("SMTP 5xx error: {msg}"));
                 } else if e.is_timeout() {
@@ -582,10 +1158,276 @@ async fn send_with_selected_transport(email: Message) -> EmptyResult {
     }
 }
 
+/// Reads the DKIM private key from a PEM file, RSA keys can be either PKCS#1 or PKCS#8 and Ed25519 keys PKCS#8.
+pub fn read_dkim_signing_key(key_file: &str) -> Result<DkimSigningKey, Error> {
+    use openssl::pkey::{Id, PKey};
+
+    let pem = match std::fs::read(key_file) {
+        Ok(pem) => pem,
+        Err(e) => err!(format!("Unable to read `{key_file}`: {e}")),
+    };
+    let Ok(key) = PKey::private_key_from_pem(&pem) else {
+        err!(format!("`{key_file}` doesn't contain a PEM encoded private key"))
+    };
+
+    let signing_key = match key.id() {
+        Id::RSA => {
+            let pkcs1 = key.rsa()?.private_key_to_pem()?;
+            DkimSigningKey::new(&String::from_utf8_lossy(&pkcs1), DkimSigningAlgorithm::Rsa)
+        }
+        Id::ED25519 => {
+            let raw = key.raw_private_key()?;
+            DkimSigningKey::new(&data_encoding::BASE64.encode(&raw), DkimSigningAlgorithm::Ed25519)
+        }
+        _ => err!("Only RSA and Ed25519 keys are supported for DKIM"),
+    };
+    signing_key.map_err(|e| Error::new(format!("Invalid DKIM key: {e}"), e.to_string()))
+}
+
+/// A parsed DKIM config, with the settings and key file modification time it was created from
+struct CachedDkimConfig {
+    settings: (String, String, String, Option<std::time::SystemTime>),
+    config: Arc<DkimConfig>,
+}
+
+static DKIM_CONFIG: Lazy<std::sync::Mutex<Option<CachedDkimConfig>>> = Lazy::new(|| std::sync::Mutex::new(None));
+
+/// Returns the DKIM config, the key file is only read again when the settings or the file itself changed.
+fn dkim_config() -> Result<Option<Arc<DkimConfig>>, Error> {
+    let (Some(selector), Some(key_file)) = (CONFIG.smtp_dkim_selector(), CONFIG.smtp_dkim_private_key()) else {
+        return Ok(None);
+    };
+    let domain = match CONFIG.smtp_dkim_domain() {
+        Some(domain) => domain,
+        None => CONFIG.smtp_from().split('@').nth(1).unwrap_or_default().to_string(),
+    };
+    let modified = std::fs::metadata(&key_file).and_then(|m| m.modified()).ok();
+    let settings = (selector, domain, key_file, modified);
+
+    let mut cached = DKIM_CONFIG.lock().unwrap();
+    if let Some(cached) = cached.as_ref().filter(|c| c.settings == settings) {
+        return Ok(Some(Arc::clone(&cached.config)));
+    }
+
+    let signing_key = read_dkim_signing_key(&settings.2)?;
+    let config = Arc::new(DkimConfig::default_config(settings.0.clone(), settings.1.clone(), signing_key));
+    *cached = Some(CachedDkimConfig {
+        settings,
+        config: Arc::clone(&config),
+    });
+    Ok(Some(config))
+}
+
+/// Reads an ASCII armored OpenPGP public key and checks that it can be used to encrypt mails.
+/// The errors are not logged, as this is also used to check keys which are expected to be unusable.
+pub fn read_pgp_public_key(armored_key: &str) -> Result<SignedPublicKey, Error> {
+    let key = match SignedPublicKey::from_string(armored_key) {
+        Ok((key, _headers)) => key,
+        Err(e) => err_silent!(format!("Invalid OpenPGP public key: {e}")),
+    };
+    if let Err(e) = key.verify() {
+        err_silent!(format!("Invalid OpenPGP public key: {e}"))
+    }
+    if key.expires_at().is_some_and(|expires_at| expires_at <= Utc::now()) {
+        err_silent!("The OpenPGP public key has expired")
+    }
+    if pgp_encryption_subkey(&key).is_none() && !key.primary_key.is_encryption_key() {
+        err_silent!("The OpenPGP public key can't be used for encryption")
+    }
+    Ok(key)
+}
+
+pub fn pgp_fingerprint(key: &SignedPublicKey) -> String {
+    data_encoding::HEXUPPER.encode(key.fingerprint().as_bytes())
+}
+
+/// Returns the first subkey which is flagged for encryption and has not expired
+fn pgp_encryption_subkey(key: &SignedPublicKey) -> Option<&SignedPublicSubKey> {
+    let now = Utc::now();
+    key.public_subkeys.iter().find(|subkey| {
+        subkey.key.is_encryption_key()
+            && subkey.signatures.iter().any(|sig| {
+                let flags = sig.key_flags();
+                (flags.encrypt_comms() || flags.encrypt_storage())
+                    && sig.key_expiration_time().map_or(true, |expiration| *subkey.key.created_at() + *expiration > now)
+            })
+    })
+}
+
+fn pgp_encrypt(key: &SignedPublicKey, data: &[u8]) -> Result<String, Error> {
+    let message = PgpMessage::new_literal_bytes("", data);
+    let mut rng = rand::thread_rng();
+    let encrypted = match pgp_encryption_subkey(key) {
+        Some(subkey) => message.encrypt_to_keys_seipdv1(&mut rng, SymmetricKeyAlgorithm::AES256, &[subkey]),
+        None => message.encrypt_to_keys_seipdv1(&mut rng, SymmetricKeyAlgorithm::AES256, &[&key.primary_key]),
+    };
+    match encrypted.and_then(|message| message.to_armored_string(ArmorOptions::default())) {
+        Ok(armored) => Ok(armored),
+        Err(e) => err!(format!("Failed to encrypt the mail: {e}")),
+    }
+}
+
+/// Encrypts a mail body and wraps it in a PGP/MIME message as described in RFC 3156
+fn pgp_mime_body(key: &SignedPublicKey, body: &MultiPart) -> Result<MultiPart, Error> {
+    let encrypted = pgp_encrypt(key, &body.formatted())?;
+
+    Ok(MultiPart::encrypted(String::from("application/pgp-encrypted"))
+        .singlepart(
+            SinglePart::builder()
+                .header("application/pgp-encrypted".parse::<ContentType>().unwrap())
+                .body(String::from("Version: 1")),
+        )
+        .singlepart(
+            SinglePart::builder()
+                .header("application/octet-stream; name=\"encrypted.asc\"".parse::<ContentType>().unwrap())
+                .header(ContentDisposition::inline_with_name("encrypted.asc"))
+                .body(encrypted),
+        ))
+}
+
+/// Sends the mail as a JSON payload to the configured HTTP mail API.
+async fn send_with_mail_api(
+    api_url: &str,
+    address: &str,
+    subject: &str,
+    body_html: Option<String>,
+    body_text: String,
+) -> EmptyResult {
+    let attachments: Vec<_> = if body_html.is_some() && CONFIG.smtp_embed_images() {
+        ["logo-gray.png", "mail-github.png"]
+            .iter()
+            .map(|name| {
+                json!({
+                    "filename": name,
+                    "contentType": "image/png",
+                    "contentId": name,
+                    "content": data_encoding::BASE64.encode(crate::api::static_files(name).unwrap().1),
+                })
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let payload = json!({
+        "from": {
+            "email": CONFIG.smtp_from(),
+            "name": CONFIG.smtp_from_name(),
+        },
+        "to": address,
+        "subject": subject,
+        "html": body_html,
+        "text": body_text,
+        "attachments": attachments,
+    });
+
+    let mut req = make_trusted_http_request(reqwest::Method::POST, api_url)?.json(&payload);
+    if let Some(token) = CONFIG.mail_api_token() {
+        req = req.bearer_auth(token);
+    }
+
+    match req.send().await.and_then(|r| r.error_for_status()) {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            debug!("Mail API error: {:#?}", e);
+            err!(format!("Mail API error: {e}"))
+        }
+    }
+}
+
 async fn send_email(address: &str, subject: &str, body_html: String, body_text: String) -> EmptyResult {
+    send_email_with_pgp_public_key(address, None, subject, body_html, body_text).await
+}
+
+/// Sends a mail, encrypted to the OpenPGP public key of the recipient if they have one.
+/// A key which expired or can't encrypt anymore shouldn't block mails like the 2FA token, so these are sent unencrypted,
+/// after which the user is told about it once per key.
+async fn send_email_with_pgp_key(
+    address: &str,
+    pgp_key: Option<&str>,
+    subject: &str,
+    body_html: String,
+    body_text: String,
+) -> EmptyResult {
+    let (pgp_key, pgp_error) = match pgp_key.map(read_pgp_public_key) {
+        Some(Ok(key)) => (Some(key), None),
+        Some(Err(e)) => {
+            warn!("Sending an unencrypted mail to {address}, their OpenPGP public key can't be used: {e:?}");
+            (None, Some(e))
+        }
+        None => (None, None),
+    };
+
+    send_email_with_pgp_public_key(address, pgp_key.as_ref(), subject, body_html, body_text).await?;
+
+    if let Some(pgp_error) = pgp_error {
+        notify_unusable_pgp_key(address, pgp_error.message()).await;
+    }
+    Ok(())
+}
+
+async fn notify_unusable_pgp_key(address: &str, pgp_error: &str) {
+    let Some(pool) = DB_POOL.get() else {
+        return;
+    };
+    let mut conn = match pool.get().await {
+        Ok(conn) => conn,
+        Err(e) => {
+            error!("Failed to get DB connection to send the OpenPGP key notice to {address}: {e:#?}");
+            return;
+        }
+    };
+    let Some(user) = User::find_by_mail(address, &mut conn).await else {
+        return;
+    };
+    if user.pgp_key_unusable_notified_at.is_some() {
+        return;
+    }
+
+    if let Err(e) = send_pgp_key_unusable(address, user.locale.as_deref(), pgp_error).await {
+        error!("Error sending OpenPGP key unusable email to {address}: {e:#?}");
+        return;
+    }
+    if let Err(e) = User::set_pgp_key_unusable_notified(&user.uuid, &Utc::now().naive_utc(), &mut conn).await {
+        error!("Error saving the OpenPGP key notice of {address}: {e:#?}");
+    }
+}
+
+async fn send_pgp_key_unusable(address: &str, locale: Option<&str>, pgp_error: &str) -> EmptyResult {
+    let (subject, body_html, body_text) = get_text(
+        "email/pgp_key_unusable",
+        locale,
+        json!({
+            "url": CONFIG.domain(),
+            "img_src": CONFIG._smtp_img_src(),
+            "pgp_error": pgp_error,
+        }),
+    )?;
+
+    send_email(address, &subject, body_html, body_text).await
+}
+
+async fn send_email_with_pgp_public_key(
+    address: &str,
+    pgp_key: Option<&SignedPublicKey>,
+    subject: &str,
+    body_html: String,
+    body_text: String,
+) -> EmptyResult {
+    if let Some(api_url) = CONFIG.mail_api_url() {
+        return match pgp_key {
+            // The mail API has no way to send a PGP/MIME message, so only the text body is sent, as an inline PGP message
+            Some(pgp_key) => {
+                let encrypted_text = pgp_encrypt(pgp_key, body_text.as_bytes())?;
+                send_with_mail_api(&api_url, address, subject, None, encrypted_text).await
+            }
+            None => send_with_mail_api(&api_url, address, subject, Some(body_html), body_text).await,
+        };
+    }
+
     let smtp_from = &CONFIG.smtp_from();
 
-    let body = if CONFIG.smtp_embed_images() {
+    let mut body = if CONFIG.smtp_embed_images() {
         let logo_gray_body = Body::new(crate::api::static_files("logo-gray.png").unwrap().1.to_vec());
         let mail_github_body = Body::new(crate::api::static_files("mail-github.png").unwrap().1.to_vec());
         MultiPart::alternative().singlepart(SinglePart::plain(body_text)).multipart(
@@ -604,12 +1446,225 @@ async fn send_email(address: &str, subject: &str, body_html: String, body_text:
         MultiPart::alternative_plain_html(body_text, body_html)
     };
 
-    let email = Message::builder()
+    if let Some(pgp_key) = pgp_key {
+        body = pgp_mime_body(pgp_key, &body)?;
+    }
+
+    let mut email = Message::builder()
         .message_id(Some(format!("<{}@{}>", crate::util::get_uuid(), smtp_from.split('@').collect::<Vec<&str>>()[1])))
         .to(Mailbox::new(None, Address::from_str(address)?))
         .from(Mailbox::new(Some(CONFIG.smtp_from_name()), Address::from_str(smtp_from)?))
         .subject(subject)
         .multipart(body)?;
 
+    if let Some(dkim_config) = dkim_config()? {
+        email.sign(&dkim_config);
+    }
+
     send_with_selected_transport(email).await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::{ec, nid::Nid, pkey::PKey, rsa::Rsa};
+    use pgp::{
+        crypto::ecc_curve::ECCCurve, types::SecretKeyTrait, KeyType, SecretKeyParamsBuilder, SubkeyParamsBuilder,
+    };
+
+    fn write_key_file(name: &str, pem: &[u8]) -> String {
+        let path = std::env::temp_dir().join(format!("vaultwarden-test-{}-{name}.pem", crate::util::get_uuid()));
+        std::fs::write(&path, pem).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn assert_dkim_key(name: &str, pem: &[u8], valid: bool) {
+        let key_file = write_key_file(name, pem);
+        let result = read_dkim_signing_key(&key_file);
+        std::fs::remove_file(&key_file).unwrap();
+        assert_eq!(result.is_ok(), valid, "{name}: {result:?}");
+    }
+
+    #[test]
+    fn test_read_dkim_signing_key() {
+        let rsa = Rsa::generate(2048).unwrap();
+        assert_dkim_key("rsa-pkcs1", &rsa.private_key_to_pem().unwrap(), true);
+        assert_dkim_key("rsa-pkcs8", &PKey::from_rsa(rsa).unwrap().private_key_to_pem_pkcs8().unwrap(), true);
+
+        let ed25519 = PKey::generate_ed25519().unwrap();
+        assert_dkim_key("ed25519", &ed25519.private_key_to_pem_pkcs8().unwrap(), true);
+
+        // Only the public key, an unsupported key type or no key at all
+        assert_dkim_key("ed25519-public", &ed25519.public_key_to_pem().unwrap(), false);
+        let group = ec::EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let ec = PKey::from_ec_key(ec::EcKey::generate(&group).unwrap()).unwrap();
+        assert_dkim_key("ec", &ec.private_key_to_pem_pkcs8().unwrap(), false);
+        assert_dkim_key("garbage", b"not a key", false);
+
+        assert!(read_dkim_signing_key("/nonexistent/dkim.pem").is_err());
+    }
+
+    /// Generates an armored OpenPGP public key, optionally with an encryption subkey
+    fn generate_pgp_key(encryption_subkey: bool) -> String {
+        let mut rng = rand::thread_rng();
+        let mut params = SecretKeyParamsBuilder::default();
+        params
+            .key_type(KeyType::EdDSALegacy)
+            .can_certify(true)
+            .can_sign(true)
+            .primary_user_id("user@example.com".into());
+        if encryption_subkey {
+            params.subkey(
+                SubkeyParamsBuilder::default()
+                    .key_type(KeyType::ECDH(ECCCurve::Curve25519))
+                    .can_encrypt(true)
+                    .build()
+                    .unwrap(),
+            );
+        }
+
+        let secret_key = params.build().unwrap().generate(&mut rng).unwrap().sign(&mut rng, String::new).unwrap();
+        let public_key = secret_key.public_key().sign(&mut rng, &secret_key, String::new).unwrap();
+        public_key.to_armored_string(ArmorOptions::default()).unwrap()
+    }
+
+    // Created on 2020-01-01 with an encryption subkey, both expired a day later
+    const EXPIRED_PGP_KEY: &str = "\
+-----BEGIN PGP PUBLIC KEY BLOCK-----
+
+mDMEXgvhABYJKwYBBAHaRw8BAQdAvMKEtih9TXb/VlJ/9CKDDnys0YGvu+4usMVs
+W4PvdTu0E2V4cGlyZWRAZXhhbXBsZS5jb22IlgQTFggAPhYhBPq0jv6nBf6vT8eo
+Ezw6cnCvqNi1BQJeC+EAAhsDBQkAAVGABQsJCAcCBhUKCQgLAgQWAgMBAh4BAheA
+AAoJEDw6cnCvqNi1woMBAM5d5CDXPD0mJvwKNIKUi/wP1bqms8vIoez3IhVEDznG
+AQD3G8N8fF6KAkzeWfqK8ncLz68UYdbIB/F06cstBTF9Brg4BF4L4QASCisGAQQB
+l1UBBQEBB0Cf7eRJBvgmESL2QxWmmEFe+VRclUwthfm4ik1A5qqjEwMBCAeIfgQY
+FggAJhYhBPq0jv6nBf6vT8eoEzw6cnCvqNi1BQJeC+EAAhsMBQkAAVGAAAoJEDw6
+cnCvqNi1SoAA/1GKL8V/0JJGJKNWVrXRrtJfQDZnjxFp/bvxsMMyVaJ6AQD7WzuM
+vW5iCDsotC/l0niOCGrkz5+qwle2CLNlss32Bg==
+=hhnj
+-----END PGP PUBLIC KEY BLOCK-----";
+
+    // Created on 2020-01-01 without an expiration, but its only encryption subkey expired a day later
+    const EXPIRED_SUBKEY_PGP_KEY: &str = "\
+-----BEGIN PGP PUBLIC KEY BLOCK-----
+
+mDMEXgvhABYJKwYBBAHaRw8BAQdABjph430VdeA7HBn2hYZPLsHYlTuvO4s3/wOG
+lAnGPSe0EnN1YmtleUBleGFtcGxlLmNvbYiQBBMWCAA4FiEEwEESu/7SmhjB4mXM
+dKIVgLVB7EMFAl4L4QACGwMFCwkIBwIGFQoJCAsCBBYCAwECHgECF4AACgkQdKIV
+gLVB7EMdYQEAkDpSGbvu04/d2MQLeEkXda/bBc6vkx8torn5bQW52SYBAJBdT+oR
+tIQKavNosq0pNTVzVKiW6WoeEzgJiU2f4mULuDgEXgvhABIKKwYBBAGXVQEFAQEH
+QCvp1VwW+4pl2m8qvVu06HgL0QItdEzxiw//UzTTWy91AwEIB4h+BBgWCAAmFiEE
+wEESu/7SmhjB4mXMdKIVgLVB7EMFAl4L4QACGwwFCQABUYAACgkQdKIVgLVB7EMM
+8gD+MKUCkcYr7KhD0gAuL/i7m+dC2Q+oF6bfY9tv1y7/M5wBAL49/35IbnK3Os8c
+6BgqW+5Iql0Gg/y/VDCD4gqC7+AE
+=bXI3
+-----END PGP PUBLIC KEY BLOCK-----";
+
+    #[test]
+    fn test_pgp_encrypt() {
+        let key = read_pgp_public_key(&generate_pgp_key(true)).unwrap();
+        assert_eq!(pgp_fingerprint(&key).len(), 40);
+
+        let encrypted = pgp_encrypt(&key, b"123456").unwrap();
+        assert!(encrypted.starts_with("-----BEGIN PGP MESSAGE-----"));
+        assert!(!encrypted.contains("123456"));
+    }
+
+    #[test]
+    fn test_read_pgp_public_key_unusable() {
+        let expired = read_pgp_public_key(EXPIRED_PGP_KEY).unwrap_err();
+        assert_eq!(format!("{expired:?}"), "The OpenPGP public key has expired");
+
+        let expired_subkey = read_pgp_public_key(EXPIRED_SUBKEY_PGP_KEY).unwrap_err();
+        assert_eq!(format!("{expired_subkey:?}"), "The OpenPGP public key can't be used for encryption");
+
+        let signing_only = read_pgp_public_key(&generate_pgp_key(false)).unwrap_err();
+        assert_eq!(format!("{signing_only:?}"), "The OpenPGP public key can't be used for encryption");
+
+        assert!(read_pgp_public_key("not a key").is_err());
+    }
+
+    #[test]
+    fn test_template_sample_data() {
+        crate::api::tests::init_test_config();
+
+        // The templates are rendered in strict mode, so every variable they use needs a sample value
+        let data = template_sample_data();
+        for (name, _) in crate::config::EMAIL_TEMPLATES {
+            if !crate::config::is_email_partial(name) {
+                if let Err(e) = CONFIG.render_template(name, &data) {
+                    panic!("{name}: {e:?}");
+                }
+            }
+        }
+    }
+
+    /// Reads a single HTTP request from the stream, returning its headers and its body
+    async fn read_http_request(stream: &mut tokio::net::TcpStream) -> (String, Vec<u8>) {
+        use tokio::io::AsyncReadExt;
+
+        let mut request = Vec::new();
+        let mut buf = [0u8; 8192];
+        loop {
+            let n = stream.read(&mut buf).await.unwrap();
+            assert_ne!(n, 0, "Connection closed before the request was complete");
+            request.extend_from_slice(&buf[..n]);
+
+            let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") else {
+                continue;
+            };
+            let headers = String::from_utf8(request[..end].to_vec()).unwrap();
+            let content_length = headers
+                .lines()
+                .find_map(|l| {
+                    l.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap())
+                })
+                .unwrap_or(0);
+            if request.len() >= end + 4 + content_length {
+                return (headers, request[end + 4..end + 4 + content_length].to_vec());
+            }
+        }
+    }
+
+    #[rocket::async_test]
+    async fn test_send_with_mail_api() {
+        use tokio::io::AsyncWriteExt;
+
+        crate::api::tests::init_test_config();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let api_url = format!("http://{}/send", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let request = read_http_request(&mut stream).await;
+            stream.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n").await.unwrap();
+            request
+        });
+
+        send_with_mail_api(&api_url, "user@example.com", "Subject", Some("<p>Html</p>".into()), "Text".into())
+            .await
+            .unwrap();
+
+        let (headers, body) = server.await.unwrap();
+        assert!(headers.starts_with("POST /send HTTP/1.1"), "{headers}");
+        assert!(headers.lines().any(|l| l.eq_ignore_ascii_case("authorization: Bearer mail-api-token")), "{headers}");
+
+        let payload: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(payload["from"]["email"], CONFIG.smtp_from());
+        assert_eq!(payload["from"]["name"], CONFIG.smtp_from_name());
+        assert_eq!(payload["to"], "user@example.com");
+        assert_eq!(payload["subject"], "Subject");
+        assert_eq!(payload["html"], "<p>Html</p>");
+        assert_eq!(payload["text"], "Text");
+
+        // The images are embedded by default
+        let attachments = payload["attachments"].as_array().unwrap();
+        assert_eq!(attachments.len(), 2);
+        assert_eq!(attachments[0]["filename"], "logo-gray.png");
+        assert_eq!(attachments[0]["contentType"], "image/png");
+        assert_eq!(attachments[0]["contentId"], "logo-gray.png");
+        let logo = data_encoding::BASE64.decode(attachments[0]["content"].as_str().unwrap().as_bytes()).unwrap();
+        assert_eq!(logo, crate::api::static_files("logo-gray.png").unwrap().1);
+        assert_eq!(attachments[1]["filename"], "mail-github.png");
+    }
+}
@@ -49,12 +49,11 @@ mod crypto;
 #[macro_use]
 mod db;
 mod http_client;
+mod jobs;
 mod mail;
 mod ratelimit;
 mod util;
 
-use crate::api::core::two_factor::duo_oidc::purge_duo_contexts;
-use crate::api::purge_auth_requests;
 use crate::api::{WS_ANONYMOUS_SUBSCRIPTIONS, WS_USERS};
 pub use config::CONFIG;
 pub use error::{Error, MapResult};
@@ -83,6 +82,8 @@ async fn main() -> Result<(), Error> {
 
     let pool = create_db_pool().await;
     schedule_jobs(pool.clone());
+    api::start_ws_broadcast(pool.clone());
+    mail::set_db_pool(pool.clone());
     crate::db::models::TwoFactor::migrate_u2f_to_webauthn(&mut pool.get().await.unwrap()).await.unwrap();
 
     let extra_debug = matches!(level, log::LevelFilter::Trace | log::LevelFilter::Debug);
@@ -582,66 +583,13 @@ fn schedule_jobs(pool: db::DbPool) {
 
             let mut sched = JobScheduler::new();
 
-            // Purge sends that are past their deletion date.
-            if !CONFIG.send_purge_schedule().is_empty() {
-                sched.add(Job::new(CONFIG.send_purge_schedule().parse().unwrap(), || {
-                    runtime.spawn(api::purge_sends(pool.clone()));
-                }));
-            }
-
-            // Purge trashed items that are old enough to be auto-deleted.
-            if !CONFIG.trash_purge_schedule().is_empty() {
-                sched.add(Job::new(CONFIG.trash_purge_schedule().parse().unwrap(), || {
-                    runtime.spawn(api::purge_trashed_ciphers(pool.clone()));
-                }));
-            }
-
-            // Send email notifications about incomplete 2FA logins, which potentially
-            // indicates that a user's master password has been compromised.
-            if !CONFIG.incomplete_2fa_schedule().is_empty() {
-                sched.add(Job::new(CONFIG.incomplete_2fa_schedule().parse().unwrap(), || {
-                    runtime.spawn(api::send_incomplete_2fa_notifications(pool.clone()));
-                }));
-            }
-
-            // Grant emergency access requests that have met the required wait time.
-            // This job should run before the emergency access reminders job to avoid
-            // sending reminders for requests that are about to be granted anyway.
-            if !CONFIG.emergency_request_timeout_schedule().is_empty() {
-                sched.add(Job::new(CONFIG.emergency_request_timeout_schedule().parse().unwrap(), || {
-                    runtime.spawn(api::emergency_request_timeout_job(pool.clone()));
-                }));
-            }
-
-            // Send reminders to emergency access grantors that there are pending
-            // emergency access requests.
-            if !CONFIG.emergency_notification_reminder_schedule().is_empty() {
-                sched.add(Job::new(CONFIG.emergency_notification_reminder_schedule().parse().unwrap(), || {
-                    runtime.spawn(api::emergency_notification_reminder_job(pool.clone()));
-                }));
-            }
-
-            if !CONFIG.auth_request_purge_schedule().is_empty() {
-                sched.add(Job::new(CONFIG.auth_request_purge_schedule().parse().unwrap(), || {
-                    runtime.spawn(purge_auth_requests(pool.clone()));
-                }));
-            }
-
-            // Clean unused, expired Duo authentication contexts.
-            if !CONFIG.duo_context_purge_schedule().is_empty() && CONFIG._enable_duo() && !CONFIG.duo_use_iframe() {
-                sched.add(Job::new(CONFIG.duo_context_purge_schedule().parse().unwrap(), || {
-                    runtime.spawn(purge_duo_contexts(pool.clone()));
-                }));
-            }
-
-            // Cleanup the event table of records x days old.
-            if CONFIG.org_events_enabled()
-                && !CONFIG.event_cleanup_schedule().is_empty()
-                && CONFIG.events_days_retain().is_some()
-            {
-                sched.add(Job::new(CONFIG.event_cleanup_schedule().parse().unwrap(), || {
-                    runtime.spawn(api::event_cleanup_job(pool.clone()));
-                }));
+            let (runtime, pool) = (&runtime, &pool);
+            for job in jobs::ScheduledJob::ALL {
+                if job.is_scheduled() {
+                    sched.add(Job::new(job.schedule().parse().unwrap(), move || {
+                        runtime.spawn(jobs::run_job(job, pool.clone(), false));
+                    }));
+                }
             }
 
             // Periodically check for jobs to run. We probably won't need any
@@ -649,10 +597,6 @@ fn schedule_jobs(pool: db::DbPool) {
             // interval of 30 seconds should be sufficient. Users who want to
             // schedule jobs to run more frequently for some reason can reduce
             // the poll interval accordingly.
-            //
-            // Note that the scheduler checks jobs in the order in which they
-            // were added, so if two jobs are both eligible to run at a given
-            // tick, the one that was added earlier will run first.
             loop {
                 sched.tick();
                 runtime.block_on(tokio::time::sleep(tokio::time::Duration::from_millis(CONFIG.job_poll_interval_ms())));
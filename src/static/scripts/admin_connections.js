@@ -0,0 +1,34 @@
+"use strict";
+/* eslint-env es2017, browser */
+/* global _post:readable, BASE_URL:readable, reload:readable */
+
+function disconnectConnection(event) {
+    event.preventDefault();
+    event.stopPropagation();
+    const id = event.target.dataset.vwConnectionId;
+    const email = event.target.dataset.vwConnectionEmail;
+    if (!id) {
+        alert("Required parameters not found!");
+        return false;
+    }
+    const confirmed = confirm(`Are you sure you want to disconnect this connection of "${email || id}"?`);
+    if (confirmed) {
+        event.target.disabled = true;
+        _post(`${BASE_URL}/admin/connections/${id}/disconnect`,
+            "Connection closed",
+            "Error closing connection"
+        );
+    }
+}
+
+// onLoad events
+document.addEventListener("DOMContentLoaded", (/*event*/) => {
+    document.querySelectorAll("button[vw-disconnect-connection]").forEach(btn => {
+        btn.addEventListener("click", disconnectConnection);
+    });
+
+    const btnReload = document.getElementById("reload");
+    if (btnReload) {
+        btnReload.addEventListener("click", reload);
+    }
+});
@@ -0,0 +1,33 @@
+"use strict";
+/* eslint-env es2017, browser */
+/* global _post:readable, BASE_URL:readable, reload:readable */
+
+function runJob(event) {
+    event.preventDefault();
+    event.stopPropagation();
+    const name = event.target.dataset.vwJobName;
+    if (!name) {
+        alert("Required parameters not found!");
+        return false;
+    }
+    const confirmed = confirm(`Are you sure you want to run the job "${name}" now?`);
+    if (confirmed) {
+        event.target.disabled = true;
+        _post(`${BASE_URL}/admin/jobs/${name}/run`,
+            "Job finished, check the recent runs for the outcome",
+            "Error running job"
+        );
+    }
+}
+
+// onLoad events
+document.addEventListener("DOMContentLoaded", (/*event*/) => {
+    document.querySelectorAll("button[vw-run-job]").forEach(btn => {
+        btn.addEventListener("click", runJob);
+    });
+
+    const btnReload = document.getElementById("reload");
+    if (btnReload) {
+        btnReload.addEventListener("click", reload);
+    }
+});
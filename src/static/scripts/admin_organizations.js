@@ -14,7 +14,11 @@ function deleteOrganization(event) {
     }
 
     // First make sure the user wants to delete this organization
-    const continueDelete = confirm(`WARNING: All data of this organization (${org_name}) will be lost!\nMake sure you have a backup, this cannot be undone!`);
+    // Unless it is already pending deletion, the organization is only deleted after the configured grace period
+    const delete_now = event.target.dataset.vwPendingDeletion === "true" || event.target.dataset.vwGraceDays === "0";
+    const continueDelete = delete_now
+        ? confirm(`WARNING: All data of this organization (${org_name}) will be lost!\nMake sure you have a backup, this cannot be undone!`)
+        : confirm(`The organization (${org_name}) will be disabled, and deleted after ${event.target.dataset.vwGraceDays} days.\nUntil then it can be restored.`);
     if (continueDelete == true) {
         const input_org_uuid = prompt(`To delete the organization "${org_name} (${billing_email})", please type the organization uuid below.`);
         if (input_org_uuid != null) {
@@ -30,10 +34,31 @@ function deleteOrganization(event) {
     }
 }
 
+function restoreOrganization(event) {
+    event.preventDefault();
+    event.stopPropagation();
+    const org_uuid = event.target.dataset.vwOrgUuid;
+    const org_name = event.target.dataset.vwOrgName;
+    if (!org_uuid) {
+        alert("Required parameters not found!");
+        return false;
+    }
+
+    if (confirm(`Are you sure you want to restore the organization "${org_name}"?`)) {
+        _post(`${BASE_URL}/admin/organizations/${org_uuid}/restore`,
+            "Organization restored correctly",
+            "Error restoring organization"
+        );
+    }
+}
+
 function initActions() {
     document.querySelectorAll("button[vw-delete-organization]").forEach(btn => {
         btn.addEventListener("click", deleteOrganization);
     });
+    document.querySelectorAll("button[vw-restore-organization]").forEach(btn => {
+        btn.addEventListener("click", restoreOrganization);
+    });
 
     if (jdenticon) {
         jdenticon();
@@ -0,0 +1,116 @@
+"use strict";
+/* eslint-env es2017, browser */
+/* global _post:readable, BASE_URL:readable */
+
+let builtinSource = "";
+
+function templateName() {
+    return document.getElementById("template-name").value;
+}
+
+function templateBody() {
+    return JSON.stringify({
+        "name": templateName(),
+        "source": document.getElementById("template-source").value
+    });
+}
+
+async function loadTemplate() {
+    const name = templateName();
+    const resp = await fetch(`${BASE_URL}/admin/templates/source?name=${encodeURIComponent(name)}`, {
+        mode: "same-origin",
+        credentials: "same-origin"
+    });
+    if (!resp.ok) {
+        alert(`Error loading template\n${resp.status} - ${resp.statusText}`);
+        return;
+    }
+    const template = await resp.json();
+    builtinSource = template.builtin;
+    document.getElementById("template-source").value = template.source;
+    document.getElementById("template-revert").disabled = !template.overridden;
+    clearPreview();
+}
+
+function clearPreview() {
+    document.getElementById("preview-subject").textContent = "";
+    document.getElementById("preview-html").srcdoc = "";
+    document.getElementById("preview-text").textContent = "";
+}
+
+async function previewTemplate(event) {
+    event.preventDefault();
+    const resp = await fetch(`${BASE_URL}/admin/templates/preview`, {
+        method: "POST",
+        body: templateBody(),
+        mode: "same-origin",
+        credentials: "same-origin",
+        headers: { "Content-Type": "application/json" }
+    });
+    const respJson = await resp.json().catch(() => ({}));
+    if (!resp.ok) {
+        const errMsg = respJson.message || `${resp.status} - ${resp.statusText}`;
+        clearPreview();
+        document.getElementById("preview-subject").textContent = errMsg;
+        return;
+    }
+    document.getElementById("preview-subject").textContent = respJson.subject;
+    document.getElementById("preview-html").srcdoc = respJson.html;
+    document.getElementById("preview-text").textContent = respJson.text;
+}
+
+function saveTemplate(event) {
+    event.preventDefault();
+    _post(`${BASE_URL}/admin/templates/save`,
+        "Template saved",
+        "Error saving template",
+        templateBody()
+    );
+}
+
+function revertTemplate(event) {
+    event.preventDefault();
+    const name = templateName();
+    const confirmed = confirm(`Are you sure you want to delete the override of "${name}" and use the built-in template?`);
+    if (confirmed) {
+        _post(`${BASE_URL}/admin/templates/revert`,
+            "Template reverted",
+            "Error reverting template",
+            JSON.stringify({ "name": name })
+        );
+    }
+}
+
+function loadBuiltin(event) {
+    event.preventDefault();
+    document.getElementById("template-source").value = builtinSource;
+    clearPreview();
+}
+
+function testTemplate(event) {
+    event.preventDefault();
+    const email = document.getElementById("template-test-email");
+    if (email.value === "") {
+        alert("Please enter an email address to send the test to.");
+        email.focus();
+        return false;
+    }
+    _post(`${BASE_URL}/admin/templates/test`,
+        "Test email sent",
+        "Error sending test email",
+        JSON.stringify({ "name": templateName(), "email": email.value }),
+        false
+    );
+}
+
+// onLoad events
+document.addEventListener("DOMContentLoaded", (/*event*/) => {
+    document.getElementById("template-name").addEventListener("change", loadTemplate);
+    document.getElementById("template-preview").addEventListener("click", previewTemplate);
+    document.getElementById("template-save").addEventListener("click", saveTemplate);
+    document.getElementById("template-revert").addEventListener("click", revertTemplate);
+    document.getElementById("template-builtin").addEventListener("click", loadBuiltin);
+    document.getElementById("template-test").addEventListener("click", testTemplate);
+
+    loadTemplate();
+});
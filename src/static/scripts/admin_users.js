@@ -11,7 +11,12 @@ function deleteUser(event) {
         alert("Required parameters not found!");
         return false;
     }
-    const input_email = prompt(`To delete user "${email}", please type the email below`);
+    // Unless it is already deleted, the account is only purged after the configured retention period
+    const retention_days = event.target.dataset.vwRetentionDays;
+    const delete_now = event.target.dataset.vwPendingDeletion === "true" || retention_days === "0";
+    const input_email = delete_now
+        ? prompt(`To permanently delete user "${email}", please type the email below`)
+        : prompt(`User "${email}" will be deleted, and can be restored during ${retention_days} days.\nTo delete this user, please type the email below`);
     if (input_email != null) {
         if (input_email == email) {
             _post(`${BASE_URL}/admin/users/${id}/delete`,
@@ -96,6 +101,24 @@ function enableUser(event) {
     }
 }
 
+function restoreUser(event) {
+    event.preventDefault();
+    event.stopPropagation();
+    const id = event.target.parentNode.dataset.vwUserUuid;
+    const email = event.target.parentNode.dataset.vwUserEmail;
+    if (!id || !email) {
+        alert("Required parameters not found!");
+        return false;
+    }
+    const confirmed = confirm(`Are you sure you want to restore user "${email}"?`);
+    if (confirmed) {
+        _post(`${BASE_URL}/admin/users/${id}/restore`,
+            "User restored successfully",
+            "Error restoring user"
+        );
+    }
+}
+
 function updateRevisions(event) {
     event.preventDefault();
     event.stopPropagation();
@@ -156,6 +179,10 @@ const ORG_TYPES = {
         "name": "Manager",
         "bg": "green"
     },
+    "4": {
+        "name": "Custom",
+        "bg": "teal"
+    },
 };
 
 // Special sort function to sort dates in ISO format
@@ -252,6 +279,9 @@ function initUserTable() {
     document.querySelectorAll("button[vw-enable-user]").forEach(btn => {
         btn.addEventListener("click", enableUser);
     });
+    document.querySelectorAll("button[vw-restore-user]").forEach(btn => {
+        btn.addEventListener("click", restoreUser);
+    });
     document.querySelectorAll("button[vw-resend-user-invite]").forEach(btn => {
         btn.addEventListener("click", resendUserInvite);
     });
@@ -4,6 +4,7 @@
 use std::{collections::HashMap, io::Cursor, ops::Deref, path::Path};
 
 use num_traits::ToPrimitive;
+use once_cell::sync::Lazy;
 use rocket::{
     fairing::{Fairing, Info, Kind},
     http::{ContentType, Header, HeaderMap, Method, Status},
@@ -356,6 +357,9 @@ pub fn get_uuid() -> String {
     uuid::Uuid::new_v4().to_string()
 }
 
+/// Identifies this process amongst the instances which share the same database.
+pub static INSTANCE_ID: Lazy<String> = Lazy::new(get_uuid);
+
 //
 // String util methods
 //
@@ -736,6 +740,59 @@ pub fn parse_experimental_client_feature_flags(experimental_client_feature_flags
     feature_states
 }
 
+/// Normalizes a locale like `pt_br` or `ZH-hant-tw` to the `pt-BR` and `zh-Hant-TW` forms the clients use.
+/// Scripts are title cased, regions upper cased and any further subtags lower cased.
+/// Returns `None` if it doesn't look like a language tag.
+pub fn normalize_locale(locale: &str) -> Option<String> {
+    let locale = locale.trim().replace('_', "-");
+    // The longest tag RFC 5646 asks implementations to support
+    if locale.len() > 35 {
+        return None;
+    }
+    let mut parts = locale.split('-');
+
+    let language = parts.next()?;
+    if !(2..=3).contains(&language.len()) || !language.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    let mut normalized = language.to_lowercase();
+    for subtag in parts {
+        if !(2..=8).contains(&subtag.len()) || !subtag.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return None;
+        }
+        normalized.push('-');
+        if subtag.len() == 4 && subtag.chars().all(|c| c.is_ascii_alphabetic()) {
+            normalized.push_str(&subtag[..1].to_uppercase());
+            normalized.push_str(&subtag[1..].to_lowercase());
+        } else if subtag.len() == 2 || (subtag.len() == 3 && subtag.chars().all(|c| c.is_ascii_digit())) {
+            normalized.push_str(&subtag.to_uppercase());
+        } else {
+            normalized.push_str(&subtag.to_lowercase());
+        }
+    }
+
+    Some(normalized)
+}
+
+/// Returns the locale the client prefers the most from an `Accept-Language` header.
+pub fn parse_accept_language(header: &str) -> Option<String> {
+    let mut best: Option<(String, f32)> = None;
+    for entry in header.split(',') {
+        let mut params = entry.split(';');
+        let Some(locale) = params.next().and_then(normalize_locale) else {
+            continue;
+        };
+        let quality =
+            params.find_map(|p| p.trim().strip_prefix("q=")).map(|q| q.parse::<f32>().unwrap_or(0.0)).unwrap_or(1.0);
+
+        if quality > 0.0 && best.as_ref().map_or(true, |(_, q)| quality > *q) {
+            best = Some((locale, quality));
+        }
+    }
+    best.map(|(locale, _)| locale)
+}
+
 /// TODO: This is extracted from IpAddr::is_global, which is unstable:
 /// https://doc.rust-lang.org/nightly/std/net/enum.IpAddr.html#method.is_global
 /// Remove once https://github.com/rust-lang/rust/issues/27709 is merged
@@ -848,3 +905,37 @@ mod tests {
         });
     }
 }
+
+#[cfg(test)]
+mod locale_tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_locale() {
+        assert_eq!(normalize_locale("en").as_deref(), Some("en"));
+        assert_eq!(normalize_locale(" pt_br ").as_deref(), Some("pt-BR"));
+        assert_eq!(normalize_locale("ZH-hant").as_deref(), Some("zh-Hant"));
+        assert_eq!(normalize_locale("zh_hant_tw").as_deref(), Some("zh-Hant-TW"));
+        assert_eq!(normalize_locale("ca-ES-Valencia").as_deref(), Some("ca-ES-valencia"));
+        assert_eq!(normalize_locale("es-419").as_deref(), Some("es-419"));
+
+        assert_eq!(normalize_locale(""), None);
+        assert_eq!(normalize_locale("*"), None);
+        assert_eq!(normalize_locale("english"), None);
+        assert_eq!(normalize_locale("en-"), None);
+        assert_eq!(normalize_locale("en-<script>"), None);
+    }
+
+    #[test]
+    fn test_parse_accept_language() {
+        assert_eq!(parse_accept_language("nl").as_deref(), Some("nl"));
+        assert_eq!(parse_accept_language("fr;q=0.5, de-de, en;q=0.8").as_deref(), Some("de-DE"));
+        assert_eq!(parse_accept_language("fr;q=0.5, en;q=0.8").as_deref(), Some("en"));
+
+        // The first of equally preferred locales wins, and invalid or refused entries are skipped
+        assert_eq!(parse_accept_language("it, es").as_deref(), Some("it"));
+        assert_eq!(parse_accept_language("*, sv;q=0.1").as_deref(), Some("sv"));
+        assert_eq!(parse_accept_language("da;q=0, fi;q=invalid"), None);
+        assert_eq!(parse_accept_language(""), None);
+    }
+}